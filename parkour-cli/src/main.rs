@@ -0,0 +1,538 @@
+mod output;
+mod progress;
+mod table;
+
+use clap::{Parser, Subcommand};
+use output::{Format, GeneratedFile, ImportSummary, Report, Row, SessionInfo};
+use parkour_core::db::Db;
+use parkour_core::duels::{
+    self, DuelEvent, EventKind, Game, MapStats, Parsed, PatternSpec, Patterns, PersonalBest,
+};
+use parkour_core::duration::format_ms;
+use parkour_core::generate::{self, Scenario};
+use parkour_core::import;
+use parkour_core::lines::lines;
+use parkour_core::log_line::parse_line;
+use parkour_core::schema::SchemaSet;
+use parkour_core::server_log::ServerLogSpec;
+use parkour_core::store::RunStore;
+use parkour_core::tail::LogTail;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(
+    name = "parkour-cli",
+    version,
+    about = "Analyze Parkour Duels logs without the GUI"
+)]
+struct Cli {
+    /// JSON file with custom parser patterns
+    #[arg(long, global = true, value_name = "JSON")]
+    patterns: Option<PathBuf>,
+    /// Read logs as a Paper or Spigot server's console log, with runs for
+    /// every player the server names
+    #[arg(long, global = true)]
+    server_log: bool,
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the games, times and personal bests in a log
+    Analyze { file: PathBuf },
+    /// Print per-map totals for a log or every log in a directory
+    Stats {
+        path: PathBuf,
+        /// Player to count for; defaults to the one logged in
+        #[arg(long)]
+        player: Option<String>,
+    },
+    /// Import every log and archive under a directory into a database the
+    /// app can open
+    Import {
+        #[arg(long, value_name = "DIR")]
+        logs_dir: PathBuf,
+        #[arg(long, value_name = "PATH")]
+        db: PathBuf,
+        /// Skip logs a previous import already finished
+        #[arg(long)]
+        resume: bool,
+    },
+    /// Write synthetic logs for tests and demos
+    Generate {
+        /// Scenario to generate; see `Scenario` in parkour-core/src/generate.rs
+        #[arg(long, value_name = "JSON")]
+        scenario: Option<PathBuf>,
+        /// Overrides the scenario's seed
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Directory to write the logs to
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+    },
+    /// Print the JSON Schema of every JSON output
+    Schema,
+    /// Follow a log and print checkpoints and finishes as they happen
+    Watch {
+        file: PathBuf,
+        #[arg(long, default_value_t = 250, value_name = "MS")]
+        interval: u64,
+    },
+}
+
+fn load_patterns(path: Option<&Path>, server_log: bool) -> Result<Patterns, String> {
+    let patterns = match path {
+        None => Patterns::default(),
+        Some(path) => {
+            let json = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let spec: PatternSpec = serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            Patterns::from_spec(&spec)?
+        }
+    };
+    match server_log {
+        true => patterns.with_server_log(&ServerLogSpec::default()),
+        false => Ok(patterns),
+    }
+}
+
+fn read_log(path: &Path) -> Result<String, String> {
+    import::read_log(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+fn fmt_ms(ms: Option<i64>) -> String {
+    ms.map(format_ms).unwrap_or_else(|| "--".to_string())
+}
+
+// Where `player` finished among the finishers, e.g. "1/3"
+fn place(game: &Game, player: &str) -> String {
+    let mut times: Vec<i64> = game
+        .players
+        .keys()
+        .filter_map(|p| game.finish(p)?.time_ms())
+        .collect();
+    times.sort();
+    match game.finish(player).and_then(|e| e.time_ms()) {
+        Some(ms) => format!(
+            "{}/{}",
+            times.iter().take_while(|t| **t < ms).count() + 1,
+            game.players.len()
+        ),
+        None => format!("-/{}", game.players.len()),
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+struct Analysis {
+    session: SessionInfo,
+    games: Vec<Game>,
+    personal_bests: Vec<PersonalBest>,
+}
+
+impl Report for Analysis {
+    fn rows(&self) -> Vec<Row<'_>> {
+        let mut rows = vec![Row::Session(&self.session)];
+        rows.extend(self.games.iter().map(Row::Game));
+        rows.extend(self.personal_bests.iter().map(Row::PersonalBest));
+        rows
+    }
+
+    fn print_table(&self) {
+        let session = &self.session;
+        let player = session.username.as_deref().unwrap_or("?");
+        println!("Session: {}", session.path);
+        println!("Player:  {}", player);
+        if let Some(format) = session.format {
+            println!("Format:  {}", format.name());
+        }
+        println!("Client:  {}", session.client.name());
+        match (&session.first_game, &session.last_game) {
+            (Some(first), Some(last)) => {
+                println!("Games:   {} ({} to {})", session.games, first, last)
+            }
+            _ => println!("Games:   {}", session.games),
+        }
+        if self.games.is_empty() {
+            return;
+        }
+
+        println!();
+        let rows: Vec<Vec<String>> = self
+            .games
+            .iter()
+            .enumerate()
+            .map(|(i, game)| {
+                let winner = game.winner();
+                vec![
+                    (i + 1).to_string(),
+                    game.time.clone().unwrap_or_default(),
+                    game.title.clone(),
+                    fmt_ms(game.finish(player).and_then(|e| e.time_ms())),
+                    place(game, player),
+                    winner.map(|(w, _)| w.to_string()).unwrap_or_default(),
+                    fmt_ms(winner.map(|(_, ms)| ms)),
+                ]
+            })
+            .collect();
+        table::print(
+            &[
+                "#",
+                "Time",
+                "Map",
+                "Your time",
+                "Place",
+                "Winner",
+                "Winning time",
+            ],
+            &rows,
+        );
+
+        if !self.personal_bests.is_empty() {
+            println!("\nPersonal bests");
+            let rows: Vec<Vec<String>> = self
+                .personal_bests
+                .iter()
+                .map(|pb| {
+                    vec![
+                        pb.player.clone(),
+                        format_ms(pb.time_ms),
+                        (pb.game + 1).to_string(),
+                    ]
+                })
+                .collect();
+            table::print(&["Player", "Best", "Game"], &rows);
+        }
+    }
+}
+
+fn analyze(file: &Path, patterns: &Patterns) -> Result<Analysis, String> {
+    let parsed = duels::parse_with(&read_log(file)?, patterns);
+    let times: Vec<&str> = parsed
+        .games
+        .iter()
+        .filter_map(|g| g.time.as_deref())
+        .collect();
+    let session = SessionInfo {
+        path: file.to_string_lossy().to_string(),
+        username: parsed.username.clone(),
+        format: parsed.format,
+        client: parsed.client.unwrap_or_default(),
+        games: parsed.games.len(),
+        first_game: times.first().map(|t| t.to_string()),
+        last_game: times.last().map(|t| t.to_string()),
+    };
+    Ok(Analysis {
+        session,
+        personal_bests: duels::personal_bests(&parsed.games),
+        games: parsed.games,
+    })
+}
+
+#[derive(Serialize, JsonSchema)]
+struct Stats {
+    player: String,
+    logs: usize,
+    maps: Vec<MapStats>,
+}
+
+impl Report for Stats {
+    fn rows(&self) -> Vec<Row<'_>> {
+        self.maps.iter().map(Row::MapStats).collect()
+    }
+
+    fn print_table(&self) {
+        println!("Player: {} ({} logs)\n", self.player, self.logs);
+        let rows: Vec<Vec<String>> = self
+            .maps
+            .iter()
+            .map(|s| {
+                vec![
+                    s.map.clone(),
+                    s.games.to_string(),
+                    s.finishes.to_string(),
+                    s.wins.to_string(),
+                    fmt_ms(s.best_ms),
+                    fmt_ms(s.average_ms),
+                ]
+            })
+            .collect();
+        table::print(
+            &["Map", "Games", "Finishes", "Wins", "Best", "Average"],
+            &rows,
+        );
+    }
+}
+
+fn stats(path: &Path, player: Option<&str>, patterns: &Patterns) -> Result<Stats, String> {
+    let files = if path.is_dir() {
+        import::find_logs(path)?
+            .into_iter()
+            .map(|log| log.path)
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let logs = files
+        .iter()
+        .map(|file| Ok(duels::parse_with(&read_log(file)?, patterns)))
+        .collect::<Result<Vec<Parsed>, String>>()?;
+    let Some(player) = player
+        .map(str::to_string)
+        .or_else(|| logs.iter().find_map(|p| p.username.clone()))
+    else {
+        return Err("No player found in the logs; pass --player".to_string());
+    };
+    Ok(Stats {
+        maps: duels::map_stats(logs.iter().flat_map(|p| &p.games), &player),
+        player,
+        logs: files.len(),
+    })
+}
+
+impl Report for ImportSummary {
+    fn rows(&self) -> Vec<Row<'_>> {
+        vec![Row::Import(self)]
+    }
+
+    fn print_table(&self) {
+        println!("Files processed:    {}", self.files_processed);
+        if self.already_imported > 0 {
+            println!("Already imported:   {}", self.already_imported);
+        }
+        println!("Runs found:         {}", self.runs_found);
+        println!("Runs added:         {}", self.runs_added);
+        println!("Duplicates skipped: {}", self.duplicates_skipped);
+        println!("Parse errors:       {}", self.parse_errors);
+    }
+}
+
+fn import_logs(
+    dir: &Path,
+    db_path: &Path,
+    resume: bool,
+    patterns: &Patterns,
+    format: Format,
+) -> Result<ImportSummary, String> {
+    let files = import::find_logs(dir)?;
+    let db = Db::open(db_path)?;
+    import_files(&db, &files, resume, patterns, format)
+}
+
+fn import_files(
+    store: &impl RunStore,
+    files: &[import::LogFile],
+    resume: bool,
+    patterns: &Patterns,
+    format: Format,
+) -> Result<ImportSummary, String> {
+    // A bar on stderr would break up the JSON error stream
+    let progress = progress::Progress::new(files.len(), format == Format::Table);
+    let mut summary = ImportSummary::default();
+    for (i, file) in files.iter().enumerate() {
+        progress.update(
+            i,
+            &file.path.file_name().unwrap_or_default().to_string_lossy(),
+        );
+        if resume && store.is_imported(file)? {
+            summary.already_imported += 1;
+            continue;
+        }
+        let imported = read_log(&file.path)
+            .and_then(|content| store.import(file, &import::analyze_log(file, &content, patterns)));
+        match imported {
+            Ok(log) => {
+                summary.files_processed += 1;
+                summary.runs_found += log.runs_found;
+                summary.runs_added += log.runs_added;
+                summary.duplicates_skipped += log.duplicates;
+            }
+            Err(e) => {
+                summary.parse_errors += 1;
+                progress.clear();
+                output::error(format, &e, Some(&file.path));
+            }
+        }
+    }
+    progress.clear();
+    Ok(summary)
+}
+
+#[derive(Serialize, JsonSchema)]
+struct Generated {
+    files: Vec<GeneratedFile>,
+}
+
+impl Report for Generated {
+    fn rows(&self) -> Vec<Row<'_>> {
+        self.files.iter().map(Row::Generated).collect()
+    }
+
+    fn print_table(&self) {
+        let rows: Vec<Vec<String>> = self
+            .files
+            .iter()
+            .map(|f| {
+                vec![
+                    f.path.clone(),
+                    f.expected.games.to_string(),
+                    f.expected.runs.to_string(),
+                    f.expected.finishes.to_string(),
+                ]
+            })
+            .collect();
+        table::print(&["File", "Games", "Runs", "Finishes"], &rows);
+    }
+}
+
+fn generate_logs(
+    scenario: Option<&Path>,
+    seed: Option<u64>,
+    out: &Path,
+) -> Result<Generated, String> {
+    let mut scenario: Scenario = match scenario {
+        Some(path) => {
+            let json = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?
+        }
+        None => Scenario::default(),
+    };
+    if let Some(seed) = seed {
+        scenario.seed = seed;
+    }
+    fs::create_dir_all(out).map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+    let mut files = Vec::new();
+    for log in generate::generate(&scenario)? {
+        let path = out.join(&log.name);
+        fs::write(&path, &log.content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        files.push(GeneratedFile {
+            path: path.to_string_lossy().to_string(),
+            expected: log.expected,
+        });
+    }
+    Ok(Generated { files })
+}
+
+fn print_event(event: &DuelEvent, text: &str) {
+    let time = parse_line(0, text).time.unwrap_or_default();
+    match event {
+        DuelEvent::GameStarted { title, .. } => println!("[{}] {} started", time, title),
+        DuelEvent::Progress { player, step, .. } => match step.kind {
+            EventKind::Checkpoint => println!(
+                "[{}] {} reached checkpoint {} in {}",
+                time, player, step.cp, step.time
+            ),
+            EventKind::Finish => println!("[{}] {} finished in {}", time, player, step.time),
+        },
+        DuelEvent::Reset { .. } => println!("[{}] run reset", time),
+    }
+}
+
+/// Prints events from lines written after it starts; what's already in the
+/// log is only read to know the player and the game in progress.
+fn watch(
+    file: &Path,
+    interval: Duration,
+    patterns: &Patterns,
+    format: Format,
+) -> Result<(), String> {
+    let mut log = LogTail::new(file);
+    let mut parser = duels::Parser::new(patterns.clone());
+    let mut caught_up = false;
+    loop {
+        match log.poll() {
+            Ok(Some(chunk)) => {
+                if chunk.offset == 0 && caught_up {
+                    output::row(format, &Row::LogRestarted, || {
+                        println!("-- log restarted --")
+                    });
+                    parser = duels::Parser::new(patterns.clone());
+                }
+                for line in lines(&chunk.content, chunk.offset as usize) {
+                    let Some(event) = parser.line(line.start as u64, line.text) else {
+                        continue;
+                    };
+                    if caught_up {
+                        output::row(format, &Row::Event(&event), || {
+                            print_event(&event, line.text)
+                        });
+                    }
+                }
+            }
+            Ok(None) => {}
+            // The game deletes and recreates the log on launch
+            Err(e) if caught_up && e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read {}: {}", file.display(), e)),
+        }
+        caught_up = true;
+        std::thread::sleep(interval);
+    }
+}
+
+// `row` is each line of --format json, the rest are the --format json-pretty
+// documents
+fn schema() -> serde_json::Value {
+    SchemaSet::default()
+        .payload::<Row>("row")
+        .payload::<Analysis>("analyze")
+        .payload::<Stats>("stats")
+        .payload::<ImportSummary>("import")
+        .payload::<Generated>("generate")
+        .payload::<output::Error>("error")
+        .models()
+        .finish(env!("CARGO_PKG_VERSION"))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let format = cli.format;
+    let patterns = load_patterns(cli.patterns.as_deref(), cli.server_log);
+    let result = patterns.and_then(|patterns| match cli.command {
+        Command::Analyze { file } => analyze(&file, &patterns).map(|r| output::print(format, &r)),
+        Command::Stats { path, player } => {
+            stats(&path, player.as_deref(), &patterns).map(|r| output::print(format, &r))
+        }
+        Command::Import {
+            logs_dir,
+            db,
+            resume,
+        } => import_logs(&logs_dir, &db, resume, &patterns, format).and_then(|summary| {
+            output::print(format, &summary);
+            match summary.parse_errors {
+                0 => Ok(()),
+                n => Err(format!("{} logs could not be imported", n)),
+            }
+        }),
+        Command::Generate {
+            scenario,
+            seed,
+            out,
+        } => generate_logs(scenario.as_deref(), seed, &out).map(|r| output::print(format, &r)),
+        Command::Schema => {
+            println!("{:#}", schema());
+            Ok(())
+        }
+        Command::Watch { file, interval } => {
+            watch(&file, Duration::from_millis(interval), &patterns, format)
+        }
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            output::error(format, &e, None);
+            ExitCode::FAILURE
+        }
+    }
+}
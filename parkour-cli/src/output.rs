@@ -0,0 +1,130 @@
+use clap::ValueEnum;
+use parkour_core::client::Client;
+use parkour_core::duels::{DuelEvent, Game, MapStats, PersonalBest};
+use parkour_core::generate::Expected;
+use parkour_core::log_line::LogFormat;
+use parkour_core::schema;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable tables
+    #[default]
+    Table,
+    /// One JSON object per line
+    Json,
+    /// One indented JSON document
+    JsonPretty,
+}
+
+/// A line of `--format json` output. `kind` says which model the rest of
+/// the object is, e.g.
+///
+/// `{"kind": "personal_best", "player": "Steve", "time_ms": 41250, "game": 3, "protocol": 1}`
+#[derive(Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Row<'a> {
+    Session(&'a SessionInfo),
+    Game(&'a Game),
+    PersonalBest(&'a PersonalBest),
+    MapStats(&'a MapStats),
+    Import(&'a ImportSummary),
+    Generated(&'a GeneratedFile),
+    Event(&'a DuelEvent),
+    LogRestarted,
+}
+
+/// `{"path": "/home/me/.minecraft/logs/latest.log", "username": "Steve", "format": "classic", "client": "lunar", "games": 12, "first_game": "14:02:11", "last_game": "16:40:00"}`
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct SessionInfo {
+    pub path: String,
+    pub username: Option<String>,
+    pub format: Option<LogFormat>,
+    pub client: Client,
+    pub games: usize,
+    pub first_game: Option<String>,
+    pub last_game: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug, Default)]
+pub struct ImportSummary {
+    pub files_processed: usize,
+    pub already_imported: usize,
+    pub runs_found: usize,
+    pub runs_added: usize,
+    pub duplicates_skipped: usize,
+    pub parse_errors: usize,
+}
+
+/// A log written by `generate`, with what the parser should find in it:
+///
+/// `{"path": "logs/2024-05-01-1.log", "games": 5, "runs": 9, "finishes": 7}`
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct GeneratedFile {
+    pub path: String,
+    #[serde(flatten)]
+    pub expected: Expected,
+}
+
+/// Output of a subcommand: printed as tables, split into rows for
+/// `--format json`, or serialized whole for `--format json-pretty`.
+pub trait Report: Serialize + JsonSchema {
+    fn rows(&self) -> Vec<Row<'_>>;
+    fn print_table(&self);
+}
+
+// Everything printed as JSON carries the protocol version
+fn json<T: Serialize>(value: &T, pretty: bool) -> String {
+    let value = schema::stamp(serde_json::to_value(value).unwrap_or(Value::Null));
+    let json = if pretty {
+        serde_json::to_string_pretty(&value)
+    } else {
+        serde_json::to_string(&value)
+    };
+    json.unwrap_or_else(|_| "null".to_string())
+}
+
+pub fn print<R: Report>(format: Format, report: &R) {
+    match format {
+        Format::Table => report.print_table(),
+        Format::Json => {
+            for row in report.rows() {
+                println!("{}", json(&row, false));
+            }
+        }
+        Format::JsonPretty => println!("{}", json(report, true)),
+    }
+}
+
+/// Prints a single row as it happens, for streaming output.
+pub fn row(format: Format, row: &Row, table: impl FnOnce()) {
+    match format {
+        Format::Table => table(),
+        Format::Json => println!("{}", json(row, false)),
+        Format::JsonPretty => println!("{}", json(row, true)),
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct Error<'a> {
+    pub error: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Reports an error on stderr, as `{"error": "...", "path": "..."}` in the
+/// JSON formats.
+pub fn error(format: Format, message: &str, path: Option<&Path>) {
+    if format == Format::Table {
+        eprintln!("{}", message);
+        return;
+    }
+    let error = Error {
+        error: message,
+        path: path.map(|p| p.to_string_lossy().to_string()),
+    };
+    eprintln!("{}", json(&error, false));
+}
@@ -0,0 +1,43 @@
+use std::io::{self, IsTerminal, Write};
+
+const WIDTH: usize = 30;
+
+/// A one-line progress bar on stderr, drawn only when stderr is a terminal
+/// so redirected output stays clean.
+pub struct Progress {
+    total: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(total: usize, enabled: bool) -> Progress {
+        Progress {
+            total,
+            enabled: enabled && io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn update(&self, done: usize, label: &str) {
+        if !self.enabled {
+            return;
+        }
+        let filled = (done * WIDTH).checked_div(self.total).unwrap_or(WIDTH);
+        eprint!(
+            "\r\x1b[K[{}{}] {}/{} {}",
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            done,
+            self.total,
+            label
+        );
+        let _ = io::stderr().flush();
+    }
+
+    // Erases the bar, before printing a message or when done
+    pub fn clear(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[K");
+            let _ = io::stderr().flush();
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use parkour_core::db::{self, Db};
+use parkour_core::duels::Patterns;
+use parkour_core::import;
+use parkour_core::model::RunQuery;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn cli(args: &[&str]) -> Vec<Value> {
+    let output = Command::new(env!("CARGO_BIN_EXE_parkour-cli"))
+        .args(["--format", "json"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("parkour-cli-import-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn user_version(db: &Db) -> i64 {
+    db.conn()
+        .unwrap()
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .unwrap()
+}
+
+fn fingerprints(db: &Db) -> Vec<String> {
+    let conn = db.conn().unwrap();
+    let mut stmt = conn
+        .prepare("SELECT fingerprint FROM runs ORDER BY fingerprint")
+        .unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.collect::<Result<_, _>>().unwrap()
+}
+
+// The schema version in the file's header, before anything opens it
+fn stored_version(path: &Path) -> i64 {
+    let header = fs::read(path).unwrap();
+    i32::from_be_bytes(header[60..64].try_into().unwrap()) as i64
+}
+
+#[test]
+fn imported_database_opens_in_the_app() {
+    let dir = temp_dir();
+    let logs = dir.join("logs");
+    let path = dir.join("runs.db");
+    let generated = cli(&["generate", "--out", logs.to_str().unwrap()]);
+    let runs: u64 = generated.iter().map(|f| f["runs"].as_u64().unwrap()).sum();
+    assert!(runs > 0);
+
+    let import = ["import", "--logs-dir", logs.to_str().unwrap(), "--db"];
+    let summary = &cli(&[&import[..], &[path.to_str().unwrap()]].concat())[0];
+    assert_eq!(summary["kind"], "import");
+    assert_eq!(summary["files_processed"], generated.len());
+    assert_eq!(summary["runs_added"], runs);
+    assert_eq!(summary["parse_errors"], 0);
+
+    let latest = user_version(&Db::open_in_memory().unwrap());
+    assert_eq!(stored_version(&path), latest);
+    let db = Db::open(&path).unwrap();
+    assert_eq!(user_version(&db), latest);
+    let page = db::select_runs(&db.conn().unwrap(), &RunQuery::default()).unwrap();
+    assert_eq!(page.total as u64, runs);
+
+    // The app importing the same logs itself stores the same runs
+    let app = Db::open_in_memory().unwrap();
+    let patterns = Patterns::default();
+    for file in import::find_logs(&logs).unwrap() {
+        let conn = db.conn().unwrap();
+        assert!(db::is_imported(&conn, &file.key(), file.size, file.modified_ms).unwrap());
+        let content = import::read_log(&file.path).unwrap();
+        let again = import::import_log(&conn, &file, &content, &patterns).unwrap();
+        assert_eq!((again.runs_added, again.duplicates), (0, again.runs_found));
+        import::import_log(&app.conn().unwrap(), &file, &content, &patterns).unwrap();
+    }
+    assert_eq!(fingerprints(&db), fingerprints(&app));
+
+    let resumed = &cli(&[&import[..], &[path.to_str().unwrap(), "--resume"]].concat())[0];
+    assert_eq!(resumed["already_imported"], generated.len());
+    assert_eq!(resumed["runs_added"], 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
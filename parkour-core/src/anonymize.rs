@@ -0,0 +1,383 @@
+use crate::duels::{self, player_name, strip_colors, DuelEvent, Parsed, Parser, Patterns};
+use crate::lines::lines;
+use regex::{Captures, Regex, RegexBuilder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+// A player talking in chat, with a rank or in vanilla's <name> form; bare
+// "Word: text" is too often a server message to count
+static CHAT_SENDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[CHAT\]\s*(?:(?:\[[^\]]+\]\s*)+([A-Za-z0-9_]{2,16})|<([A-Za-z0-9_]{2,16})>):? ")
+        .unwrap()
+});
+static ANY_CHAT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[CHAT\]\s*(?:\[[^\]]+\]\s*)*<?([A-Za-z0-9_]{2,16})>?: ").unwrap()
+});
+static IPV4: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b").unwrap());
+static CONNECTING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(Connecting to |Connected to )([^\s,:/]+)").unwrap());
+// Hostnames anywhere else, limited to common TLDs so Java class names like
+// net.minecraft.client are left alone
+static HOSTNAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(^|[^\w.-])((?:[A-Za-z0-9-]+\.)+(?:net|com|org|io|gg|me|co|uk|de|eu|us|ru|fr|pl|br|xyz|club|fun|pro))\.?($|[^\w.-])",
+    )
+    .unwrap()
+});
+
+/// A text to scrub beyond the built-in rules:
+/// `{"pattern": "discord\\.gg/\\w+", "replacement": "[invite]"}`. The
+/// replacement can use the pattern's groups, as in `$1`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RedactionPattern {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+}
+
+/// What [`redact`] scrubs from a log:
+///
+/// `{"names": true, "keep_own_name": false, "addresses": true, "drop_chat": true, "patterns": []}`
+///
+/// `names` replaces player names with pseudonyms, except the logged-in
+/// player's with `keep_own_name`. `addresses` masks IP addresses and server
+/// hostnames, and `drop_chat` removes player chat. `patterns` are applied
+/// last, in order.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactionRules {
+    pub names: bool,
+    pub keep_own_name: bool,
+    pub addresses: bool,
+    pub drop_chat: bool,
+    pub patterns: Vec<RedactionPattern>,
+}
+
+impl Default for RedactionRules {
+    fn default() -> Self {
+        RedactionRules {
+            names: true,
+            keep_own_name: false,
+            addresses: true,
+            drop_chat: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl RedactionRules {
+    /// The custom patterns, compiled, failing on the first that isn't a
+    /// valid regex.
+    pub fn compile(&self) -> Result<Vec<Regex>, String> {
+        self.patterns
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                Regex::new(&p.pattern)
+                    .map_err(|e| format!("Invalid redaction pattern {}: {}", i + 1, e))
+            })
+            .collect()
+    }
+}
+
+/// What [`redact`] did. The pseudonyms themselves are only kept in memory.
+#[derive(Clone, Debug, Default)]
+pub struct Anonymized {
+    pub content: String,
+    pub names_replaced: usize,
+    pub hosts_masked: usize,
+    pub chat_lines_dropped: usize,
+    // Matches of the custom patterns
+    pub patterns_replaced: usize,
+}
+
+// Names in the order they first show up, so the same log always gets the
+// same pseudonyms, and the ones the log was logged in as
+fn find_names(content: &str, patterns: &Patterns) -> (Vec<String>, Vec<String>) {
+    let mut names: Vec<String> = Vec::new();
+    let mut own: Vec<String> = Vec::new();
+    let mut add = |name: &str| {
+        let name = name.trim_matches(|c: char| !c.is_alphanumeric() && c != '_');
+        if !name.is_empty() && name != "You" && !names.iter().any(|n| n.eq_ignore_ascii_case(name))
+        {
+            names.push(name.to_string());
+        }
+    };
+    let mut parser = Parser::new(patterns.clone());
+    for line in lines(content, 0) {
+        let event = parser.line(line.start as u64, line.text);
+        if let Some(user) = parser.username() {
+            add(user);
+            if !own.iter().any(|n| n.eq_ignore_ascii_case(user)) {
+                own.push(user.to_string());
+            }
+        }
+        if let Some(DuelEvent::Progress { player, .. }) = &event {
+            add(player);
+        }
+        if let Some(c) = CHAT_SENDER.captures(line.text) {
+            add(c.get(1).or(c.get(2)).map_or("", |m| m.as_str()));
+        }
+        if let Some(i) = line.text.find("Opponents:") {
+            let opponents = player_name(&line.text[i + "Opponents:".len()..]);
+            opponents.split([',', ' ']).for_each(&mut add);
+        }
+    }
+    (names, own)
+}
+
+// Lines the parser reads something from, which are kept even when chat is
+// dropped
+fn structural(parser: &mut Parser, offset: u64, text: &str) -> bool {
+    let before = parser.username().map(str::to_string);
+    parser.line(offset, text).is_some()
+        || text.contains("Opponents:")
+        || parser.username().map(str::to_string) != before
+}
+
+// Everything the parser finds, minus the names, to check nothing changed:
+// each game's title and its players' (checkpoint, timer) lists
+type Shape = Vec<(String, Vec<Vec<(u32, String)>>)>;
+
+fn shape(parsed: &Parsed) -> Shape {
+    parsed
+        .games
+        .iter()
+        .map(|game| {
+            let mut players: Vec<Vec<(u32, String)>> = game
+                .players
+                .values()
+                .map(|events| events.iter().map(|e| (e.cp, e.time.clone())).collect())
+                .collect();
+            players.sort();
+            (game.title.clone(), players)
+        })
+        .collect()
+}
+
+/// Rewrites a log for sharing in a bug report: player names become stable
+/// pseudonyms (`Player1`, `Player2`, ...), IP addresses and server hostnames
+/// are masked, and with `drop_chat` player chat is removed. Timestamps,
+/// system messages and every line the parser reads are kept, and the result
+/// is checked to parse into the same games as the original.
+pub fn anonymize(
+    content: &str,
+    patterns: &Patterns,
+    drop_chat: bool,
+) -> Result<Anonymized, String> {
+    let rules = RedactionRules {
+        drop_chat,
+        ..RedactionRules::default()
+    };
+    redact(content, patterns, &rules)
+}
+
+/// Like [`anonymize`], scrubbing what `rules` asks for. A custom pattern
+/// that would change how the log parses fails the same check.
+pub fn redact(
+    content: &str,
+    patterns: &Patterns,
+    rules: &RedactionRules,
+) -> Result<Anonymized, String> {
+    let custom = rules.compile()?;
+    let (mut names, own) = find_names(content, patterns);
+    let mut pseudonyms: HashMap<String, String> = HashMap::new();
+    let mut next = 1;
+    let replaced: Vec<&String> = names
+        .iter()
+        .filter(|_| rules.names)
+        .filter(|n| !rules.keep_own_name || !own.iter().any(|o| o.eq_ignore_ascii_case(n)))
+        .collect();
+    for name in replaced {
+        // Skip pseudonyms that are somebody's real name
+        let pseudonym = loop {
+            let candidate = format!("Player{}", next);
+            next += 1;
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(&candidate)) {
+                break candidate;
+            }
+        };
+        pseudonyms.insert(name.to_lowercase(), pseudonym);
+    }
+    // Only the replaced names are looked for from here on
+    names.retain(|n| pseudonyms.contains_key(&n.to_lowercase()));
+    let mut sorted = names.clone();
+    // Longer names first so "Alex" doesn't eat the start of "Alexander"
+    sorted.sort_by_key(|n| std::cmp::Reverse(n.len()));
+    let alternation = sorted
+        .iter()
+        .map(|n| regex::escape(n))
+        .collect::<Vec<_>>()
+        .join("|");
+    let name_re = (!names.is_empty())
+        .then(|| {
+            RegexBuilder::new(&format!(r"\b(?:{})\b", alternation))
+                .case_insensitive(true)
+                .build()
+        })
+        .transpose()
+        .map_err(|e| format!("Failed to anonymize log: {}", e))?;
+
+    let mut hosts: HashMap<String, String> = HashMap::new();
+    let mut host = |name: &str| -> String {
+        let count = hosts.len();
+        hosts
+            .entry(name.to_lowercase())
+            .or_insert_with(|| format!("server{}.invalid", count + 1))
+            .clone()
+    };
+
+    let mut result = Anonymized {
+        names_replaced: names.len(),
+        ..Default::default()
+    };
+    let mut parser = Parser::new(patterns.clone());
+    for line in lines(content, 0) {
+        let keep = structural(&mut parser, line.start as u64, line.text);
+        if rules.drop_chat && !keep && ANY_CHAT.is_match(&strip_colors(line.text)) {
+            result.chat_lines_dropped += 1;
+            continue;
+        }
+        let mut text = line.text.to_string();
+        if rules.addresses {
+            text = CONNECTING
+                .replace_all(&text, |c: &Captures| format!("{}{}", &c[1], host(&c[2])))
+                .to_string();
+            text = IPV4.replace_all(&text, "x.x.x.x").to_string();
+            text = HOSTNAME
+                .replace_all(&text, |c: &Captures| {
+                    format!("{}{}{}", &c[1], host(&c[2]), &c[3])
+                })
+                .to_string();
+        }
+        if let Some(re) = &name_re {
+            text = re
+                .replace_all(&text, |c: &Captures| {
+                    pseudonyms
+                        .get(&c[0].to_lowercase())
+                        .cloned()
+                        .unwrap_or_else(|| c[0].to_string())
+                })
+                .to_string();
+        }
+        for (re, pattern) in custom.iter().zip(&rules.patterns) {
+            result.patterns_replaced += re.find_iter(&text).count();
+            text = re
+                .replace_all(&text, pattern.replacement.as_str())
+                .to_string();
+        }
+        result.content.push_str(&text);
+        result
+            .content
+            .push_str(&content[line.start + line.text.len()..line.end]);
+    }
+    result.hosts_masked = hosts.len();
+
+    if shape(&duels::parse_with(content, patterns))
+        != shape(&duels::parse_with(&result.content, patterns))
+    {
+        return Err("Anonymizing would change how the log parses, so it wasn't exported".into());
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Anomalies, Scenario};
+    use crate::import::{analyze_log, log_file, read_log, LogAnalysis};
+    use crate::tail::decode;
+    use std::fs;
+    use std::path::Path;
+
+    // Each log as import reads it, from a file named like the generated one
+    fn analyze(dir: &Path, name: &str, content: &[u8]) -> LogAnalysis {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        let content = read_log(&path).unwrap();
+        analyze_log(&log_file(path).unwrap(), &content, &Patterns::default())
+    }
+
+    // What the runs are without the names and servers that get replaced.
+    // A game's runs are in player order, which the pseudonyms change.
+    fn runs(analysis: &LogAnalysis) -> Vec<(String, String, Option<i64>, bool, usize)> {
+        let mut runs: Vec<_> = analysis
+            .runs
+            .iter()
+            .map(|r| {
+                let splits = r.splits.len();
+                (
+                    r.map.clone(),
+                    r.started_at.clone(),
+                    r.time_ms,
+                    r.completed,
+                    splits,
+                )
+            })
+            .collect();
+        runs.sort();
+        runs
+    }
+
+    #[test]
+    fn anonymized_logs_parse_into_the_same_sessions_and_runs() {
+        let scenario = Scenario {
+            sessions: 3,
+            players_per_game: 2,
+            anomalies: Anomalies {
+                garbage: 2,
+                ..Anomalies::default()
+            },
+            ..Scenario::default()
+        };
+        let logs = generate(&scenario).unwrap();
+        let dir = std::env::temp_dir().join(format!("parkour-anonymize-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for drop_chat in [false, true] {
+            let (mut sessions, mut total) = (0, 0);
+            for log in &logs {
+                let anonymized = anonymize(
+                    &decode(log.content.clone()),
+                    &Patterns::default(),
+                    drop_chat,
+                )
+                .unwrap();
+                for name in ["Steve", "Alex", "Notch", "jeb_", "hypixel"] {
+                    assert!(!anonymized.content.contains(name), "{} was kept", name);
+                }
+                assert_eq!(anonymized.chat_lines_dropped > 0, drop_chat);
+                let before = analyze(&dir.join("original"), &log.name, &log.content);
+                let after = analyze(
+                    &dir.join("anonymized"),
+                    &log.name,
+                    anonymized.content.as_bytes(),
+                );
+                assert_eq!(before.session.is_some(), after.session.is_some());
+                assert_eq!(runs(&before), runs(&after));
+                sessions += after.session.is_some() as usize;
+                total += after.runs.len();
+            }
+            assert_eq!(sessions, 3);
+            assert_eq!(total, logs.iter().map(|l| l.expected.runs).sum::<usize>());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn redaction_that_changes_the_runs_is_refused() {
+        let log = decode(generate(&Scenario::default()).unwrap().remove(0).content);
+        let rules = RedactionRules {
+            patterns: vec![RedactionPattern {
+                pattern: "COMPLETED!".to_string(),
+                replacement: "[removed]".to_string(),
+            }],
+            ..RedactionRules::default()
+        };
+        assert!(redact(&log, &Patterns::default(), &rules).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+// Lines only a client or loader writes, checked in order. A client that
+// bundles a loader logs the loader's lines too, so clients come first.
+const TELLTALES: &[(Client, &[&str])] = &[
+    (Client::Lunar, &["]: [LC] ", "Lunar Client", "lunarclient"]),
+    (Client::Badlion, &["]: [BLC] ", "Badlion Client"]),
+    (
+        Client::Forge,
+        &[
+            "] [FML]:",
+            "MinecraftForge v",
+            "Forge Mod Loader",
+            "net.minecraftforge",
+        ],
+    ),
+    (Client::Fabric, &["Fabric Loader", "] [FabricLoader]:"]),
+    (Client::Optifine, &["]: [OptiFine] ", "OptiFine_"]),
+];
+
+// Loader chatter that appears all through a Forge log, on 1.8.9 in the
+// logger format while chat stays in the classic one
+const FORGE_NOISE: &[&str] = &[
+    "] [FML]:",
+    "] [forge]:",
+    "] [LaunchWrapper]:",
+    "] [net.minecraftforge",
+    "]: [OptiFine] ",
+];
+const BADLION_CHAT: &str = "]: [BLC] Chat: ";
+
+/// Game client a log was written by, told apart by lines only that client
+/// writes while it starts up. Each one is read the same way once its own
+/// lines are filtered out, see [`Client::filter`].
+///
+/// `vanilla` covers every log without a telltale line.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Client {
+    #[default]
+    Vanilla,
+    Lunar,
+    Badlion,
+    Forge,
+    Fabric,
+    Optifine,
+}
+
+impl Client {
+    pub fn name(self) -> &'static str {
+        match self {
+            Client::Vanilla => "vanilla",
+            Client::Lunar => "lunar",
+            Client::Badlion => "badlion",
+            Client::Forge => "forge",
+            Client::Fabric => "fabric",
+            Client::Optifine => "optifine",
+        }
+    }
+
+    // Position in TELLTALES, so a client can replace the loader it bundles
+    fn rank(self) -> usize {
+        TELLTALES
+            .iter()
+            .position(|(client, _)| *client == self)
+            .unwrap_or(TELLTALES.len())
+    }
+
+    /// Client a line gives away, if any. Chat is never a telltale, since
+    /// anyone can type "Lunar Client".
+    pub fn telltale(line: &str) -> Option<Client> {
+        if line.contains("[CHAT]") {
+            return None;
+        }
+        TELLTALES
+            .iter()
+            .find(|(_, marks)| marks.iter().any(|m| line.contains(m)))
+            .map(|(client, _)| *client)
+    }
+
+    /// The client `found` on a later line overrides this one if it's more
+    /// specific, e.g. Lunar over the OptiFine it ships with.
+    pub fn prefer(current: Option<Client>, found: Option<Client>) -> Option<Client> {
+        match (current, found) {
+            (Some(current), Some(found)) if found.rank() >= current.rank() => Some(current),
+            (current, None) => current,
+            (_, found) => found,
+        }
+    }
+
+    /// A line as the parser should see it, or `None` for the client's own
+    /// lines that can't hold anything the parser reads:
+    ///
+    /// - `lunar`: `[LC]` lines are dropped
+    /// - `badlion`: chat logged as `[BLC] Chat: ...` is unwrapped to `[CHAT] ...`
+    /// - `forge`: FML, LaunchWrapper and OptiFine lines are dropped
+    /// - `optifine`: `[OptiFine]` lines are dropped
+    pub fn filter(self, line: &str) -> Option<Cow<'_, str>> {
+        let drop = match self {
+            Client::Vanilla | Client::Fabric => false,
+            Client::Lunar => line.contains("]: [LC] "),
+            Client::Badlion => {
+                if let Some(i) = line.find(BADLION_CHAT) {
+                    let message = &line[i + BADLION_CHAT.len()..];
+                    return Some(format!("{}]: [CHAT] {}", &line[..i], message).into());
+                }
+                line.contains("]: [BLC] ")
+            }
+            Client::Forge => FORGE_NOISE.iter().any(|noise| line.contains(noise)),
+            Client::Optifine => line.contains("]: [OptiFine] "),
+        };
+        (!drop).then_some(Cow::Borrowed(line))
+    }
+}
+
+/// Most specific client any line in `content` gives away.
+pub fn detect(content: &str) -> Option<Client> {
+    crate::lines::lines(content, 0).fold(None, |client, line| {
+        Client::prefer(client, Client::telltale(line.text))
+    })
+}
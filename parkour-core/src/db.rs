@@ -0,0 +1,2039 @@
+use crate::duels::FINISH;
+use crate::model::{
+    Goal, GoalProgress, GoldSplit, LeaderboardEntry, MapAlias, Marker, NewGoal, NewRun, Run,
+    RunDetail, RunEdit, RunOriginal, RunPage, RunQuery, Session, SortBy, SortDir, Split, SumOfBest,
+};
+use crate::names;
+use crate::timestamps;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        map TEXT NOT NULL,
+        username TEXT NOT NULL,
+        server TEXT,
+        started_at TEXT NOT NULL,
+        time_ms INTEGER,
+        completed INTEGER NOT NULL DEFAULT 0,
+        fails INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE run_tags (
+        run_id INTEGER NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (run_id, tag)
+    );
+    CREATE INDEX idx_runs_map ON runs(map);
+    CREATE INDEX idx_runs_username ON runs(username);
+    CREATE INDEX idx_runs_started_at ON runs(started_at);
+", "
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL,
+        server TEXT,
+        started_at TEXT NOT NULL,
+        ended_at TEXT,
+        active_ms INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX idx_sessions_started_at ON sessions(started_at);
+", "
+    CREATE TABLE splits (
+        run_id INTEGER NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+        checkpoint INTEGER NOT NULL,
+        name TEXT,
+        time_ms INTEGER NOT NULL,
+        PRIMARY KEY (run_id, checkpoint)
+    );
+", "
+    CREATE TABLE markers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        created_at TEXT NOT NULL,
+        label TEXT,
+        session_id INTEGER REFERENCES sessions(id) ON DELETE SET NULL,
+        map TEXT,
+        run_started_at TEXT,
+        run_elapsed_ms INTEGER
+    );
+    CREATE INDEX idx_markers_session ON markers(session_id);
+    CREATE INDEX idx_markers_run ON markers(map, run_started_at);
+", "
+    ALTER TABLE runs ADD COLUMN started_at_utc TEXT;
+    ALTER TABLE sessions ADD COLUMN started_at_utc TEXT;
+    ALTER TABLE sessions ADD COLUMN ended_at_utc TEXT;
+    UPDATE runs SET started_at_utc = utc_time(started_at);
+    UPDATE sessions SET started_at_utc = utc_time(started_at), ended_at_utc = utc_time(ended_at);
+    CREATE INDEX idx_runs_started_at_utc ON runs(started_at_utc);
+    CREATE TRIGGER runs_utc AFTER INSERT ON runs WHEN NEW.started_at_utc IS NULL BEGIN
+        UPDATE runs SET started_at_utc = utc_time(NEW.started_at) WHERE id = NEW.id;
+    END;
+    CREATE TRIGGER runs_utc_update AFTER UPDATE OF started_at ON runs BEGIN
+        UPDATE runs SET started_at_utc = utc_time(NEW.started_at) WHERE id = NEW.id;
+    END;
+    CREATE TRIGGER sessions_utc AFTER INSERT ON sessions BEGIN
+        UPDATE sessions SET started_at_utc = COALESCE(NEW.started_at_utc, utc_time(NEW.started_at)),
+            ended_at_utc = COALESCE(NEW.ended_at_utc, utc_time(NEW.ended_at))
+        WHERE id = NEW.id;
+    END;
+    CREATE TRIGGER sessions_utc_update AFTER UPDATE OF started_at, ended_at ON sessions BEGIN
+        UPDATE sessions SET started_at_utc = utc_time(NEW.started_at), ended_at_utc = utc_time(NEW.ended_at)
+        WHERE id = NEW.id;
+    END;
+", "
+    ALTER TABLE runs ADD COLUMN map_key TEXT;
+    UPDATE runs SET map_key = name_key(map);
+    CREATE INDEX idx_runs_map_key ON runs(map_key);
+    CREATE TRIGGER runs_map_key AFTER INSERT ON runs BEGIN
+        UPDATE runs SET map_key = name_key(NEW.map) WHERE id = NEW.id;
+    END;
+    CREATE TRIGGER runs_map_key_update AFTER UPDATE OF map ON runs BEGIN
+        UPDATE runs SET map_key = name_key(NEW.map) WHERE id = NEW.id;
+    END;
+", "
+    ALTER TABLE sessions ADD COLUMN end_reason TEXT;
+", "
+    ALTER TABLE runs ADD COLUMN fingerprint TEXT;
+    CREATE UNIQUE INDEX idx_runs_fingerprint ON runs(fingerprint);
+    CREATE TABLE imported_logs (
+        path TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        modified_ms INTEGER,
+        session_id INTEGER REFERENCES sessions(id) ON DELETE SET NULL,
+        imported_at TEXT NOT NULL
+    );
+", "
+    ALTER TABLE sessions ADD COLUMN client TEXT;
+", "
+    CREATE TABLE goals (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        map TEXT NOT NULL,
+        username TEXT,
+        target_time_ms INTEGER NOT NULL,
+        deadline TEXT,
+        created_at TEXT NOT NULL,
+        achieved_at TEXT,
+        achieved_run_id INTEGER REFERENCES runs(id) ON DELETE SET NULL,
+        achieved_time_ms INTEGER
+    );
+", "
+    ALTER TABLE runs ADD COLUMN excluded INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE runs ADD COLUMN original TEXT;
+    ALTER TABLE runs ADD COLUMN edited_at TEXT;
+", "
+    CREATE TABLE map_aliases (
+        alias_key TEXT PRIMARY KEY,
+        alias TEXT NOT NULL,
+        canonical TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    ALTER TABLE runs ADD COLUMN merged_from TEXT;
+", "
+    ALTER TABLE runs ADD COLUMN practice INTEGER NOT NULL DEFAULT 0;
+", "
+    ALTER TABLE runs ADD COLUMN invalidated_by TEXT;
+", "
+    CREATE INDEX idx_runs_map_key_username ON runs(map_key, username);
+", "
+    CREATE TABLE pb_resets (
+        map_key TEXT PRIMARY KEY,
+        map TEXT NOT NULL,
+        reset_at TEXT NOT NULL
+    );
+", "
+    ALTER TABLE runs ADD COLUMN world TEXT;
+"];
+
+pub(crate) const DEFAULT_LIMIT: u32 = 100;
+pub(crate) const MAX_LIMIT: u32 = 1000;
+
+pub struct Db(pub Mutex<Connection>);
+
+impl Db {
+    pub fn open(path: &Path) -> Result<Db, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
+        Db::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Db, String> {
+        let conn =
+            Connection::open_in_memory().map_err(|e| format!("Failed to open database: {}", e))?;
+        Db::init(conn)
+    }
+
+    pub fn conn(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.0
+            .lock()
+            .map_err(|_| "Database lock poisoned".to_string())
+    }
+
+    fn init(conn: Connection) -> Result<Db, String> {
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(|e| format!("Failed to configure database: {}", e))?;
+        // The stored local times only make sense in this machine's time zone,
+        // so their UTC copies are derived here rather than in SQL
+        conn.create_scalar_function("utc_time", 1, FunctionFlags::SQLITE_UTF8, |ctx| {
+            let local: Option<String> = ctx.get(0)?;
+            Ok(local.as_deref().and_then(timestamps::utc_column))
+        })
+        .map_err(|e| format!("Failed to configure database: {}", e))?;
+        conn.create_scalar_function(
+            "name_key",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let name: Option<String> = ctx.get(0)?;
+                Ok(name.as_deref().map(names::name_key))
+            },
+        )
+        .map_err(|e| format!("Failed to configure database: {}", e))?;
+        conn.create_scalar_function(
+            "alias_key",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let name: Option<String> = ctx.get(0)?;
+                Ok(name.as_deref().map(names::alias_key))
+            },
+        )
+        .map_err(|e| format!("Failed to configure database: {}", e))?;
+        migrate(&conn)?;
+        Ok(Db(Mutex::new(conn)))
+    }
+}
+
+fn migrate(conn: &Connection) -> Result<(), String> {
+    let version: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let target = i as i64 + 1;
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Migration {} failed: {}", target, e))?;
+        tx.execute_batch(sql)
+            .and_then(|_| tx.pragma_update(None, "user_version", target))
+            .and_then(|_| tx.commit())
+            .map_err(|e| format!("Migration {} failed: {}", target, e))?;
+    }
+    Ok(())
+}
+
+pub fn parse_date(field: &str, value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("{} must be a YYYY-MM-DD date, got \"{}\"", field, value))
+}
+
+pub(crate) type Bounds = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+// The instants `q`'s date bounds stand for, once its bounds are checked.
+// `date_to` is inclusive, so its instant is already past the end.
+pub(crate) fn query_bounds(q: &RunQuery) -> Result<Bounds, String> {
+    let bound = |field, value: &Option<String>, end| {
+        value
+            .as_deref()
+            .map(|v| timestamps::parse_bound(field, v, end))
+            .transpose()
+    };
+    let from = bound("date_from", &q.date_from, false)?;
+    let to = bound("date_to", &q.date_to, true)?;
+    if let (Some(f), Some(t), Some(df), Some(dt)) = (from, to, &q.date_from, &q.date_to) {
+        if f >= t {
+            return Err(format!("date_from ({}) is after date_to ({})", df, dt));
+        }
+    }
+    if let (Some(min), Some(max)) = (q.min_time_ms, q.max_time_ms) {
+        if min > max {
+            return Err(format!(
+                "min_time_ms ({}) is greater than max_time_ms ({})",
+                min, max
+            ));
+        }
+    }
+    if q.min_time_ms.is_some_and(|v| v < 0) || q.max_time_ms.is_some_and(|v| v < 0) {
+        return Err("Time bounds must not be negative".into());
+    }
+    Ok((from, to))
+}
+
+fn build_filter(q: &RunQuery) -> Result<(String, Vec<Value>), String> {
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut params: Vec<Value> = Vec::new();
+    let (from, to) = query_bounds(q)?;
+
+    if let Some(map) = &q.map {
+        clauses.push("map_key = name_key(?)");
+        params.push(Value::Text(map.clone()));
+    }
+    if let Some(username) = &q.username {
+        clauses.push("username = ?");
+        params.push(Value::Text(username.clone()));
+    }
+    if let Some(server) = &q.server {
+        clauses.push("server = ?");
+        params.push(Value::Text(server.clone()));
+    }
+    if let Some(world) = &q.world {
+        clauses.push("world = ?");
+        params.push(Value::Text(world.clone()));
+    }
+    if let Some(f) = from {
+        clauses.push("started_at_utc >= ?");
+        params.push(Value::Text(timestamps::format_utc(f)));
+    }
+    if let Some(t) = to {
+        clauses.push("started_at_utc < ?");
+        params.push(Value::Text(timestamps::format_utc(t)));
+    }
+    if let Some(min) = q.min_time_ms {
+        clauses.push("time_ms >= ?");
+        params.push(Value::Integer(min));
+    }
+    if let Some(max) = q.max_time_ms {
+        clauses.push("time_ms <= ?");
+        params.push(Value::Integer(max));
+    }
+    if q.completed_only {
+        clauses.push("completed = 1");
+    }
+    if let Some(practice) = q.practice {
+        clauses.push("practice = ?");
+        params.push(Value::Integer(practice as i64));
+    }
+    match q.invalidated {
+        Some(true) => clauses.push("invalidated_by IS NOT NULL"),
+        Some(false) => clauses.push("invalidated_by IS NULL"),
+        None => {}
+    }
+    for tag in &q.tags {
+        clauses.push("EXISTS (SELECT 1 FROM run_tags t WHERE t.run_id = runs.id AND t.tag = ?)");
+        params.push(Value::Text(tag.clone()));
+    }
+
+    let sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", clauses.join(" AND "))
+    };
+    Ok((sql, params))
+}
+
+fn order_clause(by: SortBy, dir: SortDir) -> String {
+    let dir = match dir {
+        SortDir::Asc => "ASC",
+        SortDir::Desc => "DESC",
+    };
+    match by {
+        SortBy::Date => format!(" ORDER BY started_at_utc {}, id {}", dir, dir),
+        // Runs without a time sort last whichever direction is requested
+        SortBy::Time => format!(" ORDER BY time_ms IS NULL, time_ms {}, id {}", dir, dir),
+        SortBy::Map => format!(" ORDER BY map_key {}, id {}", dir, dir),
+        SortBy::Fails => format!(" ORDER BY fails {}, id {}", dir, dir),
+    }
+}
+
+const RUN_COLUMNS: &str = "id, map, username, server, started_at, time_ms, completed, fails,
+    started_at_utc, excluded, original, edited_at, practice, invalidated_by, world";
+
+fn run_from_row(row: &rusqlite::Row) -> rusqlite::Result<Run> {
+    Ok(Run {
+        id: row.get(0)?,
+        map: row.get(1)?,
+        username: row.get(2)?,
+        server: row.get(3)?,
+        world: row.get(14)?,
+        started_at: row.get(4)?,
+        time_ms: row.get(5)?,
+        completed: row.get(6)?,
+        fails: row.get(7)?,
+        started_at_utc: row.get(8)?,
+        tags: Vec::new(),
+        excluded: row.get(9)?,
+        original: row
+            .get::<_, Option<String>>(10)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        edited_at: row.get(11)?,
+        practice: row.get(12)?,
+        invalidated_by: row
+            .get::<_, Option<String>>(13)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+    })
+}
+
+fn run_tags(conn: &Connection, run_id: i64) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare_cached("SELECT tag FROM run_tags WHERE run_id = ? ORDER BY tag")?;
+    let tags = stmt.query_map([run_id], |row| row.get(0))?;
+    tags.collect()
+}
+
+pub fn select_runs(conn: &Connection, q: &RunQuery) -> Result<RunPage, String> {
+    let (filter, params) = build_filter(q)?;
+    let err = |e: rusqlite::Error| format!("Run query failed: {}", e);
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM runs{}", filter),
+            params_from_iter(params.iter()),
+            |row| row.get(0),
+        )
+        .map_err(err)?;
+
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let offset = q.offset.unwrap_or(0);
+    let sql = format!(
+        "SELECT {} FROM runs{}{} LIMIT ? OFFSET ?",
+        RUN_COLUMNS,
+        filter,
+        order_clause(q.sort_by, q.sort_dir)
+    );
+    let mut page_params = params;
+    page_params.push(Value::Integer(limit as i64));
+    page_params.push(Value::Integer(offset as i64));
+
+    let mut stmt = conn.prepare(&sql).map_err(err)?;
+    let rows = stmt
+        .query_map(params_from_iter(page_params.iter()), run_from_row)
+        .map_err(err)?;
+
+    let mut runs = Vec::new();
+    for row in rows {
+        let mut run = row.map_err(err)?;
+        run.tags = run_tags(conn, run.id).map_err(err)?;
+        runs.push(run);
+    }
+    Ok(RunPage { runs, total })
+}
+
+pub fn runs_for_map(conn: &Connection, map: &str, username: &str) -> Result<Vec<Run>, String> {
+    let map = canonical_map(conn, map)?;
+    let err = |e: rusqlite::Error| format!("Run query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM runs WHERE map_key = name_key(?) AND username = ? AND excluded = 0
+            ORDER BY started_at_utc, id",
+            RUN_COLUMNS
+        ))
+        .map_err(err)?;
+    let rows = stmt
+        .query_map([map.as_str(), username], run_from_row)
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+pub fn run_splits(conn: &Connection, run_id: i64) -> Result<Vec<Split>, String> {
+    let err = |e: rusqlite::Error| format!("Split query failed: {}", e);
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT checkpoint, name, time_ms FROM splits WHERE run_id = ? ORDER BY checkpoint",
+        )
+        .map_err(err)?;
+    let rows = stmt
+        .query_map([run_id], |row| {
+            Ok(Split {
+                checkpoint: row.get(0)?,
+                name: row.get(1)?,
+                time_ms: row.get(2)?,
+            })
+        })
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+// Runs from before a map's PB was reset, see reset_pb, don't count for its
+// PB or golds. `runs` is the runs table's name in the query.
+fn after_pb_reset(runs: &str) -> String {
+    format!(
+        "NOT EXISTS (SELECT 1 FROM pb_resets p WHERE p.map_key = {0}.map_key
+            AND ({0}.started_at_utc IS NULL OR {0}.started_at_utc <= p.reset_at))",
+        runs
+    )
+}
+
+// Whether run `runs` counts for its map's PB, as SQL: a completion that
+// isn't excluded, practice, invalidated or from before a PB reset
+fn counts_for_pb(runs: &str) -> String {
+    format!(
+        "{0}.completed = 1 AND {0}.time_ms IS NOT NULL AND {0}.excluded = 0
+            AND {0}.practice = 0 AND {0}.invalidated_by IS NULL AND {1}",
+        runs,
+        after_pb_reset(runs)
+    )
+}
+
+/// SQL that's true when run `runs` was a PB when it was set: it counts for
+/// [`personal_best`] and no earlier run of the player's on the map that
+/// counts was as fast.
+pub fn set_pb(runs: &str) -> String {
+    format!(
+        "{counts} AND NOT EXISTS (SELECT 1 FROM runs b
+            WHERE b.map_key = {r}.map_key AND b.username = {r}.username AND {earlier}
+                AND b.time_ms <= {r}.time_ms
+                AND (b.started_at_utc < {r}.started_at_utc
+                    OR (b.started_at_utc IS {r}.started_at_utc AND b.id < {r}.id)))",
+        counts = counts_for_pb(runs),
+        earlier = counts_for_pb("b"),
+        r = runs
+    )
+}
+
+pub fn personal_best(
+    conn: &Connection,
+    map: &str,
+    username: Option<&str>,
+) -> Result<Option<RunDetail>, String> {
+    let map = canonical_map(conn, map)?;
+    let err = |e: rusqlite::Error| format!("PB query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM runs WHERE map_key = name_key(?1) AND (?2 IS NULL OR username = ?2)
+                AND {}
+            ORDER BY time_ms, started_at_utc, id LIMIT 1",
+            RUN_COLUMNS,
+            counts_for_pb("runs")
+        ))
+        .map_err(err)?;
+    let mut rows = stmt
+        .query_map(rusqlite::params![map, username], run_from_row)
+        .map_err(err)?;
+    match rows.next() {
+        Some(run) => {
+            let mut run = run.map_err(err)?;
+            run.tags = run_tags(conn, run.id).map_err(err)?;
+            let splits = run_splits(conn, run.id)?;
+            Ok(Some(RunDetail { run, splits }))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Best run of each player on `map` that counts for a PB, fastest first.
+/// Ties go to whoever set the time first.
+pub fn player_leaderboard(conn: &Connection, map: &str) -> Result<Vec<LeaderboardEntry>, String> {
+    let map = canonical_map(conn, map)?;
+    let err = |e: rusqlite::Error| format!("Leaderboard query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, attempts FROM (
+                SELECT runs.*, COUNT(*) OVER (PARTITION BY username) AS attempts,
+                    SUM(completed = 1 AND time_ms IS NOT NULL AND excluded = 0 AND practice = 0
+                        AND invalidated_by IS NULL) OVER (PARTITION BY username) AS counted,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY username
+                        ORDER BY completed = 1 AND time_ms IS NOT NULL AND excluded = 0
+                            AND practice = 0 AND invalidated_by IS NULL DESC,
+                            time_ms, started_at_utc, id
+                    ) AS place
+                FROM runs WHERE map_key = name_key(?1)
+            )
+            WHERE place = 1 AND counted > 0
+            ORDER BY time_ms, started_at_utc, id",
+            RUN_COLUMNS
+        ))
+        .map_err(err)?;
+    let rows = stmt
+        .query_map([map], |row| Ok((run_from_row(row)?, row.get(15)?)))
+        .map_err(err)?;
+    let mut entries = Vec::new();
+    for row in rows {
+        let (run, attempts): (Run, i64) = row.map_err(err)?;
+        entries.push(LeaderboardEntry {
+            rank: entries.len() as i64 + 1,
+            username: run.username.clone(),
+            run,
+            attempts,
+        });
+    }
+    Ok(entries)
+}
+
+pub fn get_run(conn: &Connection, run_id: i64) -> Result<Option<RunDetail>, String> {
+    let err = |e: rusqlite::Error| format!("Run query failed: {}", e);
+    let run = conn
+        .query_row(
+            &format!("SELECT {} FROM runs WHERE id = ?", RUN_COLUMNS),
+            [run_id],
+            run_from_row,
+        )
+        .optional()
+        .map_err(err)?;
+    let Some(mut run) = run else {
+        return Ok(None);
+    };
+    run.tags = run_tags(conn, run.id).map_err(err)?;
+    let splits = run_splits(conn, run.id)?;
+    Ok(Some(RunDetail { run, splits }))
+}
+
+// Best completed time on the same map set before `run` started.
+pub fn previous_best_time(conn: &Connection, run: &Run) -> Result<Option<i64>, String> {
+    conn.query_row(
+        &format!(
+            "SELECT MIN(time_ms) FROM runs
+            WHERE map_key = name_key(?1) AND username = ?2 AND {}
+                AND (started_at_utc < ?3 OR (started_at_utc IS ?3 AND id < ?4))",
+            counts_for_pb("runs")
+        ),
+        rusqlite::params![run.map, run.username, run.started_at_utc, run.id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("PB query failed: {}", e))
+}
+
+pub fn attempt_count(conn: &Connection, map: &str, username: Option<&str>) -> Result<i64, String> {
+    let map = canonical_map(conn, map)?;
+    conn.query_row(
+        "SELECT COUNT(*) FROM runs
+        WHERE map_key = name_key(?1) AND (?2 IS NULL OR username = ?2) AND excluded = 0",
+        rusqlite::params![map, username],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Run query failed: {}", e))
+}
+
+/// Starts `map`'s PB and golds over from `reset_at` (UTC): runs started up
+/// to then stay stored and in stats, but no longer count as bests.
+pub fn reset_pb(conn: &Connection, map: &str, reset_at: &str) -> Result<(), String> {
+    let map = canonical_map(conn, map)?;
+    conn.execute(
+        "INSERT INTO pb_resets (map_key, map, reset_at) VALUES (name_key(?1), ?1, ?2)
+        ON CONFLICT (map_key) DO UPDATE SET map = excluded.map, reset_at = excluded.reset_at",
+        rusqlite::params![map, reset_at],
+    )
+    .map_err(|e| format!("Failed to reset PB: {}", e))?;
+    Ok(())
+}
+
+/// When `map`'s PB was last reset, if it ever was.
+pub fn pb_reset_at(conn: &Connection, map: &str) -> Result<Option<String>, String> {
+    let map = canonical_map(conn, map)?;
+    conn.query_row(
+        "SELECT reset_at FROM pb_resets WHERE map_key = name_key(?)",
+        [map],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("PB query failed: {}", e))
+}
+
+const GOAL_COLUMNS: &str = "id, map, username, target_time_ms, deadline, created_at,
+    achieved_at, achieved_run_id, achieved_time_ms";
+
+fn goal_from_row(row: &rusqlite::Row) -> rusqlite::Result<Goal> {
+    Ok(Goal {
+        id: row.get(0)?,
+        map: row.get(1)?,
+        username: row.get(2)?,
+        target_time_ms: row.get(3)?,
+        deadline: row.get(4)?,
+        created_at: row.get(5)?,
+        achieved_at: row.get(6)?,
+        achieved_run_id: row.get(7)?,
+        achieved_time_ms: row.get(8)?,
+    })
+}
+
+// Completed runs a goal counts, as SQL over `runs r` and `goals g`
+const GOAL_RUNS: &str = "r.map_key = name_key(g.map)
+    AND (g.username IS NULL OR r.username = g.username)
+    AND r.completed = 1 AND r.time_ms IS NOT NULL AND r.excluded = 0 AND r.practice = 0
+    AND r.invalidated_by IS NULL";
+
+fn check_goal(goal: &NewGoal) -> Result<(), String> {
+    if goal.map.trim().is_empty() {
+        return Err("Goal map is empty".to_string());
+    }
+    if goal.target_time_ms <= 0 {
+        return Err(format!(
+            "target_time_ms must be positive, got {}",
+            goal.target_time_ms
+        ));
+    }
+    if let Some(deadline) = &goal.deadline {
+        parse_date("deadline", deadline)?;
+    }
+    Ok(())
+}
+
+pub fn insert_goal(conn: &Connection, goal: &NewGoal, created_at: &str) -> Result<i64, String> {
+    check_goal(goal)?;
+    conn.execute(
+        "INSERT INTO goals (map, username, target_time_ms, deadline, created_at)
+        VALUES (?, ?, ?, ?, ?)",
+        rusqlite::params![
+            goal.map,
+            goal.username,
+            goal.target_time_ms,
+            goal.deadline,
+            created_at
+        ],
+    )
+    .map_err(|e| format!("Failed to save goal: {}", e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Edits a goal. It stays achieved only while its map, player and target
+/// are unchanged. Returns false if there's no such goal.
+pub fn update_goal(conn: &Connection, goal_id: i64, goal: &NewGoal) -> Result<bool, String> {
+    check_goal(goal)?;
+    // Every right-hand side sees the row as it was before the update
+    let same = "map IS ?1 AND username IS ?2 AND target_time_ms IS ?3";
+    conn.execute(
+        &format!(
+            "UPDATE goals SET
+                achieved_at = CASE WHEN {same} THEN achieved_at END,
+                achieved_run_id = CASE WHEN {same} THEN achieved_run_id END,
+                achieved_time_ms = CASE WHEN {same} THEN achieved_time_ms END,
+                map = ?1, username = ?2, target_time_ms = ?3, deadline = ?4
+            WHERE id = ?5",
+            same = same
+        ),
+        rusqlite::params![
+            goal.map,
+            goal.username,
+            goal.target_time_ms,
+            goal.deadline,
+            goal_id
+        ],
+    )
+    .map(|n| n > 0)
+    .map_err(|e| format!("Failed to save goal: {}", e))
+}
+
+pub fn delete_goal(conn: &Connection, goal_id: i64) -> Result<bool, String> {
+    conn.execute("DELETE FROM goals WHERE id = ?", [goal_id])
+        .map(|n| n > 0)
+        .map_err(|e| format!("Failed to delete goal: {}", e))
+}
+
+pub fn get_goal(conn: &Connection, goal_id: i64) -> Result<Option<Goal>, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM goals WHERE id = ?", GOAL_COLUMNS),
+        [goal_id],
+        goal_from_row,
+    )
+    .optional()
+    .map_err(|e| format!("Goal query failed: {}", e))
+}
+
+/// Every goal with the best time toward it, open goals first, the nearest
+/// deadline first among them. A goal is overdue once its deadline has passed
+/// on `today` without it being achieved.
+pub fn goals(conn: &Connection, today: NaiveDate) -> Result<Vec<GoalProgress>, String> {
+    let err = |e: rusqlite::Error| format!("Goal query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, (SELECT MIN(r.time_ms) FROM runs r WHERE {}) FROM goals g
+            ORDER BY achieved_at IS NOT NULL, deadline IS NULL, deadline, id",
+            GOAL_COLUMNS, GOAL_RUNS
+        ))
+        .map_err(err)?;
+    let today = today.format("%Y-%m-%d").to_string();
+    let rows = stmt
+        .query_map([], |row| {
+            let goal = goal_from_row(row)?;
+            let stored: Option<i64> = row.get(9)?;
+            // A time reached live may not be stored as a run yet
+            let best_ms = match (stored, goal.achieved_time_ms) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            let achieved = goal.achieved_at.is_some();
+            Ok(GoalProgress {
+                best_ms,
+                gap_ms: best_ms.map(|best| (best - goal.target_time_ms).max(0)),
+                achieved,
+                overdue: !achieved && goal.deadline.as_ref().is_some_and(|d| *d < today),
+                goal,
+            })
+        })
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+/// Marks open goals a stored run meets as achieved by the earliest such run,
+/// e.g. after importing older logs. Returns the goals it achieved.
+pub fn achieve_goals(conn: &Connection) -> Result<Vec<Goal>, String> {
+    let err = |e: rusqlite::Error| format!("Failed to update goals: {}", e);
+    let sql = format!(
+        "UPDATE goals AS g SET (achieved_at, achieved_run_id, achieved_time_ms) = (
+            SELECT r.started_at, r.id, r.time_ms FROM runs r
+            WHERE {runs} AND r.time_ms <= g.target_time_ms
+            ORDER BY r.started_at_utc, r.id LIMIT 1
+        )
+        WHERE achieved_at IS NULL AND EXISTS (
+            SELECT 1 FROM runs r WHERE {runs} AND r.time_ms <= g.target_time_ms
+        )
+        RETURNING {columns}",
+        runs = GOAL_RUNS,
+        columns = GOAL_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).map_err(err)?;
+    let rows = stmt.query_map([], goal_from_row).map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+/// Marks open goals on `map` that a completion in `time_ms` meets as
+/// achieved `at`, for a run that isn't stored. Goals for a named player only
+/// count it when `username` is that player or unknown.
+pub fn achieve_goals_by(
+    conn: &Connection,
+    map: &str,
+    username: Option<&str>,
+    time_ms: i64,
+    at: &str,
+) -> Result<Vec<Goal>, String> {
+    let map = canonical_map(conn, map)?;
+    let err = |e: rusqlite::Error| format!("Failed to update goals: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "UPDATE goals SET achieved_at = ?4, achieved_time_ms = ?3
+            WHERE achieved_at IS NULL AND name_key(map) = name_key(?1)
+                AND (username IS NULL OR ?2 IS NULL OR username = ?2)
+                AND target_time_ms >= ?3
+            RETURNING {}",
+            GOAL_COLUMNS
+        ))
+        .map_err(err)?;
+    let rows = stmt
+        .query_map(rusqlite::params![map, username, time_ms, at], goal_from_row)
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+// A stored run reduced to what golds are made of
+struct Segments {
+    id: i64,
+    finish_ms: Option<i64>,
+    splits: Vec<Split>,
+}
+
+impl Segments {
+    fn checkpoints(&self) -> Vec<i64> {
+        self.splits.iter().map(|s| s.checkpoint).collect()
+    }
+
+    // Segment times along `route`, as far as this run followed it
+    fn along(&self, route: &[i64]) -> Vec<(i64, Option<String>, i64)> {
+        let mut segments = Vec::new();
+        let mut previous = 0;
+        for (split, checkpoint) in self.splits.iter().zip(route) {
+            if split.checkpoint != *checkpoint {
+                return segments;
+            }
+            let name = split.name.clone();
+            segments.push((split.checkpoint, name, split.time_ms - previous));
+            previous = split.time_ms;
+        }
+        if let Some(finish) = self.finish_ms.filter(|_| self.splits.len() == route.len()) {
+            segments.push((FINISH as i64, None, finish - previous));
+        }
+        segments
+    }
+}
+
+/// Best segment times on `map`, by `username` or anyone, per checkpoint
+/// route, the most run route first. Maps whose checkpoints vary between runs
+/// get one route per distinct set of checkpoints, so segments from different
+/// routes aren't mixed.
+///
+/// A route is made of the completed runs that passed exactly its
+/// checkpoints; unfinished runs add the segments they got through while on
+/// it. Golds are worked out from the stored splits on every call, so runs
+/// deleted or imported later are always accounted for.
+pub fn sum_of_best(
+    conn: &Connection,
+    map: &str,
+    username: Option<&str>,
+) -> Result<Vec<SumOfBest>, String> {
+    let map = canonical_map(conn, map)?;
+    let err = |e: rusqlite::Error| format!("Sum of best query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT r.id, CASE WHEN r.completed = 1 THEN r.time_ms END, s.checkpoint, s.name, s.time_ms
+            FROM runs r LEFT JOIN splits s ON s.run_id = r.id
+            WHERE r.map_key = name_key(?1) AND (?2 IS NULL OR r.username = ?2)
+                AND r.excluded = 0 AND r.practice = 0 AND r.invalidated_by IS NULL AND {}
+            ORDER BY r.started_at_utc, r.id, s.checkpoint",
+            after_pb_reset("r")
+        ))
+        .map_err(err)?;
+    let rows = stmt
+        .query_map(rusqlite::params![map, username], |row| {
+            let split = match row.get::<_, Option<i64>>(2)? {
+                Some(checkpoint) => Some(Split {
+                    checkpoint,
+                    name: row.get(3)?,
+                    time_ms: row.get(4)?,
+                }),
+                None => None,
+            };
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?, split))
+        })
+        .map_err(err)?;
+    let mut runs: Vec<Segments> = Vec::new();
+    for row in rows {
+        let (id, finish_ms, split) = row.map_err(err)?;
+        if runs.last().map(|r| r.id) != Some(id) {
+            runs.push(Segments {
+                id,
+                finish_ms,
+                splits: Vec::new(),
+            });
+        }
+        if let (Some(split), Some(run)) = (split, runs.last_mut()) {
+            run.splits.push(split);
+        }
+    }
+
+    let mut routes: Vec<(Vec<i64>, i64)> = Vec::new();
+    for run in runs.iter().filter(|r| r.finish_ms.is_some()) {
+        let checkpoints = run.checkpoints();
+        match routes.iter_mut().find(|(route, _)| *route == checkpoints) {
+            Some((_, count)) => *count += 1,
+            None => routes.push((checkpoints, 1)),
+        }
+    }
+    // Stable, so equally run routes stay in the order first seen
+    routes.sort_by_key(|(_, count)| -count);
+
+    Ok(routes
+        .into_iter()
+        .map(|(checkpoints, count)| {
+            let mut golds: Vec<GoldSplit> = Vec::new();
+            // Finished runs only count on the route they took
+            let on_route = runs
+                .iter()
+                .filter(|r| r.finish_ms.is_none() || r.checkpoints() == checkpoints);
+            for run in on_route {
+                for (checkpoint, name, time_ms) in run.along(&checkpoints) {
+                    // A split logged out of order isn't a segment
+                    if time_ms < 0 {
+                        continue;
+                    }
+                    match golds.iter_mut().find(|g| g.checkpoint == checkpoint) {
+                        // Ties keep the earlier run
+                        Some(gold) if gold.time_ms <= time_ms => {}
+                        Some(gold) => {
+                            *gold = GoldSplit {
+                                checkpoint,
+                                name,
+                                time_ms,
+                                run_id: run.id,
+                            }
+                        }
+                        None => golds.push(GoldSplit {
+                            checkpoint,
+                            name,
+                            time_ms,
+                            run_id: run.id,
+                        }),
+                    }
+                }
+            }
+            golds.sort_by_key(|g| g.checkpoint);
+            let total_ms = (golds.len() == checkpoints.len() + 1)
+                .then(|| golds.iter().map(|g| g.time_ms).sum());
+            SumOfBest {
+                checkpoints,
+                runs: count,
+                golds,
+                total_ms,
+            }
+        })
+        .collect())
+}
+
+/// Whether a segment of `segment_ms` into `checkpoint` (9999 for the finish),
+/// after passing the checkpoints in `passed`, beats the gold on every route
+/// it could be part of. A segment no route has a gold for yet counts as one.
+pub fn is_gold(routes: &[SumOfBest], passed: &[i64], checkpoint: i64, segment_ms: i64) -> bool {
+    routes
+        .iter()
+        .filter(|r| r.checkpoints.starts_with(passed))
+        .filter(|r| match r.checkpoints.get(passed.len()) {
+            Some(next) => *next == checkpoint,
+            None => checkpoint == FINISH as i64,
+        })
+        .filter_map(|r| r.golds.iter().find(|g| g.checkpoint == checkpoint))
+        .all(|gold| segment_ms < gold.time_ms)
+}
+
+const SESSION_COLUMNS: &str = "id, username, server, started_at, ended_at, active_ms,
+    started_at_utc, ended_at_utc, end_reason, client";
+
+fn session_from_row(row: &rusqlite::Row) -> rusqlite::Result<Session> {
+    Ok(Session {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        server: row.get(2)?,
+        started_at: row.get(3)?,
+        ended_at: row.get(4)?,
+        started_at_utc: row.get(6)?,
+        ended_at_utc: row.get(7)?,
+        active_ms: row.get(5)?,
+        end_reason: row.get(8)?,
+        client: row.get(9)?,
+    })
+}
+
+pub fn get_session(conn: &Connection, session_id: i64) -> Result<Option<Session>, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM sessions WHERE id = ?", SESSION_COLUMNS),
+        [session_id],
+        session_from_row,
+    )
+    .optional()
+    .map_err(|e| format!("Session query failed: {}", e))
+}
+
+/// Every session, newest first.
+pub fn list_sessions(conn: &Connection) -> Result<Vec<Session>, String> {
+    let err = |e: rusqlite::Error| format!("Session query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM sessions ORDER BY started_at DESC, id DESC",
+            SESSION_COLUMNS
+        ))
+        .map_err(err)?;
+    let rows = stmt.query_map([], session_from_row).map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+// Runs aren't linked to sessions directly; a session owns its player's runs
+// started inside its time window (open-ended while the session is live).
+pub fn session_runs(conn: &Connection, session: &Session) -> Result<Vec<Run>, String> {
+    let err = |e: rusqlite::Error| format!("Run query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM runs WHERE username = ?1 AND started_at_utc >= ?2
+                AND (?3 IS NULL OR started_at_utc <= ?3) AND excluded = 0
+            ORDER BY started_at_utc, id",
+            RUN_COLUMNS
+        ))
+        .map_err(err)?;
+    let rows = stmt
+        .query_map(
+            rusqlite::params![
+                session.username,
+                session.started_at_utc,
+                session.ended_at_utc
+            ],
+            run_from_row,
+        )
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+pub fn active_session(conn: &Connection) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT id FROM sessions WHERE ended_at IS NULL ORDER BY started_at_utc DESC, id DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Session query failed: {}", e))
+}
+
+pub fn end_session(
+    conn: &Connection,
+    session_id: i64,
+    ended_at: &str,
+    reason: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE sessions SET ended_at = ?2, end_reason = ?3 WHERE id = ?1 AND ended_at IS NULL",
+        rusqlite::params![session_id, ended_at, reason],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("Failed to end session: {}", e))
+}
+
+pub fn insert_marker(conn: &Connection, marker: &Marker) -> Result<i64, String> {
+    conn.execute(
+        "INSERT INTO markers (created_at, label, session_id, map, run_started_at, run_elapsed_ms)
+        VALUES (?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            marker.created_at,
+            marker.label,
+            marker.session_id,
+            marker.map,
+            marker.run_started_at,
+            marker.run_elapsed_ms
+        ],
+    )
+    .map_err(|e| format!("Failed to save marker: {}", e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn count_markers(conn: &Connection, session_id: Option<i64>) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM markers WHERE session_id IS ?",
+        [session_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Marker query failed: {}", e))
+}
+
+const MARKER_COLUMNS: &str =
+    "id, created_at, label, session_id, map, run_started_at, run_elapsed_ms";
+
+fn marker_from_row(row: &rusqlite::Row) -> rusqlite::Result<Marker> {
+    Ok(Marker {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        label: row.get(2)?,
+        session_id: row.get(3)?,
+        map: row.get(4)?,
+        run_started_at: row.get(5)?,
+        run_elapsed_ms: row.get(6)?,
+    })
+}
+
+// A marker belongs to a run when it was dropped while that run was in
+// progress, which the run's map and start time identify.
+pub fn markers(
+    conn: &Connection,
+    session_id: Option<i64>,
+    run: Option<&Run>,
+) -> Result<Vec<Marker>, String> {
+    let err = |e: rusqlite::Error| format!("Marker query failed: {}", e);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM markers WHERE (?1 IS NULL OR session_id = ?1)
+                AND (?2 IS NULL OR (map = ?2 AND run_started_at = ?3))
+            ORDER BY created_at, id",
+            MARKER_COLUMNS
+        ))
+        .map_err(err)?;
+    let rows = stmt
+        .query_map(
+            rusqlite::params![session_id, run.map(|r| &r.map), run.map(|r| &r.started_at)],
+            marker_from_row,
+        )
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+/// Whether `run` falls in a session that hasn't ended yet.
+pub fn in_active_session(conn: &Connection, run: &Run) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sessions
+        WHERE ended_at IS NULL AND username = ? AND started_at_utc <= ?",
+        rusqlite::params![run.username, run.started_at_utc],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|n| n > 0)
+    .map_err(|e| format!("Session query failed: {}", e))
+}
+
+// Goals a run achieved are open again once it's changed, until another run
+// meets them
+fn reopen_goals(conn: &Connection, run_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE goals SET achieved_at = NULL, achieved_run_id = NULL, achieved_time_ms = NULL
+        WHERE achieved_run_id = ?",
+        [run_id],
+    )?;
+    Ok(())
+}
+
+/// Changes the fields set in `edit`, keeping what the run was before its
+/// first edit in `original`. Returns `None` if there's no such run.
+pub fn update_run(
+    conn: &Connection,
+    run_id: i64,
+    edit: &RunEdit,
+    edited_at: &str,
+) -> Result<Option<Run>, String> {
+    let Some(RunDetail { run, .. }) = get_run(conn, run_id)? else {
+        return Ok(None);
+    };
+    let map = edit.map.as_deref().map(str::trim).unwrap_or(&run.map);
+    if map.is_empty() {
+        return Err("Map name is empty".to_string());
+    }
+    if let Some(time_ms) = edit.time_ms.filter(|&t| t <= 0) {
+        return Err(format!("time_ms must be positive, got {}", time_ms));
+    }
+    let original = run.original.clone().unwrap_or(RunOriginal {
+        map: run.map.clone(),
+        time_ms: run.time_ms,
+        excluded: run.excluded,
+        practice: run.practice,
+        invalidated_by: run.invalidated_by.clone(),
+    });
+    let original =
+        serde_json::to_string(&original).map_err(|e| format!("Failed to save run: {}", e))?;
+
+    let err = |e: rusqlite::Error| format!("Failed to save run: {}", e);
+    let tx = conn.unchecked_transaction().map_err(err)?;
+    tx.execute(
+        "UPDATE runs SET map = ?1, time_ms = ?2, excluded = ?3, original = ?4, edited_at = ?5,
+            practice = ?6, invalidated_by = ?7,
+            merged_from = CASE WHEN map = ?1 THEN merged_from END
+        WHERE id = ?8",
+        rusqlite::params![
+            map,
+            edit.time_ms.or(run.time_ms),
+            edit.excluded.unwrap_or(run.excluded),
+            original,
+            edited_at,
+            edit.practice.unwrap_or(run.practice),
+            invalidated_json(edit.invalidated_by.as_ref().unwrap_or(&run.invalidated_by)),
+            run_id
+        ],
+    )
+    .map_err(err)?;
+    reopen_goals(&tx, run_id).map_err(err)?;
+    achieve_goals(&tx)?;
+    tx.commit().map_err(err)?;
+    Ok(get_run(conn, run_id)?.map(|detail| detail.run))
+}
+
+/// Deletes a run with its splits and tags. Returns false if there's no such
+/// run.
+pub fn delete_run(conn: &Connection, run_id: i64) -> Result<bool, String> {
+    let err = |e: rusqlite::Error| format!("Failed to delete run: {}", e);
+    let tx = conn.unchecked_transaction().map_err(err)?;
+    reopen_goals(&tx, run_id).map_err(err)?;
+    let deleted = tx
+        .execute("DELETE FROM runs WHERE id = ?", [run_id])
+        .map_err(err)?;
+    achieve_goals(&tx)?;
+    tx.commit().map_err(err)?;
+    Ok(deleted > 0)
+}
+
+/// The name runs on `map` are stored under: the map it was merged into, or
+/// `map` itself.
+pub fn canonical_map(conn: &Connection, map: &str) -> Result<String, String> {
+    conn.query_row(
+        "SELECT canonical FROM map_aliases WHERE alias_key = alias_key(?)",
+        [map],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(|canonical| canonical.unwrap_or_else(|| map.to_string()))
+    .map_err(|e| format!("Alias query failed: {}", e))
+}
+
+pub fn map_aliases(conn: &Connection) -> Result<Vec<MapAlias>, String> {
+    let err = |e: rusqlite::Error| format!("Alias query failed: {}", e);
+    let mut stmt = conn
+        .prepare(
+            "SELECT a.alias, a.canonical, a.created_at,
+                (SELECT COUNT(*) FROM runs r
+                WHERE alias_key(r.merged_from) = a.alias_key AND r.map_key = name_key(a.canonical))
+            FROM map_aliases a ORDER BY name_key(a.canonical), a.alias_key",
+        )
+        .map_err(err)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(MapAlias {
+                alias: row.get(0)?,
+                canonical: row.get(1)?,
+                created_at: row.get(2)?,
+                runs: row.get(3)?,
+            })
+        })
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+// Goals met by a run that has since moved to another map are open again,
+// until a run on their own map meets them
+fn reopen_moved_goals(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE goals SET achieved_at = NULL, achieved_run_id = NULL, achieved_time_ms = NULL
+        WHERE achieved_run_id IN (SELECT r.id FROM runs r WHERE r.map_key != name_key(goals.map))",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Stores the runs and goals on each of `aliases` under `canonical`, and
+/// records the aliases so runs inserted later are stored under it too. Runs
+/// keep the name they were logged under in `merged_from` for
+/// [`split_alias`]. An alias that was itself a canonical name takes its own
+/// aliases along.
+pub fn merge_maps(
+    conn: &Connection,
+    canonical: &str,
+    aliases: &[String],
+    created_at: &str,
+) -> Result<Vec<MapAlias>, String> {
+    let canonical = canonical.trim();
+    if canonical.is_empty() {
+        return Err("Map name is empty".to_string());
+    }
+    let target = canonical_map(conn, canonical)?;
+    if names::alias_key(&target) != names::alias_key(canonical) {
+        return Err(format!("{} is already merged into {}", canonical, target));
+    }
+    let aliases: Vec<&str> = aliases
+        .iter()
+        .map(|a| a.trim())
+        .filter(|a| *a != canonical)
+        .collect();
+    if aliases.is_empty() {
+        return Err("No map names to merge".to_string());
+    }
+    if aliases.iter().any(|a| a.is_empty()) {
+        return Err("Map name is empty".to_string());
+    }
+
+    let err = |e: rusqlite::Error| format!("Failed to merge maps: {}", e);
+    let tx = conn.unchecked_transaction().map_err(err)?;
+    for alias in aliases {
+        tx.execute(
+            "UPDATE map_aliases SET canonical = ?1 WHERE alias_key(canonical) = alias_key(?2)",
+            [canonical, alias],
+        )
+        .map_err(err)?;
+        tx.execute(
+            "INSERT INTO map_aliases (alias_key, alias, canonical, created_at)
+            VALUES (alias_key(?1), ?1, ?2, ?3)
+            ON CONFLICT(alias_key) DO UPDATE SET
+                alias = excluded.alias, canonical = excluded.canonical, created_at = excluded.created_at",
+            [alias, canonical, created_at],
+        )
+        .map_err(err)?;
+        tx.execute(
+            "UPDATE runs SET merged_from = COALESCE(merged_from, map), map = ?1
+            WHERE alias_key(map) = alias_key(?2) AND map != ?1",
+            [canonical, alias],
+        )
+        .map_err(err)?;
+        tx.execute(
+            "UPDATE goals SET map = ?1 WHERE alias_key(map) = alias_key(?2)",
+            [canonical, alias],
+        )
+        .map_err(err)?;
+    }
+    reopen_moved_goals(&tx).map_err(err)?;
+    achieve_goals(&tx)?;
+    tx.commit().map_err(err)?;
+    map_aliases(conn)
+}
+
+/// Undoes the merge of `alias`: its runs go back to the name they were
+/// logged under, and later runs are no longer renamed. Goals stay on the
+/// canonical name. Returns false if `alias` isn't merged into anything.
+pub fn split_alias(conn: &Connection, alias: &str) -> Result<bool, String> {
+    let err = |e: rusqlite::Error| format!("Failed to split alias: {}", e);
+    let tx = conn.unchecked_transaction().map_err(err)?;
+    let canonical: Option<String> = tx
+        .query_row(
+            "DELETE FROM map_aliases WHERE alias_key = alias_key(?) RETURNING canonical",
+            [alias],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(err)?;
+    let Some(canonical) = canonical else {
+        return Ok(false);
+    };
+    // Runs edited onto another map since are left where they are
+    tx.execute(
+        "UPDATE runs SET map = merged_from, merged_from = NULL
+        WHERE alias_key(merged_from) = alias_key(?1) AND map_key = name_key(?2)",
+        [alias, &canonical],
+    )
+    .map_err(err)?;
+    reopen_moved_goals(&tx).map_err(err)?;
+    achieve_goals(&tx)?;
+    tx.commit().map_err(err)?;
+    Ok(true)
+}
+
+pub fn latest_run_id(conn: &Connection) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT id FROM runs ORDER BY started_at_utc DESC, id DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Run query failed: {}", e))
+}
+
+/// Identifies a run regardless of which tool recorded it, so importing the
+/// same log twice doesn't add it again: FNV-1a over the player, map key,
+/// local start time and time, as hex.
+pub fn run_fingerprint(run: &NewRun) -> String {
+    let key = format!(
+        "{}\n{}\n{}\n{}",
+        names::name_key(&run.username),
+        names::name_key(&run.map),
+        run.started_at,
+        run.time_ms.map_or(String::new(), |ms| ms.to_string())
+    );
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+// Rules are stored as a JSON array, NULL for a run none fired in
+fn invalidated_json(rules: &[String]) -> Option<String> {
+    serde_json::to_string(rules)
+        .ok()
+        .filter(|_| !rules.is_empty())
+}
+
+/// Saves a run and its splits, or returns `None` if a run with the same
+/// fingerprint is already stored. A run on a merged map is stored under the
+/// canonical name.
+pub fn insert_run(conn: &Connection, run: &NewRun) -> Result<Option<i64>, String> {
+    let map = canonical_map(conn, &run.map)?;
+    let merged_from = (map != run.map).then_some(&run.map);
+    let err = |e: rusqlite::Error| format!("Failed to save run: {}", e);
+    let inserted = conn
+        .execute(
+            "INSERT INTO runs (map, username, server, started_at, time_ms, completed, fails, fingerprint,
+                merged_from, practice, invalidated_by, world)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(fingerprint) DO NOTHING",
+            rusqlite::params![
+                map,
+                run.username,
+                run.server,
+                run.started_at,
+                run.time_ms,
+                run.completed,
+                run.fails,
+                run_fingerprint(run),
+                merged_from,
+                run.practice,
+                invalidated_json(&run.invalidated_by),
+                run.world
+            ],
+        )
+        .map_err(err)?;
+    if inserted == 0 {
+        return Ok(None);
+    }
+    let id = conn.last_insert_rowid();
+    for split in &run.splits {
+        conn.execute(
+            "INSERT OR IGNORE INTO splits (run_id, checkpoint, name, time_ms) VALUES (?, ?, ?, ?)",
+            rusqlite::params![id, split.checkpoint, split.name, split.time_ms],
+        )
+        .map_err(err)?;
+    }
+    Ok(Some(id))
+}
+
+pub fn insert_session(
+    conn: &Connection,
+    username: &str,
+    started_at: &str,
+    ended_at: Option<&str>,
+    active_ms: i64,
+    client: &str,
+) -> Result<i64, String> {
+    conn.execute(
+        "INSERT INTO sessions (username, started_at, ended_at, active_ms, client)
+        VALUES (?, ?, ?, ?, ?)",
+        rusqlite::params![username, started_at, ended_at, active_ms, client],
+    )
+    .map_err(|e| format!("Failed to save session: {}", e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+// A log counts as imported only while its size and modification time are
+// unchanged, so a latest.log that kept growing is read again.
+pub fn is_imported(
+    conn: &Connection,
+    path: &str,
+    size: u64,
+    modified_ms: Option<i64>,
+) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM imported_logs WHERE path = ? AND size = ? AND modified_ms IS ?",
+        rusqlite::params![path, size as i64, modified_ms],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|n| n > 0)
+    .map_err(|e| format!("Import query failed: {}", e))
+}
+
+/// Session created for a previously imported log, so importing it again
+/// reuses it.
+pub fn imported_session(conn: &Connection, path: &str) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT session_id FROM imported_logs WHERE path = ?",
+        [path],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+    .map_err(|e| format!("Import query failed: {}", e))
+}
+
+pub fn mark_imported(
+    conn: &Connection,
+    path: &str,
+    size: u64,
+    modified_ms: Option<i64>,
+    session_id: Option<i64>,
+    imported_at: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO imported_logs (path, size, modified_ms, session_id, imported_at)
+        VALUES (?, ?, ?, ?, ?)",
+        rusqlite::params![path, size as i64, modified_ms, session_id, imported_at],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("Failed to record import: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(map: &str, started_at: &str, time_ms: Option<i64>, splits: &[(i64, i64)]) -> NewRun {
+        NewRun {
+            map: map.into(),
+            username: "me".into(),
+            server: None,
+            world: None,
+            started_at: started_at.into(),
+            time_ms,
+            completed: time_ms.is_some(),
+            fails: 0,
+            practice: false,
+            invalidated_by: Vec::new(),
+            splits: splits
+                .iter()
+                .map(|&(checkpoint, time_ms)| Split {
+                    checkpoint,
+                    name: None,
+                    time_ms,
+                })
+                .collect(),
+        }
+    }
+
+    fn goal(target_time_ms: i64, deadline: Option<&str>) -> NewGoal {
+        NewGoal {
+            map: "sandstorm".into(),
+            username: None,
+            target_time_ms,
+            deadline: deadline.map(Into::into),
+        }
+    }
+
+    fn best(conn: &Connection, map: &str) -> Option<i64> {
+        personal_best(conn, map, None)
+            .unwrap()
+            .and_then(|pb| pb.run.time_ms)
+    }
+
+    #[test]
+    fn duplicate_runs_are_skipped() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        let tower = run("Tower", "2026-01-01 10:00:00", Some(30_000), &[(1, 10_000)]);
+        let id = insert_run(&conn, &tower).unwrap().unwrap();
+        assert!(insert_run(&conn, &tower).unwrap().is_none());
+        assert_eq!(get_run(&conn, id).unwrap().unwrap().splits.len(), 1);
+        assert_eq!(latest_run_id(&conn).unwrap(), Some(id));
+    }
+
+    #[test]
+    fn practice_and_invalidated_runs_are_not_bests() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        insert_run(
+            &conn,
+            &run("Tower", "2026-01-01 10:00:00", Some(60_000), &[(1, 30_000)]),
+        )
+        .unwrap();
+        let mut practice = run("Tower", "2026-01-01 11:00:00", Some(10_000), &[(1, 5_000)]);
+        practice.practice = true;
+        let practice = insert_run(&conn, &practice).unwrap().unwrap();
+        let mut flying = run("Tower", "2026-01-01 12:00:00", Some(1_000), &[(1, 500)]);
+        flying.invalidated_by = vec!["fly".into()];
+        insert_run(&conn, &flying).unwrap();
+        assert_eq!(best(&conn, "tower"), Some(60_000));
+        assert_eq!(
+            sum_of_best(&conn, "Tower", None).unwrap()[0].total_ms,
+            Some(60_000)
+        );
+
+        let edit = RunEdit {
+            practice: Some(false),
+            ..Default::default()
+        };
+        let edited = update_run(&conn, practice, &edit, "2026-01-02 00:00:00")
+            .unwrap()
+            .unwrap();
+        assert!(edited.original.unwrap().practice);
+        assert_eq!(best(&conn, "tower"), Some(10_000));
+    }
+
+    #[test]
+    fn sum_of_best_per_route() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        insert_run(
+            &conn,
+            &run(
+                "Tower",
+                "2026-01-02 10:00:00",
+                Some(30_000),
+                &[(1, 10_000), (2, 20_000)],
+            ),
+        )
+        .unwrap();
+        insert_run(
+            &conn,
+            &run(
+                "Tower",
+                "2026-01-03 10:00:00",
+                Some(29_000),
+                &[(1, 11_000), (2, 19_000)],
+            ),
+        )
+        .unwrap();
+        insert_run(
+            &conn,
+            &run("Tower", "2026-01-04 10:00:00", None, &[(1, 9_000)]),
+        )
+        .unwrap();
+        insert_run(
+            &conn,
+            &run(
+                "Tower",
+                "2026-01-05 10:00:00",
+                Some(25_000),
+                &[(1, 8_000), (3, 16_000)],
+            ),
+        )
+        .unwrap();
+        let routes = sum_of_best(&conn, "tower", None).unwrap();
+        assert_eq!(routes[0].checkpoints, [1, 2]);
+        // Segments: 1 from the unfinished run, 2 from the second, the finish
+        // from the first
+        assert_eq!(routes[0].total_ms, Some(9_000 + 8_000 + 10_000));
+        assert_eq!(routes[1].total_ms, Some(8_000 + 8_000 + 9_000));
+
+        assert!(is_gold(&routes, &[], 1, 7_999));
+        assert!(!is_gold(&routes, &[], 1, 8_000));
+        assert!(is_gold(&routes, &[1], 2, 7_999));
+        assert!(!is_gold(&routes, &[1, 2], 9_999, 10_000));
+        // A route with no runs yet has nothing to beat
+        assert!(is_gold(&routes, &[1, 4], 5, 1));
+    }
+
+    #[test]
+    fn goals_are_achieved_by_past_and_live_runs() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        for (day, time_ms) in [(1, 65_000), (2, 59_000), (3, 58_000)] {
+            let started_at = format!("2026-01-0{} 10:00:00", day);
+            insert_run(&conn, &run("Sandstorm", &started_at, Some(time_ms), &[])).unwrap();
+        }
+        let reached = insert_goal(&conn, &goal(60_000, None), "2026-01-05 10:00:00").unwrap();
+        let overdue = insert_goal(
+            &conn,
+            &goal(50_000, Some("2026-01-04")),
+            "2026-01-05 10:00:00",
+        )
+        .unwrap();
+        assert!(insert_goal(&conn, &goal(0, None), "x").is_err());
+        assert!(insert_goal(&conn, &goal(1, Some("soon")), "x").is_err());
+
+        let achieved = achieve_goals(&conn).unwrap();
+        assert_eq!(achieved.len(), 1);
+        // The first run under the target, not the best one
+        assert_eq!(
+            achieved[0].achieved_at.as_deref(),
+            Some("2026-01-02 10:00:00")
+        );
+        assert!(achieve_goals(&conn).unwrap().is_empty());
+
+        let today = NaiveDate::from_ymd_opt(2026, 1, 10).unwrap();
+        let progress = goals(&conn, today).unwrap();
+        assert_eq!(progress[0].goal.id, overdue);
+        assert_eq!(progress[0].gap_ms, Some(8_000));
+        assert!(progress[0].overdue);
+        assert!(progress[1].achieved);
+
+        // A new deadline keeps it achieved, a new target doesn't
+        assert!(update_goal(&conn, reached, &goal(60_000, Some("2026-02-01"))).unwrap());
+        assert!(get_goal(&conn, reached)
+            .unwrap()
+            .unwrap()
+            .achieved_at
+            .is_some());
+        assert!(update_goal(&conn, reached, &goal(57_000, None)).unwrap());
+        assert!(get_goal(&conn, reached)
+            .unwrap()
+            .unwrap()
+            .achieved_at
+            .is_none());
+
+        let live =
+            achieve_goals_by(&conn, "SANDSTORM", None, 49_000, "2026-01-11 12:00:00").unwrap();
+        assert_eq!(live.len(), 2);
+        assert!(delete_goal(&conn, reached).unwrap());
+        assert!(!delete_goal(&conn, reached).unwrap());
+    }
+
+    #[test]
+    fn edits_keep_the_original_run() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        let slow = insert_run(
+            &conn,
+            &run("Sandstorm", "2026-01-01 10:00:00", Some(65_000), &[]),
+        )
+        .unwrap()
+        .unwrap();
+        let fast = insert_run(
+            &conn,
+            &run(
+                "Sandstorm",
+                "2026-01-02 10:00:00",
+                Some(50_000),
+                &[(1, 1_000)],
+            ),
+        )
+        .unwrap()
+        .unwrap();
+        let target = insert_goal(&conn, &goal(70_000, None), "x").unwrap();
+        assert_eq!(achieve_goals(&conn).unwrap()[0].achieved_run_id, Some(slow));
+
+        let exclude = RunEdit {
+            excluded: Some(true),
+            ..Default::default()
+        };
+        let edited = update_run(&conn, slow, &exclude, "2026-02-01 00:00:00")
+            .unwrap()
+            .unwrap();
+        assert!(edited.excluded && !edited.original.unwrap().excluded);
+        // The goal moves on to the next run that reached it
+        assert_eq!(
+            get_goal(&conn, target).unwrap().unwrap().achieved_run_id,
+            Some(fast)
+        );
+
+        let rename = RunEdit {
+            map: Some(" Desert ".into()),
+            ..Default::default()
+        };
+        let edited = update_run(&conn, fast, &rename, "y").unwrap().unwrap();
+        assert_eq!(edited.map, "Desert");
+        assert_eq!(edited.original.unwrap().map, "Sandstorm");
+        let blank = RunEdit {
+            map: Some(" ".into()),
+            ..Default::default()
+        };
+        assert!(update_run(&conn, fast, &blank, "y").is_err());
+        assert!(update_run(&conn, 99, &RunEdit::default(), "y")
+            .unwrap()
+            .is_none());
+
+        assert!(delete_run(&conn, fast).unwrap());
+        assert!(!delete_run(&conn, fast).unwrap());
+        let splits: i64 = conn
+            .query_row("SELECT COUNT(*) FROM splits", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(splits, 0);
+    }
+
+    #[test]
+    fn merged_maps_share_bests() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        insert_run(
+            &conn,
+            &run("Sandstorm", "2026-01-01 10:00:00", Some(60_000), &[]),
+        )
+        .unwrap();
+        insert_run(
+            &conn,
+            &run("sandstorm", "2026-01-01 11:00:00", Some(59_000), &[]),
+        )
+        .unwrap();
+        insert_run(
+            &conn,
+            &run("Sandstorm (Hard)", "2026-01-01 12:00:00", Some(50_000), &[]),
+        )
+        .unwrap();
+        assert_eq!(best(&conn, "Sandstorm"), Some(59_000));
+
+        let aliases = [
+            "§6sandstorm (HARD)".into(),
+            "sandstorm".into(),
+            "Sandstorm".into(),
+        ];
+        assert_eq!(
+            merge_maps(&conn, "Sandstorm", &aliases, "now")
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(best(&conn, "Sandstorm"), Some(50_000));
+        assert_eq!(best(&conn, "sandstorm (hard)"), Some(50_000));
+        assert_eq!(attempt_count(&conn, "Sandstorm", None).unwrap(), 3);
+        let id = insert_run(
+            &conn,
+            &run(
+                "§6Sandstorm (Hard)",
+                "2026-01-02 12:00:00",
+                Some(49_000),
+                &[],
+            ),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(get_run(&conn, id).unwrap().unwrap().run.map, "Sandstorm");
+        assert!(merge_maps(&conn, "Sandstorm (hard)", &["x".into()], "now").is_err());
+
+        // Merging the canonical name moves its aliases along
+        merge_maps(&conn, "Desert", &["Sandstorm".into()], "now").unwrap();
+        assert!(map_aliases(&conn)
+            .unwrap()
+            .iter()
+            .all(|a| a.canonical == "Desert"));
+        assert_eq!(attempt_count(&conn, "Desert", None).unwrap(), 4);
+        assert!(split_alias(&conn, "SANDSTORM (hard)").unwrap());
+        assert!(!split_alias(&conn, "SANDSTORM (hard)").unwrap());
+        // Both runs go back to the names they were played under, and only
+        // aliases ignore color codes
+        assert_eq!(attempt_count(&conn, "Desert", None).unwrap(), 2);
+        assert_eq!(attempt_count(&conn, "Sandstorm (Hard)", None).unwrap(), 1);
+        assert!(merge_maps(&conn, "Desert", &["Desert".into()], "now").is_err());
+    }
+
+    #[test]
+    fn pb_reset_hides_earlier_runs() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        insert_run(
+            &conn,
+            &run("Tower", "2026-01-02 10:00:00", Some(30_000), &[(1, 15_000)]),
+        )
+        .unwrap();
+        let cut = timestamps::utc_column("2026-01-03 10:00:00").unwrap();
+        reset_pb(&conn, "TOWER", &cut).unwrap();
+        assert_eq!(pb_reset_at(&conn, "tower").unwrap(), Some(cut));
+        assert_eq!(best(&conn, "tower"), None);
+        assert!(sum_of_best(&conn, "tower", None).unwrap().is_empty());
+
+        insert_run(
+            &conn,
+            &run("Tower", "2026-01-04 10:00:00", Some(40_000), &[(1, 20_000)]),
+        )
+        .unwrap();
+        let id = insert_run(
+            &conn,
+            &run("Tower", "2026-01-05 10:00:00", Some(35_000), &[(1, 17_000)]),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(best(&conn, "tower"), Some(35_000));
+        let latest = get_run(&conn, id).unwrap().unwrap().run;
+        assert_eq!(previous_best_time(&conn, &latest).unwrap(), Some(40_000));
+        // Runs from before the reset are still listed
+        assert_eq!(select_runs(&conn, &RunQuery::default()).unwrap().total, 3);
+    }
+
+    #[test]
+    fn set_pb_agrees_with_personal_best() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        let mut practice = run("Tower", "2026-01-01 09:00:00", Some(10_000), &[]);
+        practice.practice = true;
+        let mut flying = run("Tower", "2026-01-01 09:30:00", Some(5_000), &[]);
+        flying.invalidated_by = vec!["fly".into()];
+        let times = [
+            ("2026-01-01 10:00:00", Some(60_000)),
+            ("2026-01-01 11:00:00", Some(70_000)),
+            ("2026-01-01 12:00:00", None),
+            ("2026-01-01 13:00:00", Some(50_000)),
+            ("2026-01-03 10:00:00", Some(55_000)),
+            ("2026-01-03 11:00:00", Some(55_000)),
+        ];
+        let mut runs = vec![practice, flying];
+        runs.extend(times.map(|(at, time)| run("Tower", at, time, &[])));
+        for run in &runs {
+            insert_run(&conn, run).unwrap();
+        }
+        let pbs = |conn: &Connection| -> Vec<i64> {
+            let sql = format!(
+                "SELECT r.time_ms FROM runs r WHERE {} ORDER BY r.started_at_utc",
+                set_pb("r")
+            );
+            let mut stmt = conn.prepare(&sql).unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.collect::<Result<_, _>>().unwrap()
+        };
+        assert_eq!(pbs(&conn), [60_000, 50_000]);
+        assert_eq!(best(&conn, "Tower"), Some(50_000));
+
+        // Runs before a reset are no longer there to beat, nor PBs themselves
+        let cut = timestamps::utc_column("2026-01-02 00:00:00").unwrap();
+        reset_pb(&conn, "Tower", &cut).unwrap();
+        assert_eq!(pbs(&conn), [55_000]);
+        assert_eq!(best(&conn, "Tower"), Some(55_000));
+    }
+
+    // Three runs on two maps, the last one tagged twice
+    fn seeded() -> Db {
+        let db = Db::open_in_memory().unwrap();
+        db.conn()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO runs (map, username, server, started_at, time_ms, completed, fails) VALUES
+                    ('Café', 'me', 'hyp', '2026-01-01 10:00:00', 5000, 1, 2),
+                    ('Башня', 'me', NULL, '2026-01-02 10:00:00', NULL, 0, 1),
+                    ('CAFÉ', 'you', 'hyp', '2026-01-03 10:00:00', 4000, 1, 0);
+                INSERT INTO run_tags VALUES (1, 'x'), (3, 'x'), (3, 'y');",
+            )
+            .unwrap();
+        db
+    }
+
+    fn ids(conn: &Connection, q: &RunQuery) -> Vec<i64> {
+        select_runs(conn, q)
+            .unwrap()
+            .runs
+            .iter()
+            .map(|r| r.id)
+            .collect()
+    }
+
+    #[test]
+    fn unfiltered_runs_are_newest_first() {
+        let (filter, params) = build_filter(&RunQuery::default()).unwrap();
+        assert!(filter.is_empty() && params.is_empty());
+        let db = seeded();
+        let conn = db.conn().unwrap();
+        let page = select_runs(&conn, &RunQuery::default()).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(
+            page.runs.iter().map(|r| r.id).collect::<Vec<_>>(),
+            [3, 2, 1]
+        );
+        assert_eq!(page.runs[0].tags, ["x", "y"]);
+    }
+
+    #[test]
+    fn runs_without_a_time_sort_last() {
+        let db = seeded();
+        let conn = db.conn().unwrap();
+        let mut q = RunQuery {
+            sort_by: SortBy::Time,
+            sort_dir: SortDir::Asc,
+            ..Default::default()
+        };
+        assert_eq!(ids(&conn, &q), [3, 1, 2]);
+        q.sort_dir = SortDir::Desc;
+        assert_eq!(ids(&conn, &q), [1, 3, 2]);
+    }
+
+    #[test]
+    fn filters_combine() {
+        let db = seeded();
+        let conn = db.conn().unwrap();
+        let q = |edit: fn(&mut RunQuery)| {
+            let mut q = RunQuery::default();
+            edit(&mut q);
+            ids(&conn, &q)
+        };
+        // Maps match whatever the case
+        assert_eq!(q(|q| q.map = Some("café".into())), [3, 1]);
+        assert_eq!(q(|q| q.map = Some("БАШНЯ".into())), [2]);
+        assert_eq!(q(|q| q.username = Some("me".into())), [2, 1]);
+        assert_eq!(q(|q| q.server = Some("hyp".into())), [3, 1]);
+        assert_eq!(q(|q| q.completed_only = true), [3, 1]);
+        // Every tag has to be on the run
+        assert_eq!(q(|q| q.tags = vec!["x".into()]), [3, 1]);
+        assert_eq!(q(|q| q.tags = vec!["x".into(), "y".into()]), [3]);
+        let slower = |q: &mut RunQuery| {
+            q.min_time_ms = Some(4500);
+            q.max_time_ms = Some(5000);
+        };
+        assert_eq!(q(slower), [1]);
+        assert_eq!(q(|q| q.practice = Some(true)), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn date_to_includes_its_day() {
+        let db = seeded();
+        let conn = db.conn().unwrap();
+        let day = RunQuery {
+            date_from: Some("2026-01-02".into()),
+            date_to: Some("2026-01-02".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&conn, &day), [2]);
+        let open_ended = RunQuery {
+            date_from: Some("2026-01-02".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&conn, &open_ended), [3, 2]);
+    }
+
+    #[test]
+    fn bad_bounds_are_rejected() {
+        let reversed = RunQuery {
+            date_from: Some("2026-01-03".into()),
+            date_to: Some("2026-01-02".into()),
+            ..Default::default()
+        };
+        assert!(build_filter(&reversed).unwrap_err().contains("after"));
+        let not_a_date = RunQuery {
+            date_from: Some("yesterday".into()),
+            ..Default::default()
+        };
+        assert!(build_filter(&not_a_date).unwrap_err().contains("date_from"));
+        let crossed = RunQuery {
+            min_time_ms: Some(2),
+            max_time_ms: Some(1),
+            ..Default::default()
+        };
+        assert!(build_filter(&crossed).unwrap_err().contains("greater"));
+        let negative = RunQuery {
+            min_time_ms: Some(-1),
+            ..Default::default()
+        };
+        assert!(build_filter(&negative).is_err());
+    }
+
+    #[test]
+    fn pages_keep_the_total() {
+        let db = seeded();
+        let conn = db.conn().unwrap();
+        let page = RunQuery {
+            limit: Some(2),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let found = select_runs(&conn, &page).unwrap();
+        assert_eq!(found.total, 3);
+        assert_eq!(found.runs.iter().map(|r| r.id).collect::<Vec<_>>(), [2, 1]);
+        let past_the_end = RunQuery {
+            offset: Some(10),
+            ..Default::default()
+        };
+        let found = select_runs(&conn, &past_the_end).unwrap();
+        assert_eq!(found.total, 3);
+        assert!(found.runs.is_empty());
+        let filtered_past_the_end = RunQuery {
+            map: Some("Башня".into()),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let found = select_runs(&conn, &filtered_past_the_end).unwrap();
+        assert_eq!(found.total, 1);
+        assert!(found.runs.is_empty());
+    }
+}
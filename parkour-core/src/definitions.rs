@@ -0,0 +1,435 @@
+use crate::duels::{strip_colors, EventKind, PlayerEvent, FINISH};
+use crate::duration::format_ms;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A chat message to react to, matched against the message without color
+/// codes: `{"contains": "Run started"}` or `{"regex": "Floor \\d+ cleared in (?P<time>[\\d:.]+)"}`.
+///
+/// A regex with a `time` group gives the run timer from chat; otherwise the
+/// time is counted from the log times since the start.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Trigger {
+    Contains(String),
+    Regex(String),
+}
+
+/// A run the built-in patterns don't know, defined by the messages that
+/// start, split and end it:
+///
+/// `{"name": "Tower", "map": "Tower Parkour", "start": {"contains": "The climb has begun"}, "splits": [{"contains": "Floor 1 cleared"}, {"contains": "Floor 2 cleared"}], "end": {"contains": "You reached the top"}, "resets": [{"contains": "You fell off"}]}`
+///
+/// Runs are stored under `map`, or `name` without one. The player is the
+/// logged-in user.
+///
+/// When several triggers could apply, these rules decide:
+///
+/// - Only one defined run is timed at a time. While it runs, only its own
+///   triggers are checked, in order: resets, end, splits, start. Its start
+///   trigger begins a new run.
+/// - With no run going, definitions are tried in order and the first whose
+///   start matches wins, so an earlier definition takes precedence over a
+///   later one for the same map.
+/// - A split counts when it's the next one or any later one; the splits
+///   skipped over are left out of the run. Splits already passed are
+///   ignored.
+/// - The end finishes the run even with splits missing. A reset abandons it,
+///   keeping the splits reached like a run that wasn't finished.
+/// - A line a defined run used is not read by the built-in patterns.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RunDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub map: Option<String>,
+    pub start: Trigger,
+    #[serde(default)]
+    pub splits: Vec<Trigger>,
+    pub end: Trigger,
+    #[serde(default)]
+    pub resets: Vec<Trigger>,
+    // Kept but not run
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+impl RunDefinition {
+    pub fn title(&self) -> &str {
+        self.map.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    Contains(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn compile(name: &str, trigger: &Trigger) -> Result<Matcher, String> {
+        match trigger {
+            Trigger::Contains(text) if text.is_empty() => {
+                Err(format!("Invalid run definition {}: empty trigger", name))
+            }
+            Trigger::Contains(text) => Ok(Matcher::Contains(text.clone())),
+            Trigger::Regex(pattern) => Regex::new(pattern)
+                .map(Matcher::Regex)
+                .map_err(|e| format!("Invalid run definition {}: {}", name, e)),
+        }
+    }
+
+    // None when the message doesn't match, otherwise the chat timer if any
+    fn hit(&self, message: &str) -> Option<Option<String>> {
+        match self {
+            Matcher::Contains(text) => message.contains(text.as_str()).then_some(None),
+            Matcher::Regex(re) => {
+                let c = re.captures(message)?;
+                Some(c.name("time").map(|t| t.as_str().to_string()))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Compiled {
+    title: String,
+    start: Matcher,
+    splits: Vec<Matcher>,
+    end: Matcher,
+    resets: Vec<Matcher>,
+}
+
+/// What a defined run did on a line. Splits are numbered from 1 like
+/// checkpoints, and the end is [`FINISH`]:
+///
+/// `{"step": "reached", "cp": 2, "time": "0:41.000", "type": "checkpoint"}`
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    Started { title: String, restarted: bool },
+    Reached(PlayerEvent),
+    Reset,
+}
+
+#[derive(Clone, Debug)]
+struct Running {
+    definition: usize,
+    // Index of the next split
+    next: usize,
+    // Log time of the start in seconds, to time runs without a chat timer
+    started_at: Option<u32>,
+}
+
+/// Compiled run definitions and the run they're timing.
+#[derive(Clone, Debug, Default)]
+pub struct Definitions {
+    compiled: Vec<Compiled>,
+    running: Option<Running>,
+}
+
+impl Definitions {
+    /// Compiles every enabled definition, in order.
+    pub fn compile(definitions: &[RunDefinition]) -> Result<Definitions, String> {
+        let mut compiled = Vec::new();
+        for definition in definitions.iter().filter(|d| !d.disabled) {
+            let name = &definition.name;
+            let all = |triggers: &[Trigger]| -> Result<Vec<Matcher>, String> {
+                triggers.iter().map(|t| Matcher::compile(name, t)).collect()
+            };
+            compiled.push(Compiled {
+                title: definition.title().to_string(),
+                start: Matcher::compile(name, &definition.start)?,
+                splits: all(&definition.splits)?,
+                end: Matcher::compile(name, &definition.end)?,
+                resets: all(&definition.resets)?,
+            });
+        }
+        Ok(Definitions {
+            compiled,
+            running: None,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.compiled.is_empty()
+    }
+
+    /// Title of the run being timed.
+    pub fn running(&self) -> Option<&str> {
+        let running = self.running.as_ref()?;
+        Some(&self.compiled[running.definition].title)
+    }
+
+    pub fn clear(&mut self) {
+        self.running = None;
+    }
+
+    /// Feeds a chat message without color codes, logged `at` seconds since
+    /// midnight. Returns what it did to the defined runs, if anything.
+    pub fn message(&mut self, message: &str, at: Option<u32>) -> Option<Step> {
+        let Some(running) = &mut self.running else {
+            let (definition, def) = self
+                .compiled
+                .iter()
+                .enumerate()
+                .find(|(_, d)| d.start.hit(message).is_some())?;
+            self.running = Some(Running {
+                definition,
+                next: 0,
+                started_at: at,
+            });
+            return Some(Step::Started {
+                title: def.title.clone(),
+                restarted: false,
+            });
+        };
+        let def = &self.compiled[running.definition];
+        if def.resets.iter().any(|r| r.hit(message).is_some()) {
+            self.running = None;
+            return Some(Step::Reset);
+        }
+        // The timer in chat, or the time since the start by the log
+        let elapsed = |timer: Option<String>| {
+            timer.or_else(|| {
+                let secs = (at? + 86_400 - running.started_at?) % 86_400;
+                Some(format_ms(secs as i64 * 1000))
+            })
+        };
+        if let Some(timer) = def.end.hit(message) {
+            let time = elapsed(timer);
+            self.running = None;
+            return Some(Step::Reached(PlayerEvent {
+                cp: FINISH,
+                time: time?,
+                kind: EventKind::Finish,
+            }));
+        }
+        let split = def.splits[running.next..]
+            .iter()
+            .position(|s| s.hit(message).is_some())
+            .map(|i| running.next + i);
+        if let Some(split) = split {
+            let timer = def.splits[split].hit(message).flatten();
+            running.next = split + 1;
+            return Some(Step::Reached(PlayerEvent {
+                cp: split as u32 + 1,
+                time: elapsed(timer)?,
+                kind: EventKind::Checkpoint,
+            }));
+        }
+        if def.start.hit(message).is_some() {
+            running.next = 0;
+            running.started_at = at;
+            return Some(Step::Started {
+                title: def.title.clone(),
+                restarted: true,
+            });
+        }
+        None
+    }
+}
+
+/// The chat message in a log line, without color codes, as definitions see
+/// it.
+pub fn chat_message(line: &str) -> Option<String> {
+    let i = line.find("[CHAT]")?;
+    Some(strip_colors(line[i + "[CHAT]".len()..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(text: &str) -> Trigger {
+        Trigger::Contains(text.into())
+    }
+
+    fn tower(name: &str) -> RunDefinition {
+        RunDefinition {
+            name: name.into(),
+            map: Some("Tower".into()),
+            start: contains("The climb has begun"),
+            splits: vec![
+                contains("Floor 1 cleared"),
+                contains("Floor 2 cleared"),
+                contains("Floor 3 cleared"),
+            ],
+            end: contains("You reached the top"),
+            resets: vec![contains("You fell off")],
+            disabled: false,
+        }
+    }
+
+    // What each message, logged at the given second of the day, did
+    fn feed(definitions: &mut Definitions, messages: &[(&str, u32)]) -> Vec<Option<Step>> {
+        messages
+            .iter()
+            .map(|(message, at)| definitions.message(message, Some(*at)))
+            .collect()
+    }
+
+    fn reached(cp: u32, time: &str) -> Option<Step> {
+        let kind = match cp {
+            FINISH => EventKind::Finish,
+            _ => EventKind::Checkpoint,
+        };
+        Some(Step::Reached(PlayerEvent {
+            cp,
+            time: time.into(),
+            kind,
+        }))
+    }
+
+    fn started(title: &str, restarted: bool) -> Option<Step> {
+        Some(Step::Started {
+            title: title.into(),
+            restarted,
+        })
+    }
+
+    #[test]
+    fn out_of_order_splits() {
+        let mut definitions = Definitions::compile(&[tower("Tower")]).unwrap();
+        let steps = feed(
+            &mut definitions,
+            &[
+                ("Floor 1 cleared", 0),
+                ("The climb has begun", 100),
+                // Floor 1 is skipped over and left out
+                ("Floor 2 cleared", 110),
+                // Already passed
+                ("Floor 1 cleared", 115),
+                ("Floor 2 cleared", 118),
+                // The end with floor 3 missing still finishes
+                ("You reached the top", 130),
+                ("Floor 3 cleared", 140),
+            ],
+        );
+        assert_eq!(
+            steps,
+            [
+                None,
+                started("Tower", false),
+                reached(2, "0:10.000"),
+                None,
+                None,
+                reached(FINISH, "0:30.000"),
+                None,
+            ]
+        );
+        assert!(definitions.running().is_none());
+    }
+
+    #[test]
+    fn own_triggers_are_checked_resets_first() {
+        let mut run = tower("Tower");
+        run.resets = vec![contains("top")];
+        run.splits = vec![contains("cleared"), contains("Floor 2 cleared")];
+        let mut definitions = Definitions::compile(&[run]).unwrap();
+        let steps = feed(
+            &mut definitions,
+            &[
+                ("The climb has begun", 0),
+                // Matches both splits; the next one takes it
+                ("Floor 2 cleared", 5),
+                ("The climb has begun", 10),
+                // Matches the reset and the end
+                ("You reached the top", 20),
+            ],
+        );
+        assert_eq!(
+            steps,
+            [
+                started("Tower", false),
+                reached(1, "0:05.000"),
+                started("Tower", true),
+                Some(Step::Reset),
+            ]
+        );
+    }
+
+    #[test]
+    fn earlier_definitions_win_for_the_same_map() {
+        let mut speedrun = tower("Tower speedrun");
+        speedrun.start = contains("begun");
+        speedrun.splits = vec![contains("Checkpoint!")];
+        let definitions = [tower("Tower"), speedrun.clone()];
+        let mut compiled = Definitions::compile(&definitions).unwrap();
+        let steps = feed(
+            &mut compiled,
+            &[
+                ("The climb has begun", 0),
+                // Only the running definition's triggers count
+                ("Checkpoint!", 5),
+                ("Floor 1 cleared", 8),
+            ],
+        );
+        assert_eq!(
+            steps,
+            [started("Tower", false), None, reached(1, "0:08.000")]
+        );
+        assert_eq!(compiled.running(), Some("Tower"));
+
+        let mut first = tower("Tower");
+        first.disabled = true;
+        let mut compiled = Definitions::compile(&[first, speedrun]).unwrap();
+        let steps = feed(
+            &mut compiled,
+            &[("The climb has begun", 0), ("Checkpoint!", 5)],
+        );
+        assert_eq!(steps, [started("Tower", false), reached(1, "0:05.000")]);
+    }
+
+    #[test]
+    fn chat_timer_or_log_time() {
+        let mut run = tower("Tower");
+        run.end = Trigger::Regex(r"top in (?P<time>[\d:.]+)".into());
+        let mut definitions = Definitions::compile(&[run]).unwrap();
+        // The log time crosses midnight
+        let steps = feed(
+            &mut definitions,
+            &[
+                ("The climb has begun", 86_390),
+                ("Floor 1 cleared", 5),
+                ("You reached the top in 0:14.250", 6),
+            ],
+        );
+        assert_eq!(
+            steps,
+            [
+                started("Tower", false),
+                reached(1, "0:15.000"),
+                reached(FINISH, "0:14.250"),
+            ]
+        );
+        definitions.message("The climb has begun", Some(0));
+        definitions.clear();
+        assert!(definitions.running().is_none());
+    }
+
+    #[test]
+    fn bad_triggers_are_rejected() {
+        let mut run = tower("Tower");
+        run.splits.push(contains(""));
+        assert!(Definitions::compile(&[run])
+            .unwrap_err()
+            .contains("empty trigger"));
+        let mut run = tower("Tower");
+        run.start = Trigger::Regex("(".into());
+        assert!(Definitions::compile(&[run])
+            .unwrap_err()
+            .starts_with("Invalid run definition Tower"));
+        let mut run = tower("Tower");
+        run.disabled = true;
+        assert!(Definitions::compile(&[run]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn chat_messages_lose_their_colors() {
+        let line = "[10:00:00] [Client thread/INFO]: [CHAT] §aFloor 1 cleared";
+        assert_eq!(chat_message(line).as_deref(), Some("Floor 1 cleared"));
+        assert_eq!(chat_message("[10:00:00] [main/INFO]: Loading"), None);
+    }
+}
@@ -0,0 +1,1247 @@
+use crate::client::Client;
+use crate::definitions::{chat_message, Definitions, RunDefinition, Step};
+use crate::duration::{normalize_timer, parse_ms};
+use crate::language::detect_message_language;
+use crate::legitimacy::{self, LegitimacyRule, Rules};
+use crate::lines::lines;
+use crate::log_line::{seconds_of_day, LineParser, LogFormat};
+use crate::server_log::{ServerLog, ServerLogSpec, ServerRuns};
+use regex::{Captures, Regex};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::LazyLock;
+
+/// Checkpoint number used for the finish line so it sorts after every
+/// checkpoint.
+pub const FINISH: u32 = 9999;
+
+// Built-in patterns as (language, pattern, decimal comma), in the order
+// they're tried when a message's language doesn't pick one
+pub(crate) type Builtin = &'static [(&'static str, &'static str, bool)];
+
+const USER: Builtin = &[("en", r"Setting user:\s*(\S+)", false)];
+const YOU_CHECKPOINT: Builtin = &[
+    (
+        "en",
+        r"\[CHAT\].*?CHECKPOINT!\s+You\s+reached checkpoint\s+(\d+)\s+in\s+([\d:.]+)!",
+        false,
+    ),
+    (
+        "de",
+        r"\[CHAT\].*?CHECKPOINT!\s+Du\s+hast\s+Checkpoint\s+(\d+)\s+in\s+([\d:.,]+)\s+erreicht!",
+        true,
+    ),
+    (
+        "ru",
+        r"\[CHAT\].*?КОНТРОЛЬНАЯ ТОЧКА!\s+Вы\s+достигли контрольной точки\s+(\d+)\s+за\s+([\d:.,]+)!",
+        true,
+    ),
+];
+const OTHER_CHECKPOINT: Builtin = &[
+    (
+        "en",
+        r"\[CHAT\].*?CHECKPOINT!\s+(.+?)\s+reached checkpoint\s+(\d+)\s+in\s+([\d:.]+)!",
+        false,
+    ),
+    (
+        "de",
+        r"\[CHAT\].*?CHECKPOINT!\s+(.+?)\s+hat\s+Checkpoint\s+(\d+)\s+in\s+([\d:.,]+)\s+erreicht!",
+        true,
+    ),
+    (
+        "ru",
+        r"\[CHAT\].*?КОНТРОЛЬНАЯ ТОЧКА!\s+(.+?)\s+достигл?а? контрольной точки\s+(\d+)\s+за\s+([\d:.,]+)!",
+        true,
+    ),
+];
+const YOU_FINISH: Builtin = &[
+    (
+        "en",
+        r"\[CHAT\].*?COMPLETED!\s+You\s+completed the parkour in\s+([\d:.]+)!",
+        false,
+    ),
+    (
+        "de",
+        r"\[CHAT\].*?ABGESCHLOSSEN!\s+Du\s+hast den Parkour in\s+([\d:.,]+)\s+abgeschlossen!",
+        true,
+    ),
+    (
+        "ru",
+        r"\[CHAT\].*?ПРОЙДЕНО!\s+Вы\s+прошли паркур за\s+([\d:.,]+)!",
+        true,
+    ),
+];
+const OTHER_FINISH: Builtin = &[
+    (
+        "en",
+        r"\[CHAT\].*?COMPLETED!\s+(.+?)\s+completed the parkour in\s+([\d:.]+)!",
+        false,
+    ),
+    (
+        "de",
+        r"\[CHAT\].*?ABGESCHLOSSEN!\s+(.+?)\s+hat den Parkour in\s+([\d:.,]+)\s+abgeschlossen!",
+        true,
+    ),
+    (
+        "ru",
+        r"\[CHAT\].*?ПРОЙДЕНО!\s+(.+?)\s+(?:прош[её]л|прошла) паркур за\s+([\d:.,]+)!",
+        true,
+    ),
+];
+const PRACTICE: Builtin = &[
+    (
+        "en",
+        r"(?i)(teleported|sent) (you )?(back )?to (your |the )?(last )?checkpoint|practice mode (is now )?(enabled|on)",
+        false,
+    ),
+    (
+        "de",
+        r"(?i)(du wurdest )?zum letzten checkpoint (zurück)?teleportiert|übungsmodus (ist jetzt )?(aktiviert|an)",
+        false,
+    ),
+    (
+        "ru",
+        r"(?i)(вы )?(телепортированы|перемещены) (на|к) (последн(юю|ей) )?контрольн(ую|ой) точк[еу]|режим тренировки (включ[её]н|активирован)",
+        false,
+    ),
+];
+const RESET: Builtin = &[
+    (
+        "en",
+        r"(?i)reset your timer|your (parkour )?timer (has been|was) reset|parkour challenge (cancelled|failed)",
+        false,
+    ),
+    (
+        "de",
+        r"(?i)dein (parkour-)?timer wurde zurückgesetzt|parkour-herausforderung abgebrochen",
+        false,
+    ),
+    (
+        "ru",
+        r"(?i)ваш таймер (был )?сброшен|испытание паркура (отменено|провалено)",
+        false,
+    ),
+];
+// Words that open a progress message, to tell the server's apart from chat
+const PROGRESS_WORDS: &[&str] = &[
+    "CHECKPOINT!",
+    "COMPLETED!",
+    "ABGESCHLOSSEN!",
+    "КОНТРОЛЬНАЯ ТОЧКА!",
+    "ПРОЙДЕНО!",
+];
+
+static RANK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[.*?\]\s*").unwrap());
+static DEFAULT_PATTERNS: LazyLock<Patterns> =
+    LazyLock::new(|| Patterns::from_spec(&PatternSpec::default()).unwrap());
+
+/// Custom parser definitions, e.g. for a server with its own chat format.
+/// Every field is optional and falls back to the built-in pattern; the
+/// capture groups must match the built-in ones:
+///
+/// `{"you_finish": "FINISHED! You did it in ([\\d:.]+)", "game_start": "^Speed Duels$"}`
+///
+/// Each field is one pattern or a list of [`Variant`]s of it, e.g. for
+/// each language the server speaks.
+///
+/// - `user`: player name
+/// - `you_checkpoint`: checkpoint, time
+/// - `other_checkpoint`: player, checkpoint, time
+/// - `you_finish`: time
+/// - `other_finish`: player, time
+/// - `game_start`: matched against the chat message without color codes,
+///   which becomes the game's title
+/// - `practice`: matched against the chat message without color codes, like
+///   a /cp teleport's "Teleported to checkpoint"; marks the player's
+///   unfinished run as practice
+/// - `reset`: matched against the chat message without color codes, like
+///   a /reset's "Reset your timer"; starts the player's unfinished run over
+/// - `client`: the [`Client`] the log is from, instead of detecting it
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PatternSpec {
+    pub user: Option<Variants>,
+    pub you_checkpoint: Option<Variants>,
+    pub other_checkpoint: Option<Variants>,
+    pub you_finish: Option<Variants>,
+    pub other_finish: Option<Variants>,
+    pub game_start: Option<Variants>,
+    pub practice: Option<Variants>,
+    pub reset: Option<Variants>,
+    pub client: Option<Client>,
+}
+
+/// A pattern, or its variants sharing the same capture groups:
+///
+/// `[{"pattern": "CHECKPOINT! You reached checkpoint (\\d+) in ([\\d:.]+)!", "language": "en"}, {"pattern": "CHECKPOINT! Du hast Checkpoint (\\d+) in ([\\d:,]+) erreicht!", "language": "de", "decimal_comma": true}]`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Variants {
+    One(String),
+    Many(Vec<Variant>),
+}
+
+/// One way a server words a message. Variants in the language
+/// [`detect_message_language`] finds in a line are tried first, then the
+/// rest in order. With `decimal_comma`, timers it captures are written like
+/// `0:41,250`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Variant {
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub decimal_comma: bool,
+}
+
+impl Variants {
+    pub(crate) fn list(&self) -> Vec<Variant> {
+        match self {
+            Variants::One(pattern) => vec![Variant {
+                pattern: pattern.clone(),
+                language: None,
+                decimal_comma: false,
+            }],
+            Variants::Many(variants) => variants.clone(),
+        }
+    }
+}
+
+pub(crate) fn builtin(patterns: Builtin) -> Vec<Variant> {
+    patterns
+        .iter()
+        .map(|(language, pattern, decimal_comma)| Variant {
+            pattern: pattern.to_string(),
+            language: Some(language.to_string()),
+            decimal_comma: *decimal_comma,
+        })
+        .collect()
+}
+
+impl PatternSpec {
+    /// Each field of `self`, or of `fallback` where `self` leaves it unset.
+    pub fn or(self, fallback: PatternSpec) -> PatternSpec {
+        PatternSpec {
+            user: self.user.or(fallback.user),
+            you_checkpoint: self.you_checkpoint.or(fallback.you_checkpoint),
+            other_checkpoint: self.other_checkpoint.or(fallback.other_checkpoint),
+            you_finish: self.you_finish.or(fallback.you_finish),
+            other_finish: self.other_finish.or(fallback.other_finish),
+            game_start: self.game_start.or(fallback.game_start),
+            practice: self.practice.or(fallback.practice),
+            reset: self.reset.or(fallback.reset),
+            client: self.client.or(fallback.client),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Compiled {
+    language: Option<String>,
+    re: Regex,
+    decimal_comma: bool,
+}
+
+// A field's variants, compiled
+#[derive(Clone, Debug)]
+pub(crate) struct Localized(Vec<Compiled>);
+
+impl Localized {
+    // The first variant to match `text`, trying those in `language` first,
+    // with whether its timers use a decimal comma
+    pub(crate) fn captures<'t>(
+        &self,
+        text: &'t str,
+        language: Option<&str>,
+    ) -> Option<(Captures<'t>, bool)> {
+        let preferred = |v: &&Compiled| language.is_some() && v.language.as_deref() == language;
+        let first = self.0.iter().filter(preferred);
+        let rest = self.0.iter().filter(|v| !preferred(v));
+        first
+            .chain(rest)
+            .find_map(|v| Some((v.re.captures(text)?, v.decimal_comma)))
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        self.0.iter().any(|v| v.re.is_match(text))
+    }
+
+    // The first of `groups` some variant doesn't name
+    pub(crate) fn missing<'g>(&self, groups: &[&'g str]) -> Option<&'g str> {
+        groups.iter().copied().find(|group| {
+            self.0
+                .iter()
+                .any(|v| !v.re.capture_names().flatten().any(|name| name == *group))
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Patterns {
+    user: Localized,
+    you_checkpoint: Localized,
+    other_checkpoint: Localized,
+    you_finish: Localized,
+    other_finish: Localized,
+    game_start: Option<Localized>,
+    practice: Localized,
+    reset: Localized,
+    client: Option<Client>,
+    trust_player_chat: bool,
+    definitions: Definitions,
+    legitimacy: Rules,
+    server_log: Option<ServerLog>,
+}
+
+pub(crate) fn compile(
+    field: &str,
+    variants: &[Variant],
+    groups: usize,
+) -> Result<Localized, String> {
+    if variants.is_empty() {
+        return Err(format!("Invalid pattern {}: no variants", field));
+    }
+    let mut compiled = Vec::with_capacity(variants.len());
+    for variant in variants {
+        let re = Regex::new(&variant.pattern)
+            .map_err(|e| format!("Invalid pattern {}: {}", field, e))?;
+        // captures_len counts the whole match as group 0
+        let found = re.captures_len() - 1;
+        if found < groups {
+            return Err(format!(
+                "Invalid pattern {}: expected {} capture groups, found {}",
+                field, groups, found
+            ));
+        }
+        compiled.push(Compiled {
+            language: variant.language.clone(),
+            re,
+            decimal_comma: variant.decimal_comma,
+        });
+    }
+    Ok(Localized(compiled))
+}
+
+impl Patterns {
+    pub fn from_spec(spec: &PatternSpec) -> Result<Patterns, String> {
+        let pick = |custom: &Option<Variants>, default: Builtin| match custom {
+            Some(custom) => custom.list(),
+            None => builtin(default),
+        };
+        Ok(Patterns {
+            user: compile("user", &pick(&spec.user, USER), 1)?,
+            you_checkpoint: compile(
+                "you_checkpoint",
+                &pick(&spec.you_checkpoint, YOU_CHECKPOINT),
+                2,
+            )?,
+            other_checkpoint: compile(
+                "other_checkpoint",
+                &pick(&spec.other_checkpoint, OTHER_CHECKPOINT),
+                3,
+            )?,
+            you_finish: compile("you_finish", &pick(&spec.you_finish, YOU_FINISH), 1)?,
+            other_finish: compile("other_finish", &pick(&spec.other_finish, OTHER_FINISH), 2)?,
+            game_start: match &spec.game_start {
+                Some(start) => Some(compile("game_start", &start.list(), 0)?),
+                None => None,
+            },
+            practice: compile("practice", &pick(&spec.practice, PRACTICE), 0)?,
+            reset: compile("reset", &pick(&spec.reset, RESET), 0)?,
+            client: spec.client,
+            trust_player_chat: true,
+            definitions: Definitions::default(),
+            legitimacy: Rules::compile(&legitimacy::builtin_rules())?,
+            server_log: None,
+        })
+    }
+
+    /// Also times the runs in `definitions`, see [`RunDefinition`].
+    pub fn with_definitions(mut self, definitions: &[RunDefinition]) -> Result<Patterns, String> {
+        self.definitions = Definitions::compile(definitions)?;
+        Ok(self)
+    }
+
+    /// Checks the player's runs against `rules` instead of the built-in
+    /// ones, see [`LegitimacyRule`].
+    pub fn with_legitimacy_rules(mut self, rules: &[LegitimacyRule]) -> Result<Patterns, String> {
+        self.legitimacy = Rules::compile(rules)?;
+        Ok(self)
+    }
+
+    /// Reads logs as a server's console log, see [`ServerLogSpec`]. Runs go
+    /// to the player each message names, and the client patterns and run
+    /// definitions aren't used.
+    pub fn with_server_log(mut self, spec: &ServerLogSpec) -> Result<Patterns, String> {
+        self.server_log = Some(ServerLog::compile(spec)?);
+        Ok(self)
+    }
+
+    /// With `false`, checkpoints and finishes in a message that looks typed
+    /// by a player, like `[VIP] Bob: CHECKPOINT! You reached...`, are ignored.
+    pub fn with_trust_player_chat(mut self, trust: bool) -> Patterns {
+        self.trust_player_chat = trust;
+        self
+    }
+
+    /// Whether a chat message without color codes says the player is
+    /// practicing rather than doing a full run.
+    pub fn is_practice(&self, message: &str) -> bool {
+        self.practice.is_match(message)
+    }
+
+    /// Reads every log as coming from `client`, or detects it again with
+    /// `None`.
+    pub fn with_client(mut self, client: Option<Client>) -> Patterns {
+        self.client = client;
+        self
+    }
+}
+
+impl Default for Patterns {
+    fn default() -> Patterns {
+        DEFAULT_PATTERNS.clone()
+    }
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Checkpoint,
+    Finish,
+}
+
+/// `{"cp": 3, "time": "0:41.250", "type": "checkpoint"}`
+///
+/// `time` is the timer as shown in chat, with a decimal point even where
+/// chat writes a comma; the finish has `cp` set to [`FINISH`].
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct PlayerEvent {
+    pub cp: u32,
+    pub time: String,
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+}
+
+impl PlayerEvent {
+    pub fn time_ms(&self) -> Option<i64> {
+        parse_ms(&self.time)
+    }
+}
+
+/// One Parkour Duels game. `players` maps each name to their checkpoints and
+/// finish in order; `offset` is where the game's announcement starts in the
+/// log and `time` is the log time on that line.
+///
+/// `practice` names the players whose run wasn't a full one: the practice
+/// pattern matched before they finished, or they reached a checkpoint after
+/// a later one, as when teleporting back with /cp. `invalidated_by` names
+/// the legitimacy rules set off during the logged-in player's run.
+#[derive(Serialize, JsonSchema, Clone, Debug, Default)]
+pub struct Game {
+    pub offset: u64,
+    pub time: Option<String>,
+    pub title: String,
+    pub players: BTreeMap<String, Vec<PlayerEvent>>,
+    pub opponents: String,
+    pub practice: BTreeSet<String>,
+    pub invalidated_by: BTreeSet<String>,
+}
+
+impl Game {
+    pub fn finish(&self, player: &str) -> Option<&PlayerEvent> {
+        self.players
+            .get(player)?
+            .iter()
+            .find(|e| e.kind == EventKind::Finish)
+    }
+
+    /// Fastest finisher and their time.
+    pub fn winner(&self) -> Option<(&str, i64)> {
+        self.players
+            .keys()
+            .filter_map(|p| Some((p.as_str(), self.finish(p)?.time_ms()?)))
+            .min_by_key(|(_, ms)| *ms)
+    }
+
+    // Only the first report of each checkpoint counts
+    pub(crate) fn record(&mut self, player: &str, event: &PlayerEvent) -> bool {
+        let events = self.players.entry(player.to_string()).or_default();
+        if event.kind == EventKind::Checkpoint
+            && events
+                .iter()
+                .any(|e| e.kind == EventKind::Checkpoint && e.cp > event.cp)
+        {
+            self.practice.insert(player.to_string());
+        }
+        if events
+            .iter()
+            .any(|e| e.kind == event.kind && e.cp == event.cp)
+        {
+            return false;
+        }
+        events.push(event.clone());
+        true
+    }
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug, Default)]
+pub struct Parsed {
+    pub games: Vec<Game>,
+    // From the launcher's "Setting user:" line, used for "You" in chat
+    pub username: Option<String>,
+    pub format: Option<LogFormat>,
+    // Detected from startup lines unless the patterns name one
+    pub client: Option<Client>,
+}
+
+/// Something [`Parser::line`] recognized, e.g.
+///
+/// `{"event": "progress", "offset": 2048, "player": "Steve", "cp": 3, "time": "0:41.250", "type": "checkpoint"}`
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DuelEvent {
+    GameStarted {
+        offset: u64,
+        title: String,
+    },
+    Progress {
+        offset: u64,
+        player: String,
+        #[serde(flatten)]
+        step: PlayerEvent,
+    },
+    // The logged-in player's unfinished run starting over
+    Reset {
+        offset: u64,
+    },
+}
+
+/// Removes `§x` formatting codes.
+pub fn strip_colors(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn chat(line: &str) -> &str {
+    match line.find("[CHAT]") {
+        Some(i) => line[i + "[CHAT]".len()..].trim(),
+        None => line.trim(),
+    }
+}
+
+// Whether the part of a chat message before `end` is a sender, e.g. "<Bob> "
+// or "[VIP] Bob: ", rather than the message coming from the server
+pub(crate) fn sent_by_player(message: &str, end: usize) -> bool {
+    message[..end].contains([':', '<'])
+}
+
+/// Who sent a chat message a player typed and what they wrote, from the
+/// message after `[CHAT]` without color codes: `<Steve> hi`, `[VIP] Steve: hi`
+/// or `Steve: hi` all give `("Steve", "hi")`.
+pub fn player_chat(message: &str) -> Option<(&str, &str)> {
+    let (sender, text) = match message.strip_prefix('<') {
+        Some(rest) => rest.split_once("> ")?,
+        None => {
+            let (sender, text) = message.split_once(": ")?;
+            // The name comes after any ranks, and anything else before it is
+            // a server message like "Checkpoint reached: 3"
+            let (ranks, name) = sender.rsplit_once(' ').unwrap_or(("", sender));
+            let ranked = ranks
+                .split(' ')
+                .all(|w| w.is_empty() || (w.starts_with('[') && w.ends_with(']')));
+            if !ranked {
+                return None;
+            }
+            (name, text)
+        }
+    };
+    let valid = (1..=16).contains(&sender.len())
+        && sender
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some((sender, text))
+}
+
+// Whether a progress message looks typed by a player
+fn typed(line: &str) -> bool {
+    let message = strip_colors(chat(line));
+    let end = PROGRESS_WORDS
+        .iter()
+        .filter_map(|w| message.find(w))
+        .min()
+        .unwrap_or(0);
+    sent_by_player(&message, end)
+}
+
+pub(crate) fn player_name(raw: &str) -> String {
+    RANK.replace_all(&strip_colors(raw), "").trim().to_string()
+}
+
+fn checkpoint(cp: &str, time: &str, decimal_comma: bool) -> Option<PlayerEvent> {
+    Some(PlayerEvent {
+        cp: cp.parse().ok()?,
+        time: normalize_timer(time, decimal_comma),
+        kind: EventKind::Checkpoint,
+    })
+}
+
+fn finish(time: &str, decimal_comma: bool) -> PlayerEvent {
+    PlayerEvent {
+        cp: FINISH,
+        time: normalize_timer(time, decimal_comma),
+        kind: EventKind::Finish,
+    }
+}
+
+/// Reads a log one line at a time, for callers that see it as it's written.
+/// [`parse`] does the same over a whole log.
+pub struct Parser {
+    patterns: Patterns,
+    parsed: Parsed,
+    current: Option<Game>,
+    lines: LineParser,
+    definitions: Definitions,
+    // Run being timed by a definition, kept apart from the built-in game
+    defined: Option<Game>,
+    // Every player's run in a server log
+    server: Option<ServerRuns>,
+}
+
+impl Parser {
+    pub fn new(patterns: Patterns) -> Parser {
+        Parser {
+            definitions: patterns.definitions.clone(),
+            server: patterns.server_log.clone().map(ServerRuns::new),
+            patterns,
+            parsed: Parsed::default(),
+            current: None,
+            lines: LineParser::default(),
+            defined: None,
+        }
+    }
+
+    pub fn username(&self) -> Option<&str> {
+        self.parsed.username.as_deref()
+    }
+
+    /// Reads the lines after with `patterns`, e.g. once the player joins a
+    /// server with patterns of its own, keeping the games and player seen so
+    /// far. Run definitions and the server log stay as they were given to
+    /// [`Parser::new`].
+    pub fn set_patterns(&mut self, patterns: Patterns) {
+        self.patterns = patterns;
+    }
+
+    // Title of the game a line announces, if it does
+    fn game_title(&self, line: &str) -> Option<String> {
+        if let Some(start) = &self.patterns.game_start {
+            let after = strip_colors(chat(line));
+            return start.is_match(&after).then_some(after);
+        }
+        if !line.contains("[CHAT]")
+            || !line.contains("Parkour Duels")
+            || ["Winstreak", "TITLE", "CHECKPOINT", "COMPLETED"]
+                .iter()
+                .any(|w| line.contains(w))
+        {
+            return None;
+        }
+        // The announcement is just the game name; longer lines mention it in passing
+        let after = strip_colors(chat(line));
+        (after.contains("Parkour Duels") && after.chars().count() < 40).then_some(after)
+    }
+
+    fn close_game(&mut self) {
+        if let Some(game) = self.current.take().filter(|g| !g.players.is_empty()) {
+            self.parsed.games.push(game);
+        }
+    }
+
+    fn close_defined(&mut self) {
+        if let Some(game) = self.defined.take().filter(|g| !g.players.is_empty()) {
+            self.parsed.games.push(game);
+        }
+    }
+
+    // What a run definition made of a chat line; `None` when none used it,
+    // so the built-in patterns read it instead
+    fn defined_step(
+        &mut self,
+        offset: u64,
+        time: Option<&str>,
+        text: &str,
+    ) -> Option<Option<DuelEvent>> {
+        let message = chat_message(text)?;
+        let step = self
+            .definitions
+            .message(&message, time.and_then(seconds_of_day))?;
+        Some(match step {
+            Step::Started { title, .. } => {
+                self.close_defined();
+                self.defined = Some(Game {
+                    offset,
+                    time: time.map(str::to_string),
+                    title: title.clone(),
+                    ..Default::default()
+                });
+                Some(DuelEvent::GameStarted { offset, title })
+            }
+            Step::Reached(step) => {
+                let player = self.parsed.username.clone();
+                let game = self.defined.as_mut();
+                let event = match (player, game) {
+                    (Some(player), Some(game)) => {
+                        game.record(&player, &step).then_some(DuelEvent::Progress {
+                            offset,
+                            player,
+                            step: step.clone(),
+                        })
+                    }
+                    _ => None,
+                };
+                if step.kind == EventKind::Finish {
+                    self.close_defined();
+                }
+                event
+            }
+            Step::Reset => {
+                self.close_defined();
+                None
+            }
+        })
+    }
+
+    fn progress(&mut self, offset: u64, player: String, step: PlayerEvent) -> Option<DuelEvent> {
+        let game = self.current.as_mut()?;
+        game.record(&player, &step).then_some(DuelEvent::Progress {
+            offset,
+            player,
+            step,
+        })
+    }
+
+    /// Feeds the line starting at `offset` in the log, without its
+    /// terminator. Returns what it added, if anything.
+    pub fn line(&mut self, offset: u64, text: &str) -> Option<DuelEvent> {
+        // A line at offset 0 means the log started over, which the client's
+        // filter could otherwise hide from the line parser
+        if offset == 0 {
+            self.parsed.client = None;
+            self.lines = LineParser::default();
+            self.close_defined();
+            self.definitions.clear();
+            if let Some(server) = &mut self.server {
+                server.clear();
+            }
+        }
+        self.parsed.client = match self.patterns.client {
+            Some(client) => Some(client),
+            None => Client::prefer(self.parsed.client, Client::telltale(text)),
+        };
+        let text = self.parsed.client.unwrap_or_default().filter(text)?;
+        let text = text.as_ref();
+        let time = self.lines.time(offset, text);
+        self.parsed.format = self.lines.format();
+        if let Some(server) = &mut self.server {
+            let message = strip_colors(&self.lines.parse(offset, text).message);
+            return server.line(offset, time, &message, &mut self.parsed.games);
+        }
+        if let Some((user, _)) = self.patterns.user.captures(text, None) {
+            self.parsed.username = Some(user[1].to_string());
+        }
+        let message = chat_message(text);
+        let language = message.as_deref().and_then(detect_message_language);
+        if let (Some(user), Some(message)) = (&self.parsed.username, &message) {
+            let practice = self.patterns.is_practice(message);
+            let fired: Vec<&str> = self.patterns.legitimacy.fired(message).collect();
+            for game in [&mut self.current, &mut self.defined].into_iter().flatten() {
+                if game.finish(user).is_some() {
+                    continue;
+                }
+                if practice {
+                    game.practice.insert(user.clone());
+                }
+                game.invalidated_by
+                    .extend(fired.iter().map(|rule| rule.to_string()));
+            }
+        }
+        if !self.definitions.is_empty() {
+            if let Some(event) = self.defined_step(offset, time, text) {
+                return event;
+            }
+        }
+        let started = self.game_title(text);
+        if let Some(title) = &started {
+            self.close_game();
+            self.current = Some(Game {
+                offset,
+                time: time.map(str::to_string),
+                title: title.clone(),
+                ..Default::default()
+            });
+        }
+        let game = self.current.as_mut()?;
+        if text.contains("Opponents:") {
+            let names = strip_colors(chat(text));
+            let names = names
+                .strip_prefix("Opponents:")
+                .unwrap_or(&names)
+                .trim_start();
+            if !game.opponents.is_empty() {
+                game.opponents.push(' ');
+            }
+            game.opponents.push_str(names);
+        }
+        if let Some(title) = started {
+            return Some(DuelEvent::GameStarted { offset, title });
+        }
+
+        if !self.patterns.trust_player_chat && typed(text) {
+            return None;
+        }
+        if let (Some(user), Some(message)) = (&self.parsed.username, &message) {
+            if game.finish(user).is_none() && self.patterns.reset.is_match(message) {
+                // What the player reached before doesn't belong to the new run
+                game.players.remove(user);
+                game.practice.remove(user);
+                game.invalidated_by.clear();
+                return Some(DuelEvent::Reset { offset });
+            }
+        }
+        // The "other" patterns also match our own lines, as player "You"
+        let other = |name: &str| Some(player_name(name)).filter(|n| n != "You");
+        let user = self.parsed.username.clone();
+        let p = &self.patterns;
+        let event = if let (Some((c, comma)), Some(user)) =
+            (p.you_checkpoint.captures(text, language), &user)
+        {
+            Some((user.clone(), checkpoint(&c[1], &c[2], comma)?))
+        } else if let Some((c, comma)) = p.other_checkpoint.captures(text, language) {
+            Some((other(&c[1])?, checkpoint(&c[2], &c[3], comma)?))
+        } else if let (Some((c, comma)), Some(user)) =
+            (p.you_finish.captures(text, language), &user)
+        {
+            Some((user.clone(), finish(&c[1], comma)))
+        } else {
+            let (c, comma) = p.other_finish.captures(text, language)?;
+            Some((other(&c[1])?, finish(&c[2], comma)))
+        };
+        let (player, event) = event?;
+        self.progress(offset, player, event)
+    }
+
+    /// Every game seen so far, including the one in progress. In a server
+    /// log, runs still going are left out until a later read sees them end.
+    pub fn finish(mut self) -> Parsed {
+        self.close_game();
+        self.close_defined();
+        // Defined runs can close out of order with the built-in games
+        self.parsed.games.sort_by_key(|g| g.offset);
+        for game in &mut self.parsed.games {
+            for events in game.players.values_mut() {
+                events.sort_by_key(|e| e.cp);
+            }
+        }
+        self.parsed
+    }
+}
+
+/// Finds every Parkour Duels game in a log, with each player's checkpoint
+/// and finish times. Games nobody reached a checkpoint in are dropped.
+pub fn parse(raw: &str) -> Parsed {
+    parse_with(raw, &Patterns::default())
+}
+
+pub fn parse_with(raw: &str, patterns: &Patterns) -> Parsed {
+    let mut parser = Parser::new(patterns.clone());
+    for line in lines(raw, 0) {
+        parser.line(line.start as u64, line.text);
+    }
+    parser.finish()
+}
+
+/// `{"map": "Parkour Duels", "games": 12, "finishes": 9, "wins": 4, "best_ms": 41250, "average_ms": 47800}`
+///
+/// Duels logs don't name the map, so games are grouped by their title.
+/// Everything but `map` is counted for one player.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct MapStats {
+    pub map: String,
+    pub games: i64,
+    pub finishes: i64,
+    pub wins: i64,
+    pub best_ms: Option<i64>,
+    pub average_ms: Option<i64>,
+}
+
+/// Per-map totals for the games `player` took part in.
+pub fn map_stats<'a>(games: impl IntoIterator<Item = &'a Game>, player: &str) -> Vec<MapStats> {
+    let mut by_map: BTreeMap<&str, (i64, i64, Vec<i64>)> = BTreeMap::new();
+    for game in games {
+        if !game.players.contains_key(player) {
+            continue;
+        }
+        let (played, wins, times) = by_map.entry(&game.title).or_default();
+        *played += 1;
+        if let Some(ms) = game.finish(player).and_then(PlayerEvent::time_ms) {
+            times.push(ms);
+        }
+        if game.winner().is_some_and(|(winner, _)| winner == player) {
+            *wins += 1;
+        }
+    }
+    by_map
+        .into_iter()
+        .map(|(map, (games, wins, times))| MapStats {
+            map: map.to_string(),
+            games,
+            finishes: times.len() as i64,
+            wins,
+            best_ms: times.iter().copied().min(),
+            average_ms: (!times.is_empty()).then(|| times.iter().sum::<i64>() / times.len() as i64),
+        })
+        .collect()
+}
+
+/// `{"player": "Steve", "time_ms": 41250, "game": 3}`
+///
+/// `game` is the index of the game the time was set in.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct PersonalBest {
+    pub player: String,
+    pub time_ms: i64,
+    pub game: usize,
+}
+
+/// Each player's fastest finish, fastest first.
+pub fn personal_bests(games: &[Game]) -> Vec<PersonalBest> {
+    let mut best: BTreeMap<&str, PersonalBest> = BTreeMap::new();
+    for (i, game) in games.iter().enumerate() {
+        for player in game.players.keys() {
+            let Some(ms) = game.finish(player).and_then(PlayerEvent::time_ms) else {
+                continue;
+            };
+            if best.get(player.as_str()).is_none_or(|pb| ms < pb.time_ms) {
+                best.insert(
+                    player,
+                    PersonalBest {
+                        player: player.clone(),
+                        time_ms: ms,
+                        game: i,
+                    },
+                );
+            }
+        }
+    }
+    let mut best: Vec<_> = best.into_values().collect();
+    best.sort_by_key(|pb| pb.time_ms);
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Anomalies, Expected, MapSpec, Scenario};
+    use crate::log_line::LogFormat;
+    use crate::tail::decode;
+
+    #[test]
+    fn parses_a_game() {
+        let log = "\
+[10:00:00] [main/INFO]: Setting user: Steve
+[10:00:01] [Client thread/INFO]: [CHAT] §eParkour Duels
+[10:00:02] [Client thread/INFO]: [CHAT] Opponents: Alex
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!
+[10:00:04] [Client thread/INFO]: [CHAT] CHECKPOINT! §a[VIP] Alex reached checkpoint 1 in 0:06.000!
+[10:00:05] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:20.000!
+";
+        let parsed = parse(log);
+        assert_eq!(parsed.username.as_deref(), Some("Steve"));
+        assert_eq!(parsed.games.len(), 1);
+        let game = &parsed.games[0];
+        assert_eq!(game.opponents, "Alex");
+        assert_eq!(game.players["Steve"].len(), 2);
+        assert_eq!(game.finish("Steve").unwrap().time_ms(), Some(20_000));
+        assert_eq!(game.players["Alex"][0].cp, 1);
+        assert!(game.finish("Alex").is_none());
+    }
+
+    #[test]
+    fn localized_messages() {
+        let log = "\
+[10:00:00] [main/INFO]: Setting user: Steve
+[10:00:01] [Client thread/INFO]: [CHAT] §eParkour Duels
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! Du hast Checkpoint 1 in 0:05,250 erreicht!
+[10:00:03] [Client thread/INFO]: [CHAT] КОНТРОЛЬНАЯ ТОЧКА! Алекс достигла контрольной точки 1 за 0:06,5!
+[10:00:04] [Client thread/INFO]: [CHAT] CHECKPOINT! Alex reached checkpoint 2 in 0:07.250!
+[10:00:05] [Client thread/INFO]: [CHAT] ПРОЙДЕНО! Вы прошли паркур за 0:20,125!
+";
+        let game = &parse(log).games[0];
+        let steve: Vec<i64> = game.players["Steve"]
+            .iter()
+            .filter_map(|e| e.time_ms())
+            .collect();
+        assert_eq!(steve, [5_250, 20_125]);
+        assert_eq!(game.players["Алекс"][0].time_ms(), Some(6_500));
+        assert_eq!(game.players["Alex"][0].time_ms(), Some(7_250));
+    }
+
+    #[test]
+    fn player_chat_splits_off_the_sender() {
+        assert_eq!(player_chat("<Steve> !reset"), Some(("Steve", "!reset")));
+        assert_eq!(player_chat("Steve: !split"), Some(("Steve", "!split")));
+        assert_eq!(
+            player_chat("[VIP] Steve: !note a: b"),
+            Some(("Steve", "!note a: b"))
+        );
+        assert_eq!(
+            player_chat("[MVP+] [GUILD] Steve_2: hi"),
+            Some(("Steve_2", "hi"))
+        );
+        assert_eq!(player_chat("Checkpoint reached: 3"), None);
+        assert_eq!(player_chat("Guild > Steve: hi"), None);
+        assert_eq!(player_chat("<> hi"), None);
+        assert_eq!(player_chat("<Steve>hi"), None);
+        assert_eq!(player_chat("Seventeen_letters: hi"), None);
+        assert_eq!(player_chat("St.eve: hi"), None);
+        assert_eq!(player_chat("no sender here"), None);
+    }
+
+    #[test]
+    fn player_chat_is_only_trusted_when_asked() {
+        let log = "\
+[20:00:00] [main/INFO]: Setting user: Steve
+[20:00:01] [Client thread/INFO]: [CHAT] Parkour Duels
+[20:00:02] [Client thread/INFO]: [CHAT] [VIP] Bob: CHECKPOINT! Alex reached checkpoint 1 in 0:01.000!
+[20:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! Alex reached checkpoint 2 in 0:05.000!
+";
+        let trusted = parse_with(log, &Patterns::default());
+        let strict = parse_with(log, &Patterns::default().with_trust_player_chat(false));
+        assert_eq!(trusted.games[0].players["Alex"].len(), 2);
+        assert_eq!(strict.games[0].players["Alex"].len(), 1);
+    }
+
+    #[test]
+    fn practice_marks_the_players_who_used_it() {
+        let log = "\
+[10:00:00] [main/INFO]: Setting user: Steve
+[10:00:01] [Client thread/INFO]: [CHAT] §eParkour Duels
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! Alex reached checkpoint 2 in 0:05.250!
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! Alex reached checkpoint 1 in 0:06.250!
+[10:00:04] [Client thread/INFO]: [CHAT] §aTeleported to checkpoint!
+[10:00:05] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:20.000!
+[10:00:06] [Client thread/INFO]: [CHAT] §eParkour Duels
+[10:00:07] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:20.000!
+[10:00:08] [Client thread/INFO]: [CHAT] Practice mode enabled
+";
+        let parsed = parse(log);
+        // Alex went back a checkpoint, Steve teleported
+        let practice: Vec<&str> = parsed.games[0]
+            .practice
+            .iter()
+            .map(|p| p.as_str())
+            .collect();
+        assert_eq!(practice, ["Alex", "Steve"]);
+        // Practice after the finish doesn't count against it
+        assert!(parsed.games[1].practice.is_empty());
+
+        let spec = PatternSpec {
+            practice: Some(Variants::One("^/cp used$".into())),
+            ..Default::default()
+        };
+        let custom = Patterns::from_spec(&spec).unwrap();
+        assert!(custom.is_practice("/cp used"));
+        assert!(!custom.is_practice("Teleported to checkpoint"));
+    }
+
+    #[test]
+    fn legitimacy_rules_invalidate_the_game() {
+        let log = "\
+[10:00:00] [main/INFO]: Setting user: Steve
+[10:00:01] [Client thread/INFO]: [CHAT] §eParkour Duels
+[10:00:02] [Client thread/INFO]: [CHAT] Bob: anticheat disabled lol
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!
+[10:00:05] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:20.000!
+[10:00:06] [Client thread/INFO]: [CHAT] §eParkour Duels
+[10:00:06] [Client thread/INFO]: [CHAT] Set fly mode enabled for Steve.
+[10:00:07] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:20.000!
+";
+        let parsed = parse(log);
+        assert!(parsed.games[0].invalidated_by.is_empty());
+        let rules: Vec<&str> = parsed.games[1]
+            .invalidated_by
+            .iter()
+            .map(|r| r.as_str())
+            .collect();
+        assert_eq!(rules, ["fly"]);
+        let unnamed = crate::legitimacy::LegitimacyRule {
+            name: "".into(),
+            pattern: "x".into(),
+        };
+        assert!(Patterns::default()
+            .with_legitimacy_rules(&[unnamed])
+            .is_err());
+    }
+
+    #[test]
+    fn spec_variants_are_checked() {
+        let spec: PatternSpec = serde_json::from_str(
+            r#"{"you_finish": [{"pattern": "FERTIG in ([\\d:,]+)", "language": "de", "decimal_comma": true}, {"pattern": "DONE in ([\\d:.]+)"}], "game_start": "^Speed$"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_string(&spec.game_start).unwrap(),
+            r#""^Speed$""#
+        );
+        Patterns::from_spec(&spec).unwrap();
+        let empty: PatternSpec = serde_json::from_str(r#"{"you_finish": []}"#).unwrap();
+        assert!(Patterns::from_spec(&empty)
+            .unwrap_err()
+            .contains("no variants"));
+        let groupless: PatternSpec =
+            serde_json::from_str(r#"{"you_finish": [{"pattern": "x"}]}"#).unwrap();
+        assert!(Patterns::from_spec(&groupless)
+            .unwrap_err()
+            .contains("capture groups"));
+    }
+
+    #[test]
+    fn reset_starts_the_players_run_over() {
+        let log = "\
+[10:00:00] [main/INFO]: Setting user: Steve
+[10:00:01] [Client thread/INFO]: [CHAT] Parkour Duels
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!
+[10:00:04] [Client thread/INFO]: [CHAT] CHECKPOINT! Alex reached checkpoint 1 in 0:06.000!
+[10:00:05] [Client thread/INFO]: [CHAT] §cReset your timer to 00:00! Get to the finish line!
+[10:00:09] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:04.000!
+[10:00:20] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:15.000!
+[10:00:21] [Client thread/INFO]: [CHAT] §cReset your timer to 00:00! Get to the finish line!
+";
+        let mut parser = Parser::new(Patterns::default());
+        let events: Vec<DuelEvent> = lines(log, 0)
+            .filter_map(|l| parser.line(l.start as u64, l.text))
+            .collect();
+        let resets: Vec<u64> = events
+            .iter()
+            .filter_map(|e| match e {
+                DuelEvent::Reset { offset } => Some(*offset),
+                _ => None,
+            })
+            .collect();
+        // A reset after the finish has no run to start over
+        assert_eq!(resets, [log.find("[10:00:05]").unwrap() as u64]);
+
+        let game = &parser.finish().games[0];
+        let steve: Vec<i64> = game.players["Steve"]
+            .iter()
+            .filter_map(|e| e.time_ms())
+            .collect();
+        assert_eq!(steve, [4_000, 15_000]);
+        assert_eq!(game.players["Alex"].len(), 1);
+    }
+
+    #[test]
+    fn custom_reset_pattern() {
+        let spec = PatternSpec {
+            reset: Some(Variants::One("^Run restarted$".into())),
+            ..Default::default()
+        };
+        let patterns = Patterns::from_spec(&spec).unwrap();
+        assert!(patterns.reset.is_match("Run restarted"));
+        assert!(!patterns.reset.is_match("Reset your timer to 00:00!"));
+    }
+
+    // Games, runs and finishes, counted the way generate::Expected is
+    fn found(parsed: &Parsed) -> Expected {
+        let finishes = |g: &Game| g.players.keys().filter(|p| g.finish(p).is_some()).count();
+        Expected {
+            games: parsed.games.len(),
+            runs: parsed.games.iter().map(|g| g.players.len()).sum(),
+            finishes: parsed.games.iter().map(finishes).sum(),
+        }
+    }
+
+    #[test]
+    fn generated_logs_parse_as_expected() {
+        let anomalies = [
+            Anomalies::default(),
+            Anomalies {
+                truncated: true,
+                garbage: 3,
+                json_chat: 3,
+                crash: true,
+            },
+        ];
+        for language in ["en", "de", "ru"] {
+            for format in [LogFormat::Classic, LogFormat::Logger, LogFormat::Dated] {
+                for anomalies in &anomalies {
+                    let scenario = Scenario {
+                        seed: 7,
+                        sessions: 2,
+                        games: 6,
+                        players_per_game: 2,
+                        language: language.into(),
+                        format,
+                        anomalies: anomalies.clone(),
+                        ..Scenario::default()
+                    };
+                    for log in generate(&scenario).unwrap() {
+                        let parsed = parse(&decode(log.content));
+                        let what = (language, format, &log.name);
+                        assert_eq!(found(&parsed), log.expected, "{:?}", what);
+                        assert_eq!(parsed.username.as_deref(), Some("Steve"), "{:?}", what);
+                        let times = parsed
+                            .games
+                            .iter()
+                            .flat_map(|g| g.players.values().flatten());
+                        for event in times {
+                            assert!(event.time_ms().is_some(), "{:?} {:?}", what, event);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_splits_follow_the_map() {
+        let scenario = Scenario {
+            maps: vec![MapSpec {
+                checkpoints: 3,
+                split_ms: (1000, 2000),
+                ..MapSpec::default()
+            }],
+            finish_rate: 1.0,
+            ..Scenario::default()
+        };
+        let log = generate(&scenario).unwrap().remove(0);
+        let parsed = parse(&decode(log.content));
+        assert_eq!(parsed.games.len(), scenario.games);
+        for game in &parsed.games {
+            let steve = &game.players["Steve"];
+            let cps: Vec<u32> = steve.iter().map(|e| e.cp).collect();
+            assert_eq!(cps, [1, 2, 3, FINISH]);
+            let times: Vec<i64> = steve.iter().filter_map(|e| e.time_ms()).collect();
+            assert!(times
+                .windows(2)
+                .all(|w| (1000..=2000).contains(&(w[1] - w[0]))));
+        }
+        assert!(generate(&Scenario {
+            language: "xx".into(),
+            ..Scenario::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,56 @@
+pub fn format_ms(ms: i64) -> String {
+    let sign = if ms < 0 { "-" } else { "" };
+    let ms = ms.abs();
+    format!(
+        "{}{}:{:02}.{:03}",
+        sign,
+        ms / 60_000,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+pub fn format_delta(ms: i64) -> String {
+    if ms > 0 {
+        format!("+{}", format_ms(ms))
+    } else {
+        format_ms(ms)
+    }
+}
+
+pub fn format_span(ms: i64) -> String {
+    let secs = ms.max(0) / 1000;
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs / 60 % 60)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// A chat timer the way [`parse_ms`] reads it: with `decimal_comma`, as
+/// servers speaking German or Russian write them, `1:23,456` becomes
+/// `1:23.456`.
+pub fn normalize_timer(s: &str, decimal_comma: bool) -> String {
+    match decimal_comma {
+        true => s.replace(',', "."),
+        false => s.to_string(),
+    }
+}
+
+/// Parses a chat timer like `1:23.456`, `23.456` or a delta like `(-0:01.200)`
+/// into milliseconds.
+pub fn parse_ms(s: &str) -> Option<i64> {
+    let s: String = s.chars().filter(|c| !"()+ ".contains(*c)).collect();
+    if let Some(rest) = s.strip_prefix('-') {
+        return parse_ms(rest).map(|ms| -ms);
+    }
+    let (minutes, seconds) = match s.split_once(':') {
+        Some((m, s)) => (m.parse::<i64>().ok()?, s),
+        None => (0, s.as_str()),
+    };
+    let seconds: f64 = seconds.parse().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    Some(minutes * 60_000 + (seconds * 1000.0).round() as i64)
+}
@@ -0,0 +1,556 @@
+use crate::client::Client;
+use crate::duration::format_ms;
+use crate::log_line::LogFormat;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Synthetic logs for tests and demos, so nobody has to share a real chat
+/// log. The same scenario and seed always give the same bytes.
+///
+/// Every field has a default, so `{}` is a valid scenario:
+///
+/// `{"seed": 7, "sessions": 2, "start": "2024-05-01T23:40:00", "servers": ["mc.hypixel.net"], "maps": [{"title": "Parkour Duels", "checkpoints": 4, "split_ms": [6000, 15000]}], "anomalies": {"crash": true, "garbage": 2}}`
+///
+/// `format` picks the line prefix (see [`LogFormat`]) and `client` the
+/// client whose startup lines and quirks the log has (see [`Client`]), so one
+/// scenario gives a fixture for each. `language` is the one the server
+/// writes progress messages in: `en`, `de` or `ru`. Map titles are what the game announces; the built-in patterns only
+/// recognize titles containing "Parkour Duels". A `start` shortly before
+/// midnight makes the first session roll over to the next day.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub seed: u64,
+    pub sessions: usize,
+    pub start: NaiveDateTime,
+    pub username: String,
+    pub opponents: Vec<String>,
+    // Opponents in each game
+    pub players_per_game: usize,
+    pub servers: Vec<String>,
+    pub maps: Vec<MapSpec>,
+    // Games in each session
+    pub games: usize,
+    // Chance that a player reaches the finish
+    pub finish_rate: f64,
+    pub anomalies: Anomalies,
+    pub format: LogFormat,
+    // "Client thread" before 1.13, "Render thread" after
+    pub thread: String,
+    pub client: Client,
+    pub language: String,
+}
+
+impl Default for Scenario {
+    fn default() -> Scenario {
+        Scenario {
+            seed: 1,
+            sessions: 1,
+            start: NaiveDate::from_ymd_opt(2024, 5, 1)
+                .and_then(|d| d.and_hms_opt(20, 0, 0))
+                .unwrap_or_default(),
+            username: "Steve".into(),
+            opponents: vec!["Alex".into(), "Notch".into(), "jeb_".into()],
+            players_per_game: 1,
+            servers: vec!["mc.hypixel.net".into()],
+            maps: vec![MapSpec::default()],
+            games: 5,
+            finish_rate: 0.8,
+            anomalies: Anomalies::default(),
+            format: LogFormat::Classic,
+            thread: "Client thread".into(),
+            client: Client::Vanilla,
+            language: "en".into(),
+        }
+    }
+}
+
+/// Checkpoints on the way to the finish and the range each split's time is
+/// picked from.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapSpec {
+    pub title: String,
+    pub checkpoints: u32,
+    pub split_ms: (i64, i64),
+}
+
+impl Default for MapSpec {
+    fn default() -> MapSpec {
+        MapSpec {
+            title: "Parkour Duels".into(),
+            checkpoints: 4,
+            split_ms: (6000, 15000),
+        }
+    }
+}
+
+/// Things real logs contain that the parser has to survive. None of them
+/// change what it should find, except `crash`, which ends the last session
+/// in the middle of a game.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Anomalies {
+    // The last log stops halfway through a line
+    pub truncated: bool,
+    // Chat lines with bytes that aren't valid UTF-8
+    pub garbage: usize,
+    // Chat logged as raw JSON text components
+    pub json_chat: usize,
+    pub crash: bool,
+}
+
+/// What the parser should find in a generated log: `runs` counts every
+/// player with at least one checkpoint or finish in a game.
+#[derive(Serialize, JsonSchema, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Expected {
+    pub games: usize,
+    pub runs: usize,
+    pub finishes: usize,
+}
+
+/// `name` is an archive name like `2024-05-01-1.log`, so a folder of these
+/// imports in order.
+#[derive(Clone, Debug)]
+pub struct GeneratedLog {
+    pub name: String,
+    pub content: Vec<u8>,
+    pub expected: Expected,
+}
+
+// SplitMix64: small, and the same sequence on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn range(&mut self, (low, high): (i64, i64)) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+const RANKS: [&str; 4] = ["", "[VIP] ", "[VIP+] ", "[MVP+] "];
+const CHAT: [&str; 5] = ["gl", "gg", "gg wp", "close one", "rematch?"];
+
+struct Writer<'a> {
+    out: Vec<u8>,
+    now: NaiveDateTime,
+    format: LogFormat,
+    thread: &'a str,
+    client: Client,
+}
+
+impl Writer<'_> {
+    // A line from the game's client thread
+    fn line(&mut self, level: &str, message: &str) {
+        self.raw(self.thread, level, message.as_bytes());
+    }
+
+    fn chat(&mut self, message: &str) {
+        if self.client == Client::Badlion {
+            self.line("INFO", &format!("[BLC] Chat: {}", message));
+        } else {
+            self.line("INFO", &format!("[CHAT] {}", message));
+        }
+    }
+
+    fn raw(&mut self, thread: &str, level: &str, message: &[u8]) {
+        let logger = if message.starts_with(b"[CHAT]") {
+            "minecraft/ChatComponent"
+        } else {
+            "minecraft/Minecraft"
+        };
+        let logger = (self.format == LogFormat::Logger).then_some(logger);
+        self.write(thread, level, logger, message);
+    }
+
+    // A line with its own logger, which loaders write in every format
+    fn tagged(&mut self, thread: &str, logger: &str, message: &str) {
+        self.write(thread, "INFO", Some(logger), message.as_bytes());
+    }
+
+    fn write(&mut self, thread: &str, level: &str, logger: Option<&str>, message: &[u8]) {
+        let time = match self.format {
+            LogFormat::Dated => self.now.format("%Y-%m-%d %H:%M:%S%.3f"),
+            LogFormat::Classic | LogFormat::Logger => self.now.format("%H:%M:%S"),
+            LogFormat::Server => {
+                let prefix = format!("[{} {}]: ", self.now.format("%H:%M:%S"), level);
+                self.out.extend_from_slice(prefix.as_bytes());
+                self.out.extend_from_slice(message);
+                self.out.push(b'\n');
+                return;
+            }
+        };
+        let logger = logger.map(|l| format!(" [{}]", l)).unwrap_or_default();
+        let prefix = format!("[{}] [{}/{}]{}: ", time, thread, level, logger);
+        self.out.extend_from_slice(prefix.as_bytes());
+        self.out.extend_from_slice(message);
+        self.out.push(b'\n');
+    }
+
+    // The client's own lines while it starts up
+    fn startup(&mut self) {
+        match self.client {
+            Client::Vanilla => {}
+            Client::Lunar => self.line("INFO", "[LC] Starting Lunar Client 1.8.9"),
+            Client::Badlion => self.line("INFO", "[BLC] Badlion Client v4.2.0 starting"),
+            Client::Forge => self.tagged(
+                self.thread,
+                "FML",
+                "MinecraftForge v11.15.1.2318 Initialized",
+            ),
+            Client::Fabric => {
+                self.line("INFO", "Loading Minecraft 1.20.1 with Fabric Loader 0.15.0")
+            }
+            Client::Optifine => self.line("INFO", "[OptiFine] OptiFine_1.8.9_HD_U_M5"),
+        }
+    }
+
+    // Chatter the client keeps writing between games
+    fn client_noise(&mut self) {
+        match self.client {
+            Client::Vanilla | Client::Badlion | Client::Fabric => {}
+            Client::Lunar => self.line("INFO", "[LC] Refreshed cosmetics"),
+            Client::Forge => self.tagged(self.thread, "FML", "Unloading dimension 1"),
+            Client::Optifine => self.line("INFO", "[OptiFine] Loading shaders: (internal)"),
+        }
+    }
+
+    fn wait(&mut self, ms: i64) {
+        self.now += Duration::milliseconds(ms);
+    }
+}
+
+// Progress messages in one language, with `{}` standing for the player,
+// checkpoint and timer in that order
+struct Phrases {
+    you_checkpoint: &'static str,
+    other_checkpoint: &'static str,
+    you_finish: &'static str,
+    other_finish: &'static str,
+    decimal_comma: bool,
+}
+
+const PHRASES: &[(&str, Phrases)] = &[
+    (
+        "en",
+        Phrases {
+            you_checkpoint: "CHECKPOINT! You reached checkpoint {} in {}!",
+            other_checkpoint: "CHECKPOINT! {} reached checkpoint {} in {}!",
+            you_finish: "COMPLETED! You completed the parkour in {}!",
+            other_finish: "COMPLETED! {} completed the parkour in {}!",
+            decimal_comma: false,
+        },
+    ),
+    (
+        "de",
+        Phrases {
+            you_checkpoint: "CHECKPOINT! Du hast Checkpoint {} in {} erreicht!",
+            other_checkpoint: "CHECKPOINT! {} hat Checkpoint {} in {} erreicht!",
+            you_finish: "ABGESCHLOSSEN! Du hast den Parkour in {} abgeschlossen!",
+            other_finish: "ABGESCHLOSSEN! {} hat den Parkour in {} abgeschlossen!",
+            decimal_comma: true,
+        },
+    ),
+    (
+        "ru",
+        Phrases {
+            you_checkpoint: "КОНТРОЛЬНАЯ ТОЧКА! Вы достигли контрольной точки {} за {}!",
+            other_checkpoint: "КОНТРОЛЬНАЯ ТОЧКА! {} достиг контрольной точки {} за {}!",
+            you_finish: "ПРОЙДЕНО! Вы прошли паркур за {}!",
+            other_finish: "ПРОЙДЕНО! {} прошёл паркур за {}!",
+            decimal_comma: true,
+        },
+    ),
+];
+
+fn phrases(language: &str) -> Option<&'static Phrases> {
+    PHRASES
+        .iter()
+        .find(|(code, _)| *code == language)
+        .map(|(_, phrases)| phrases)
+}
+
+// Fills the `{}`s of a phrase in order
+fn fill(phrase: &str, values: &[&str]) -> String {
+    let mut out = String::new();
+    let mut parts = phrase.split("{}");
+    out.push_str(parts.next().unwrap_or_default());
+    for (part, value) in parts.zip(values) {
+        out.push_str(value);
+        out.push_str(part);
+    }
+    out
+}
+
+// One player's checkpoints and finish, as (timer, whether it's the finish,
+// chat message)
+fn race(rng: &mut Rng, scenario: &Scenario, map: &MapSpec, name: &str) -> Vec<(i64, bool, String)> {
+    let phrases = phrases(&scenario.language).unwrap_or(&PHRASES[0].1);
+    let shown = |timer: i64| {
+        let shown = format_ms(timer);
+        match phrases.decimal_comma {
+            true => shown.replace('.', ","),
+            false => shown,
+        }
+    };
+    let finishes = rng.chance(scenario.finish_rate);
+    let reached = if finishes {
+        map.checkpoints
+    } else {
+        rng.below(map.checkpoints as usize + 1) as u32
+    };
+    let you = name == scenario.username;
+    let mut timer = 0;
+    let mut events = Vec::new();
+    for cp in 1..=reached {
+        timer += rng.range(map.split_ms);
+        let cp = cp.to_string();
+        let message = if you {
+            fill(phrases.you_checkpoint, &[&cp, &shown(timer)])
+        } else {
+            fill(phrases.other_checkpoint, &[name, &cp, &shown(timer)])
+        };
+        events.push((timer, false, message));
+    }
+    if finishes {
+        timer += rng.range(map.split_ms);
+        let message = if you {
+            fill(phrases.you_finish, &[&shown(timer)])
+        } else {
+            fill(phrases.other_finish, &[name, &shown(timer)])
+        };
+        events.push((timer, true, message));
+    }
+    events
+}
+
+// Plays one game; with `crash`, the log ends halfway through it
+fn game(rng: &mut Rng, scenario: &Scenario, w: &mut Writer, crash: bool) -> Expected {
+    let map = rng.pick(&scenario.maps);
+    let mut opponents: Vec<String> = Vec::new();
+    while opponents.len() < scenario.players_per_game.min(scenario.opponents.len()) {
+        let name = rng.pick(&scenario.opponents);
+        if !opponents.contains(name) {
+            opponents.push(name.clone());
+        }
+    }
+    let ranked: Vec<String> = opponents
+        .iter()
+        .map(|name| format!("{}{}", rng.pick(&RANKS), name))
+        .collect();
+
+    w.chat(&format!("                          {}", map.title));
+    w.chat(&format!("Opponents: {}", ranked.join(", ")));
+    w.wait(5000);
+    let start = w.now;
+
+    let mut expected = Expected {
+        games: 1,
+        ..Default::default()
+    };
+    let mut events = Vec::new();
+    let you = race(rng, scenario, map, &scenario.username);
+    expected.runs += !you.is_empty() as usize;
+    events.extend(you.into_iter().map(|(t, done, m)| (t, 0, done, m)));
+    for (i, name) in ranked.iter().enumerate() {
+        let theirs = race(rng, scenario, map, name);
+        expected.runs += !theirs.is_empty() as usize;
+        events.extend(theirs.into_iter().map(|(t, done, m)| (t, i + 1, done, m)));
+    }
+    events.sort();
+    if crash {
+        events.truncate(events.len().div_ceil(2).max(1));
+        expected.runs = (0..=ranked.len())
+            .filter(|p| events.iter().any(|(_, player, _, _)| player == p))
+            .count();
+    }
+    expected.finishes = events.iter().filter(|(_, _, done, _)| *done).count();
+    if events.is_empty() {
+        // Nobody got anywhere, so the parser doesn't count the game
+        expected.games = 0;
+    }
+
+    let mut last = 0;
+    for (timer, _, _, message) in &events {
+        w.now = start + Duration::milliseconds(*timer);
+        w.chat(message);
+        last = *timer;
+    }
+    w.now = start + Duration::milliseconds(last);
+    if crash {
+        w.wait(1500);
+        w.line("FATAL", "Unreported exception thrown!");
+        w.out
+            .extend_from_slice(b"---- Minecraft Crash Report ----\n// Oops.\n\n");
+        w.out.extend_from_slice(
+            b"Description: Unexpected error\n\njava.lang.NullPointerException: Unexpected error\n",
+        );
+        return expected;
+    }
+    w.wait(3000);
+    for name in &ranked {
+        if !rng.chance(0.5) {
+            continue;
+        }
+        w.chat(&format!("{}: {}", name, rng.pick(&CHAT)));
+        w.wait(rng.range((500, 4000)));
+    }
+    expected
+}
+
+// Lobby chat between games, in the formats the parser has to skip over
+fn noise(rng: &mut Rng, scenario: &Scenario, w: &mut Writer, json: usize, garbage: usize) {
+    for _ in 0..json {
+        let name = rng.pick(&scenario.opponents);
+        w.chat(&format!(
+            r#"{{"text":"","extra":[{{"text":"{}","color":"gray"}},{{"text":": {}"}}]}}"#,
+            name,
+            rng.pick(&CHAT)
+        ));
+        w.wait(rng.range((500, 3000)));
+    }
+    for _ in 0..garbage {
+        let name = rng.pick(&scenario.opponents);
+        let mut message = format!("[CHAT] {}: caf", name).into_bytes();
+        // Latin-1 "é" and stray bytes, as old logs in the system code page have
+        message.extend_from_slice(&[0xe9, b' ', 0xff, 0xfe, b'!']);
+        w.raw(w.thread, "INFO", &message);
+        w.wait(rng.range((500, 3000)));
+    }
+}
+
+// How many of `count` anomalies land in each of `slots` places
+fn spread(rng: &mut Rng, count: usize, slots: usize) -> Vec<usize> {
+    let mut spread = vec![0; slots.max(1)];
+    for _ in 0..count {
+        let slot = rng.below(spread.len());
+        spread[slot] += 1;
+    }
+    spread
+}
+
+/// Writes one log per session of `scenario`, oldest first.
+pub fn generate(scenario: &Scenario) -> Result<Vec<GeneratedLog>, String> {
+    if scenario.maps.is_empty() || scenario.servers.is_empty() {
+        return Err("A scenario needs at least one map and one server".into());
+    }
+    if scenario.players_per_game > 0 && scenario.opponents.is_empty() {
+        return Err("A scenario with players_per_game needs opponents".into());
+    }
+    if phrases(&scenario.language).is_none() {
+        return Err(format!(
+            "Unknown language {}: expected en, de or ru",
+            scenario.language
+        ));
+    }
+    if let Some(map) = scenario
+        .maps
+        .iter()
+        .find(|m| m.split_ms.0 < 0 || m.split_ms.0 > m.split_ms.1)
+    {
+        return Err(format!(
+            "Invalid split_ms for {}: expected [low, high] with 0 <= low <= high",
+            map.title
+        ));
+    }
+
+    let mut rng = Rng(scenario.seed);
+    let slots = scenario.sessions * scenario.games;
+    let json = spread(&mut rng, scenario.anomalies.json_chat, slots);
+    let garbage = spread(&mut rng, scenario.anomalies.garbage, slots);
+    let mut logs: Vec<GeneratedLog> = Vec::new();
+    let mut now = scenario.start;
+    for session in 0..scenario.sessions {
+        let last_session = session + 1 == scenario.sessions;
+        let date = now.date();
+        let mut w = Writer {
+            out: Vec::new(),
+            now,
+            format: scenario.format,
+            thread: &scenario.thread,
+            client: scenario.client,
+        };
+        let server = rng.pick(&scenario.servers).clone();
+        // Forge starts through LaunchWrapper, whose lines carry a logger even
+        // in logs whose chat doesn't
+        if scenario.client == Client::Forge {
+            w.tagged(
+                "main",
+                "LaunchWrapper",
+                "Loading tweak class name net.minecraftforge.fml.common.launcher.FMLTweaker",
+            );
+        }
+        w.raw(
+            "main",
+            "INFO",
+            format!("Setting user: {}", scenario.username).as_bytes(),
+        );
+        w.wait(4000);
+        w.line("INFO", "LWJGL Version: 2.9.4");
+        w.startup();
+        w.wait(rng.range((5000, 20000)));
+        w.line("INFO", &format!("Connecting to {}, 25565", server));
+        w.wait(rng.range((3000, 10000)));
+
+        let mut expected = Expected::default();
+        // The crash hits the middle game of the last session
+        let crash_at = (scenario.anomalies.crash && last_session).then_some(scenario.games / 2);
+        for g in 0..scenario.games {
+            let slot = session * scenario.games + g;
+            w.client_noise();
+            noise(&mut rng, scenario, &mut w, json[slot], garbage[slot]);
+            let crash = crash_at == Some(g);
+            let played = game(&mut rng, scenario, &mut w, crash);
+            expected.games += played.games;
+            expected.runs += played.runs;
+            expected.finishes += played.finishes;
+            if crash {
+                break;
+            }
+            w.wait(rng.range((10_000, 40_000)));
+        }
+        if scenario.anomalies.truncated && last_session {
+            let name = rng.pick(&scenario.opponents);
+            w.chat(&format!("{}: good game everyone", name));
+            // Drop the end of the line and the newline, as if the game was
+            // killed mid-write
+            let cut = w.out.len() - 10;
+            w.out.truncate(cut);
+        } else if crash_at.is_none() {
+            w.line("INFO", "Stopping!");
+        }
+
+        let index = logs
+            .iter()
+            .filter(|l| l.name.starts_with(&date.to_string()))
+            .count()
+            + 1;
+        logs.push(GeneratedLog {
+            name: format!("{}-{}.log", date, index),
+            content: w.out,
+            expected,
+        });
+        now = w.now + Duration::hours(2);
+    }
+    Ok(logs)
+}
@@ -0,0 +1,617 @@
+use crate::db;
+use crate::duels::{self, EventKind, MapStats, Parsed, Patterns};
+use crate::lines::lines;
+use crate::log_line::{parse_line, seconds_of_day, LineParser};
+use crate::model::{NewRun, Split};
+use crate::servers::PlayContext;
+use crate::timestamps::LOCAL_FORMAT;
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use flate2::read::GzDecoder;
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// A log found by [`find_logs`]. `date` is the day it was started: from the
+/// name for the game's `2024-05-01-3.log.gz` archives, otherwise the day it
+/// was last written.
+#[derive(Clone, Debug)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub date: NaiveDate,
+    pub size: u64,
+    pub modified_ms: Option<i64>,
+    // Archive number within the day; latest.log and friends come after
+    index: u32,
+}
+
+impl LogFile {
+    pub fn key(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+
+    /// Whether it's one of the game's rotated logs, named by its date.
+    pub fn is_archive(&self) -> bool {
+        self.index != u32::MAX
+    }
+}
+
+fn is_log(name: &str) -> bool {
+    name.ends_with(".log") || name.ends_with(".log.gz")
+}
+
+// "2024-05-01-3.log.gz" -> (2024-05-01, 3)
+fn archive_name(name: &str) -> Option<(NaiveDate, u32)> {
+    let stem = name
+        .strip_suffix(".gz")
+        .unwrap_or(name)
+        .strip_suffix(".log")?;
+    let (date, index) = stem.rsplit_once('-')?;
+    Some((
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?,
+        index.parse().ok()?,
+    ))
+}
+
+/// A single log, dated the same way as by [`find_logs`].
+pub fn log_file(path: PathBuf) -> io::Result<LogFile> {
+    let meta = fs::metadata(&path)?;
+    let modified = meta.modified().ok();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (date, index) = match archive_name(&name) {
+        Some(archive) => archive,
+        None => {
+            let written = modified.map_or_else(Local::now, chrono::DateTime::<Local>::from);
+            (written.date_naive(), u32::MAX)
+        }
+    };
+    Ok(LogFile {
+        path,
+        date,
+        size: meta.len(),
+        modified_ms: modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64),
+        index,
+    })
+}
+
+/// Every `.log` and `.log.gz` under `dir`, oldest first.
+pub fn find_logs(dir: &Path) -> Result<Vec<LogFile>, String> {
+    // Imports are recorded by path, which has to be the same from any cwd
+    let dir =
+        fs::canonicalize(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut found = Vec::new();
+    let mut pending = vec![dir];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+                .path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_log(&path.file_name().unwrap_or_default().to_string_lossy()) {
+                found.push(
+                    log_file(path.clone())
+                        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+                );
+            }
+        }
+    }
+    found.sort_by(|a, b| (a.date, a.index, &a.path).cmp(&(b.date, b.index, &b.path)));
+    Ok(found)
+}
+
+/// Text of a log, decompressing archives. Old logs can hold chat in the
+/// system code page, which is read as cp1252, see [`decode`](crate::tail::decode).
+pub fn read_log(path: &Path) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        GzDecoder::new(file).read_to_end(&mut bytes)?;
+    } else {
+        io::BufReader::new(file).read_to_end(&mut bytes)?;
+    }
+    let content = crate::tail::decode(bytes);
+    Ok(crate::tail::strip_bom(&content).to_string())
+}
+
+// Log lines only carry the time of day; the date moves on whenever the
+// time goes backwards past midnight.
+struct Clock {
+    date: NaiveDate,
+    last: Option<u32>,
+}
+
+impl Clock {
+    fn at(&mut self, time: &str) -> Option<NaiveDateTime> {
+        let secs = seconds_of_day(time)?;
+        if self.last.is_some_and(|last| secs < last) {
+            self.date = self.date.succ_opt()?;
+        }
+        self.last = Some(secs);
+        Some(
+            self.date
+                .and_time(NaiveTime::from_num_seconds_from_midnight_opt(secs, 0)?),
+        )
+    }
+}
+
+/// What reading one log found. `runs_found` counts every player's run in
+/// every game, `duplicates` those that were already stored.
+#[derive(Serialize, JsonSchema, Clone, Debug, Default)]
+pub struct LogImport {
+    pub runs_found: usize,
+    pub runs_added: usize,
+    pub duplicates: usize,
+    pub session_id: Option<i64>,
+}
+
+struct Dated {
+    parsed: Parsed,
+    // Start of each game, in order, and where it was played
+    starts: Vec<Option<NaiveDateTime>>,
+    contexts: Vec<PlayContext>,
+    first: Option<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+    active: ActiveTime,
+}
+
+fn date(content: &str, file: &LogFile, patterns: &Patterns) -> Dated {
+    let parsed = duels::parse_with(content, patterns);
+    let mut dated = date_from(content, parsed, file.date);
+    // A log dated by when it was last written ends on that day, not starts
+    if file.index == u32::MAX {
+        if let Some(last) = dated.last.filter(|last| last.date() > file.date) {
+            let start = file.date - (last.date() - file.date);
+            dated = date_from(content, dated.parsed, start);
+        }
+    }
+    dated
+}
+
+fn date_from(content: &str, parsed: Parsed, date: NaiveDate) -> Dated {
+    let mut clock = Clock { date, last: None };
+    let mut offsets = parsed.games.iter().map(|g| g.offset).peekable();
+    let (mut starts, mut first, mut last) = (Vec::new(), None, None);
+    let mut active = ActiveTime::default();
+    let (mut contexts, mut context) = (Vec::new(), PlayContext::default());
+    let mut parser = LineParser::default();
+    let client = parsed.client.unwrap_or_default();
+    for line in lines(content, 0) {
+        let text = client.filter(line.text);
+        let at = text
+            .as_deref()
+            .and_then(|text| parser.time(line.start as u64, text))
+            .and_then(|t| clock.at(t));
+        if let Some(at) = at {
+            active.add(at);
+            first = first.or(Some(at));
+            last = Some(at);
+        }
+        // Only lines that can change it are parsed in full
+        if let Some(text) = text.as_deref().filter(|t| {
+            t.contains("Connecting to ") || t.contains(" level ") || t.contains("integrated")
+        }) {
+            if let Some(next) = context.next(&parse_line(line.start as u64, text).message) {
+                context = next;
+            }
+        }
+        if offsets.next_if_eq(&(line.start as u64)).is_some() {
+            starts.push(at);
+            contexts.push(context.clone());
+        }
+    }
+    Dated {
+        parsed,
+        starts,
+        contexts,
+        first,
+        last,
+        active,
+    }
+}
+
+fn runs(dated: &Dated) -> Vec<NewRun> {
+    let mut runs = Vec::new();
+    for ((game, start), context) in dated
+        .parsed
+        .games
+        .iter()
+        .zip(&dated.starts)
+        .zip(&dated.contexts)
+    {
+        let Some(start) = start.or(dated.first) else {
+            continue;
+        };
+        for (player, events) in &game.players {
+            let finish = game.finish(player).and_then(|e| e.time_ms());
+            runs.push(NewRun {
+                map: game.title.clone(),
+                username: player.clone(),
+                server: context.server.clone(),
+                world: context.world.clone(),
+                started_at: start.format(LOCAL_FORMAT).to_string(),
+                time_ms: finish,
+                completed: finish.is_some(),
+                fails: 0,
+                practice: game.practice.contains(player),
+                invalidated_by: match &dated.parsed.username {
+                    Some(user) if user == player => game.invalidated_by.iter().cloned().collect(),
+                    _ => Vec::new(),
+                },
+                splits: events
+                    .iter()
+                    .filter(|e| e.kind == EventKind::Checkpoint)
+                    .filter_map(|e| {
+                        Some(Split {
+                            checkpoint: e.cp as i64,
+                            name: None,
+                            time_ms: e.time_ms()?,
+                        })
+                    })
+                    .collect(),
+            });
+        }
+    }
+    runs
+}
+
+/// Lines further apart than this mean the player was away in between.
+pub const IDLE_AFTER_MS: i64 = 5 * 60 * 1000;
+
+/// Time spent playing, summed from timestamps in order: the gaps between
+/// them, leaving out those longer than [`IDLE_AFTER_MS`].
+#[derive(Default, Clone, Copy, Debug)]
+pub struct ActiveTime {
+    last: Option<NaiveDateTime>,
+    pub ms: i64,
+}
+
+impl ActiveTime {
+    pub fn add(&mut self, at: NaiveDateTime) {
+        if let Some(last) = self.last {
+            let gap = (at - last).num_milliseconds();
+            if (0..=IDLE_AFTER_MS).contains(&gap) {
+                self.ms += gap;
+            }
+        }
+        self.last = Some(at);
+    }
+}
+
+/// The logged-in player's time in one log, from its first to its last line.
+/// `active_ms` leaves out the gaps where they were away, see [`ActiveTime`].
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LogSession {
+    pub username: String,
+    pub started_at: String,
+    pub ended_at: String,
+    pub active_ms: i64,
+    pub client: String,
+}
+
+/// Everything in one log, read without storing it: the session of the
+/// player logged in, every player's runs, and the logged-in player's totals
+/// per map. `username` is unset for a log without a login, like a server's,
+/// which then has no session or map totals.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LogAnalysis {
+    pub path: String,
+    pub username: Option<String>,
+    pub session: Option<LogSession>,
+    pub runs: Vec<NewRun>,
+    pub maps: Vec<MapStats>,
+}
+
+/// Reads one log the way [`import_log`] does, without touching a database.
+pub fn analyze_log(file: &LogFile, content: &str, patterns: &Patterns) -> LogAnalysis {
+    let dated = date(content, file, patterns);
+    let username = dated.parsed.username.clone();
+    let session = match (&username, dated.first, dated.last) {
+        (Some(user), Some(first), Some(last)) => Some(LogSession {
+            username: user.clone(),
+            started_at: first.format(LOCAL_FORMAT).to_string(),
+            ended_at: last.format(LOCAL_FORMAT).to_string(),
+            active_ms: dated.active.ms,
+            client: dated.parsed.client.unwrap_or_default().name().to_string(),
+        }),
+        _ => None,
+    };
+    let maps = match &username {
+        Some(user) => duels::map_stats(&dated.parsed.games, user),
+        None => Vec::new(),
+    };
+    LogAnalysis {
+        path: file.key(),
+        username,
+        session,
+        runs: runs(&dated),
+        maps,
+    }
+}
+
+/// Stores the runs in one log, plus a session for the logged-in player, in a
+/// single transaction. Runs already in the database are skipped, and a log
+/// imported before keeps its session.
+pub fn import_log(
+    conn: &Connection,
+    file: &LogFile,
+    content: &str,
+    patterns: &Patterns,
+) -> Result<LogImport, String> {
+    import_analysis(conn, file, &analyze_log(file, content, patterns))
+}
+
+/// Stores what [`analyze_log`] read from `file`, as [`import_log`] would.
+pub fn import_analysis(
+    conn: &Connection,
+    file: &LogFile,
+    analysis: &LogAnalysis,
+) -> Result<LogImport, String> {
+    let runs = &analysis.runs;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to start import: {}", e))?;
+    let mut result = LogImport {
+        runs_found: runs.len(),
+        session_id: db::imported_session(&tx, &file.key())?,
+        ..Default::default()
+    };
+    if let (None, Some(session)) = (result.session_id, &analysis.session) {
+        result.session_id = Some(db::insert_session(
+            &tx,
+            &session.username,
+            &session.started_at,
+            Some(&session.ended_at),
+            session.active_ms,
+            &session.client,
+        )?);
+    }
+    for run in runs {
+        match db::insert_run(&tx, run)? {
+            Some(_) => result.runs_added += 1,
+            None => result.duplicates += 1,
+        }
+    }
+    db::mark_imported(
+        &tx,
+        &file.key(),
+        file.size,
+        file.modified_ms,
+        result.session_id,
+        &Local::now().format(LOCAL_FORMAT).to_string(),
+    )?;
+    tx.commit()
+        .map_err(|e| format!("Failed to finish import: {}", e))?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Anomalies, Scenario};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const LOG: &str = "\
+[23:59:00] [main/INFO]: Setting user: Steve
+[23:59:01] [Client thread/INFO]: Connecting to mc.hypixel.net, 25565
+[23:59:05] [Client thread/INFO]: [CHAT] Parkour Duels
+[23:59:10] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!
+[23:59:11] [Client thread/INFO]: [CHAT] CHECKPOINT! Alex reached checkpoint 1 in 0:06.000!
+[23:59:25] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:20.000!
+[00:01:00] [Client thread/INFO]: [CHAT] Parkour Duels
+[00:01:30] [Client thread/INFO]: [CHAT] COMPLETED! Alex completed the parkour in 0:25.500!
+";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("parkour-import-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn latest() -> LogFile {
+        LogFile {
+            path: "latest.log".into(),
+            date: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            size: LOG.len() as u64,
+            modified_ms: None,
+            index: u32::MAX,
+        }
+    }
+
+    #[test]
+    fn archives_are_dated_by_name() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        assert_eq!(archive_name("2024-05-01-3.log.gz"), Some((day, 3)));
+        assert_eq!(archive_name("2024-05-01-12.log"), Some((day, 12)));
+        assert_eq!(archive_name("latest.log"), None);
+        assert_eq!(archive_name("2024-13-01-1.log.gz"), None);
+    }
+
+    #[test]
+    fn finds_and_reads_logs_oldest_first() {
+        let dir = temp_dir("find");
+        fs::create_dir_all(dir.join("old")).unwrap();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(LOG.as_bytes()).unwrap();
+        fs::write(dir.join("old/2024-05-01-2.log.gz"), gz.finish().unwrap()).unwrap();
+        fs::write(dir.join("2024-05-01-1.log"), "\u{feff}one\n").unwrap();
+        fs::write(dir.join("latest.log"), "two\n").unwrap();
+        fs::write(dir.join("notes.txt"), "not a log\n").unwrap();
+
+        let found = find_logs(&dir).unwrap();
+        let names: Vec<String> = found
+            .iter()
+            .map(|f| f.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names[..2], ["2024-05-01-1.log", "2024-05-01-2.log.gz"]);
+        assert_eq!(names.len(), 3);
+        assert!(found[..2].iter().all(LogFile::is_archive));
+        assert_eq!(read_log(&found[0].path).unwrap(), "one\n");
+        assert_eq!(read_log(&found[1].path).unwrap(), LOG);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn analyzes_every_players_run() {
+        let analysis = analyze_log(&latest(), LOG, &Patterns::default());
+        assert_eq!(analysis.username.as_deref(), Some("Steve"));
+        let runs: Vec<(&str, &str, Option<i64>)> = analysis
+            .runs
+            .iter()
+            .map(|r| (r.username.as_str(), r.started_at.as_str(), r.time_ms))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                // latest.log was last written on the 1st, after midnight
+                ("Alex", "2024-04-30 23:59:05", None),
+                ("Steve", "2024-04-30 23:59:05", Some(20_000)),
+                ("Alex", "2024-05-01 00:01:00", Some(25_500)),
+            ]
+        );
+        assert!(analysis
+            .runs
+            .iter()
+            .all(|r| r.server.as_deref() == Some("mc.hypixel.net")));
+        assert_eq!(analysis.runs[1].splits.len(), 1);
+        let session = analysis.session.unwrap();
+        assert_eq!(session.started_at, "2024-04-30 23:59:00");
+        assert_eq!(session.active_ms, 150_000);
+    }
+
+    #[test]
+    fn idle_gaps_are_left_out_of_active_time() {
+        let log = format!(
+            "{}[00:20:00] [Client thread/INFO]: [CHAT] Parkour Duels
+[00:20:30] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!
+",
+            LOG
+        );
+        let session = analyze_log(&latest(), &log, &Patterns::default())
+            .session
+            .unwrap();
+        assert_eq!(session.ended_at, "2024-05-01 00:20:30");
+        // The 18.5 minutes away after 00:01:30 don't count
+        assert_eq!(session.active_ms, 180_000);
+
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, LOCAL_FORMAT).unwrap();
+        let mut active = ActiveTime::default();
+        for t in [
+            "2024-05-01 10:00:00",
+            "2024-05-01 10:05:00",
+            "2024-05-01 10:10:01",
+        ] {
+            active.add(at(t));
+        }
+        assert_eq!(active.ms, IDLE_AFTER_MS);
+        // Going back in time adds nothing
+        active.add(at("2024-05-01 09:00:00"));
+        assert_eq!(active.ms, IDLE_AFTER_MS);
+    }
+
+    #[test]
+    fn importing_again_adds_nothing() {
+        let db = db::Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        let file = latest();
+        let first = import_log(&conn, &file, LOG, &Patterns::default()).unwrap();
+        assert_eq!(
+            (first.runs_found, first.runs_added, first.duplicates),
+            (3, 3, 0)
+        );
+        assert!(first.session_id.is_some());
+        let again = import_log(&conn, &file, LOG, &Patterns::default()).unwrap();
+        assert_eq!((again.runs_added, again.duplicates), (0, 3));
+        assert_eq!(again.session_id, first.session_id);
+    }
+
+    #[test]
+    fn generated_archives_import_as_expected() {
+        let scenario = Scenario {
+            seed: 7,
+            sessions: 3,
+            start: NaiveDate::from_ymd_opt(2024, 5, 1)
+                .and_then(|d| d.and_hms_opt(23, 55, 0))
+                .unwrap(),
+            players_per_game: 2,
+            servers: vec!["mc.hypixel.net".into(), "play.example.com".into()],
+            anomalies: Anomalies {
+                garbage: 2,
+                json_chat: 2,
+                crash: true,
+                ..Anomalies::default()
+            },
+            ..Scenario::default()
+        };
+        let logs = generate(&scenario).unwrap();
+        let dir = temp_dir("generated");
+        for (i, log) in logs.iter().enumerate() {
+            // Older archives are compressed, as the game does
+            match i {
+                0 => {
+                    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+                    gz.write_all(&log.content).unwrap();
+                    let path = dir.join(format!("{}.gz", log.name));
+                    fs::write(path, gz.finish().unwrap()).unwrap();
+                }
+                _ => fs::write(dir.join(&log.name), &log.content).unwrap(),
+            }
+        }
+
+        let db = db::Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        let files = find_logs(&dir).unwrap();
+        assert_eq!(files.len(), logs.len());
+        let mut sessions = Vec::new();
+        for (file, log) in files.iter().zip(&logs) {
+            let content = read_log(&file.path).unwrap();
+            let analysis = analyze_log(file, &content, &Patterns::default());
+            let runs = &analysis.runs;
+            assert_eq!(runs.len(), log.expected.runs, "{}", log.name);
+            let finished = runs.iter().filter(|r| r.completed).count();
+            assert_eq!(finished, log.expected.finishes, "{}", log.name);
+            for run in runs {
+                assert_eq!(run.completed, run.time_ms.is_some());
+                assert!(!run.splits.is_empty());
+                assert!(scenario.servers.contains(run.server.as_ref().unwrap()));
+            }
+            let imported = import_analysis(&conn, file, &analysis).unwrap();
+            assert_eq!(imported.runs_added, log.expected.runs);
+            sessions.push(imported.session_id.unwrap());
+        }
+        sessions.dedup();
+        assert_eq!(sessions.len(), 3);
+
+        // The first session started before midnight and played into the 2nd
+        let first = analyze_log(
+            &files[0],
+            &read_log(&files[0].path).unwrap(),
+            &Patterns::default(),
+        );
+        let session = first.session.unwrap();
+        assert!(session.started_at.starts_with("2024-05-01 23:5"));
+        assert!(session.ended_at.starts_with("2024-05-02"));
+        assert!(first
+            .runs
+            .iter()
+            .any(|r| r.started_at.starts_with("2024-05-02")));
+
+        for file in &files {
+            let content = read_log(&file.path).unwrap();
+            let again = import_log(&conn, file, &content, &Patterns::default()).unwrap();
+            assert_eq!(again.runs_added, 0);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
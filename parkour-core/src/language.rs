@@ -0,0 +1,40 @@
+// Words common in German chat that English doesn't use
+const GERMAN_WORDS: &[&str] = &[
+    "du",
+    "hast",
+    "hat",
+    "den",
+    "der",
+    "die",
+    "das",
+    "und",
+    "nicht",
+    "zum",
+    "erreicht",
+    "abgeschlossen",
+    "wurdest",
+];
+
+/// Language a chat message without color codes is most likely in, as the
+/// code pattern variants name it: `ru` for Cyrillic, `de` for German and
+/// `en` for other Latin text. `None` when it has no letters to go by, like
+/// `0:41.250`.
+pub fn detect_message_language(message: &str) -> Option<&'static str> {
+    if message
+        .chars()
+        .any(|c| matches!(c, '\u{0400}'..='\u{04ff}'))
+    {
+        return Some("ru");
+    }
+    if message.contains(['ä', 'ö', 'ü', 'ß', 'Ä', 'Ö', 'Ü'])
+        || message
+            .split(|c: char| !c.is_alphabetic())
+            .any(|word| GERMAN_WORDS.contains(&word.to_lowercase().as_str()))
+    {
+        return Some("de");
+    }
+    message
+        .chars()
+        .any(|c| c.is_ascii_alphabetic())
+        .then_some("en")
+}
@@ -0,0 +1,87 @@
+use crate::duels::sent_by_player;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A message that makes the run it's logged during not count, like the
+/// server confirming /fly:
+///
+/// `{"name": "fly", "pattern": "(?i)^set fly mode enabled"}`
+///
+/// `pattern` is a regex matched against the chat message without color
+/// codes. Messages typed by a player, e.g. `Bob: anticheat off lol`, never
+/// match. A rule named like a built-in one replaces it.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LegitimacyRule {
+    pub name: String,
+    pub pattern: String,
+}
+
+// Only game modes that make a course trivial; servers put players in
+// adventure mode at the start of a game
+const BUILTIN_RULES: &[(&str, &str)] = &[
+    (
+        "gamemode",
+        r"(?i)^(your game ?mode has been (updated|changed) to|set own game ?mode to) (creative|spectator)",
+    ),
+    (
+        "fly",
+        r"(?i)^(set )?fly(ing)? mode (set to )?(enabled|on)\b",
+    ),
+    ("speed", r"(?i)^set (your )?(walk(ing)?|fly(ing)?) speed to"),
+    (
+        "anticheat",
+        r"(?i)anti-?cheat (has been |is now )?(disabled|off)\b",
+    ),
+];
+
+pub fn builtin_rules() -> Vec<LegitimacyRule> {
+    BUILTIN_RULES
+        .iter()
+        .map(|(name, pattern)| LegitimacyRule {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+        })
+        .collect()
+}
+
+/// The built-in rules with `custom` replacing those of the same name and
+/// added after the rest.
+pub fn with_builtin(custom: &[LegitimacyRule]) -> Vec<LegitimacyRule> {
+    let mut rules: Vec<LegitimacyRule> = builtin_rules()
+        .into_iter()
+        .filter(|b| !custom.iter().any(|c| c.name == b.name))
+        .collect();
+    rules.extend_from_slice(custom);
+    rules
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Rules(Vec<(String, Regex)>);
+
+impl Rules {
+    pub fn compile(rules: &[LegitimacyRule]) -> Result<Rules, String> {
+        let mut compiled = Vec::with_capacity(rules.len());
+        for (i, rule) in rules.iter().enumerate() {
+            if rule.name.trim().is_empty() {
+                return Err(format!("Legitimacy rule {} needs a name", i + 1));
+            }
+            let re = Regex::new(&rule.pattern)
+                .map_err(|e| format!("Invalid pattern for legitimacy rule {}: {}", rule.name, e))?;
+            compiled.push((rule.name.clone(), re));
+        }
+        Ok(Rules(compiled))
+    }
+
+    /// Names of the rules a chat message without color codes sets off.
+    pub fn fired<'a>(&'a self, message: &'a str) -> impl Iterator<Item = &'a str> {
+        self.0
+            .iter()
+            .filter(move |(_, re)| {
+                re.find(message)
+                    .is_some_and(|m| !sent_by_player(message, m.start()))
+            })
+            .map(|(name, _)| name.as_str())
+    }
+}
@@ -0,0 +1,25 @@
+//! Log parsing, run models and storage for Parkour Analyzer, with no
+//! dependency on Tauri so the same code backs the desktop app and headless
+//! tools.
+
+pub mod anonymize;
+pub mod client;
+pub mod db;
+pub mod definitions;
+pub mod duels;
+pub mod duration;
+pub mod generate;
+pub mod import;
+pub mod language;
+pub mod legitimacy;
+pub mod line_filter;
+pub mod lines;
+pub mod log_line;
+pub mod model;
+pub mod names;
+pub mod schema;
+pub mod server_log;
+pub mod servers;
+pub mod store;
+pub mod tail;
+pub mod timestamps;
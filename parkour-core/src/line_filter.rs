@@ -0,0 +1,97 @@
+use crate::lines::{lines, Capped, TruncatedLine};
+use regex::RegexSet;
+
+/// Lines to keep out of a log's text, by the regexes a line has to match
+/// one of. Without patterns every line is kept.
+#[derive(Clone, Debug, Default)]
+pub struct LineFilter {
+    patterns: Vec<String>,
+    set: Option<RegexSet>,
+}
+
+/// What [`LineFilter::apply`] kept of a text.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Filtered {
+    pub content: String,
+    pub truncated: Vec<TruncatedLine>,
+    pub kept: usize,
+    pub dropped: usize,
+}
+
+impl LineFilter {
+    pub fn compile(patterns: &[String]) -> Result<LineFilter, String> {
+        let set = (!patterns.is_empty())
+            .then(|| RegexSet::new(patterns))
+            .transpose()
+            .map_err(|e| format!("Invalid line filter: {}", e))?;
+        Ok(LineFilter {
+            patterns: patterns.to_vec(),
+            set,
+        })
+    }
+
+    /// The patterns it was compiled from.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_none()
+    }
+
+    /// The lines of `chunk` that match, keeping the [`TruncatedLine`]s of
+    /// those that were cut short. Lines are matched as capped, without their
+    /// terminator.
+    pub fn apply(&self, chunk: &Capped) -> Filtered {
+        let mut filtered = Filtered::default();
+        let mut truncated = chunk.truncated.iter().peekable();
+        for (line, raw) in lines(&chunk.content, 0).zip(chunk.lines()) {
+            let cut = truncated.next_if(|t| t.offset as usize == raw.start);
+            if self
+                .set
+                .as_ref()
+                .is_some_and(|set| !set.is_match(line.text))
+            {
+                filtered.dropped += 1;
+                continue;
+            }
+            filtered.kept += 1;
+            filtered
+                .content
+                .push_str(&chunk.content[line.start..line.end]);
+            filtered.truncated.extend(cut.cloned());
+        }
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_matching_lines_and_their_truncation() {
+        let f = LineFilter::compile(&[r"\[CHAT\].*Checkpoint".into()]).unwrap();
+        let text = "[12:00:00] [CHAT] hello\n[12:00:01] [CHAT] Checkpoint 1\r\n[12:00:02] [CHAT] Checkpoint 2 xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx\n";
+        let chunk = Capped::new(text.to_string(), 100, 40);
+        let r = f.apply(&chunk);
+        assert_eq!(r.kept, 2);
+        assert_eq!(r.dropped, 1);
+        assert!(r.content.starts_with("[12:00:01] [CHAT] Checkpoint 1\r\n"));
+        assert_eq!(r.truncated, chunk.truncated);
+        assert_eq!(
+            r.truncated[0].offset,
+            100 + text.find("[12:00:02]").unwrap() as u64
+        );
+        assert!(LineFilter::compile(&["(".into()]).is_err());
+    }
+
+    #[test]
+    fn drops_truncation_of_filtered_lines() {
+        let f = LineFilter::compile(&["keep".into()]).unwrap();
+        let text = format!("{}\nkeep\n", "x".repeat(50));
+        let r = f.apply(&Capped::new(text, 0, 10));
+        assert_eq!(r.content, "keep\n");
+        assert!(r.truncated.is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// One line of the log. `start..end` is its raw byte range including the
+/// line terminator; `text` has the terminator removed.
+pub struct Line<'a> {
+    pub start: usize,
+    pub end: usize,
+    pub text: &'a str,
+}
+
+// Windows logs end lines with \r\n, and files copied between systems can mix
+// both, so \r is only dropped when it sits right before the \n.
+pub fn strip_terminator(line: &str) -> &str {
+    match line.strip_suffix('\n') {
+        Some(line) => line.strip_suffix('\r').unwrap_or(line),
+        None => line,
+    }
+}
+
+pub fn strip_terminator_bytes(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
+}
+
+/// Lines of `content`, which starts at raw offset `base` in the file. A last
+/// line without a terminator is still yielded.
+pub fn lines(content: &str, base: usize) -> impl Iterator<Item = Line<'_>> {
+    let mut pos = base;
+    content.split_inclusive('\n').map(move |raw| {
+        let start = pos;
+        pos += raw.len();
+        Line {
+            start,
+            end: pos,
+            text: strip_terminator(raw),
+        }
+    })
+}
+
+/// A line that was cut down to the configured maximum. `offset` is its raw
+/// start in the file and `length` its full size in bytes, terminator excluded.
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct TruncatedLine {
+    pub offset: u64,
+    pub length: u64,
+}
+
+// The longest prefix of `text` that fits in `max` bytes without splitting a
+// character.
+pub fn cap(text: &str, max: usize) -> &str {
+    let mut end = text.len().min(max);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Cuts every line of `content` longer than `max` bytes, keeping terminators
+/// so line boundaries survive. Offsets in the result still refer to the raw
+/// file, starting at `base`.
+pub fn cap_lines(content: String, base: u64, max: usize) -> (String, Vec<TruncatedLine>) {
+    if content.len() <= max || !lines(&content, 0).any(|l| l.text.len() > max) {
+        return (content, Vec::new());
+    }
+    let mut capped = String::with_capacity(content.len().min(max * 16));
+    let mut truncated = Vec::new();
+    for line in lines(&content, 0) {
+        if line.text.len() <= max {
+            capped.push_str(&content[line.start..line.end]);
+            continue;
+        }
+        capped.push_str(cap(line.text, max));
+        capped.push_str(&content[line.start + line.text.len()..line.end]);
+        truncated.push(TruncatedLine {
+            offset: base + line.start as u64,
+            length: line.text.len() as u64,
+        });
+    }
+    (capped, truncated)
+}
+
+/// A chunk of the log with its long lines cut short by [`cap_lines`], for
+/// the scanners that read it line by line. `base` is the raw offset the
+/// text starts at, and `offset` where the read began, which is 0 for a log
+/// read from its start even when a BOM was stripped before `base`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capped {
+    pub content: String,
+    pub truncated: Vec<TruncatedLine>,
+    pub base: u64,
+    pub offset: u64,
+}
+
+impl Capped {
+    pub fn new(content: String, base: u64, max: usize) -> Capped {
+        let (content, truncated) = cap_lines(content, base, max);
+        Capped {
+            content,
+            truncated,
+            base,
+            offset: base,
+        }
+    }
+
+    /// Whether this is the log from its start, read for the first time or
+    /// after it started over.
+    pub fn starts_log(&self) -> bool {
+        self.offset == 0
+    }
+
+    /// Lines of the capped text, with `start..end` still their raw range in
+    /// the file, as [`lines`] would give for the text before it was capped.
+    pub fn lines(&self) -> impl Iterator<Item = Line<'_>> {
+        let base = self.base as usize;
+        let mut skipped = 0;
+        let mut truncated = self.truncated.iter().peekable();
+        lines(&self.content, 0).map(move |line| {
+            let start = base + line.start + skipped;
+            let mut end = base + line.end + skipped;
+            if let Some(cut) = truncated.next_if(|t| t.offset as usize == start) {
+                let cut_by = cut.length as usize - line.text.len();
+                end += cut_by;
+                skipped += cut_by;
+            }
+            Line {
+                start,
+                end,
+                text: line.text,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_keep_terminators_in_their_range() {
+        let found: Vec<(usize, usize, &str)> = lines("a\r\nb\rc\nlast", 5)
+            .map(|l| (l.start, l.end, l.text))
+            .collect();
+        assert_eq!(found, [(5, 8, "a"), (8, 12, "b\rc"), (12, 16, "last")]);
+    }
+
+    #[test]
+    fn cap_keeps_characters_whole() {
+        assert_eq!(cap("héllo", 2), "h");
+        assert_eq!(cap("héllo", 3), "hé");
+        assert_eq!(cap("hi", 10), "hi");
+    }
+
+    #[test]
+    fn short_lines_are_left_alone() {
+        let (content, truncated) = cap_lines("a\nb".into(), 0, 1);
+        assert_eq!(content, "a\nb");
+        assert!(truncated.is_empty());
+    }
+
+    #[test]
+    fn capped_lines_keep_raw_offsets() {
+        let content = format!("short\r\n{}\r\nafter\nend", "x".repeat(100));
+        let raw: Vec<(usize, usize)> = lines(&content, 40).map(|l| (l.start, l.end)).collect();
+        let capped = Capped::new(content, 40, 10);
+        assert_eq!(
+            capped.truncated,
+            vec![TruncatedLine {
+                offset: 47,
+                length: 100
+            }]
+        );
+        let texts: Vec<&str> = capped.lines().map(|l| l.text).collect();
+        assert_eq!(texts, ["short", "xxxxxxxxxx", "after", "end"]);
+        let offsets: Vec<(usize, usize)> = capped.lines().map(|l| (l.start, l.end)).collect();
+        assert_eq!(offsets, raw);
+    }
+
+    #[test]
+    fn a_log_with_a_bom_still_starts_at_zero() {
+        let chunk = Capped {
+            offset: 0,
+            ..Capped::new("a\nb\n".to_string(), 3, 16)
+        };
+        assert!(chunk.starts_log());
+        let found: Vec<(usize, &str)> = chunk.lines().map(|l| (l.start, l.text)).collect();
+        assert_eq!(found, vec![(3, "a"), (5, "b")]);
+        assert!(!Capped::new("c\n".to_string(), 3, 16).starts_log());
+        assert!(Capped::new("c\n".to_string(), 0, 16).starts_log());
+    }
+
+    #[test]
+    fn a_50_mb_line_is_cut_to_the_limit() {
+        let max = 256 * 1024;
+        let prefix = "[10:00:00] [Client thread/INFO]: [CHAT] ";
+        let long = 50 * 1024 * 1024;
+        let mut content = String::with_capacity(long + 128);
+        content.push_str("[09:59:59] [main/INFO]: before\n");
+        let at = content.len();
+        content.push_str(prefix);
+        content.push_str(&"é".repeat((long - prefix.len()) / 2));
+        let length = content.len() - at;
+        content.push_str("\n[10:00:01] [main/INFO]: after\n");
+        let total = content.len();
+
+        let capped = Capped::new(content, 1000, max);
+        assert!(capped.content.len() < 2 * max);
+        assert_eq!(
+            capped.truncated,
+            vec![TruncatedLine {
+                offset: 1000 + at as u64,
+                length: length as u64,
+            }]
+        );
+        let found: Vec<Line> = capped.lines().collect();
+        assert_eq!(found.len(), 3);
+        assert!(found[1].text.len() <= max && found[1].text.starts_with(prefix));
+        assert_eq!(found[1].end, 1000 + at + length + 1);
+        assert_eq!(
+            (found[2].start, found[2].end, found[2].text),
+            (
+                1000 + at + length + 1,
+                1000 + total,
+                "[10:00:01] [main/INFO]: after"
+            )
+        );
+        let line = crate::log_line::parse_line(found[1].start as u64, found[1].text);
+        assert_eq!(line.time.as_deref(), Some("10:00:00"));
+        assert!(line.message.starts_with("[CHAT] éé"));
+    }
+}
@@ -0,0 +1,194 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One log entry, as emitted on `replay-line`:
+///
+/// `{"offset": 1024, "time": "12:34:56", "thread": "Client thread", "level": "INFO", "message": "[CHAT] ..."}`
+///
+/// Lines that aren't in any [`LogFormat`] come through with only `offset`
+/// and `message` set. A line that was cut short
+/// before parsing has `"truncated": true` and its full `length`.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LogLine {
+    pub offset: u64,
+    pub time: Option<String>,
+    pub thread: Option<String>,
+    pub level: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<u64>,
+}
+
+// Seconds since midnight for a `HH:MM:SS` log time.
+pub fn seconds_of_day(time: &str) -> Option<u32> {
+    let mut parts = time.split(':').map(|p| p.parse::<u32>().ok());
+    let (h, m, s) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(h * 3600 + m * 60 + s)
+}
+
+/// How a log prefixes its lines. Versions and loaders differ:
+///
+/// - `classic`: `[12:34:56] [Client thread/INFO]: ...`, `Render thread` since 1.13
+/// - `logger`: `[12:34:56] [Render thread/INFO] [minecraft/ChatComponent]: ...`, from 1.20.2 and on Forge
+/// - `dated`: `[2024-05-01 12:34:56.789] [Render thread/INFO]: ...`, with or without the logger, as some Fabric setups write
+/// - `server`: `[12:34:56 INFO]: ...`, the console log of a Paper or Spigot server, whose lines all come from `Server thread`
+///
+/// Every format parses into the same [`LogLine`], with `time` as `HH:MM:SS`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Classic,
+    Logger,
+    Dated,
+    Server,
+}
+
+impl LogFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            LogFormat::Classic => "classic",
+            LogFormat::Logger => "logger",
+            LogFormat::Dated => "dated",
+            LogFormat::Server => "server",
+        }
+    }
+}
+
+struct Fields<'a> {
+    format: LogFormat,
+    time: &'a str,
+    thread: &'a str,
+    level: &'a str,
+    message: &'a str,
+}
+
+// "[12:34:56 INFO]: message", which has no thread to split off
+fn split_server(line: &str) -> Option<Fields<'_>> {
+    let (stamp, message) = line.strip_prefix('[')?.split_once("]: ")?;
+    let (time, level) = stamp.split_once(' ')?;
+    seconds_of_day(time)?;
+    if level.is_empty() || !level.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    Some(Fields {
+        format: LogFormat::Server,
+        time,
+        thread: "Server thread",
+        level,
+        message,
+    })
+}
+
+fn split(line: &str) -> Option<Fields<'_>> {
+    split_client(line).or_else(|| split_server(line))
+}
+
+fn split_client(line: &str) -> Option<Fields<'_>> {
+    let (stamp, rest) = line.strip_prefix('[')?.split_once("] [")?;
+    let (date, time) = match stamp.rsplit_once(' ') {
+        Some((date, time)) => (Some(date), time),
+        None => (None, stamp),
+    };
+    // Milliseconds are dropped so every format gives the same time
+    let time = time.split_once('.').map_or(time, |(time, _)| time);
+    seconds_of_day(time)?;
+    if date.is_some_and(|d| !d.chars().any(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let (source, rest) = rest.split_once(']')?;
+    let (thread, level) = source.rsplit_once('/')?;
+    let (logger, message) = match rest.strip_prefix(": ") {
+        Some(message) => (false, message),
+        None => (true, rest.strip_prefix(" [")?.split_once("]: ")?.1),
+    };
+    let format = match (date, logger) {
+        (Some(_), _) => LogFormat::Dated,
+        (None, true) => LogFormat::Logger,
+        (None, false) => LogFormat::Classic,
+    };
+    Some(Fields {
+        format,
+        time,
+        thread,
+        level,
+        message,
+    })
+}
+
+fn unparsed(offset: u64, line: &str) -> LogLine {
+    LogLine {
+        offset,
+        time: None,
+        thread: None,
+        level: None,
+        message: line.into(),
+        truncated: false,
+        length: None,
+    }
+}
+
+fn entry(offset: u64, fields: &Fields) -> LogLine {
+    LogLine {
+        offset,
+        time: Some(fields.time.into()),
+        thread: Some(fields.thread.into()),
+        level: Some(fields.level.into()),
+        message: fields.message.into(),
+        truncated: false,
+        length: None,
+    }
+}
+
+/// Splits a line in any [`LogFormat`]. `offset` is where the line starts in
+/// the log and is passed through unchanged. Reading a whole log is better
+/// done with a [`LineParser`], which sticks to the log's format.
+pub fn parse_line(offset: u64, line: &str) -> LogLine {
+    match split(line) {
+        Some(fields) => entry(offset, &fields),
+        None => unparsed(offset, line),
+    }
+}
+
+/// Format of the first line in `content` that has one.
+pub fn detect_format(content: &str) -> Option<LogFormat> {
+    crate::lines::lines(content, 0).find_map(|line| Some(split(line.text)?.format))
+}
+
+/// Parses the lines of a log in order. The first line in a known format
+/// decides the format for the rest of the file, so a stray line that happens
+/// to look like another format isn't taken for an entry. A line at offset 0
+/// means the log started over, and the format is detected again.
+#[derive(Clone, Debug, Default)]
+pub struct LineParser {
+    format: Option<LogFormat>,
+}
+
+impl LineParser {
+    pub fn format(&self) -> Option<LogFormat> {
+        self.format
+    }
+
+    fn fields<'a>(&mut self, offset: u64, line: &'a str) -> Option<Fields<'a>> {
+        if offset == 0 {
+            self.format = None;
+        }
+        let fields = split(line).filter(|f| self.format.is_none_or(|format| format == f.format))?;
+        self.format = Some(fields.format);
+        Some(fields)
+    }
+
+    pub fn parse(&mut self, offset: u64, line: &str) -> LogLine {
+        match self.fields(offset, line) {
+            Some(fields) => entry(offset, &fields),
+            None => unparsed(offset, line),
+        }
+    }
+
+    /// Just the `HH:MM:SS` time of a line, for callers that read every line.
+    pub fn time<'a>(&mut self, offset: u64, line: &'a str) -> Option<&'a str> {
+        Some(self.fields(offset, line)?.time)
+    }
+}
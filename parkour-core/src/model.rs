@@ -0,0 +1,254 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct Run {
+    pub id: i64,
+    pub map: String,
+    pub username: String,
+    pub server: Option<String>,
+    // Singleplayer world the run was played in
+    pub world: Option<String>,
+    // Local wall-clock time as logged, for display
+    pub started_at: String,
+    // The same instant as RFC 3339 UTC; None if started_at didn't parse
+    pub started_at_utc: Option<String>,
+    pub time_ms: Option<i64>,
+    pub completed: bool,
+    pub fails: i64,
+    pub tags: Vec<String>,
+    // Left out of PBs, golds and stats, e.g. a practice run counted by mistake
+    pub excluded: bool,
+    // Had a practice teleport in it, so it's left out of PBs, golds and goals
+    pub practice: bool,
+    // Legitimacy rules set off during the run, which keep it out of PBs,
+    // golds, goals and exports
+    pub invalidated_by: Vec<String>,
+    // The run as it was before it was first edited, and when it last was
+    pub original: Option<RunOriginal>,
+    pub edited_at: Option<String>,
+}
+
+/// `{"map": "Sandstorm", "time_ms": 58120, "excluded": false, "practice": false}`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct RunOriginal {
+    pub map: String,
+    pub time_ms: Option<i64>,
+    pub excluded: bool,
+    #[serde(default)]
+    pub practice: bool,
+    #[serde(default)]
+    pub invalidated_by: Vec<String>,
+}
+
+/// The fields of a run to change; those left out stay as they are:
+///
+/// `{"map": "Sandstorm", "excluded": true}`
+///
+/// `practice` can clear a practice flag set by mistake, or set one, and
+/// `invalidated_by` set to `[]` clears rules that fired by mistake.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunEdit {
+    pub map: Option<String>,
+    pub time_ms: Option<i64>,
+    pub excluded: Option<bool>,
+    pub practice: Option<bool>,
+    pub invalidated_by: Option<Vec<String>>,
+}
+
+/// A map name merged into another:
+///
+/// `{"alias": "Sandstorm (Hard)", "canonical": "Sandstorm", "created_at": "2026-03-01 18:00:00", "runs": 42}`
+///
+/// Aliases match whatever the case and color codes.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct MapAlias {
+    pub alias: String,
+    pub canonical: String,
+    pub created_at: String,
+    // Runs stored under the canonical name that were logged under the alias
+    pub runs: i64,
+}
+
+/// A player's best run on a map, ranked against everyone else's:
+///
+/// `{"rank": 1, "username": "Steve", "run": {...}, "attempts": 37}`
+///
+/// `attempts` counts every run the player has stored on the map.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub username: String,
+    pub run: Run,
+    pub attempts: i64,
+}
+
+/// A run that isn't stored yet, e.g. one read from an old log.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct NewRun {
+    pub map: String,
+    pub username: String,
+    pub server: Option<String>,
+    pub world: Option<String>,
+    pub started_at: String,
+    pub time_ms: Option<i64>,
+    pub completed: bool,
+    pub fails: i64,
+    pub practice: bool,
+    pub invalidated_by: Vec<String>,
+    pub splits: Vec<Split>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct Split {
+    pub checkpoint: i64,
+    pub name: Option<String>,
+    pub time_ms: i64,
+}
+
+/// Fastest time ever done on one segment, from the checkpoint before it (or
+/// the start) to `checkpoint`, which is 9999 for the segment to the finish.
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct GoldSplit {
+    pub checkpoint: i64,
+    pub name: Option<String>,
+    pub time_ms: i64,
+    // Run the gold was set in
+    pub run_id: i64,
+}
+
+/// Golds for one checkpoint route through a map:
+///
+/// `{"checkpoints": [1, 2, 3], "runs": 14, "golds": [{"checkpoint": 1, "name": null, "time_ms": 9800, "run_id": 3}, ...], "total_ms": 41020}`
+///
+/// `runs` counts the completed runs that took this route. `total_ms` is the
+/// sum of the golds, `null` while a segment has none.
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct SumOfBest {
+    pub checkpoints: Vec<i64>,
+    pub runs: i64,
+    pub golds: Vec<GoldSplit>,
+    pub total_ms: Option<i64>,
+}
+
+/// A goal as created or edited:
+///
+/// `{"map": "Sandstorm", "username": null, "target_time_ms": 60000, "deadline": "2026-10-31"}`
+///
+/// `username` null counts anyone's runs. `deadline` is a local date.
+#[derive(Deserialize, Clone, Debug)]
+pub struct NewGoal {
+    pub map: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    pub target_time_ms: i64,
+    #[serde(default)]
+    pub deadline: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct Goal {
+    pub id: i64,
+    pub map: String,
+    pub username: Option<String>,
+    pub target_time_ms: i64,
+    pub deadline: Option<String>,
+    pub created_at: String,
+    // Local time of the run that met the target, and the run if it's stored
+    pub achieved_at: Option<String>,
+    pub achieved_run_id: Option<i64>,
+    pub achieved_time_ms: Option<i64>,
+}
+
+/// `gap_ms` is how much faster than `best_ms` the target is, 0 once met.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct GoalProgress {
+    #[serde(flatten)]
+    pub goal: Goal,
+    pub best_ms: Option<i64>,
+    pub gap_ms: Option<i64>,
+    pub achieved: bool,
+    pub overdue: bool,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct Session {
+    pub id: i64,
+    pub username: String,
+    pub server: Option<String>,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub started_at_utc: Option<String>,
+    pub ended_at_utc: Option<String>,
+    pub active_ms: i64,
+    // "crash" when the game crashed mid-session
+    pub end_reason: Option<String>,
+    // Game client the log came from, e.g. "lunar"; unset before it was recorded
+    pub client: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct Marker {
+    pub id: i64,
+    pub created_at: String,
+    pub label: Option<String>,
+    pub session_id: Option<i64>,
+    pub map: Option<String>,
+    pub run_started_at: Option<String>,
+    pub run_elapsed_ms: Option<i64>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct RunDetail {
+    #[serde(flatten)]
+    pub run: Run,
+    pub splits: Vec<Split>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Date,
+    Time,
+    Map,
+    Fails,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDir {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct RunQuery {
+    pub map: Option<String>,
+    pub username: Option<String>,
+    pub server: Option<String>,
+    pub world: Option<String>,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub min_time_ms: Option<i64>,
+    pub max_time_ms: Option<i64>,
+    pub completed_only: bool,
+    // Only practice runs, or only full ones; both by default
+    pub practice: Option<bool>,
+    // Only runs a legitimacy rule fired in, or only the others
+    pub invalidated: Option<bool>,
+    pub tags: Vec<String>,
+    pub sort_by: SortBy,
+    pub sort_dir: SortDir,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct RunPage {
+    pub runs: Vec<Run>,
+    pub total: i64,
+}
@@ -0,0 +1,17 @@
+use crate::duels::strip_colors;
+use unicode_normalization::UnicodeNormalization;
+
+/// Key shared by every spelling of the same map name: "Café" typed with a
+/// combining accent, "CAFÉ" from another server's scoreboard and "café" all
+/// map to one key. Names are NFC-normalized, case-folded, then normalized
+/// again because folding can leave decomposed sequences behind.
+pub fn name_key(name: &str) -> String {
+    let folded = caseless::default_case_fold_str(&name.trim().nfc().collect::<String>());
+    folded.nfc().collect()
+}
+
+/// Key map aliases are matched on, which also ignores `§x` color codes, so
+/// "§6Sandstorm" as scraped from a title is the same alias as "sandstorm".
+pub fn alias_key(name: &str) -> String {
+    name_key(&strip_colors(name))
+}
@@ -0,0 +1,383 @@
+use crate::duels::{DuelEvent, Game, MapStats, PersonalBest};
+use crate::import::{LogAnalysis, LogImport};
+use crate::lines::TruncatedLine;
+use crate::log_line::LogLine;
+use crate::model::{
+    Goal, GoalProgress, LeaderboardEntry, MapAlias, Marker, Run, RunDetail, RunPage, Session,
+    Split, SumOfBest,
+};
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, SchemaGenerator};
+use serde_json::{json, Map, Value};
+
+/// Version of the payload format, sent as `protocol` in every event and JSON
+/// output row. Bumped whenever a field is removed, renamed or changes type;
+/// adding a field doesn't bump it.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Adds `protocol` to a payload that serializes to an object.
+pub fn stamp(mut payload: Value) -> Value {
+    if let Value::Object(fields) = &mut payload {
+        fields.insert("protocol".into(), PROTOCOL_VERSION.into());
+    }
+    payload
+}
+
+/// JSON Schema for a set of named payloads, sharing one `$defs` table:
+///
+/// `{"$schema": "...", "version": "1.0.0", "protocol": 1, "payloads": {"log-update": {"$ref": "#/$defs/LogUpdatePayload"}}, "$defs": {...}}`
+pub struct SchemaSet {
+    generator: SchemaGenerator,
+    payloads: Map<String, Value>,
+}
+
+impl Default for SchemaSet {
+    fn default() -> SchemaSet {
+        SchemaSet {
+            generator: SchemaSettings::draft2020_12()
+                .for_serialize()
+                .into_generator(),
+            payloads: Map::new(),
+        }
+    }
+}
+
+impl SchemaSet {
+    pub fn payload<T: JsonSchema>(mut self, name: &str) -> SchemaSet {
+        let schema = self.generator.subschema_for::<T>();
+        self.payloads.insert(name.to_string(), schema.to_value());
+        self
+    }
+
+    /// The run, session and log models shared by the app and the CLI, under
+    /// their type names.
+    pub fn models(self) -> SchemaSet {
+        self.payload::<Run>("Run")
+            .payload::<RunDetail>("RunDetail")
+            .payload::<RunPage>("RunPage")
+            .payload::<Split>("Split")
+            .payload::<SumOfBest>("SumOfBest")
+            .payload::<Session>("Session")
+            .payload::<Marker>("Marker")
+            .payload::<Goal>("Goal")
+            .payload::<GoalProgress>("GoalProgress")
+            .payload::<MapAlias>("MapAlias")
+            .payload::<LeaderboardEntry>("LeaderboardEntry")
+            .payload::<LogLine>("LogLine")
+            .payload::<TruncatedLine>("TruncatedLine")
+            .payload::<Game>("Game")
+            .payload::<DuelEvent>("DuelEvent")
+            .payload::<MapStats>("MapStats")
+            .payload::<PersonalBest>("PersonalBest")
+            .payload::<LogImport>("LogImport")
+            .payload::<LogAnalysis>("LogAnalysis")
+    }
+
+    /// `version` is the version of the program the schema describes.
+    pub fn finish(mut self, version: &str) -> Value {
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "version": version,
+            "protocol": PROTOCOL_VERSION,
+            "payloads": self.payloads,
+            "$defs": self.generator.take_definitions(true),
+        })
+    }
+}
+
+/// Checks `payload` against the schema for `name` in a finished set, so
+/// tests can keep fixtures and schemas from drifting apart. Only the
+/// keywords schemars writes are understood, and formats aren't checked.
+pub fn check(set: &Value, name: &str, payload: &Value) -> Result<(), String> {
+    let schema = set["payloads"]
+        .get(name)
+        .ok_or_else(|| format!("No schema for {}", name))?;
+    check_value(&set["$defs"], schema, payload, name)
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn check_value(defs: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: nothing is allowed here", at)),
+        Value::Object(schema) => schema,
+        _ => return Err(format!("{}: not a schema", at)),
+    };
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.trim_start_matches("#/$defs/");
+        let target = defs
+            .get(name)
+            .ok_or_else(|| format!("{}: unknown {}", at, reference))?;
+        check_value(defs, target, value, at)?;
+    }
+    if let Some(types) = schema.get("type") {
+        let found = json_type(value);
+        let allowed = |t: &Value| t == found || (t == "number" && found == "integer");
+        let ok = match types {
+            Value::Array(types) => types.iter().any(allowed),
+            t => allowed(t),
+        };
+        if !ok {
+            return Err(format!("{}: expected {}, got {}", at, types, found));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            return Err(format!("{}: {} is not one of {:?}", at, value, options));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(format!("{}: expected {}, got {}", at, expected, value));
+        }
+    }
+    if let (Some(min), Some(n)) = (
+        schema.get("minimum").and_then(Value::as_f64),
+        value.as_f64(),
+    ) {
+        if n < min {
+            return Err(format!("{}: {} is below {}", at, n, min));
+        }
+    }
+    if let Value::Object(fields) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for required in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let required = required.as_str().unwrap_or_default();
+            if !fields.contains_key(required) {
+                return Err(format!("{}: missing {}", at, required));
+            }
+        }
+        for (key, field) in fields {
+            let path = format!("{}.{}", at, key);
+            match properties.and_then(|p| p.get(key)) {
+                Some(property) => check_value(defs, property, field, &path)?,
+                None => {
+                    if let Some(extra) = schema.get("additionalProperties") {
+                        check_value(defs, extra, field, &path)?;
+                    }
+                }
+            }
+        }
+    }
+    if let Value::Array(items) = value {
+        if let Some(item) = schema.get("items") {
+            for (i, value) in items.iter().enumerate() {
+                check_value(defs, item, value, &format!("{}[{}]", at, i))?;
+            }
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true))
+            && items
+                .iter()
+                .enumerate()
+                .any(|(i, v)| items[..i].contains(v))
+        {
+            return Err(format!("{}: items are not unique", at));
+        }
+    }
+    if let Some(options) = schema.get("anyOf").and_then(Value::as_array) {
+        if !options
+            .iter()
+            .any(|o| check_value(defs, o, value, at).is_ok())
+        {
+            return Err(format!("{}: matches none of anyOf", at));
+        }
+    }
+    if let Some(options) = schema.get("oneOf").and_then(Value::as_array) {
+        let matched = options
+            .iter()
+            .filter(|o| check_value(defs, o, value, at).is_ok())
+            .count();
+        if matched != 1 {
+            return Err(format!("{}: matches {} of oneOf", at, matched));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, Db};
+    use crate::duels::{self, Parser, Patterns};
+    use crate::import::{analyze_log, import_analysis, log_file};
+    use crate::lines::{lines, Capped};
+    use crate::log_line::parse_line;
+    use crate::model::{NewGoal, NewRun, RunQuery, Split};
+    use chrono::NaiveDate;
+    use serde::Serialize;
+
+    const LOG: &str = "\
+[10:00:00] [main/INFO]: Setting user: Steve
+[10:00:01] [Client thread/INFO]: Connecting to mc.hypixel.net, 25565
+[10:00:02] [Client thread/INFO]: [CHAT] Parkour Duels
+[10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!
+[10:00:04] [Client thread/INFO]: [CHAT] CHECKPOINT! Alex reached checkpoint 1 in 0:06.000!
+[10:00:05] [Client thread/INFO]: [CHAT] Reset your timer to 00:00! Get to the finish line!
+[10:00:20] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:15.000!
+";
+
+    fn assert_valid(set: &Value, name: &str, payload: impl Serialize) {
+        let payload = serde_json::to_value(payload).unwrap();
+        if let Err(e) = check(set, name, &payload) {
+            panic!("{} doesn't match its schema: {}\n{}", name, e, payload);
+        }
+    }
+
+    fn models() -> Value {
+        SchemaSet::default().models().finish("0.0.0")
+    }
+
+    #[test]
+    fn parsed_log_payloads_match() {
+        let set = models();
+        let mut parser = Parser::new(Patterns::default());
+        let events: Vec<_> = lines(LOG, 0)
+            .filter_map(|l| parser.line(l.start as u64, l.text))
+            .collect();
+        assert!(events.len() >= 4);
+        for event in &events {
+            assert_valid(&set, "DuelEvent", event);
+        }
+        let parsed = parser.finish();
+        assert_valid(&set, "Game", &parsed.games[0]);
+        for stats in duels::map_stats(&parsed.games, "Steve") {
+            assert_valid(&set, "MapStats", stats);
+        }
+        for best in duels::personal_bests(&parsed.games) {
+            assert_valid(&set, "PersonalBest", best);
+        }
+        for line in lines(LOG, 0) {
+            assert_valid(&set, "LogLine", parse_line(line.start as u64, line.text));
+        }
+        let long = Capped::new(format!("{}\n", "x".repeat(64)), 0, 16);
+        assert_valid(&set, "TruncatedLine", &long.truncated[0]);
+    }
+
+    #[test]
+    fn stored_payloads_match() {
+        let set = models();
+        let dir = std::env::temp_dir().join(format!("parkour-schema-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("2026-01-01-1.log");
+        std::fs::write(&path, LOG).unwrap();
+        let file = log_file(path).unwrap();
+        let analysis = analyze_log(&file, LOG, &Patterns::default());
+        assert_valid(&set, "LogAnalysis", &analysis);
+
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        assert_valid(
+            &set,
+            "LogImport",
+            import_analysis(&conn, &file, &analysis).unwrap(),
+        );
+        let run = NewRun {
+            map: "Tower".into(),
+            username: "Steve".into(),
+            server: None,
+            world: Some("Parkour Map".into()),
+            started_at: "2026-01-02 10:00:00".into(),
+            time_ms: Some(30_000),
+            completed: true,
+            fails: 1,
+            practice: false,
+            invalidated_by: vec!["fly".into()],
+            splits: vec![Split {
+                checkpoint: 1,
+                name: Some("Ledge".into()),
+                time_ms: 12_000,
+            }],
+        };
+        let id = db::insert_run(&conn, &run).unwrap().unwrap();
+        db::update_run(
+            &conn,
+            id,
+            &crate::model::RunEdit {
+                time_ms: Some(29_000),
+                ..Default::default()
+            },
+            "2026-01-03 10:00:00",
+        )
+        .unwrap();
+        let page = db::select_runs(&conn, &RunQuery::default()).unwrap();
+        assert_valid(&set, "RunPage", &page);
+        for run in &page.runs {
+            assert_valid(&set, "Run", run);
+        }
+        assert_valid(&set, "RunDetail", db::get_run(&conn, id).unwrap().unwrap());
+        for route in db::sum_of_best(&conn, "Tower", None).unwrap() {
+            assert_valid(&set, "SumOfBest", route);
+        }
+        let sessions = db::list_sessions(&conn).unwrap();
+        assert!(!sessions.is_empty());
+        for session in sessions {
+            assert_valid(&set, "Session", session);
+        }
+        let leaderboard = db::player_leaderboard(&conn, "Parkour Duels").unwrap();
+        assert!(!leaderboard.is_empty());
+        for entry in leaderboard {
+            assert_valid(&set, "LeaderboardEntry", entry);
+        }
+        let goal = NewGoal {
+            map: "Parkour Duels".into(),
+            username: None,
+            target_time_ms: 20_000,
+            deadline: Some("2026-02-01".into()),
+        };
+        db::insert_goal(&conn, &goal, "2026-01-01 09:00:00").unwrap();
+        db::achieve_goals(&conn).unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 10).unwrap();
+        for progress in db::goals(&conn, today).unwrap() {
+            assert_valid(&set, "GoalProgress", &progress);
+            assert_valid(&set, "Goal", &progress.goal);
+        }
+        db::merge_maps(&conn, "Tower", &["Parkour Duels".into()], "now").unwrap();
+        for alias in db::map_aliases(&conn).unwrap() {
+            assert_valid(&set, "MapAlias", alias);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stamped_payloads_still_match() {
+        let set = models();
+        let split = Split {
+            checkpoint: 2,
+            name: None,
+            time_ms: 9_000,
+        };
+        let stamped = stamp(serde_json::to_value(&split).unwrap());
+        assert_eq!(stamped["protocol"], PROTOCOL_VERSION);
+        check(&set, "Split", &stamped).unwrap();
+    }
+
+    #[test]
+    fn drift_is_caught() {
+        let set = models();
+        let split = json!({"checkpoint": 2, "name": null});
+        assert!(check(&set, "Split", &split)
+            .unwrap_err()
+            .contains("missing time_ms"));
+        let split = json!({"checkpoint": "2", "name": null, "time_ms": 1});
+        assert!(check(&set, "Split", &split)
+            .unwrap_err()
+            .contains("Split.checkpoint"));
+        assert!(check(&set, "Nothing", &split).is_err());
+    }
+}
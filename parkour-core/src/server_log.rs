@@ -0,0 +1,252 @@
+use crate::duels::{
+    builtin, compile, sent_by_player, Builtin, DuelEvent, EventKind, Game, Localized, PlayerEvent,
+    Variants, FINISH,
+};
+use crate::duration::{format_ms, normalize_timer};
+use crate::language::detect_message_language;
+use crate::log_line::seconds_of_day;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Course plugins log to the console under their own tag, e.g. "[Parkour] "
+const START: Builtin = &[(
+    "en",
+    r"^(?:\[[^\]]+\]\s*)?(?P<player>\w{1,16}) (?:joined|started) (?:the )?(?:parkour|course) (?P<map>.+?)[.!]?$",
+    false,
+)];
+const CHECKPOINT: Builtin = &[(
+    "en",
+    r"^(?:\[[^\]]+\]\s*)?(?P<player>\w{1,16}) reached checkpoint (?P<cp>\d+)(?: (?:in|at) (?P<time>[\d:.]+))?",
+    false,
+)];
+const FINISH_MESSAGE: Builtin = &[(
+    "en",
+    r"^(?:\[[^\]]+\]\s*)?(?P<player>\w{1,16}) (?:finished|completed) (?:the )?(?:(?:parkour|course) )?(?P<map>.+?) in (?P<time>[\d:.]+)",
+    false,
+)];
+// Leaving the course, then the server
+const LEAVE: Builtin = &[
+    (
+        "en",
+        r"^(?:\[[^\]]+\]\s*)?(?P<player>\w{1,16}) left (?:the )?(?:parkour|course)",
+        false,
+    ),
+    (
+        "en",
+        r"^(?P<player>\w{1,16}) (?:left the game|lost connection)",
+        false,
+    ),
+];
+const STOPPING: &str = "Stopping server";
+
+/// Plugin messages a Paper or Spigot server logs about its players' runs,
+/// for reading a server's console log instead of a client's. Every field is
+/// optional and falls back to the built-in pattern, and takes variants like
+/// a [`PatternSpec`](crate::duels::PatternSpec) field. Patterns are matched
+/// against the message after the line prefix, without color codes, and
+/// capture named groups:
+///
+/// `{"start": "^(?P<player>\\w+) began (?P<map>.+)$", "finish": "^(?P<player>\\w+) beat (?P<map>.+) in (?P<time>[\\d:.]+)$"}`
+///
+/// - `start`: `player` and `map`; starts the player's run
+/// - `checkpoint`: `player`, `cp` and optionally `time`
+/// - `finish`: `player`, optionally `map` and `time`; ends the run
+/// - `leave`: `player`; abandons the run, as the server stopping does for
+///   everyone's
+///
+/// Without a `time` the time is counted from the log times since the start.
+/// A match that starts inside a player's chat message, like
+/// `<Bob> Alex finished Tower in 0:12.000`, doesn't count.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerLogSpec {
+    pub start: Option<Variants>,
+    pub checkpoint: Option<Variants>,
+    pub finish: Option<Variants>,
+    pub leave: Option<Variants>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ServerLog {
+    start: Localized,
+    checkpoint: Localized,
+    finish: Localized,
+    leave: Localized,
+}
+
+fn pick(
+    field: &str,
+    custom: &Option<Variants>,
+    default: Builtin,
+    groups: &[&str],
+) -> Result<Localized, String> {
+    let variants = match custom {
+        Some(custom) => custom.list(),
+        None => builtin(default),
+    };
+    let localized = compile(field, &variants, 0)?;
+    if let Some(group) = localized.missing(groups) {
+        return Err(format!(
+            "Invalid pattern {}: needs a {} group",
+            field, group
+        ));
+    }
+    Ok(localized)
+}
+
+impl ServerLog {
+    pub(crate) fn compile(spec: &ServerLogSpec) -> Result<ServerLog, String> {
+        Ok(ServerLog {
+            start: pick("start", &spec.start, START, &["player", "map"])?,
+            checkpoint: pick(
+                "checkpoint",
+                &spec.checkpoint,
+                CHECKPOINT,
+                &["player", "cp"],
+            )?,
+            finish: pick("finish", &spec.finish, FINISH_MESSAGE, &["player"])?,
+            leave: pick("leave", &spec.leave, LEAVE, &["player"])?,
+        })
+    }
+}
+
+// A run in progress, with its log time in seconds at the start
+struct Open {
+    game: Game,
+    started_at: Option<u32>,
+}
+
+/// Runs being timed in a server log, one per player.
+pub(crate) struct ServerRuns {
+    log: ServerLog,
+    open: BTreeMap<String, Open>,
+}
+
+impl ServerRuns {
+    pub(crate) fn new(log: ServerLog) -> ServerRuns {
+        ServerRuns {
+            log,
+            open: BTreeMap::new(),
+        }
+    }
+
+    /// Drops the runs still going, e.g. when the log ends or starts over.
+    pub(crate) fn clear(&mut self) {
+        self.open.clear();
+    }
+
+    /// Feeds the message of the line at `offset`, logged at `time`. Runs it
+    /// ends are added to `games`.
+    pub(crate) fn line(
+        &mut self,
+        offset: u64,
+        time: Option<&str>,
+        message: &str,
+        games: &mut Vec<Game>,
+    ) -> Option<DuelEvent> {
+        if message.starts_with(STOPPING) {
+            let open = std::mem::take(&mut self.open);
+            games.extend(
+                open.into_values()
+                    .map(|open| open.game)
+                    .filter(|game| !game.players.is_empty()),
+            );
+            return None;
+        }
+        let language = detect_message_language(message);
+        let at = time.and_then(seconds_of_day);
+        // A match that came from the server, not a player
+        let captured = |localized: &Localized| {
+            let (c, comma) = localized.captures(message, language)?;
+            let player = c.name("player")?;
+            if sent_by_player(message, player.start()) {
+                return None;
+            }
+            Some((c, comma))
+        };
+
+        if let Some((c, _)) = captured(&self.log.start) {
+            let player = c["player"].to_string();
+            let title = c.name("map")?.as_str().trim().to_string();
+            self.open.insert(
+                player,
+                Open {
+                    game: Game {
+                        offset,
+                        time: time.map(str::to_string),
+                        title: title.clone(),
+                        ..Default::default()
+                    },
+                    started_at: at,
+                },
+            );
+            return Some(DuelEvent::GameStarted { offset, title });
+        }
+        if let Some((c, comma)) = captured(&self.log.checkpoint) {
+            let player = c["player"].to_string();
+            let open = self.open.get_mut(&player)?;
+            let step = PlayerEvent {
+                cp: c["cp"].parse().ok()?,
+                time: timer(c.name("time").map(|t| t.as_str()), comma, open, at)?,
+                kind: EventKind::Checkpoint,
+            };
+            return open
+                .game
+                .record(&player, &step)
+                .then_some(DuelEvent::Progress {
+                    offset,
+                    player,
+                    step,
+                });
+        }
+        if let Some((c, comma)) = captured(&self.log.finish) {
+            let player = c["player"].to_string();
+            // A plugin that only logs finishes still gives a run
+            let mut open = match self.open.remove(&player) {
+                Some(open) => open,
+                None => Open {
+                    game: Game {
+                        offset,
+                        time: time.map(str::to_string),
+                        title: c.name("map")?.as_str().trim().to_string(),
+                        ..Default::default()
+                    },
+                    started_at: at,
+                },
+            };
+            let step = PlayerEvent {
+                cp: FINISH,
+                time: timer(c.name("time").map(|t| t.as_str()), comma, &open, at)?,
+                kind: EventKind::Finish,
+            };
+            open.game.record(&player, &step);
+            games.push(open.game);
+            return Some(DuelEvent::Progress {
+                offset,
+                player,
+                step,
+            });
+        }
+        if let Some((c, _)) = captured(&self.log.leave) {
+            // Checkpoints reached before leaving still make a run
+            if let Some(open) = self.open.remove(&c["player"]) {
+                if !open.game.players.is_empty() {
+                    games.push(open.game);
+                }
+            }
+        }
+        None
+    }
+}
+
+// The timer in the message, or the time since the run started by the log
+fn timer(shown: Option<&str>, decimal_comma: bool, open: &Open, at: Option<u32>) -> Option<String> {
+    match shown {
+        Some(shown) => Some(normalize_timer(shown, decimal_comma)),
+        None => {
+            let secs = (at? + 86_400 - open.started_at?) % 86_400;
+            Some(format_ms(secs as i64 * 1000))
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::duels::PatternSpec;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Name of the built-in patterns, used when no server parser matches.
+pub const GENERIC: &str = "generic";
+
+// A matching address outweighs any number of join messages
+const HOST_SCORE: usize = 100;
+
+/// Addresses that share settings with another, as (alias, address). Aliases
+/// in the settings take precedence, so one can be undone by mapping it to
+/// itself.
+pub const ALIASES: &[(&str, &str)] = &[("mc.hypixel.net", "hypixel.net")];
+
+/// Patterns for one server's chat, with what gives the server away: its
+/// addresses and messages shown in the first minute after joining.
+///
+/// `{"name": "mineplex", "hosts": ["mineplex.com"], "join_messages": ["Welcome to Mineplex"], "patterns": {"game_start": "^Parkour Race$"}}`
+///
+/// A host matches itself and its subdomains, so `hypixel.net` covers
+/// `mc.hypixel.net`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerParser {
+    pub name: String,
+    pub hosts: Vec<String>,
+    pub join_messages: Vec<String>,
+    pub patterns: PatternSpec,
+}
+
+impl ServerParser {
+    fn score(&self, address: &str, messages: &[String]) -> usize {
+        let address = address.trim_end_matches('.').to_lowercase();
+        let host = self.hosts.iter().any(|host| {
+            let host = host.to_lowercase();
+            address == host || address.ends_with(&format!(".{}", host))
+        });
+        let seen = self
+            .join_messages
+            .iter()
+            .filter(|m| messages.iter().any(|message| message.contains(m.as_str())))
+            .count();
+        if host {
+            HOST_SCORE + seen
+        } else {
+            seen
+        }
+    }
+}
+
+/// Parsers that ship with the app.
+pub fn builtin() -> Vec<ServerParser> {
+    vec![ServerParser {
+        name: "hypixel".into(),
+        hosts: vec!["hypixel.net".into()],
+        join_messages: vec!["joined the lobby!".into(), "Sending you to mini".into()],
+        patterns: PatternSpec::default(),
+    }]
+}
+
+/// Every parser to choose from: `custom` ones first, so one can replace the
+/// built-in parser of the same name.
+pub fn registry(custom: &[ServerParser]) -> Vec<ServerParser> {
+    let mut parsers = custom.to_vec();
+    for parser in builtin() {
+        if !parsers.iter().any(|p| p.name == parser.name) {
+            parsers.push(parser);
+        }
+    }
+    parsers
+}
+
+/// Best parser for `address` given the chat seen since joining it, or `None`
+/// for the [`GENERIC`] patterns. Earlier parsers win ties.
+pub fn resolve<'a>(
+    parsers: &'a [ServerParser],
+    address: &str,
+    messages: &[String],
+) -> Option<&'a ServerParser> {
+    parsers
+        .iter()
+        .map(|p| (p.score(address, messages), p))
+        .filter(|(score, _)| *score > 0)
+        .fold(
+            None,
+            |best: Option<(usize, &ServerParser)>, (score, p)| match best {
+                Some((top, _)) if top >= score => best,
+                _ => Some((score, p)),
+            },
+        )
+        .map(|(_, p)| p)
+}
+
+/// Address in the game's "Connecting to mc.hypixel.net, 25565" message.
+pub fn connecting_to(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("Connecting to ")?;
+    let host = rest.split([',', ' ', ':']).next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+/// Name of the singleplayer world in the integrated server's
+/// `Preparing level "New World"` message, or in the
+/// `Saving chunks for level 'New World'/Overworld` it logs when saving
+/// (`'ServerLevel[New World]'/minecraft:overworld` on newer versions).
+pub fn world_loaded(message: &str) -> Option<&str> {
+    let name = match message.strip_prefix("Preparing level \"") {
+        Some(rest) => rest.split('"').next()?,
+        None => {
+            let rest = message.strip_prefix("Saving chunks for level '")?;
+            let name = rest.split("'/").next()?;
+            name.strip_prefix("ServerLevel[")
+                .and_then(|n| n.strip_suffix(']'))
+                .unwrap_or(name)
+        }
+    };
+    (!name.is_empty()).then_some(name)
+}
+
+/// Where the player is: `{"server": "mc.hypixel.net", "world": null}` on a
+/// server, `{"server": null, "world": "New World"}` in singleplayer. Both
+/// are unset before the log says, and `world` until a singleplayer world's
+/// name is logged.
+#[derive(Serialize, JsonSchema, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayContext {
+    pub server: Option<String>,
+    pub world: Option<String>,
+}
+
+impl PlayContext {
+    /// The context `message` moves to from this one, if it changes it.
+    pub fn next(&self, message: &str) -> Option<PlayContext> {
+        let next = if let Some(server) = connecting_to(message) {
+            PlayContext {
+                server: Some(server.to_string()),
+                world: None,
+            }
+        } else if let Some(world) = world_loaded(message) {
+            PlayContext {
+                server: None,
+                world: Some(world.to_string()),
+            }
+        } else if message.starts_with("Starting integrated minecraft server") {
+            PlayContext::default()
+        } else {
+            return None;
+        };
+        (next != *self).then_some(next)
+    }
+}
+
+// Lowercase, without a port or trailing dot
+fn clean(address: &str) -> String {
+    let address = address.trim();
+    let host = address.split(':').next().unwrap_or(address);
+    host.trim_end_matches('.').to_lowercase()
+}
+
+/// The address a server's settings are stored under, with `aliases` and then
+/// the built-in [`ALIASES`] applied: `MC.Hypixel.net:25565` becomes
+/// `hypixel.net`.
+pub fn normalize(address: &str, aliases: &BTreeMap<String, String>) -> String {
+    let address = clean(address);
+    if let Some((_, target)) = aliases.iter().find(|(alias, _)| clean(alias) == address) {
+        return clean(target);
+    }
+    match ALIASES.iter().find(|(alias, _)| *alias == address) {
+        Some((_, target)) => target.to_string(),
+        None => address,
+    }
+}
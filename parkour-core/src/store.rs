@@ -0,0 +1,432 @@
+use crate::db::{self, Db};
+use crate::import::{self, LogAnalysis, LogFile, LogImport};
+use crate::model::{Run, RunDetail, RunPage, RunQuery, Session, SortBy, SortDir};
+use crate::names;
+use crate::timestamps;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+
+/// Where recorded runs are kept, so the commands and the CLI don't depend on
+/// how. [`Db`] implements it on top of SQLite, [`MemoryStore`] in memory.
+pub trait RunStore {
+    fn query_runs(&self, query: &RunQuery) -> Result<RunPage, String>;
+    fn get_run(&self, run_id: i64) -> Result<Option<RunDetail>, String>;
+    fn runs_for_map(&self, map: &str, username: &str) -> Result<Vec<Run>, String>;
+    /// Fastest completed run on `map`, by `username` or anyone.
+    fn personal_best(&self, map: &str, username: Option<&str>)
+        -> Result<Option<RunDetail>, String>;
+    fn get_session(&self, session_id: i64) -> Result<Option<Session>, String>;
+    /// Every session, newest first.
+    fn list_sessions(&self) -> Result<Vec<Session>, String>;
+    /// Whether `file` was imported as it is now, same size and time.
+    fn is_imported(&self, file: &LogFile) -> Result<bool, String>;
+    /// Stores what [`import::analyze_log`] read from `file`, skipping runs
+    /// already stored; see [`import::import_analysis`].
+    fn import(&self, file: &LogFile, analysis: &LogAnalysis) -> Result<LogImport, String>;
+}
+
+impl RunStore for Db {
+    fn query_runs(&self, query: &RunQuery) -> Result<RunPage, String> {
+        db::select_runs(&*self.conn()?, query)
+    }
+
+    fn get_run(&self, run_id: i64) -> Result<Option<RunDetail>, String> {
+        db::get_run(&*self.conn()?, run_id)
+    }
+
+    fn runs_for_map(&self, map: &str, username: &str) -> Result<Vec<Run>, String> {
+        db::runs_for_map(&*self.conn()?, map, username)
+    }
+
+    fn personal_best(
+        &self,
+        map: &str,
+        username: Option<&str>,
+    ) -> Result<Option<RunDetail>, String> {
+        db::personal_best(&*self.conn()?, map, username)
+    }
+
+    fn get_session(&self, session_id: i64) -> Result<Option<Session>, String> {
+        db::get_session(&*self.conn()?, session_id)
+    }
+
+    fn list_sessions(&self) -> Result<Vec<Session>, String> {
+        db::list_sessions(&*self.conn()?)
+    }
+
+    fn is_imported(&self, file: &LogFile) -> Result<bool, String> {
+        db::is_imported(&*self.conn()?, &file.key(), file.size, file.modified_ms)
+    }
+
+    fn import(&self, file: &LogFile, analysis: &LogAnalysis) -> Result<LogImport, String> {
+        import::import_analysis(&*self.conn()?, file, analysis)
+    }
+}
+
+#[derive(Default)]
+struct Memory {
+    runs: Vec<RunDetail>,
+    fingerprints: BTreeSet<String>,
+    sessions: Vec<Session>,
+    // Path of each imported log: its size, time and session
+    imported: BTreeMap<String, (u64, Option<i64>, Option<i64>)>,
+}
+
+/// Runs kept in memory, for tests and one-off analysis. It answers queries
+/// the way [`Db`] does, without the tags, edits, map merges and PB resets
+/// only the database has.
+#[derive(Default)]
+pub struct MemoryStore(Mutex<Memory>);
+
+impl MemoryStore {
+    fn memory(&self) -> Result<MutexGuard<'_, Memory>, String> {
+        self.0
+            .lock()
+            .map_err(|_| "Run store lock poisoned".to_string())
+    }
+}
+
+fn matches(run: &Run, q: &RunQuery, bounds: &db::Bounds) -> bool {
+    let (from, to) = bounds;
+    let utc = run.started_at_utc.as_deref();
+    let at_least = |t: Option<i64>, min: Option<i64>| min.is_none_or(|m| t.is_some_and(|t| t >= m));
+    let at_most = |t: Option<i64>, max: Option<i64>| max.is_none_or(|m| t.is_some_and(|t| t <= m));
+    q.map
+        .as_deref()
+        .is_none_or(|m| names::name_key(m) == names::name_key(&run.map))
+        && q.username.as_deref().is_none_or(|u| u == run.username)
+        && q.server
+            .as_deref()
+            .is_none_or(|s| run.server.as_deref() == Some(s))
+        && q.world
+            .as_deref()
+            .is_none_or(|w| run.world.as_deref() == Some(w))
+        && from.is_none_or(|f| utc.is_some_and(|u| u >= timestamps::format_utc(f).as_str()))
+        && to.is_none_or(|t| utc.is_some_and(|u| u < timestamps::format_utc(t).as_str()))
+        && at_least(run.time_ms, q.min_time_ms)
+        && at_most(run.time_ms, q.max_time_ms)
+        && (!q.completed_only || run.completed)
+        && q.practice.is_none_or(|p| p == run.practice)
+        && q.invalidated
+            .is_none_or(|i| i != run.invalidated_by.is_empty())
+        && q.tags.iter().all(|t| run.tags.contains(t))
+}
+
+// The order select_runs sorts in: runs without a time last either way, and
+// ties broken by id in the same direction
+fn order(a: &Run, b: &Run, by: SortBy, dir: SortDir) -> Ordering {
+    let directed = |o: Ordering| match dir {
+        SortDir::Asc => o,
+        SortDir::Desc => o.reverse(),
+    };
+    let key = match by {
+        SortBy::Date => directed(a.started_at_utc.cmp(&b.started_at_utc)),
+        SortBy::Time => {
+            let untimed = a.time_ms.is_none().cmp(&b.time_ms.is_none());
+            untimed.then(directed(a.time_ms.cmp(&b.time_ms)))
+        }
+        SortBy::Map => directed(names::name_key(&a.map).cmp(&names::name_key(&b.map))),
+        SortBy::Fails => directed(a.fails.cmp(&b.fails)),
+    };
+    key.then(directed(a.id.cmp(&b.id)))
+}
+
+// As counts_for_pb in db, with no PB resets to leave runs out
+fn counts_for_pb(run: &Run) -> bool {
+    run.completed
+        && run.time_ms.is_some()
+        && !run.excluded
+        && !run.practice
+        && run.invalidated_by.is_empty()
+}
+
+impl RunStore for MemoryStore {
+    fn query_runs(&self, query: &RunQuery) -> Result<RunPage, String> {
+        let bounds = db::query_bounds(query)?;
+        let memory = self.memory()?;
+        let mut runs: Vec<Run> = memory
+            .runs
+            .iter()
+            .map(|r| &r.run)
+            .filter(|r| matches(r, query, &bounds))
+            .cloned()
+            .collect();
+        runs.sort_by(|a, b| order(a, b, query.sort_by, query.sort_dir));
+        let total = runs.len() as i64;
+        let limit = query.limit.unwrap_or(db::DEFAULT_LIMIT).min(db::MAX_LIMIT);
+        let runs = runs
+            .into_iter()
+            .skip(query.offset.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .collect();
+        Ok(RunPage { runs, total })
+    }
+
+    fn get_run(&self, run_id: i64) -> Result<Option<RunDetail>, String> {
+        let memory = self.memory()?;
+        Ok(memory.runs.iter().find(|r| r.run.id == run_id).cloned())
+    }
+
+    fn runs_for_map(&self, map: &str, username: &str) -> Result<Vec<Run>, String> {
+        let key = names::name_key(map);
+        let mut runs: Vec<Run> = self
+            .memory()?
+            .runs
+            .iter()
+            .map(|r| &r.run)
+            .filter(|r| names::name_key(&r.map) == key && r.username == username && !r.excluded)
+            .cloned()
+            .collect();
+        runs.sort_by(|a, b| order(a, b, SortBy::Date, SortDir::Asc));
+        Ok(runs)
+    }
+
+    fn personal_best(
+        &self,
+        map: &str,
+        username: Option<&str>,
+    ) -> Result<Option<RunDetail>, String> {
+        let key = names::name_key(map);
+        let memory = self.memory()?;
+        let best = memory
+            .runs
+            .iter()
+            .filter(|r| names::name_key(&r.run.map) == key && counts_for_pb(&r.run))
+            .filter(|r| username.is_none_or(|u| u == r.run.username))
+            .min_by(|a, b| {
+                let (a, b) = (&a.run, &b.run);
+                a.time_ms
+                    .cmp(&b.time_ms)
+                    .then(order(a, b, SortBy::Date, SortDir::Asc))
+            });
+        Ok(best.cloned())
+    }
+
+    fn get_session(&self, session_id: i64) -> Result<Option<Session>, String> {
+        let memory = self.memory()?;
+        Ok(memory.sessions.iter().find(|s| s.id == session_id).cloned())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<Session>, String> {
+        let mut sessions = self.memory()?.sessions.clone();
+        sessions.sort_by(|a, b| (&b.started_at, b.id).cmp(&(&a.started_at, a.id)));
+        Ok(sessions)
+    }
+
+    fn is_imported(&self, file: &LogFile) -> Result<bool, String> {
+        let memory = self.memory()?;
+        let imported = memory.imported.get(&file.key());
+        Ok(imported.is_some_and(|&(size, modified_ms, _)| {
+            (size, modified_ms) == (file.size, file.modified_ms)
+        }))
+    }
+
+    fn import(&self, file: &LogFile, analysis: &LogAnalysis) -> Result<LogImport, String> {
+        let mut memory = self.memory()?;
+        let memory = &mut *memory;
+        let mut result = LogImport {
+            runs_found: analysis.runs.len(),
+            session_id: memory.imported.get(&file.key()).and_then(|i| i.2),
+            ..Default::default()
+        };
+        if let (None, Some(session)) = (result.session_id, &analysis.session) {
+            let id = memory.sessions.len() as i64 + 1;
+            memory.sessions.push(Session {
+                id,
+                username: session.username.clone(),
+                server: None,
+                started_at: session.started_at.clone(),
+                ended_at: Some(session.ended_at.clone()),
+                started_at_utc: timestamps::utc_column(&session.started_at),
+                ended_at_utc: timestamps::utc_column(&session.ended_at),
+                active_ms: session.active_ms,
+                end_reason: None,
+                client: Some(session.client.clone()),
+            });
+            result.session_id = Some(id);
+        }
+        for run in &analysis.runs {
+            if !memory.fingerprints.insert(db::run_fingerprint(run)) {
+                result.duplicates += 1;
+                continue;
+            }
+            let id = memory.runs.len() as i64 + 1;
+            memory.runs.push(RunDetail {
+                run: Run {
+                    id,
+                    map: run.map.clone(),
+                    username: run.username.clone(),
+                    server: run.server.clone(),
+                    world: run.world.clone(),
+                    started_at: run.started_at.clone(),
+                    started_at_utc: timestamps::utc_column(&run.started_at),
+                    time_ms: run.time_ms,
+                    completed: run.completed,
+                    fails: run.fails,
+                    tags: Vec::new(),
+                    excluded: false,
+                    practice: run.practice,
+                    invalidated_by: run.invalidated_by.clone(),
+                    original: None,
+                    edited_at: None,
+                },
+                splits: run.splits.clone(),
+            });
+            result.runs_added += 1;
+        }
+        memory
+            .imported
+            .insert(file.key(), (file.size, file.modified_ms, result.session_id));
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::LogSession;
+    use crate::model::NewRun;
+    use std::fs;
+
+    fn run(map: &str, username: &str, started_at: &str, time_ms: Option<i64>) -> NewRun {
+        NewRun {
+            map: map.into(),
+            username: username.into(),
+            server: Some("mc.example.net".into()),
+            world: None,
+            started_at: started_at.into(),
+            time_ms,
+            completed: time_ms.is_some(),
+            fails: time_ms.map_or(3, |t| t % 4),
+            practice: false,
+            invalidated_by: Vec::new(),
+            splits: Vec::new(),
+        }
+    }
+
+    fn analysis() -> LogAnalysis {
+        let mut practice = run("Sandstorm", "me", "2026-01-01 10:09:00", Some(40_000));
+        practice.practice = true;
+        let mut invalid = run("Sandstorm", "me", "2026-01-01 10:10:00", Some(41_000));
+        invalid.invalidated_by = vec!["teleport".into()];
+        LogAnalysis {
+            path: "2026-01-01-1.log".into(),
+            username: Some("me".into()),
+            session: Some(LogSession {
+                username: "me".into(),
+                started_at: "2026-01-01 10:00:00".into(),
+                ended_at: "2026-01-01 11:00:00".into(),
+                active_ms: 3_000_000,
+                client: "vanilla".into(),
+            }),
+            runs: vec![
+                run("Sandstorm", "me", "2026-01-01 10:01:00", Some(62_000)),
+                run("sandstorm ", "me", "2026-01-01 10:03:00", Some(58_000)),
+                run("Sandstorm", "other", "2026-01-01 10:04:00", Some(50_000)),
+                run("Sandstorm", "me", "2026-01-01 10:05:00", None),
+                run("Glacier", "me", "2026-01-01 10:06:00", Some(58_000)),
+                run("Glacier", "me", "2026-01-02 09:00:00", Some(57_000)),
+                practice,
+                invalid,
+                run("Sandstorm", "me", "2026-01-01 10:03:00", Some(58_000)),
+            ],
+            maps: Vec::new(),
+        }
+    }
+
+    fn ids(runs: &[Run]) -> Vec<i64> {
+        runs.iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn memory_store_answers_like_the_database() {
+        let dir = std::env::temp_dir().join("parkour-store-test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("2026-01-01-1.log");
+        fs::write(&path, "log").unwrap();
+        let file = import::log_file(path).unwrap();
+        let db = Db::open_in_memory().unwrap();
+        let memory = MemoryStore::default();
+        let stores: [&dyn RunStore; 2] = [&db, &memory];
+
+        for store in stores {
+            assert!(!store.is_imported(&file).unwrap());
+            let imported = store.import(&file, &analysis()).unwrap();
+            assert_eq!((imported.runs_found, imported.runs_added), (9, 8));
+            assert_eq!(imported.duplicates, 1);
+            assert!(store.is_imported(&file).unwrap());
+            let again = store.import(&file, &analysis()).unwrap();
+            assert_eq!((again.runs_added, again.duplicates), (0, 9));
+            assert_eq!(again.session_id, imported.session_id);
+        }
+
+        let queries = [
+            RunQuery::default(),
+            RunQuery {
+                map: Some("SANDSTORM".into()),
+                sort_by: SortBy::Time,
+                sort_dir: SortDir::Asc,
+                ..Default::default()
+            },
+            RunQuery {
+                sort_by: SortBy::Time,
+                ..Default::default()
+            },
+            RunQuery {
+                username: Some("me".into()),
+                completed_only: true,
+                practice: Some(false),
+                invalidated: Some(false),
+                sort_by: SortBy::Map,
+                ..Default::default()
+            },
+            RunQuery {
+                date_from: Some("2026-01-02".into()),
+                ..Default::default()
+            },
+            RunQuery {
+                date_to: Some("2026-01-01".into()),
+                min_time_ms: Some(50_000),
+                max_time_ms: Some(60_000),
+                sort_by: SortBy::Fails,
+                ..Default::default()
+            },
+            RunQuery {
+                tags: vec!["pb".into()],
+                ..Default::default()
+            },
+            RunQuery {
+                limit: Some(3),
+                offset: Some(2),
+                ..Default::default()
+            },
+        ];
+        for q in &queries {
+            let (expected, got) = (db.query_runs(q).unwrap(), memory.query_runs(q).unwrap());
+            assert_eq!(ids(&got.runs), ids(&expected.runs), "{:?}", q);
+            assert_eq!(got.total, expected.total, "{:?}", q);
+        }
+        let bad = RunQuery {
+            min_time_ms: Some(2),
+            max_time_ms: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(db.query_runs(&bad).err(), memory.query_runs(&bad).err());
+
+        for store in stores {
+            let sandstorm = store.runs_for_map("sandstorm", "me").unwrap();
+            assert_eq!(ids(&sandstorm), [1, 2, 4, 7, 8]);
+            let pb = |user| store.personal_best("Sandstorm", user).unwrap();
+            assert_eq!(pb(Some("me")).map(|r| r.run.id), Some(2));
+            assert_eq!(pb(None).map(|r| r.run.id), Some(3));
+            assert_eq!(store.get_run(5).unwrap().unwrap().run.map, "Glacier");
+            assert!(store.get_run(99).unwrap().is_none());
+            let sessions = store.list_sessions().unwrap();
+            assert_eq!(sessions.len(), 1);
+            let session = store.get_session(sessions[0].id).unwrap().unwrap();
+            assert_eq!(session.active_ms, 3_000_000);
+            assert_eq!(session.started_at_utc, sessions[0].started_at_utc);
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const RETRY_DELAYS_MS: [u64; 3] = [50, 150, 400];
+// How much of the start of the log is kept to recognize it, see LogTail::poll
+const HEAD_LEN: usize = 64;
+
+/// Bytes appended to the log since the last read. `offset` is 0 whenever the
+/// file was (re)opened from the start, so the consumer should replace rather
+/// than append. `offset..end` is in raw file bytes; `content` is shorter when
+/// a BOM was stripped, and longer when it was decoded from cp1252, see
+/// [`decode`].
+pub struct Chunk {
+    pub offset: u64,
+    pub end: u64,
+    // Raw offset `content` begins at, past a stripped BOM
+    pub start: u64,
+    pub content: String,
+    // Set when this chunk starts over a log that was read before
+    pub rotation: Option<Rotation>,
+}
+
+/// Why the log was read again from the start: another file took its place,
+/// or it got shorter than what was read. `previous_end` is how far the
+/// earlier one had been read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rotation {
+    pub truncated: bool,
+    pub previous_end: u64,
+}
+
+const BOM: &str = "\u{feff}";
+
+impl Chunk {
+    /// The complete lines in `bytes`, read from the log at `offset`.
+    /// Anything after the last newline is left out for a later read, so
+    /// `end` can fall short of the bytes given.
+    pub fn complete(offset: u64, mut bytes: Vec<u8>) -> io::Result<Chunk> {
+        let complete = bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        bytes.truncate(complete);
+        let end = offset + bytes.len() as u64;
+        let mut content = decode(bytes);
+        let mut start = offset;
+        if offset == 0 && content.starts_with(BOM) {
+            content.drain(..BOM.len());
+            start = BOM.len() as u64;
+        }
+        Ok(Chunk {
+            offset,
+            end,
+            start,
+            content,
+            rotation: None,
+        })
+    }
+
+    /// Whether `content` has the same bytes as the file, so offsets within it
+    /// are raw file offsets. A cp1252 chunk's are only close.
+    pub fn is_raw(&self) -> bool {
+        self.start + self.content.len() as u64 == self.end
+    }
+}
+
+// What cp1252 has at 0x80..=0x9F; latin-1 covers the rest. The five unused
+// bytes are kept as the control characters latin-1 has there.
+const CP1252: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// Text of log bytes. Localized clients and some mods write chat in the
+/// system code page, so a line that isn't UTF-8 is read as cp1252 instead
+/// of failing the read; the other lines are left as they are.
+pub fn decode(bytes: Vec<u8>) -> String {
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return text,
+        Err(e) => e.into_bytes(),
+    };
+    let mut text = String::with_capacity(bytes.len() + bytes.len() / 8);
+    // Kept whole for strip_bom, even when the first line is cp1252
+    let rest = match bytes.strip_prefix(BOM.as_bytes()) {
+        Some(rest) => {
+            text.push_str(BOM);
+            rest
+        }
+        None => &bytes,
+    };
+    for line in rest.split_inclusive(|b| *b == b'\n') {
+        match std::str::from_utf8(line) {
+            Ok(line) => text.push_str(line),
+            Err(_) => text.extend(line.iter().map(|&b| match b {
+                0x80..=0x9f => CP1252[(b - 0x80) as usize],
+                _ => b as char,
+            })),
+        }
+    }
+    text
+}
+
+/// The whole log as text, see [`decode`], retrying while it's locked.
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    with_retry(|| fs::read(path)).map(decode)
+}
+
+/// Text of the log without the BOM some editors and launchers add. Offsets
+/// stay in raw file bytes, so only use this on content starting at byte 0.
+pub fn strip_bom(content: &str) -> &str {
+    content.strip_prefix(BOM).unwrap_or(content)
+}
+
+// Keeps the log open between reads and only reads what was appended.
+pub struct LogTail {
+    path: PathBuf,
+    file: Option<File>,
+    id: Option<FileId>,
+    pos: u64,
+    // First bytes of the file, for ids that can't tell a recreated log apart
+    head: Vec<u8>,
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    // Let the game delete or rename the log while we hold it open
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_SHARE_READ: u32 = 0x1;
+        const FILE_SHARE_WRITE: u32 = 0x2;
+        const FILE_SHARE_DELETE: u32 = 0x4;
+        options.share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE);
+    }
+    options.open(path)
+}
+
+// ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION: the game briefly holds
+// the log in a way that blocks readers even with full share flags.
+pub fn is_lock_error(err: &io::Error) -> bool {
+    cfg!(windows) && matches!(err.raw_os_error(), Some(32) | Some(33))
+}
+
+/// Runs `f` again with a short backoff while it fails because the log is
+/// locked, returning the last error once the retries are used up.
+pub fn with_retry<T>(mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delays = RETRY_DELAYS_MS.iter();
+    loop {
+        match f() {
+            Err(e) if is_lock_error(&e) => match delays.next() {
+                Some(ms) => std::thread::sleep(Duration::from_millis(*ms)),
+                None => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Which file a path pointed to: device and inode where the platform has
+/// them, plus the creation time where it's recorded. A log that was deleted
+/// and recreated gets a new id even if its content is byte-for-byte the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileId {
+    inode: Option<(u64, u64)>,
+    created: Option<SystemTime>,
+}
+
+impl FileId {
+    pub fn of(meta: &Metadata) -> FileId {
+        #[cfg(unix)]
+        let inode = {
+            use std::os::unix::fs::MetadataExt;
+            Some((meta.dev(), meta.ino()))
+        };
+        // std has no stable file index on Windows, so creation time has to do
+        #[cfg(not(unix))]
+        let inode = None;
+        FileId {
+            inode,
+            created: meta.created().ok(),
+        }
+    }
+
+    pub fn of_path(path: &Path) -> io::Result<FileId> {
+        fs::metadata(path).map(|m| FileId::of(&m))
+    }
+}
+
+impl LogTail {
+    pub fn new(path: &Path) -> LogTail {
+        LogTail {
+            path: path.to_path_buf(),
+            file: None,
+            id: None,
+            pos: 0,
+            head: Vec::new(),
+        }
+    }
+
+    /// Identity of the file the last chunk came from.
+    pub fn id(&self) -> Option<FileId> {
+        self.id
+    }
+
+    // Without an inode, a log recreated under the same name can keep the old
+    // one's creation time (NTFS carries it over), so the id stays the same.
+    // Its first bytes start with another timestamp, though.
+    fn head_changed(&self) -> bool {
+        if self.head.is_empty() || self.id.is_some_and(|id| id.inode.is_some()) {
+            return false;
+        }
+        let mut head = Vec::with_capacity(self.head.len());
+        match open(&self.path).and_then(|f| f.take(self.head.len() as u64).read_to_end(&mut head)) {
+            Ok(_) => head != self.head,
+            Err(_) => false,
+        }
+    }
+
+    // Reading from scratch is needed when the log was rotated or truncated.
+    // Length alone misses a log recreated with the same size, so the file
+    // identity is checked as well, and the head before reading more of
+    // what may be another file.
+    fn rotation(&self, meta: &Metadata) -> Option<Rotation> {
+        self.file.as_ref()?;
+        let truncated = meta.len() < self.pos;
+        let replaced =
+            self.id != Some(FileId::of(meta)) || (meta.len() > self.pos && self.head_changed());
+        (truncated || replaced).then_some(Rotation {
+            truncated: truncated && !replaced,
+            previous_end: self.pos,
+        })
+    }
+
+    /// Returns the newly appended lines, or `None` if nothing changed. The
+    /// game writes lines in pieces, so anything after the last newline is
+    /// left in the file for a later call and read again from there.
+    pub fn poll(&mut self) -> io::Result<Option<Chunk>> {
+        let meta = fs::metadata(&self.path)?;
+        let len = meta.len();
+        let rotation = self.rotation(&meta);
+        let reopened = self.file.is_none() || rotation.is_some();
+        if reopened {
+            let file = open(&self.path)?;
+            self.id = Some(FileId::of(&file.metadata()?));
+            self.file = Some(file);
+            self.pos = 0;
+        }
+        let Some(file) = self.file.as_mut() else {
+            return Ok(None);
+        };
+        if len == self.pos && !reopened {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(self.pos))?;
+        let mut bytes = Vec::new();
+        file.take(len - self.pos).read_to_end(&mut bytes)?;
+        if self.pos == 0 {
+            self.head = bytes[..bytes.len().min(HEAD_LEN)].to_vec();
+        }
+        let mut chunk = Chunk::complete(self.pos, bytes)?;
+        chunk.rotation = rotation;
+        self.pos = chunk.end;
+        if chunk.end == chunk.offset && !reopened {
+            return Ok(None);
+        }
+        Ok(Some(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // A fresh latest.log in a directory of its own
+    fn temp_log(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("parkour-tail-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("latest.log")
+    }
+
+    fn append(path: &Path, bytes: &[u8]) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    #[test]
+    fn decodes_cp1252_lines_only() {
+        assert_eq!(decode("héllo\n".as_bytes().to_vec()), "héllo\n");
+        let mut bytes = "ütf ok\n".as_bytes().to_vec();
+        bytes.extend_from_slice(b"caf\xe9 \x80 \x93x\n");
+        assert_eq!(decode(bytes), "ütf ok\ncafé € “x\n");
+    }
+
+    #[test]
+    fn chunk_leaves_out_the_partial_line() {
+        let chunk = Chunk::complete(10, b"abc\nde".to_vec()).unwrap();
+        assert_eq!((chunk.offset, chunk.start, chunk.end), (10, 10, 14));
+        assert_eq!(chunk.content, "abc\n");
+        assert!(chunk.is_raw());
+        let chunk = Chunk::complete(0, b"a\xe9\n".to_vec()).unwrap();
+        assert_eq!((chunk.end, chunk.content.as_str()), (3, "aé\n"));
+        assert!(!chunk.is_raw());
+    }
+
+    #[test]
+    fn bom_is_stripped_but_counted_in_offsets() {
+        assert_eq!(strip_bom("\u{feff}abc\n"), "abc\n");
+        assert_eq!(strip_bom("abc\n"), "abc\n");
+        let chunk = Chunk::complete(0, b"\xef\xbb\xbfabc\nde".to_vec()).unwrap();
+        assert_eq!((chunk.offset, chunk.start, chunk.end), (0, 3, 7));
+        assert_eq!(chunk.content, "abc\n");
+        assert!(chunk.is_raw());
+        let chunk = Chunk::complete(0, b"\xef\xbb\xbfcaf\xe9\n".to_vec()).unwrap();
+        assert_eq!(
+            (chunk.start, chunk.end, chunk.content.as_str()),
+            (3, 8, "café\n")
+        );
+        // Only the start of the file can have one
+        let chunk = Chunk::complete(7, "\u{feff}x\n".as_bytes().to_vec()).unwrap();
+        assert_eq!((chunk.start, chunk.content.as_str()), (7, "\u{feff}x\n"));
+    }
+
+    #[test]
+    fn bom_log_is_polled_at_raw_offsets() {
+        let path = temp_log("bom");
+        fs::write(&path, b"\xef\xbb\xbfone\n").unwrap();
+        let mut tail = LogTail::new(&path);
+        let chunk = tail.poll().unwrap().unwrap();
+        assert_eq!((chunk.offset, chunk.start, chunk.end), (0, 3, 7));
+        assert_eq!(chunk.content, "one\n");
+        append(&path, b"two\n");
+        let chunk = tail.poll().unwrap().unwrap();
+        assert_eq!((chunk.offset, chunk.start, chunk.end), (7, 7, 11));
+        assert_eq!(chunk.content, "two\n");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn polls_appended_lines() {
+        let path = temp_log("append");
+        fs::write(&path, b"one\n").unwrap();
+        let mut tail = LogTail::new(&path);
+        let chunk = tail.poll().unwrap().unwrap();
+        assert_eq!(
+            (chunk.offset, chunk.end, chunk.content.as_str()),
+            (0, 4, "one\n")
+        );
+        assert!(chunk.rotation.is_none());
+        append(&path, b"caf\xe9\npart");
+        let chunk = tail.poll().unwrap().unwrap();
+        assert_eq!((chunk.offset, chunk.start, chunk.end), (4, 4, 9));
+        assert_eq!(chunk.content, "café\n");
+        assert!(tail.poll().unwrap().is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn line_written_in_pieces_is_read_once() {
+        let path = temp_log("pieces");
+        fs::write(&path, b"[10:00:00] [Client thread/INFO]: start\n").unwrap();
+        let mut tail = LogTail::new(&path);
+        tail.poll().unwrap().unwrap();
+        let mut chunks = Vec::new();
+        for piece in [
+            "[10:00:01] [Client th",
+            "read/INFO]: [CHAT] Che",
+            "ckpoint!\n",
+        ] {
+            append(&path, piece.as_bytes());
+            chunks.extend(tail.poll().unwrap());
+        }
+        assert_eq!(chunks.len(), 1);
+        let chunk = &chunks[0];
+        assert_eq!((chunk.offset, chunk.end), (39, 91));
+        let lines: Vec<_> = chunk.content.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let line = crate::log_line::parse_line(chunk.offset, lines[0]);
+        assert_eq!(line.time.as_deref(), Some("10:00:01"));
+        assert_eq!(line.thread.as_deref(), Some("Client thread"));
+        assert_eq!(line.message, "[CHAT] Checkpoint!");
+        assert!(tail.poll().unwrap().is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn recreated_log_with_the_same_content_is_read_again() {
+        let path = temp_log("recreate");
+        let content = "[10:00:00] [Client thread/INFO]: [CHAT] Hi\n";
+        fs::write(&path, content).unwrap();
+        let mut tail = LogTail::new(&path);
+        tail.poll().unwrap().unwrap();
+        let id = tail.id();
+        assert!(tail.poll().unwrap().is_none());
+
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, content).unwrap();
+        let chunk = tail.poll().unwrap().unwrap();
+        assert_ne!(tail.id(), id);
+        assert_eq!((chunk.offset, chunk.content.as_str()), (0, content));
+        let rotation = chunk.rotation.unwrap();
+        assert!(!rotation.truncated);
+        assert_eq!(rotation.previous_end, content.len() as u64);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reports_truncation_and_replacement() {
+        let path = temp_log("rotation");
+        fs::write(&path, "[10:00:00] one\n[10:00:01] two\n").unwrap();
+        let mut tail = LogTail::new(&path);
+        tail.poll().unwrap().unwrap();
+
+        fs::write(&path, "[10:00:00] x\n").unwrap();
+        let chunk = tail.poll().unwrap().unwrap();
+        assert_eq!(chunk.offset, 0);
+        let rotation = chunk.rotation.unwrap();
+        assert!(rotation.truncated);
+        assert_eq!(rotation.previous_end, 30);
+
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, "[11:00:00] a new file longer than before\n").unwrap();
+        let chunk = tail.poll().unwrap().unwrap();
+        let rotation = chunk.rotation.unwrap();
+        assert!(!rotation.truncated);
+        assert_eq!(rotation.previous_end, 13);
+
+        append(&path, b"more\n");
+        let chunk = tail.poll().unwrap().unwrap();
+        assert!(chunk.rotation.is_none());
+        assert_eq!(chunk.content, "more\n");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
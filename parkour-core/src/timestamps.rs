@@ -0,0 +1,193 @@
+use chrono::{
+    DateTime, Duration as ChronoDuration, Local, LocalResult, NaiveDate, NaiveDateTime, Offset,
+    SecondsFormat, TimeZone, Utc,
+};
+
+/// How the log and the database write local wall-clock times.
+pub const LOCAL_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+// Fixed width, so stored UTC times sort and compare as plain strings.
+const UTC_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Converts a wall-clock time in `tz` to UTC using the offset in effect on
+/// that date. DST transitions are resolved the same way every time:
+///
+/// - a time that occurs twice (clocks going back) is the first occurrence,
+///   still on summer time;
+/// - a time that never occurs (clocks going forward) is read with the offset
+///   from just before the jump, so 02:30 on a night that skips 02:00–03:00
+///   becomes 03:30.
+pub fn local_to_utc<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(t) => t.with_timezone(&Utc),
+        // Not every TimeZone returns the pair in order
+        LocalResult::Ambiguous(a, b) => a.with_timezone(&Utc).min(b.with_timezone(&Utc)),
+        LocalResult::None => {
+            // No transition is longer than a few hours, so this is before it
+            let before = local - ChronoDuration::hours(3);
+            let offset = match tz.offset_from_local_datetime(&before) {
+                LocalResult::Single(o) | LocalResult::Ambiguous(o, _) => o.fix(),
+                LocalResult::None => Utc.fix(),
+            };
+            Utc.from_utc_datetime(&(local - offset))
+        }
+    }
+}
+
+/// A `LOCAL_FORMAT` string from this machine as stored UTC, or `None` if it
+/// doesn't parse.
+pub fn utc_column(local: &str) -> Option<String> {
+    let local = NaiveDateTime::parse_from_str(local, LOCAL_FORMAT).ok()?;
+    Some(format_utc(local_to_utc(&Local, local)))
+}
+
+pub fn format_utc(t: DateTime<Utc>) -> String {
+    t.format(UTC_FORMAT).to_string()
+}
+
+pub fn parse_utc(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// RFC 3339 with this machine's offset on that date, e.g.
+/// `2024-03-31T00:00:00+01:00`.
+pub fn local_rfc3339(t: DateTime<Utc>) -> String {
+    t.with_timezone(&Local)
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// A range bound given either as a `YYYY-MM-DD` local date or an RFC 3339
+/// timestamp with an offset. End bounds are inclusive on input and returned
+/// as the exclusive end: a date moves to the start of the next day.
+pub fn parse_bound(field: &str, value: &str, end: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let date = if end {
+            date + ChronoDuration::days(1)
+        } else {
+            date
+        };
+        return Ok(local_to_utc(&Local, date.and_time(Default::default())));
+    }
+    let t = parse_utc(value).ok_or_else(|| {
+        format!(
+            "{} must be a YYYY-MM-DD date or an RFC 3339 timestamp, got \"{}\"",
+            field, value
+        )
+    })?;
+    // Stored times have whole seconds, so both ends round up to the next one
+    let round_up = end || t.timestamp_subsec_nanos() > 0;
+    Ok(Utc
+        .timestamp_opt(t.timestamp() + round_up as i64, 0)
+        .single()
+        .unwrap_or(t))
+}
+
+/// The local calendar date a bound falls on, for queries grouped by day.
+pub fn local_date(field: &str, value: &str) -> Result<NaiveDate, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date);
+    }
+    parse_utc(value)
+        .map(|t| t.with_timezone(&Local).date_naive())
+        .ok_or_else(|| {
+            format!(
+                "{} must be a YYYY-MM-DD date or an RFC 3339 timestamp, got \"{}\"",
+                field, value
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, NaiveTime};
+
+    // Central European time in 2024: summer time from 31 March to 27
+    // October, switching at 01:00 UTC. `swapped` returns the two readings of
+    // an ambiguous time later one first.
+    #[derive(Clone, Copy, Debug)]
+    struct Berlin {
+        swapped: bool,
+    }
+
+    fn hours(h: i32) -> FixedOffset {
+        FixedOffset::east_opt(h * 3600).unwrap()
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, LOCAL_FORMAT).unwrap()
+    }
+
+    impl TimeZone for Berlin {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Berlin {
+            Berlin { swapped: false }
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let fits = |o: FixedOffset| self.offset_from_utc_datetime(&(*local - o)) == o;
+            match (fits(hours(2)), fits(hours(1))) {
+                (true, true) if self.swapped => LocalResult::Ambiguous(hours(1), hours(2)),
+                (true, true) => LocalResult::Ambiguous(hours(2), hours(1)),
+                (true, false) => LocalResult::Single(hours(2)),
+                (false, true) => LocalResult::Single(hours(1)),
+                (false, false) => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let summer = at("2024-03-31 01:00:00")..at("2024-10-27 01:00:00");
+            match summer.contains(utc) {
+                true => hours(2),
+                false => hours(1),
+            }
+        }
+    }
+
+    fn utc(local: &str) -> String {
+        format_utc(local_to_utc(&Berlin { swapped: false }, at(local)))
+    }
+
+    #[test]
+    fn times_outside_transitions_use_their_offset() {
+        assert_eq!(utc("2024-01-15 12:00:00"), "2024-01-15T11:00:00Z");
+        assert_eq!(utc("2024-07-01 12:00:00"), "2024-07-01T10:00:00Z");
+        assert_eq!(utc("2024-03-31 01:59:59"), "2024-03-31T00:59:59Z");
+        assert_eq!(utc("2024-03-31 03:00:00"), "2024-03-31T01:00:00Z");
+    }
+
+    #[test]
+    fn a_time_that_occurs_twice_is_the_first_occurrence() {
+        // 02:00 to 03:00 on 27 October happens on summer time, then again
+        assert_eq!(utc("2024-10-27 02:30:00"), "2024-10-27T00:30:00Z");
+        let swapped = local_to_utc(&Berlin { swapped: true }, at("2024-10-27 02:30:00"));
+        assert_eq!(format_utc(swapped), "2024-10-27T00:30:00Z");
+        assert_eq!(utc("2024-10-27 03:00:00"), "2024-10-27T02:00:00Z");
+    }
+
+    #[test]
+    fn a_skipped_time_is_read_with_the_offset_before_the_jump() {
+        // 02:00 to 03:00 on 31 March never happens; 02:30 is 03:30 summer time
+        assert_eq!(utc("2024-03-31 02:30:00"), "2024-03-31T01:30:00Z");
+        assert_eq!(utc("2024-03-31 02:00:00"), "2024-03-31T01:00:00Z");
+        assert_eq!(utc("2024-03-31 02:00:00"), utc("2024-03-31 03:00:00"));
+    }
+
+    #[test]
+    fn stored_utc_times_round_trip() {
+        let t = local_to_utc(&Utc, at("2024-05-01 20:00:00"));
+        assert_eq!(format_utc(t), "2024-05-01T20:00:00Z");
+        assert_eq!(parse_utc("2024-05-01T22:00:00+02:00"), Some(t));
+        assert_eq!(parse_utc("2024-05-01 20:00:00"), None);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::db::Db;
+use crate::events;
+use crate::goals;
+use crate::settings::SettingsStore;
+use parkour_core::import::{self, LogAnalysis, LogImport};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// What a log holds, see [`LogAnalysis`], plus what storing it did when
+/// that was asked for.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct FileAnalysis {
+    #[serde(flatten)]
+    pub analysis: LogAnalysis,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported: Option<LogImport>,
+}
+
+/// `{"path": "/home/me/Downloads/latest.log", "analysis": {"username": "Alex", "runs": [...], ...}}`
+///
+/// Emitted for each file dropped on the main window; `error` takes the place
+/// of `analysis` when the file couldn't be read.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct FileAnalyzedPayload {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<FileAnalysis>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Parsed on its own, so the watched log's live state is left alone
+fn analyze(app: &AppHandle, path: &Path, persist: bool) -> Result<FileAnalysis, String> {
+    let read = |e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+    let file = import::log_file(fs::canonicalize(path).map_err(read)?).map_err(read)?;
+    let content = import::read_log(&file.path).map_err(read)?;
+    let patterns = app.state::<SettingsStore>().get().patterns()?;
+    let analysis = import::analyze_log(&file, &content, &patterns);
+    let imported = match persist {
+        true => {
+            let db = app.state::<Db>();
+            let conn = db.conn()?;
+            Some(import::import_analysis(&conn, &file, &analysis)?)
+        }
+        false => None,
+    };
+    if imported.as_ref().is_some_and(|i| i.runs_added > 0) {
+        goals::sweep(app);
+    }
+    Ok(FileAnalysis { analysis, imported })
+}
+
+/// Reads any log, e.g. one a friend sent, including `.log.gz` archives,
+/// and returns its session, runs and per-map totals. Nothing is stored
+/// unless `persist` is true; the runs then go in as by `parkour-cli import`,
+/// under the players the log names.
+#[tauri::command]
+pub fn analyze_file(path: String, persist: bool, app: AppHandle) -> Result<FileAnalysis, String> {
+    analyze(&app, Path::new(&path), persist)
+}
+
+/// Analyzes dropped files in the background, one `file-analyzed` event each.
+pub fn on_drop(app: &AppHandle, paths: &[PathBuf]) {
+    let app = app.clone();
+    let paths = paths.to_vec();
+    std::thread::spawn(move || {
+        for path in paths {
+            let (analysis, error) = match analyze(&app, &path, false) {
+                Ok(analysis) => (Some(analysis), None),
+                Err(e) => (None, Some(e)),
+            };
+            let payload = FileAnalyzedPayload {
+                path: path.to_string_lossy().to_string(),
+                analysis,
+                error,
+            };
+            events::emit(&app, events::FILE_ANALYZED, &payload);
+        }
+    });
+}
@@ -0,0 +1,80 @@
+use crate::live::Live;
+use crate::settings::SettingsStore;
+use parkour_core::anonymize::{anonymize, redact, Anonymized, RedactionRules};
+use parkour_core::import::read_log;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, Manager, State};
+
+/// `{"path": "/home/me/Desktop/latest-anonymized.log", "names_replaced": 4, "hosts_masked": 1, "chat_lines_dropped": 0, "patterns_replaced": 0}`
+#[derive(Serialize, Clone, Debug)]
+pub struct AnonymizedExport {
+    pub path: String,
+    pub names_replaced: usize,
+    pub hosts_masked: usize,
+    pub chat_lines_dropped: usize,
+    pub patterns_replaced: usize,
+}
+
+fn read(app: &AppHandle, path: Option<String>) -> Result<String, String> {
+    let path = path
+        .or_else(|| app.state::<Live>().snapshot().log_path)
+        .ok_or("No log file is being watched")?;
+    read_log(Path::new(&path)).map_err(|e| format!("Failed to read log: {}", e))
+}
+
+fn write(dest: String, anonymized: Anonymized) -> Result<AnonymizedExport, String> {
+    fs::write(&dest, &anonymized.content)
+        .map_err(|e| format!("Failed to write {}: {}", dest, e))?;
+    Ok(AnonymizedExport {
+        path: dest,
+        names_replaced: anonymized.names_replaced,
+        hosts_masked: anonymized.hosts_masked,
+        chat_lines_dropped: anonymized.chat_lines_dropped,
+        patterns_replaced: anonymized.patterns_replaced,
+    })
+}
+
+/// Writes a copy of `path`, or of the watched log, that's safe to attach to
+/// an issue. Which pseudonym stands for whom is never written anywhere.
+#[tauri::command]
+pub fn export_anonymized_log(
+    dest: String,
+    path: Option<String>,
+    drop_chat: Option<bool>,
+    app: AppHandle,
+) -> Result<AnonymizedExport, String> {
+    let content = read(&app, path)?;
+    let patterns = app.state::<SettingsStore>().get().patterns()?;
+    write(
+        dest,
+        anonymize(&content, &patterns, drop_chat.unwrap_or(false))?,
+    )
+}
+
+/// Writes the watched log to `path` with the saved redaction rules applied:
+/// by default every player name, address and chat line is scrubbed. See
+/// [`RedactionRules`] for what can be kept or scrubbed besides.
+#[tauri::command]
+pub fn export_sanitized_log(path: String, app: AppHandle) -> Result<AnonymizedExport, String> {
+    let content = read(&app, None)?;
+    let settings = app.state::<SettingsStore>().get();
+    let patterns = settings.patterns()?;
+    write(path, redact(&content, &patterns, &settings.redaction)?)
+}
+
+/// `{"names": true, "keep_own_name": true, "addresses": true, "drop_chat": false, "patterns": [{"pattern": "discord\\.gg/\\w+", "replacement": "[invite]"}]}`
+///
+/// Saves the rules `export_sanitized_log` applies.
+#[tauri::command]
+pub fn set_redaction_rules(
+    rules: RedactionRules,
+    store: State<'_, SettingsStore>,
+) -> Result<RedactionRules, String> {
+    let mut settings = store.get();
+    settings.redaction = rules;
+    crate::settings::validate(&settings)?;
+    store.replace(settings.clone())?;
+    Ok(settings.redaction)
+}
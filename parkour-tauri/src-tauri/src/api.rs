@@ -0,0 +1,236 @@
+use crate::db::{self, Db, RunQuery};
+use crate::live::{Live, MAIN};
+use crate::overlay::{error_response, header_value, json_response, query_param};
+use crate::pb;
+use crate::settings::{ApiSettings, SettingsStore};
+use percent_encoding::percent_decode_str;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+use tiny_http::{Method, Request, Server};
+
+struct Running {
+    port: u16,
+    token: String,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+pub struct ApiServer(Mutex<Option<Running>>);
+
+impl ApiServer {
+    pub fn port(&self) -> Option<u16> {
+        self.0.lock().ok()?.as_ref().map(|r| r.port)
+    }
+}
+
+fn shutdown(running: Running) {
+    running.server.unblock();
+    if let Some(thread) = running.thread {
+        let _ = thread.join();
+    }
+}
+
+// 128 bits from the OS random number generator
+fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to make an API token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Serves the API on localhost at `port`, restarting it for a new port or
+/// token, and returns the port it listens on.
+pub fn start(app: &AppHandle, port: u16, token: &str) -> Result<u16, String> {
+    let api = app.state::<ApiServer>();
+    let mut running = api.0.lock().map_err(|_| "API lock poisoned")?;
+    if let Some(current) = running.take() {
+        if current.port == port && current.token == token {
+            let bound = current.port;
+            *running = Some(current);
+            return Ok(bound);
+        }
+        shutdown(current);
+    }
+
+    let server = Server::http(("127.0.0.1", port))
+        .map(Arc::new)
+        .map_err(|e| format!("Could not start the API server on port {}: {}", port, e))?;
+    let bound = server
+        .server_addr()
+        .to_ip()
+        .map(|a| a.port())
+        .unwrap_or(port);
+
+    let handle = app.clone();
+    let srv = server.clone();
+    let expected = token.to_string();
+    let thread = std::thread::spawn(move || {
+        for request in srv.incoming_requests() {
+            handle_request(&handle, request, &expected);
+        }
+    });
+
+    *running = Some(Running {
+        port: bound,
+        token: token.to_string(),
+        server,
+        thread: Some(thread),
+    });
+    Ok(bound)
+}
+
+pub fn stop(app: &AppHandle) {
+    let running = app
+        .state::<ApiServer>()
+        .0
+        .lock()
+        .ok()
+        .and_then(|mut running| running.take());
+    if let Some(running) = running {
+        shutdown(running);
+    }
+}
+
+pub fn apply_settings(app: &AppHandle, settings: &ApiSettings) -> Result<(), String> {
+    match (settings.enabled, settings.token.as_deref()) {
+        (true, Some(token)) => start(app, settings.port, token).map(|_| ()),
+        _ => {
+            stop(app);
+            Ok(())
+        }
+    }
+}
+
+// The token from `Authorization: Bearer <token>` or `?token=`, compared
+// without stopping at the first difference
+fn authorized(request: &Request, query: &str, token: &str) -> bool {
+    let given = header_value(request, "Authorization")
+        .and_then(|v| v.strip_prefix("Bearer ").map(str::to_string))
+        .or_else(|| query_param(query, "token"));
+    given.is_some_and(|given| {
+        given.len() == token.len()
+            && given
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    })
+}
+
+// `?map=Tower&username=Steve&server=mc.hypixel.net&world=New%20World&completed=true&limit=50&offset=0`
+fn run_query(query: &str) -> Result<RunQuery, String> {
+    let number = |key: &str| {
+        query_param(query, key)
+            .map(|v| v.parse().map_err(|_| format!("{} must be a number", key)))
+            .transpose()
+    };
+    Ok(RunQuery {
+        map: query_param(query, "map"),
+        username: query_param(query, "username"),
+        server: query_param(query, "server"),
+        world: query_param(query, "world"),
+        completed_only: query_param(query, "completed").is_some_and(|v| v == "true"),
+        limit: number("limit")?,
+        offset: number("offset")?,
+        ..RunQuery::default()
+    })
+}
+
+fn handle_request(app: &AppHandle, request: Request, token: &str) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let response = if !authorized(&request, query, token) {
+        error_response(401, "Missing or wrong token")
+    } else if request.method() != &Method::Get {
+        error_response(405, "Method not allowed")
+    } else if path == "/runs" {
+        let db = app.state::<Db>();
+        let page = run_query(query).and_then(|q| {
+            let conn = db.conn()?;
+            db::select_runs(&conn, &q)
+        });
+        match page {
+            Ok(page) => json_response(&page),
+            Err(e) => error_response(400, &e),
+        }
+    } else if path == "/current" {
+        // `?source=alt` for an instance log's run
+        let source = query_param(query, "source").unwrap_or_else(|| MAIN.into());
+        match app.state::<Live>().source(&source) {
+            Some(state) => json_response(&state.current_run),
+            None => error_response(404, "No such source"),
+        }
+    } else if let Some(map) = path.strip_prefix("/pb/").filter(|m| !m.is_empty()) {
+        match percent_decode_str(map).decode_utf8() {
+            Err(_) => error_response(400, "Map isn't valid UTF-8"),
+            Ok(map) => {
+                let username = query_param(query, "username");
+                let db = app.state::<Db>();
+                let pbs = db
+                    .conn()
+                    .and_then(|conn| pb::personal_bests(&conn, &map, username.as_deref()));
+                match pbs {
+                    Ok(pbs) => json_response(&pbs),
+                    Err(e) => error_response(500, &e),
+                }
+            }
+        }
+    } else {
+        error_response(404, "Not found")
+    };
+    let _ = request.respond(response);
+}
+
+fn save(
+    app: &AppHandle,
+    store: &SettingsStore,
+    f: impl FnOnce(&mut ApiSettings),
+) -> Result<ApiSettings, String> {
+    let mut settings = store.get();
+    f(&mut settings.api);
+    store.replace(settings.clone())?;
+    apply_settings(app, &settings.api)?;
+    Ok(settings.api)
+}
+
+/// `{"enabled": true, "port": 17834, "token": "3f9c0a..."}`
+///
+/// Turns the local API on or off and saves it, making a token the first
+/// time. Scripts send the token with every request to `GET /runs`,
+/// `GET /current` and `GET /pb/{map}` on `127.0.0.1:{port}`.
+#[tauri::command]
+pub fn set_api_enabled(
+    enabled: bool,
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+) -> Result<ApiSettings, String> {
+    let token = new_token()?;
+    save(&app, &store, |api| {
+        api.enabled = enabled;
+        api.token.get_or_insert(token);
+    })
+}
+
+/// Replaces the API token, so scripts using the old one are turned away.
+#[tauri::command]
+pub fn reset_api_token(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+) -> Result<ApiSettings, String> {
+    let token = new_token()?;
+    save(&app, &store, |api| api.token = Some(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_random_hex() {
+        let token = new_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_ne!(token, new_token().unwrap());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::live::Live;
+use parkour_core::import::{self, LogFile};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// `{"name": "2024-05-01-1.log.gz", "path": "/home/me/.minecraft/logs/2024-05-01-1.log.gz", "date": "2024-05-01", "size": 18234, "modified_ms": 1714600000000}`
+///
+/// One of the earlier logs the game keeps next to `latest.log`. Its runs can
+/// be read with `analyze_file` on `path`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ArchivedLog {
+    pub name: String,
+    pub path: String,
+    pub date: String,
+    // Compressed size on disk
+    pub size: u64,
+    pub modified_ms: Option<i64>,
+}
+
+// The folder of the log being watched, or of the one the game writes
+fn folder(app: &AppHandle) -> Result<PathBuf, String> {
+    let log = app
+        .state::<Live>()
+        .snapshot()
+        .log_path
+        .map(PathBuf::from)
+        .filter(|path| path.is_file())
+        .or_else(crate::get_log_path)
+        .ok_or("Could not find Minecraft log file")?;
+    log.parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| format!("{} has no parent folder", log.display()))
+}
+
+fn archives(dir: &Path) -> Result<Vec<LogFile>, String> {
+    let read = |e: std::io::Error| format!("Failed to read {}: {}", dir.display(), e);
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir).map_err(read)? {
+        let path = entry.map_err(read)?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let file = import::log_file(path).map_err(read)?;
+        if file.is_archive() {
+            found.push(file);
+        }
+    }
+    Ok(found)
+}
+
+fn name(file: &LogFile) -> String {
+    file.path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The archived logs in the watched log's folder, newest first.
+#[tauri::command]
+pub fn get_archived_logs(app: AppHandle) -> Result<Vec<ArchivedLog>, String> {
+    let mut files = archives(&folder(&app)?)?;
+    // Archives of the same day were written one after another
+    files.sort_by_key(|file| Reverse((file.date, file.modified_ms, name(file))));
+    Ok(files
+        .into_iter()
+        .map(|file| ArchivedLog {
+            name: name(&file),
+            path: file.key(),
+            date: file.date.format("%Y-%m-%d").to_string(),
+            size: file.size,
+            modified_ms: file.modified_ms,
+        })
+        .collect())
+}
+
+/// Text of the archived log `name`, as listed by `get_archived_logs`,
+/// decompressed.
+#[tauri::command]
+pub fn read_archived_log(name: String, app: AppHandle) -> Result<String, String> {
+    let dir = folder(&app)?;
+    // Only names in the folder, so nothing outside it can be read
+    let file = archives(&dir)?
+        .into_iter()
+        .find(|file| self::name(file) == name)
+        .ok_or_else(|| format!("No archived log named {}", name))?;
+    import::read_log(&file.path)
+        .map_err(|e| format!("Failed to read {}: {}", file.path.display(), e))
+}
@@ -0,0 +1,54 @@
+use tauri::{AppHandle, Manager};
+use tauri_plugin_autostart::ManagerExt;
+
+// Appended to the registered login command line so a login launch can be
+// told apart from the user opening the app.
+pub const AUTOSTART_FLAG: &str = "--autostart";
+
+pub fn launched_at_login() -> bool {
+    std::env::args().any(|a| a == AUTOSTART_FLAG)
+}
+
+// Login launches start in the tray; without one, fall back to minimized.
+pub fn start_hidden(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let hidden = if app.try_state::<crate::tray::Tray>().is_some() {
+        window.hide()
+    } else {
+        window.minimize()
+    };
+    if let Err(e) = hidden {
+        eprintln!("Failed to start minimized: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_autostart(app: AppHandle) -> Result<bool, String> {
+    app.autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to read login item: {}", e))
+}
+
+#[tauri::command]
+pub fn set_autostart(enabled: bool, app: AppHandle) -> Result<bool, String> {
+    let launcher = app.autolaunch();
+    let result = if enabled {
+        launcher.enable()
+    } else {
+        launcher.disable()
+    };
+    result.map_err(|e| {
+        let action = if enabled { "register" } else { "remove" };
+        format!("Failed to {} login item: {}", action, e)
+    })?;
+    // Some sandboxes accept the write and silently discard it
+    let actual = launcher
+        .is_enabled()
+        .map_err(|e| format!("Failed to read login item: {}", e))?;
+    if actual != enabled {
+        return Err("The login item change was not applied by the system".into());
+    }
+    Ok(actual)
+}
@@ -0,0 +1,133 @@
+use crate::hotkeys;
+use crate::lines::Capped;
+use crate::live::{is_fresh, MAIN};
+use crate::markers;
+use crate::parser;
+use crate::settings::{ChatCommandSettings, SettingsStore};
+use parkour_core::definitions::chat_message;
+use parkour_core::duels::player_chat;
+use parkour_core::log_line::parse_line;
+use tauri::{AppHandle, Manager, State};
+
+enum Command {
+    Reset,
+    Split,
+    Note(String),
+}
+
+fn command(prefix: &str, text: &str) -> Option<Command> {
+    let text = text.trim().strip_prefix(prefix)?;
+    let (name, rest) = text.split_once(' ').unwrap_or((text, ""));
+    match name.to_lowercase().as_str() {
+        "reset" => Some(Command::Reset),
+        "split" => Some(Command::Split),
+        "note" => Some(Command::Note(rest.trim().to_string())),
+        _ => None,
+    }
+}
+
+// The command in a log line, if `username` typed one in chat
+fn own_command(prefix: &str, username: &str, line: &str) -> Option<Command> {
+    let message = chat_message(line)?;
+    let (sender, text) = player_chat(&message)?;
+    if !sender.eq_ignore_ascii_case(username) {
+        return None;
+    }
+    command(prefix, text)
+}
+
+/// Runs the chat commands the player typed in newly read log text of
+/// `source`. Like the hotkeys they act on the
+/// main log's run, so other sources are ignored.
+pub fn scan(app: &AppHandle, source: &str, chunk: &Capped) {
+    let settings = app.state::<SettingsStore>().get().chat_commands;
+    if !settings.enabled || source != MAIN {
+        return;
+    }
+    // Nobody's commands count until it's known whose are wanted
+    let Some(username) = settings.username.or_else(|| parser::username(app, source)) else {
+        return;
+    };
+    for line in chunk.lines() {
+        let Some(command) = own_command(&settings.prefix, &username, line.text) else {
+            continue;
+        };
+        // Commands already in the log when it's first read have been dealt with
+        let time = parse_line(line.start as u64, line.text).time;
+        if !is_fresh(time.as_deref(), chunk.offset) {
+            continue;
+        }
+        let label = match command {
+            Command::Reset => {
+                hotkeys::reset(app);
+                continue;
+            }
+            Command::Split => None,
+            Command::Note(text) => Some(text),
+        };
+        if let Err(e) = markers::record(app, label) {
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Saves which chat commands run, see [`ChatCommandSettings`].
+#[tauri::command]
+pub fn set_chat_commands(
+    commands: ChatCommandSettings,
+    store: State<'_, SettingsStore>,
+) -> Result<ChatCommandSettings, String> {
+    let mut settings = store.get();
+    settings.chat_commands = commands;
+    crate::settings::validate(&settings)?;
+    store.replace(settings.clone())?;
+    Ok(settings.chat_commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(command: Option<Command>) -> Option<String> {
+        match command {
+            Some(Command::Note(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn commands_after_the_prefix() {
+        assert!(matches!(command("!", "!reset"), Some(Command::Reset)));
+        assert!(matches!(command("!", "  !SPLIT "), Some(Command::Split)));
+        assert!(matches!(command("!", "!split now"), Some(Command::Split)));
+        assert_eq!(
+            note(command("!", "!note  missed the jump ")).as_deref(),
+            Some("missed the jump")
+        );
+        assert_eq!(note(command("!", "!note")).as_deref(), Some(""));
+        assert!(command("!", "!resets").is_none());
+        assert!(command("!", "reset").is_none());
+        assert!(command("!", "gg !reset").is_none());
+        assert!(matches!(command(".", ".reset"), Some(Command::Reset)));
+        assert!(command(".", "!reset").is_none());
+    }
+
+    #[test]
+    fn only_the_players_own_chat_counts() {
+        let line = |message: &str| format!("[10:00:00] [Client thread/INFO]: [CHAT] {}", message);
+        let own = |message: &str| own_command("!", "Steve", &line(message));
+        assert!(matches!(own("<Steve> !reset"), Some(Command::Reset)));
+        assert!(matches!(
+            own("§b[MVP§c+§b] steve§f: !split"),
+            Some(Command::Split)
+        ));
+        assert_eq!(note(own("[VIP] Steve: !note hi")).as_deref(), Some("hi"));
+        assert!(own("<Alex> !reset").is_none());
+        assert!(own("[VIP] Alex: !reset").is_none());
+        // Somebody quoting the player isn't the player
+        assert!(own("<Alex> Steve: !reset").is_none());
+        assert!(own("Guild > Steve: !reset").is_none());
+        assert!(own("Steve: hello").is_none());
+        assert!(own_command("!", "Steve", "[10:00:00] [main/INFO]: <Steve> !reset").is_none());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::db::{Db, RunDetail, Split};
+use crate::names;
+use parkour_core::duels::FINISH;
+use rusqlite::Connection;
+use serde::Serialize;
+use tauri::State;
+
+/// One checkpoint of two runs side by side; `checkpoint` is 9999 for the
+/// finish. Deltas are run b less run a, so negative means b was faster:
+///
+/// `{"checkpoint": 2, "name": null, "a_cumulative_ms": 21000, "b_cumulative_ms": 20400, "a_segment_ms": 11000, "b_segment_ms": 10900, "cumulative_delta_ms": -600, "segment_delta_ms": -100, "only_in": null}`
+///
+/// `only_in` is `"a"` or `"b"` for a checkpoint just one run reached. A
+/// segment delta is only given when both runs came from the same checkpoint
+/// before it.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CheckpointComparison {
+    pub checkpoint: i64,
+    pub name: Option<String>,
+    pub a_cumulative_ms: Option<i64>,
+    pub b_cumulative_ms: Option<i64>,
+    pub a_segment_ms: Option<i64>,
+    pub b_segment_ms: Option<i64>,
+    pub cumulative_delta_ms: Option<i64>,
+    pub segment_delta_ms: Option<i64>,
+    pub only_in: Option<&'static str>,
+}
+
+/// Two runs on the same map compared checkpoint by checkpoint. When either
+/// run has no splits stored, `checkpoints` is empty and only the totals are
+/// compared.
+#[derive(Serialize, Clone, Debug)]
+pub struct RunComparison {
+    pub a: RunDetail,
+    pub b: RunDetail,
+    pub checkpoints: Vec<CheckpointComparison>,
+    pub time_delta_ms: Option<i64>,
+    pub fails_delta: i64,
+}
+
+// Cumulative and segment time into each checkpoint, plus the finish, with
+// the checkpoint each segment starts from
+fn segments(detail: &RunDetail) -> Vec<(i64, Option<String>, i64, i64, i64)> {
+    let mut splits: Vec<&Split> = detail.splits.iter().collect();
+    splits.sort_by_key(|s| s.checkpoint);
+    let (mut from, mut previous) = (0, 0);
+    let mut segments = Vec::new();
+    for split in splits {
+        let name = split.name.clone();
+        segments.push((
+            split.checkpoint,
+            name,
+            split.time_ms,
+            split.time_ms - previous,
+            from,
+        ));
+        from = split.checkpoint;
+        previous = split.time_ms;
+    }
+    if let Some(time_ms) = detail.run.time_ms.filter(|_| detail.run.completed) {
+        segments.push((FINISH as i64, None, time_ms, time_ms - previous, from));
+    }
+    segments
+}
+
+pub fn compare(conn: &Connection, run_a: i64, run_b: i64) -> Result<RunComparison, String> {
+    let get = |id: i64| -> Result<RunDetail, String> {
+        parkour_core::db::get_run(conn, id)?.ok_or_else(|| format!("Run {} not found", id))
+    };
+    let (a, b) = (get(run_a)?, get(run_b)?);
+    if names::name_key(&a.run.map) != names::name_key(&b.run.map) {
+        return Err(format!(
+            "Runs {} and {} are on different maps ({} and {})",
+            run_a, run_b, a.run.map, b.run.map
+        ));
+    }
+    let time = |d: &RunDetail| d.run.time_ms.filter(|_| d.run.completed);
+    let time_delta_ms = time(&b).zip(time(&a)).map(|(b, a)| b - a);
+
+    let mut checkpoints = Vec::new();
+    if !a.splits.is_empty() && !b.splits.is_empty() {
+        let (sa, sb) = (segments(&a), segments(&b));
+        let mut ids: Vec<i64> = sa.iter().chain(&sb).map(|s| s.0).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        for id in ids {
+            let ea = sa.iter().find(|s| s.0 == id);
+            let eb = sb.iter().find(|s| s.0 == id);
+            let both = ea.zip(eb);
+            checkpoints.push(CheckpointComparison {
+                checkpoint: id,
+                name: ea.or(eb).and_then(|s| s.1.clone()),
+                a_cumulative_ms: ea.map(|s| s.2),
+                b_cumulative_ms: eb.map(|s| s.2),
+                a_segment_ms: ea.map(|s| s.3),
+                b_segment_ms: eb.map(|s| s.3),
+                cumulative_delta_ms: both.map(|(a, b)| b.2 - a.2),
+                segment_delta_ms: both.filter(|(a, b)| a.4 == b.4).map(|(a, b)| b.3 - a.3),
+                only_in: match (ea, eb) {
+                    (Some(_), None) => Some("a"),
+                    (None, Some(_)) => Some("b"),
+                    _ => None,
+                },
+            });
+        }
+    }
+    Ok(RunComparison {
+        fails_delta: b.run.fails - a.run.fails,
+        a,
+        b,
+        checkpoints,
+        time_delta_ms,
+    })
+}
+
+#[tauri::command]
+pub fn compare_runs(run_a: i64, run_b: i64, db: State<'_, Db>) -> Result<RunComparison, String> {
+    let conn = db.conn()?;
+    compare(&conn, run_a, run_b)
+}
@@ -0,0 +1,166 @@
+use crate::db::{self, Db};
+use crate::events;
+use crate::lifecycle;
+use crate::lines::Capped;
+use crate::live::{source_field, CurrentRun, Live, MAIN};
+use crate::timestamps::LOCAL_FORMAT;
+use parkour_core::log_line::parse_line;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+// Only a line that is exactly this counts; chat lines always carry a prefix.
+const REPORT_HEADER: &str = "---- Minecraft Crash Report ----";
+const MAX_REPORT_BYTES: usize = 1024 * 1024;
+
+/// `{"offset": 20480, "summary": "Unexpected error: java.lang.NullPointerException", "abandoned_run": {...}}`
+///
+/// `offset` is where the crash output starts in the log. `abandoned_run` is
+/// the run that was in progress, if any. Crashes found while replaying a
+/// saved log carry `"replayed": true`, and those of an instance log its id
+/// as `source`.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct CrashPayload {
+    pub offset: u64,
+    pub summary: String,
+    pub abandoned_run: Option<CurrentRun>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub replayed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Crash {
+    pub offset: u64,
+    pub summary: String,
+    pub report: String,
+}
+
+#[derive(Default)]
+pub(crate) struct Tracker {
+    last: Option<Crash>,
+    // Still appending to `last` because the report is being written
+    collecting: bool,
+}
+
+// One tracker per watched source
+#[derive(Default)]
+pub struct CrashState(Mutex<BTreeMap<String, Tracker>>);
+
+// The description and the exception right after it, e.g.
+// "Unexpected error: java.lang.NullPointerException: ...".
+fn summarize(report: &str) -> Option<String> {
+    let mut rest = report
+        .lines()
+        .skip_while(|l| !l.starts_with("Description: "));
+    let description = rest.next()?.trim_start_matches("Description: ").trim();
+    let exception = rest.map(str::trim).find(|l| !l.is_empty());
+    Some(match exception {
+        Some(exception) => format!("{}: {}", description, exception),
+        None => description.to_string(),
+    })
+}
+
+fn on_crash(app: &AppHandle, source: &str, offset: u64, summary: String) {
+    let mut abandoned_run = None;
+    app.state::<Live>()
+        .update_source(source, |s| abandoned_run = s.current_run.take());
+    lifecycle::end_session(app, source);
+    // An instance's session is stored when it's imported, with its own end
+    if source == MAIN {
+        let ended_at = chrono::Local::now().format(LOCAL_FORMAT).to_string();
+        let db = app.state::<Db>();
+        let ended = db.conn().and_then(|conn| match db::active_session(&conn)? {
+            Some(id) => db::end_session(&conn, id, &ended_at, "crash"),
+            None => Ok(()),
+        });
+        if let Err(e) = ended {
+            eprintln!("{}", e);
+        }
+    }
+    events::emit_all(
+        app,
+        events::GAME_CRASHED,
+        &CrashPayload {
+            offset,
+            summary,
+            abandoned_run,
+            replayed: false,
+            source: source_field(source),
+        },
+    );
+}
+
+impl Tracker {
+    // Offset and summary of a crash that starts in `chunk`
+    pub(crate) fn scan(&mut self, chunk: &Capped) -> Option<(u64, String)> {
+        if chunk.starts_log() {
+            self.collecting = false;
+        }
+        let mut detected = None;
+        for line in chunk.lines() {
+            if self.collecting {
+                if let Some(crash) = self.last.as_mut() {
+                    if crash.report.len() < MAX_REPORT_BYTES {
+                        crash.report.push_str(line.text);
+                        crash.report.push('\n');
+                    }
+                }
+                continue;
+            }
+            let summary = if line.text.trim() == REPORT_HEADER {
+                "Minecraft crash report".to_string()
+            } else {
+                let parsed = parse_line(line.start as u64, line.text);
+                if parsed.level.as_deref() != Some("FATAL") {
+                    continue;
+                }
+                parsed.message
+            };
+            self.collecting = true;
+            self.last = Some(Crash {
+                offset: line.start as u64,
+                summary,
+                report: format!("{}\n", line.text),
+            });
+            detected = Some(line.start as u64);
+        }
+        let offset = detected?;
+        let crash = self.last.as_mut()?;
+        if let Some(summary) = summarize(&crash.report) {
+            crash.summary = summary;
+        }
+        Some((offset, crash.summary.clone()))
+    }
+}
+
+/// Looks for the start of a crash in newly read log text of `source`: a crash report header or a FATAL-level
+/// entry. Everything from there on is kept as the report until the log
+/// starts over.
+pub fn scan(app: &AppHandle, source: &str, chunk: &Capped) {
+    let state = app.state::<CrashState>();
+    let detected = match state.0.lock() {
+        Ok(mut trackers) => trackers.entry(source.to_string()).or_default().scan(chunk),
+        Err(_) => return,
+    };
+    if let Some((offset, summary)) = detected {
+        on_crash(app, source, offset, summary);
+    }
+}
+
+/// Drops what was tracked for a source that's no longer watched.
+pub fn forget(app: &AppHandle, source: &str) {
+    if let Ok(mut trackers) = app.state::<CrashState>().0.lock() {
+        trackers.remove(source);
+    }
+}
+
+/// The last crash in the main log, or in the instance log `source`.
+#[tauri::command]
+pub fn get_last_crash(source: Option<String>, state: State<'_, CrashState>) -> Option<Crash> {
+    let source = source.unwrap_or_else(|| MAIN.to_string());
+    state.0.lock().ok()?.get(&source)?.last.clone()
+}
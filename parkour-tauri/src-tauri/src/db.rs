@@ -0,0 +1,133 @@
+use crate::live::Live;
+use crate::names;
+use crate::timestamps::LOCAL_FORMAT;
+pub use parkour_core::db::*;
+pub use parkour_core::model::*;
+use parkour_core::store::RunStore;
+use rusqlite::Connection;
+use tauri::{AppHandle, Manager, State};
+
+#[tauri::command]
+pub fn query_runs(filter: Option<RunQuery>, db: State<'_, Db>) -> Result<RunPage, String> {
+    db.query_runs(&filter.unwrap_or_default())
+}
+
+/// One stored run with its splits and tags.
+#[tauri::command]
+pub fn get_run(run_id: i64, db: State<'_, Db>) -> Result<RunDetail, String> {
+    db.get_run(run_id)?.ok_or_else(|| format!("Run {} not found", run_id))
+}
+
+#[tauri::command]
+pub fn get_sessions(db: State<'_, Db>) -> Result<Vec<Session>, String> {
+    db.list_sessions()
+}
+
+/// Gold splits on `map` and what they add up to, one entry per checkpoint
+/// route; see [`sum_of_best`].
+#[tauri::command]
+pub fn get_sum_of_best(
+    map: String,
+    username: Option<String>,
+    db: State<'_, Db>,
+) -> Result<Vec<SumOfBest>, String> {
+    let conn = db.conn()?;
+    sum_of_best(&conn, &map, username.as_deref())
+}
+
+/// Every player's best time on `map`, fastest first, for a server's logs
+/// where many players have runs; see [`player_leaderboard`].
+#[tauri::command]
+pub fn get_player_leaderboard(
+    map: String,
+    db: State<'_, Db>,
+) -> Result<Vec<LeaderboardEntry>, String> {
+    let conn = db.conn()?;
+    player_leaderboard(&conn, &map)
+}
+
+// Runs the watched log may still be writing are left alone: the one in
+// progress, and any in the session being played
+fn check_editable(app: &AppHandle, conn: &Connection, run_id: i64) -> Result<(), String> {
+    let Some(RunDetail { run, .. }) = parkour_core::db::get_run(conn, run_id)? else {
+        return Err(format!("Run {} not found", run_id));
+    };
+    let live = app.state::<Live>().snapshot();
+    let current = live.current_run.as_ref().is_some_and(|current| {
+        current.started_at == run.started_at
+            && current
+                .map
+                .as_deref()
+                .is_some_and(|map| names::name_key(map) == names::name_key(&run.map))
+    });
+    if current || (live.watching && in_active_session(conn, &run)?) {
+        return Err(format!(
+            "Run {} belongs to the live session; edit it once the session has ended",
+            run_id
+        ));
+    }
+    Ok(())
+}
+
+/// Changes a run's map, time or whether it counts; see [`RunEdit`]. PBs,
+/// golds and stats leave excluded runs out from then on.
+#[tauri::command]
+pub fn update_run(run_id: i64, edit: RunEdit, app: AppHandle) -> Result<Run, String> {
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    check_editable(&app, &conn, run_id)?;
+    let edited_at = chrono::Local::now().format(LOCAL_FORMAT).to_string();
+    let run = parkour_core::db::update_run(&conn, run_id, &edit, &edited_at)?
+        .ok_or_else(|| format!("Run {} not found", run_id))?;
+    drop(conn);
+    crate::text_output::refresh(&app);
+    Ok(run)
+}
+
+#[tauri::command]
+pub fn delete_run(run_id: i64, app: AppHandle) -> Result<(), String> {
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    check_editable(&app, &conn, run_id)?;
+    parkour_core::db::delete_run(&conn, run_id)?;
+    drop(conn);
+    crate::text_output::refresh(&app);
+    Ok(())
+}
+
+/// Every merged map name, grouped by the name it was merged into.
+#[tauri::command]
+pub fn list_map_aliases(db: State<'_, Db>) -> Result<Vec<MapAlias>, String> {
+    let conn = db.conn()?;
+    map_aliases(&conn)
+}
+
+/// Stores the runs on `aliases` under `canonical`, now and when they're
+/// logged later; see [`merge_maps`]. PBs, golds and streaks take in the
+/// merged runs as one history from then on.
+#[tauri::command]
+pub fn merge_maps(
+    canonical: String,
+    aliases: Vec<String>,
+    app: AppHandle,
+) -> Result<Vec<MapAlias>, String> {
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    let created_at = chrono::Local::now().format(LOCAL_FORMAT).to_string();
+    let aliases = parkour_core::db::merge_maps(&conn, &canonical, &aliases, &created_at)?;
+    drop(conn);
+    crate::text_output::refresh(&app);
+    Ok(aliases)
+}
+
+#[tauri::command]
+pub fn split_alias(alias: String, app: AppHandle) -> Result<(), String> {
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    if !parkour_core::db::split_alias(&conn, &alias)? {
+        return Err(format!("{} isn't merged into another map", alias));
+    }
+    drop(conn);
+    crate::text_output::refresh(&app);
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::settings::WatcherSettings;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const WINDOW: usize = 16;
+
+// Follows how often the log is being written: bursts of writes (an active
+// run) pull the debounce down to the floor, quiet periods let it drift back
+// up to the ceiling.
+#[derive(Default)]
+pub struct AdaptiveDebounce {
+    arrivals: VecDeque<Instant>,
+}
+
+impl AdaptiveDebounce {
+    pub fn observe(&mut self, at: Instant) {
+        if self.arrivals.len() >= WINDOW {
+            self.arrivals.pop_front();
+        }
+        self.arrivals.push_back(at);
+    }
+
+    pub fn effective(&self, settings: &WatcherSettings, now: Instant) -> Duration {
+        let fixed = Duration::from_millis(settings.debounce_ms);
+        if !settings.adaptive_debounce {
+            return fixed;
+        }
+        let floor = Duration::from_millis(settings.min_debounce_ms);
+        let ceiling = Duration::from_millis(settings.max_debounce_ms);
+        let Some(first) = self.arrivals.front() else {
+            return fixed.clamp(floor, ceiling);
+        };
+        // The time since the last write counts as one more gap, so silence
+        // relaxes the debounce even before the next write arrives
+        let mean = now.duration_since(*first) / self.arrivals.len() as u32;
+        mean.clamp(floor, ceiling)
+    }
+}
@@ -0,0 +1,117 @@
+use crate::db::parse_date;
+use crate::events;
+use crate::notifications::focus_main_window;
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+pub const SCHEME: &str = "parkour-analyzer";
+
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(tag = "view", rename_all = "snake_case")]
+pub enum NavigationIntent {
+    Map { map: String },
+    Run { run_id: i64 },
+    Session { date: String },
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct NavigationError {
+    pub url: String,
+    pub message: String,
+}
+
+// The intent a cold launch was opened with, held until the frontend has
+// mounted and asks for it.
+#[derive(Default)]
+pub struct PendingNavigation(Mutex<Option<NavigationIntent>>);
+
+fn segments(url: &str) -> Result<Vec<String>, String> {
+    let rest = url
+        .strip_prefix(SCHEME)
+        .and_then(|r| r.strip_prefix(':'))
+        .ok_or_else(|| format!("Not a {}:// link", SCHEME))?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    rest.split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            percent_decode_str(s)
+                .decode_utf8()
+                .map(|s| s.into_owned())
+                .map_err(|_| format!("Invalid UTF-8 in link segment '{}'", s))
+        })
+        .collect()
+}
+
+pub fn parse_url(url: &str) -> Result<NavigationIntent, String> {
+    let segments = segments(url)?;
+    match segments.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["map", map] => Ok(NavigationIntent::Map { map: map.into() }),
+        ["run", id] => id
+            .parse()
+            .map(|run_id| NavigationIntent::Run { run_id })
+            .map_err(|_| format!("Invalid run id '{}'", id)),
+        ["session", date] => parse_date("date", date).map(|d| NavigationIntent::Session {
+            date: d.to_string(),
+        }),
+        [] => Err("Link has no destination".into()),
+        _ => Err(format!("Unknown link destination '{}'", segments.join("/"))),
+    }
+}
+
+pub fn handle_url(app: &AppHandle, url: &str) -> Option<NavigationIntent> {
+    focus_main_window(app);
+    match parse_url(url) {
+        Ok(intent) => {
+            events::emit(app, events::NAVIGATE, &intent);
+            Some(intent)
+        }
+        Err(message) => {
+            events::emit(
+                app,
+                events::NAVIGATE_ERROR,
+                &NavigationError {
+                    url: url.to_string(),
+                    message,
+                },
+            );
+            None
+        }
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    // Installed builds register through the bundle; dev builds need it at runtime
+    #[cfg(all(debug_assertions, any(windows, target_os = "linux")))]
+    if let Err(e) = app.deep_link().register_all() {
+        eprintln!("Failed to register {}:// links: {}", SCHEME, e);
+    }
+
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle_url(&handle, url.as_str());
+        }
+    });
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => {
+            let mut intent = None;
+            for url in urls {
+                intent = handle_url(app, url.as_str()).or(intent);
+            }
+            if let Ok(mut pending) = app.state::<PendingNavigation>().0.lock() {
+                *pending = intent;
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to read launch link: {}", e),
+    }
+}
+
+#[tauri::command]
+pub fn take_pending_navigation(pending: State<'_, PendingNavigation>) -> Option<NavigationIntent> {
+    pending.0.lock().ok().and_then(|mut p| p.take())
+}
@@ -0,0 +1,297 @@
+use crate::db::{self, Db, Split};
+use crate::events;
+use crate::lifecycle;
+use crate::lines::Capped;
+use crate::live::{is_fresh, now_ms, source_field, CurrentRun, Live};
+use crate::metrics;
+use crate::settings::SettingsStore;
+use crate::timestamps::LOCAL_FORMAT;
+use parkour_core::definitions::{chat_message, Definitions, RunDefinition, Step};
+use parkour_core::duels::{EventKind, PatternSpec, Patterns, PlayerEvent};
+use parkour_core::log_line::{parse_line, seconds_of_day};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+/// `{"offset": 4096, "definition": "Tower Parkour", "step": "reached", "cp": 1, "time": "0:12.000", "type": "checkpoint", "gold": true, "practice": false}`
+///
+/// A step of a run timed by a [`RunDefinition`]. `definition` is the title
+/// its runs are stored under; see [`Step`] for the rest.
+///
+/// Checkpoints and finishes carry `gold`, true when the segment just done
+/// was faster than in any stored run. `practice` is true once the server's
+/// practice pattern matched during the run; practice runs get no golds.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct DefinedRunPayload {
+    pub offset: u64,
+    pub definition: String,
+    #[serde(flatten)]
+    pub step: Step,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gold: Option<bool>,
+    pub practice: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Default)]
+pub(crate) struct Runner {
+    // What `state` was compiled from, to notice edits
+    definitions: Vec<RunDefinition>,
+    state: Definitions,
+    // The server's patterns, for its practice pattern, and what they were
+    // compiled from
+    spec: PatternSpec,
+    patterns: Patterns,
+    // Whether the run going is a practice run
+    practice: bool,
+}
+
+#[derive(Default)]
+pub struct DefinedRuns(Mutex<BTreeMap<String, Runner>>);
+
+impl Runner {
+    fn scan(
+        &mut self,
+        definitions: &[RunDefinition],
+        spec: &PatternSpec,
+        chunk: &Capped,
+    ) -> Vec<(DefinedRunPayload, bool)> {
+        let offset = chunk.offset;
+        if self.definitions != definitions {
+            // Saved definitions were validated, so this only fails on a
+            // hand-edited settings file
+            self.state = Definitions::compile(definitions).unwrap_or_default();
+            self.definitions = definitions.to_vec();
+        }
+        if self.spec != *spec {
+            // Like the definitions, only a hand-edited file fails here
+            self.patterns = Patterns::from_spec(spec).unwrap_or_default();
+            self.spec = spec.clone();
+        }
+        if chunk.starts_log() {
+            self.state.clear();
+            self.practice = false;
+        }
+        let mut steps = Vec::new();
+        if self.state.is_empty() {
+            return steps;
+        }
+        for line in chunk.lines() {
+            let Some(message) = chat_message(line.text) else {
+                continue;
+            };
+            if self.state.running().is_some() && self.patterns.is_practice(&message) {
+                self.practice = true;
+            }
+            let time = parse_line(line.start as u64, line.text).time;
+            let before = self.state.running().map(str::to_string);
+            let Some(step) = self
+                .state
+                .message(&message, time.as_deref().and_then(seconds_of_day))
+            else {
+                continue;
+            };
+            if let Step::Started { .. } = step {
+                self.practice = false;
+            }
+            let definition = self.state.running().map(str::to_string).or(before);
+            let payload = DefinedRunPayload {
+                offset: line.start as u64,
+                definition: definition.unwrap_or_default(),
+                step,
+                gold: None,
+                practice: self.practice,
+                source: None,
+            };
+            steps.push((payload, is_fresh(time.as_deref(), offset)));
+        }
+        steps
+    }
+}
+
+// Whether the segment `event` ends beats the golds of the live run's map;
+// checked before the split is added to the run
+fn gold(app: &AppHandle, source: &str, event: &PlayerEvent) -> Option<bool> {
+    let run = app.state::<Live>().source(source)?.current_run?;
+    let map = run.map.as_deref()?;
+    let passed: Vec<i64> = run.splits.iter().map(|s| s.checkpoint).collect();
+    let segment_ms = event.time_ms()? - run.splits.last().map_or(0, |s| s.time_ms);
+    let db = app.state::<Db>();
+    let conn = db.conn().ok()?;
+    let routes = db::sum_of_best(&conn, map, run.username.as_deref()).ok()?;
+    Some(db::is_gold(&routes, &passed, event.cp as i64, segment_ms))
+}
+
+// Keeps the live run in step as the built-in runs do; old lines only end
+// the run they came after
+fn apply(app: &AppHandle, source: &str, payload: &DefinedRunPayload, fresh: bool) {
+    if !fresh {
+        if let Step::Started { .. } = payload.step {
+            app.state::<Live>()
+                .update_source(source, |s| s.current_run = None);
+        }
+        return;
+    }
+    let mut started = None;
+    let mut split = None;
+    let mut finished = None;
+    let mut reset = None;
+    app.state::<Live>()
+        .update_source(source, |s| match &payload.step {
+            Step::Started { title, .. } => {
+                let run = CurrentRun {
+                    map: Some(title.clone()),
+                    username: None,
+                    started_at: chrono::Local::now().format(LOCAL_FORMAT).to_string(),
+                    started_ms: Some(now_ms()),
+                    splits: Vec::new(),
+                    practice: false,
+                    paused_ms: 0,
+                    paused_since_ms: None,
+                };
+                started = Some(run.clone());
+                s.current_run = Some(run);
+            }
+            Step::Reached(event) if event.kind == EventKind::Checkpoint => {
+                if let (Some(run), Some(time_ms)) = (s.current_run.as_mut(), event.time_ms()) {
+                    run.practice = payload.practice;
+                    run.splits.push(Split {
+                        checkpoint: event.cp as i64,
+                        name: None,
+                        time_ms,
+                    });
+                    split = Some(run.clone());
+                }
+            }
+            Step::Reached(event) => {
+                let run = s.current_run.take().map(|mut run| {
+                    run.practice = payload.practice;
+                    run
+                });
+                finished = run.zip(event.time_ms());
+            }
+            Step::Reset => reset = s.current_run.take(),
+        });
+    if let Some(run) = started {
+        lifecycle::started(app, source, &run);
+    }
+    if let Some(run) = split {
+        lifecycle::split(app, source, &run);
+    }
+    if let Some((run, time_ms)) = finished {
+        lifecycle::finished(app, source, &run, time_ms);
+    }
+    if let Some(run) = reset {
+        lifecycle::reset(app, source, run);
+    }
+}
+
+/// Runs the enabled run definitions over newly read log text of `source`.
+pub fn scan(app: &AppHandle, source: &str, chunk: &Capped) {
+    let definitions = app.state::<SettingsStore>().get().run_definitions;
+    let spec = app
+        .state::<Live>()
+        .source(source)
+        .and_then(|s| s.server_settings)
+        .map(|s| s.patterns)
+        .unwrap_or_default();
+    let (steps, practice) = match app.state::<DefinedRuns>().0.lock() {
+        Ok(mut runners) => {
+            let runner = runners.entry(source.to_string()).or_default();
+            (runner.scan(&definitions, &spec, chunk), runner.practice)
+        }
+        Err(_) => return,
+    };
+    for (mut payload, fresh) in steps {
+        if let Step::Reached(event) = &payload.step {
+            if event.time_ms().is_none() {
+                metrics::record(app, |m| &m.parse_errors, 1);
+            }
+            payload.gold = gold(app, source, event).filter(|_| !payload.practice);
+        }
+        payload.source = source_field(source);
+        apply(app, source, &payload, fresh);
+        events::emit_all(app, events::DEFINED_RUN, &payload);
+    }
+    // A practice notice after the last step still shows on the live run
+    if practice {
+        app.state::<Live>().update_source(source, |s| {
+            if let Some(run) = s.current_run.as_mut() {
+                run.practice = true;
+            }
+        });
+    }
+}
+
+/// Drops what was tracked for a source that's no longer watched.
+pub fn forget(app: &AppHandle, source: &str) {
+    if let Ok(mut runners) = app.state::<DefinedRuns>().0.lock() {
+        runners.remove(source);
+    }
+}
+
+fn save(
+    store: &SettingsStore,
+    edit: impl FnOnce(&mut Vec<RunDefinition>),
+) -> Result<Vec<RunDefinition>, String> {
+    let mut settings = store.get();
+    edit(&mut settings.run_definitions);
+    crate::settings::validate(&settings)?;
+    store.replace(settings.clone())?;
+    Ok(settings.run_definitions)
+}
+
+/// Every run definition, in precedence order.
+#[tauri::command]
+pub fn get_run_definitions(store: State<'_, SettingsStore>) -> Vec<RunDefinition> {
+    store.get().run_definitions
+}
+
+/// Adds `definition`, or replaces the one named `previous_name` (its own
+/// name by default) in place.
+#[tauri::command]
+pub fn save_run_definition(
+    definition: RunDefinition,
+    previous_name: Option<String>,
+    store: State<'_, SettingsStore>,
+) -> Result<Vec<RunDefinition>, String> {
+    let name = previous_name.unwrap_or_else(|| definition.name.clone());
+    save(&store, |definitions| {
+        match definitions.iter_mut().find(|d| d.name == name) {
+            Some(existing) => *existing = definition,
+            None => definitions.push(definition),
+        }
+    })
+}
+
+#[tauri::command]
+pub fn delete_run_definition(
+    name: String,
+    store: State<'_, SettingsStore>,
+) -> Result<Vec<RunDefinition>, String> {
+    if !store.get().run_definitions.iter().any(|d| d.name == name) {
+        return Err(format!("No run definition named {}", name));
+    }
+    save(&store, |definitions| definitions.retain(|d| d.name != name))
+}
+
+/// Puts the definitions in the order of `names`, which decides which one
+/// wins when several could start on the same line. Definitions left out
+/// keep their order after the named ones.
+#[tauri::command]
+pub fn reorder_run_definitions(
+    names: Vec<String>,
+    store: State<'_, SettingsStore>,
+) -> Result<Vec<RunDefinition>, String> {
+    save(&store, |definitions| {
+        definitions.sort_by_key(|d| {
+            names
+                .iter()
+                .position(|n| *n == d.name)
+                .unwrap_or(names.len())
+        });
+    })
+}
@@ -0,0 +1,729 @@
+use crate::analyze::FileAnalyzedPayload;
+use crate::crash::CrashPayload;
+use crate::db::{Goal, Marker};
+use crate::definitions::DefinedRunPayload;
+use crate::deep_link::{NavigationError, NavigationIntent};
+use crate::hotkeys::{RunResetPayload, TimerPausedPayload};
+use crate::instances::SourceRemovedPayload;
+use crate::line_filter::LinesFilteredPayload;
+use crate::lines::TruncatedLine;
+use crate::parser::{CheckpointReachedPayload, RunFinishedPayload, RunStartedPayload};
+use crate::pb::PbComparisonPayload;
+use crate::perf;
+use crate::permissions;
+use crate::rate_limit;
+use crate::replay::ReplayFinished;
+use crate::servers::{ContextChangedPayload, ParserSelectedPayload};
+use crate::timer::{SplitRecordedPayload, TimerTickPayload};
+use crate::webhooks::WebhookErrorPayload;
+use crate::ws::WsHub;
+use parkour_core::log_line::LogLine;
+pub use parkour_core::schema::PROTOCOL_VERSION;
+use parkour_core::schema::SchemaSet;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+pub const LOG_UPDATE: &str = "log-update";
+pub const LOG_LOCATION: &str = "log-location";
+pub const LOG_ERROR: &str = "log-error";
+pub const LOG_ROTATED: &str = "log-rotated";
+pub const LINES_FILTERED: &str = "lines-filtered";
+pub const WATCHER_PAUSED: &str = "watcher-paused";
+pub const MANUAL_MARKER: &str = "manual-marker";
+pub const NAVIGATE: &str = "navigate";
+pub const NAVIGATE_ERROR: &str = "navigate-error";
+pub const WEBHOOK_ERROR: &str = "webhook-error";
+pub const LIVESPLIT_ERROR: &str = "livesplit-error";
+pub const PRESENCE_ERROR: &str = "presence-error";
+pub const TRAY_ERROR: &str = "tray-error";
+pub const REPLAY_CHUNK: &str = "replay-chunk";
+pub const REPLAY_LINE: &str = "replay-line";
+pub const REPLAY_FINISHED: &str = "replay-finished";
+pub const GAME_CRASHED: &str = "game-crashed";
+pub const PARSER_SELECTED: &str = "parser-selected";
+pub const CONTEXT_CHANGED: &str = "context-changed";
+pub const DEFINED_RUN: &str = "defined-run";
+pub const GOAL_ACHIEVED: &str = "goal-achieved";
+pub const FILE_ANALYZED: &str = "file-analyzed";
+pub const SOURCE_REMOVED: &str = "source-removed";
+pub const RUN_STARTED: &str = "run-started";
+pub const CHECKPOINT_REACHED: &str = "checkpoint-reached";
+pub const RUN_FINISHED: &str = "run-finished";
+pub const TIMER_TICK: &str = "timer-tick";
+pub const SPLIT_RECORDED: &str = "split-recorded";
+pub const PB_COMPARISON: &str = "pb-comparison";
+pub const RUN_RESET: &str = "run-reset";
+pub const TIMER_PAUSED: &str = "timer-paused";
+
+/// Every payload below is emitted with an extra `seq` field: one counter
+/// shared by all event kinds, so a gap in `seq` means an event was missed.
+/// Object payloads also carry `protocol`, the payload format version
+/// ([`PROTOCOL_VERSION`]); `get_event_schema` describes them all.
+///
+/// `{"content": "...", "offset": 0, "end": 5120, "path": "/home/me/.minecraft/logs/latest.log"}`
+///
+/// `content` covers the byte range `offset..end` of the file. A frontend whose
+/// last `end` doesn't match the next `offset` has missed an update and should
+/// call `sync`.
+///
+/// Lines longer than `watcher.max_line_bytes` are cut short in `content` and
+/// listed in `truncated`, which is omitted when empty; `offset` and `end` are
+/// unaffected.
+///
+/// With `watcher.line_filters` set, `content` only holds the matching lines
+/// of the range and `dropped` counts the others; a chunk with none left is
+/// still sent, so `offset` and `end` keep lining up.
+///
+/// Updates fed by `start_log_replay` carry `"replayed": true`, and `path` is
+/// the replayed file rather than the watched log.
+///
+/// Updates of an instance log, see `watcher.instances`, carry its id as
+/// `source`, and their offsets are in that log. The same goes for every
+/// other event a watched log's lines lead to.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LogUpdatePayload {
+    pub content: String,
+    pub offset: u64,
+    pub end: u64,
+    pub path: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub truncated: Vec<TruncatedLine>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dropped: Option<usize>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub replayed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogErrorKind {
+    LogNotFound,
+    PermissionDenied,
+    WatchSetupFailed,
+    ReadFailed,
+    Utf8Decode,
+    Rotation,
+    // A remote log's server couldn't be reached or dropped the connection
+    ConnectionFailed,
+    AuthFailed,
+    HostKeyRejected,
+    Internal,
+}
+
+/// `{"kind": "permission_denied", "message": "Failed to read log: Permission denied (os error 13)", "path": "/home/me/.minecraft/logs/latest.log", "os_code": 13}`
+///
+/// `path` and `os_code` are omitted when they don't apply. For a remote log
+/// `path` is its `sftp://` URL.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LogErrorPayload {
+    pub kind: LogErrorKind,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_code: Option<i32>,
+    // Suggested fix, set for permission problems
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    // Instance log the error is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl LogErrorPayload {
+    pub fn new(kind: LogErrorKind, message: impl Into<String>) -> Self {
+        LogErrorPayload {
+            kind,
+            message: message.into(),
+            path: None,
+            os_code: None,
+            hint: None,
+            source: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_string_lossy().to_string());
+        self
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = crate::live::source_field(source);
+        self
+    }
+
+    pub fn io(context: &str, path: &Path, err: &io::Error) -> Self {
+        let kind = match err.kind() {
+            io::ErrorKind::NotFound => LogErrorKind::LogNotFound,
+            io::ErrorKind::PermissionDenied => LogErrorKind::PermissionDenied,
+            // read_to_string reports invalid UTF-8 as InvalidData
+            io::ErrorKind::InvalidData => LogErrorKind::Utf8Decode,
+            _ => LogErrorKind::ReadFailed,
+        };
+        LogErrorPayload {
+            kind,
+            message: format!("{}: {}", context, err),
+            path: Some(path.to_string_lossy().to_string()),
+            os_code: err.raw_os_error(),
+            hint: (kind == LogErrorKind::PermissionDenied).then(|| permissions::hint(path)),
+            source: None,
+        }
+    }
+
+    pub fn watch(context: &str, path: &Path, err: &notify::Error) -> Self {
+        let (kind, os_code) = match &err.kind {
+            notify::ErrorKind::Io(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                (LogErrorKind::PermissionDenied, e.raw_os_error())
+            }
+            notify::ErrorKind::Io(e) => (LogErrorKind::WatchSetupFailed, e.raw_os_error()),
+            notify::ErrorKind::PathNotFound => (LogErrorKind::LogNotFound, None),
+            _ => (LogErrorKind::WatchSetupFailed, None),
+        };
+        LogErrorPayload {
+            kind,
+            message: format!("{}: {}", context, err),
+            path: Some(path.to_string_lossy().to_string()),
+            os_code,
+            hint: (kind == LogErrorKind::PermissionDenied).then(|| permissions::hint(path)),
+            source: None,
+        }
+    }
+}
+
+impl std::fmt::Display for LogErrorPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationSource {
+    AutoDetected,
+    User,
+    Remote,
+}
+
+/// `{"path": "/home/me/.minecraft/logs/latest.log", "source": "auto_detected"}`
+///
+/// An instance log starting to be watched is announced with its id as
+/// `instance`, and `source` is `user`.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LogLocationPayload {
+    pub path: String,
+    pub source: LocationSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationReason {
+    // The game archived the log and started a new one
+    Replaced,
+    Truncated,
+}
+
+/// `{"path": "/home/me/.minecraft/logs/latest.log", "reason": "replaced", "previous_end": 48213}`
+///
+/// The watched log starting over, `previous_end` bytes into the one read
+/// before. The `log-update` that follows has offset 0 and the new file from
+/// its start, to replace what was shown.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LogRotatedPayload {
+    pub path: String,
+    pub reason: RotationReason,
+    pub previous_end: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// `{"paused": true}`
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct WatcherPausedPayload {
+    pub paused: bool,
+}
+
+/// `{"message": "Failed to connect to LiveSplit Server: connection refused"}`
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct MessagePayload {
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RecordedEvent {
+    pub seq: u64,
+    pub event: &'static str,
+    pub payload: Value,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EventsSince {
+    pub events: Vec<RecordedEvent>,
+    pub seq: u64,
+    // Events after the requested seq were already dropped from the buffer
+    pub resync_required: bool,
+}
+
+struct Buffer {
+    seq: u64,
+    capacity: usize,
+    recent: VecDeque<RecordedEvent>,
+}
+
+pub struct EventLog(Mutex<Buffer>);
+
+impl EventLog {
+    pub fn new(capacity: usize) -> EventLog {
+        EventLog(Mutex::new(Buffer {
+            seq: 0,
+            capacity,
+            recent: VecDeque::new(),
+        }))
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.0.lock().map(|b| b.seq).unwrap_or(0)
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        if let Ok(mut buffer) = self.0.lock() {
+            buffer.capacity = capacity;
+            while buffer.recent.len() > capacity {
+                buffer.recent.pop_front();
+            }
+        }
+    }
+
+    fn record(&self, event: &'static str, mut payload: Value) -> Value {
+        let Ok(mut buffer) = self.0.lock() else {
+            return payload;
+        };
+        buffer.seq += 1;
+        if let Value::Object(fields) = &mut payload {
+            fields.insert("seq".into(), buffer.seq.into());
+        }
+        if buffer.recent.len() >= buffer.capacity {
+            buffer.recent.pop_front();
+        }
+        let seq = buffer.seq;
+        buffer.recent.push_back(RecordedEvent {
+            seq,
+            event,
+            payload: payload.clone(),
+        });
+        payload
+    }
+
+    pub fn since(&self, seq: u64) -> EventsSince {
+        let Ok(buffer) = self.0.lock() else {
+            return EventsSince {
+                events: Vec::new(),
+                seq,
+                resync_required: true,
+            };
+        };
+        let oldest = buffer.recent.front().map_or(buffer.seq + 1, |e| e.seq);
+        EventsSince {
+            events: buffer
+                .recent
+                .iter()
+                .filter(|e| e.seq > seq)
+                .cloned()
+                .collect(),
+            seq: buffer.seq,
+            resync_required: seq > buffer.seq || (seq < buffer.seq && seq + 1 < oldest),
+        }
+    }
+}
+
+fn sequenced<T: Serialize>(app: &AppHandle, event: &'static str, payload: &T) -> Value {
+    let value = perf::time(
+        app,
+        |p| &p.emit_serialize_us,
+        || serde_json::to_value(payload).unwrap_or(Value::Null),
+    );
+    match app.try_state::<EventLog>() {
+        Some(log) => log.record(event, value),
+        None => value,
+    }
+}
+
+pub fn emit<T: Serialize>(app: &AppHandle, event: &'static str, payload: &T) {
+    let payload = sequenced(app, event, payload);
+    rate_limit::emit(app, event, payload);
+}
+
+// Same as emit, but also forwarded to WebSocket clients, which aren't rate
+// limited.
+pub fn emit_all<T: Serialize>(app: &AppHandle, event: &'static str, payload: &T) {
+    let payload = sequenced(app, event, payload);
+    app.state::<WsHub>().broadcast(event, &payload);
+    rate_limit::emit(app, event, payload);
+}
+
+// For events too frequent to keep, like timer ticks: they skip the event
+// log and the rate limiter, so they can't push real events out of either.
+pub fn emit_transient<T: Serialize>(app: &AppHandle, event: &'static str, payload: &T) {
+    let payload = serde_json::to_value(payload).unwrap_or(Value::Null);
+    app.state::<WsHub>().broadcast(event, &payload);
+    crate::routing::emit(app, event, payload);
+}
+
+#[tauri::command]
+pub fn get_events_since(seq: u64, log: State<'_, EventLog>) -> EventsSince {
+    log.since(seq)
+}
+
+/// JSON Schema for every event payload above, keyed by event name, plus the
+/// shared run and log models.
+#[tauri::command]
+pub fn get_event_schema() -> Value {
+    SchemaSet::default()
+        .payload::<LogUpdatePayload>(LOG_UPDATE)
+        .payload::<LogLocationPayload>(LOG_LOCATION)
+        .payload::<LogErrorPayload>(LOG_ERROR)
+        .payload::<LogRotatedPayload>(LOG_ROTATED)
+        .payload::<LinesFilteredPayload>(LINES_FILTERED)
+        .payload::<WatcherPausedPayload>(WATCHER_PAUSED)
+        .payload::<Marker>(MANUAL_MARKER)
+        .payload::<NavigationIntent>(NAVIGATE)
+        .payload::<NavigationError>(NAVIGATE_ERROR)
+        .payload::<WebhookErrorPayload>(WEBHOOK_ERROR)
+        .payload::<MessagePayload>(LIVESPLIT_ERROR)
+        .payload::<MessagePayload>(PRESENCE_ERROR)
+        .payload::<MessagePayload>(TRAY_ERROR)
+        .payload::<LogUpdatePayload>(REPLAY_CHUNK)
+        .payload::<LogLine>(REPLAY_LINE)
+        .payload::<ReplayFinished>(REPLAY_FINISHED)
+        .payload::<CrashPayload>(GAME_CRASHED)
+        .payload::<ParserSelectedPayload>(PARSER_SELECTED)
+        .payload::<ContextChangedPayload>(CONTEXT_CHANGED)
+        .payload::<DefinedRunPayload>(DEFINED_RUN)
+        .payload::<Goal>(GOAL_ACHIEVED)
+        .payload::<FileAnalyzedPayload>(FILE_ANALYZED)
+        .payload::<SourceRemovedPayload>(SOURCE_REMOVED)
+        .payload::<RunStartedPayload>(RUN_STARTED)
+        .payload::<CheckpointReachedPayload>(CHECKPOINT_REACHED)
+        .payload::<RunFinishedPayload>(RUN_FINISHED)
+        .payload::<TimerTickPayload>(TIMER_TICK)
+        .payload::<SplitRecordedPayload>(SPLIT_RECORDED)
+        .payload::<PbComparisonPayload>(PB_COMPARISON)
+        .payload::<RunResetPayload>(RUN_RESET)
+        .payload::<TimerPausedPayload>(TIMER_PAUSED)
+        .models()
+        .finish(env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parkour_core::schema::check;
+    use serde_json::json;
+
+    fn matches(name: &str, payload: impl Serialize) {
+        let value = serde_json::to_value(payload).unwrap();
+        if let Err(e) = check(&get_event_schema(), name, &value) {
+            panic!("{} doesn't match its schema: {}\n{}", name, e, value);
+        }
+    }
+
+    #[test]
+    fn log_payloads_serialize_as_documented() {
+        let update = LogUpdatePayload {
+            content: "hi\n".to_string(),
+            offset: 1024,
+            end: 1027,
+            path: "/logs/latest.log".to_string(),
+            truncated: Vec::new(),
+            dropped: None,
+            replayed: false,
+            source: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({"content": "hi\n", "offset": 1024, "end": 1027, "path": "/logs/latest.log"})
+        );
+        let update = LogUpdatePayload {
+            truncated: vec![TruncatedLine {
+                offset: 1024,
+                length: 70000,
+            }],
+            dropped: Some(2),
+            replayed: true,
+            source: Some("prism:1.8.9".to_string()),
+            ..update
+        };
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({
+                "content": "hi\n",
+                "offset": 1024,
+                "end": 1027,
+                "path": "/logs/latest.log",
+                "truncated": [{"offset": 1024, "length": 70000}],
+                "dropped": 2,
+                "replayed": true,
+                "source": "prism:1.8.9"
+            })
+        );
+        let location = LogLocationPayload {
+            path: "/logs/latest.log".to_string(),
+            source: LocationSource::AutoDetected,
+            instance: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&location).unwrap(),
+            json!({"path": "/logs/latest.log", "source": "auto_detected"})
+        );
+        let error = LogErrorPayload::new(LogErrorKind::Internal, "Failed to start watcher");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({"kind": "internal", "message": "Failed to start watcher"})
+        );
+        let rotated = LogRotatedPayload {
+            path: "/logs/latest.log".to_string(),
+            reason: RotationReason::Replaced,
+            previous_end: 48213,
+            source: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&rotated).unwrap(),
+            json!({"path": "/logs/latest.log", "reason": "replaced", "previous_end": 48213})
+        );
+    }
+
+    #[test]
+    fn error_kinds_serialize_as_snake_case() {
+        let kinds = [
+            (LogErrorKind::LogNotFound, "log_not_found"),
+            (LogErrorKind::PermissionDenied, "permission_denied"),
+            (LogErrorKind::WatchSetupFailed, "watch_setup_failed"),
+            (LogErrorKind::ReadFailed, "read_failed"),
+            (LogErrorKind::Utf8Decode, "utf8_decode"),
+            (LogErrorKind::Rotation, "rotation"),
+            (LogErrorKind::ConnectionFailed, "connection_failed"),
+            (LogErrorKind::AuthFailed, "auth_failed"),
+            (LogErrorKind::HostKeyRejected, "host_key_rejected"),
+            (LogErrorKind::Internal, "internal"),
+        ];
+        for (kind, name) in kinds {
+            assert_eq!(serde_json::to_value(kind).unwrap(), json!(name));
+        }
+    }
+
+    #[test]
+    fn io_errors_carry_kind_path_and_code() {
+        let path = Path::new("/logs/latest.log");
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(
+            serde_json::to_value(LogErrorPayload::io("Failed to read log", path, &missing))
+                .unwrap(),
+            json!({
+                "kind": "log_not_found",
+                "message": format!("Failed to read log: {}", missing),
+                "path": "/logs/latest.log"
+            })
+        );
+        let denied = io::Error::from_raw_os_error(13);
+        let error = LogErrorPayload::io("Failed to read log", path, &denied);
+        assert_eq!(
+            (error.kind, error.os_code),
+            (LogErrorKind::PermissionDenied, Some(13))
+        );
+        assert!(error.hint.is_some());
+        let bad = io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        );
+        assert_eq!(
+            LogErrorPayload::io("Failed to read log", path, &bad).kind,
+            LogErrorKind::Utf8Decode
+        );
+        let other = io::Error::other("disk on fire");
+        let error = LogErrorPayload::io("Failed to read log", path, &other);
+        assert_eq!(
+            (error.kind, error.os_code, error.hint),
+            (LogErrorKind::ReadFailed, None, None)
+        );
+
+        let gone = notify::Error::path_not_found();
+        assert_eq!(
+            LogErrorPayload::watch("Failed to watch", path, &gone).kind,
+            LogErrorKind::LogNotFound
+        );
+        let failed = notify::Error::generic("inotify limit reached");
+        assert_eq!(
+            LogErrorPayload::watch("Failed to watch", path, &failed).kind,
+            LogErrorKind::WatchSetupFailed
+        );
+    }
+
+    #[test]
+    fn log_payloads_match() {
+        let update = LogUpdatePayload {
+            content: "[12:00:00] [Client thread/INFO]: [CHAT] Hi\n".to_string(),
+            offset: 0,
+            end: 46,
+            path: "/logs/latest.log".to_string(),
+            truncated: vec![TruncatedLine {
+                offset: 0,
+                length: 70000,
+            }],
+            dropped: Some(2),
+            replayed: true,
+            source: Some("prism:1.8.9".to_string()),
+        };
+        matches(LOG_UPDATE, &update);
+        matches(
+            LOG_UPDATE,
+            LogUpdatePayload {
+                truncated: Vec::new(),
+                dropped: None,
+                replayed: false,
+                source: None,
+                ..update
+            },
+        );
+
+        let err = io::Error::from_raw_os_error(13);
+        matches(
+            LOG_ERROR,
+            LogErrorPayload::io("Failed to read log", Path::new("/logs/latest.log"), &err),
+        );
+        matches(
+            LOG_ERROR,
+            LogErrorPayload::new(LogErrorKind::ConnectionFailed, "Failed to connect")
+                .with_source("server"),
+        );
+        matches(
+            LOG_LOCATION,
+            LogLocationPayload {
+                path: "/logs/latest.log".to_string(),
+                source: LocationSource::AutoDetected,
+                instance: None,
+            },
+        );
+        matches(
+            LOG_ROTATED,
+            LogRotatedPayload {
+                path: "/logs/latest.log".to_string(),
+                reason: RotationReason::Truncated,
+                previous_end: 48213,
+                source: None,
+            },
+        );
+        matches(WATCHER_PAUSED, WatcherPausedPayload { paused: true });
+        matches(
+            LIVESPLIT_ERROR,
+            MessagePayload {
+                message: "Failed to connect".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn run_payloads_match() {
+        matches(
+            RUN_STARTED,
+            RunStartedPayload {
+                offset: 1024,
+                map: "Tower".to_string(),
+                source: None,
+            },
+        );
+        matches(
+            CHECKPOINT_REACHED,
+            CheckpointReachedPayload {
+                offset: 2048,
+                map: "Tower".to_string(),
+                player: "Steve".to_string(),
+                index: 3,
+                time: "0:41.250".to_string(),
+                time_ms: Some(41250),
+                source: None,
+            },
+        );
+        matches(
+            RUN_FINISHED,
+            RunFinishedPayload {
+                offset: 4096,
+                map: "Tower".to_string(),
+                player: "Steve".to_string(),
+                time: "1:02.500".to_string(),
+                total_ms: None,
+                source: Some("prism:1.8.9".to_string()),
+            },
+        );
+        matches(
+            TIMER_TICK,
+            TimerTickPayload {
+                map: None,
+                elapsed_ms: 1500,
+                splits: 0,
+                source: None,
+            },
+        );
+        matches(
+            SPLIT_RECORDED,
+            SplitRecordedPayload {
+                map: Some("Tower".to_string()),
+                checkpoint: 3,
+                name: Some("Ladder".to_string()),
+                time_ms: 41250,
+                segment_ms: 12000,
+                elapsed_ms: None,
+                source: None,
+            },
+        );
+        matches(
+            PB_COMPARISON,
+            PbComparisonPayload {
+                map: "Tower".to_string(),
+                checkpoint: 3,
+                time_ms: 41250,
+                pb_time_ms: 42000,
+                delta_ms: -750,
+                ahead: true,
+                segment_delta_ms: None,
+                gained: Some(true),
+                source: None,
+            },
+        );
+        matches(
+            RUN_RESET,
+            RunResetPayload {
+                map: None,
+                elapsed_ms: Some(5000),
+                splits: 1,
+                source: None,
+            },
+        );
+        matches(
+            TIMER_PAUSED,
+            TimerPausedPayload {
+                map: Some("Tower".to_string()),
+                paused: false,
+                elapsed_ms: None,
+                source: None,
+            },
+        );
+    }
+
+    #[test]
+    fn wrong_payloads_are_caught() {
+        let schema = get_event_schema();
+        assert!(check(&schema, RUN_STARTED, &json!({"offset": 1024})).is_err());
+        assert!(check(&schema, WATCHER_PAUSED, &json!({"paused": "yes"})).is_err());
+        let error = json!({"kind": "disk_full", "message": "Failed to read log"});
+        assert!(check(&schema, LOG_ERROR, &error).is_err());
+    }
+}
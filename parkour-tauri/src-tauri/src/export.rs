@@ -0,0 +1,235 @@
+use crate::db::{Db, RunDetail, RunQuery};
+use crate::duration::format_ms;
+use parkour_core::store::RunStore;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use tauri::State;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+// select_runs caps a page at this many runs
+const PAGE: u32 = 1000;
+
+// Every completed run the filter matches, in its order and past its limit.
+// Invalidated runs stay out unless the filter asks for them.
+fn completed_runs(store: &impl RunStore, filter: RunQuery) -> Result<Vec<RunDetail>, String> {
+    let mut q = RunQuery {
+        completed_only: true,
+        invalidated: filter.invalidated.or(Some(false)),
+        limit: Some(PAGE),
+        offset: Some(0),
+        ..filter
+    };
+    let mut runs = Vec::new();
+    loop {
+        let page = store.query_runs(&q)?;
+        let last = page.runs.len() < PAGE as usize;
+        for run in page.runs {
+            let splits = store.get_run(run.id)?.map_or_else(Vec::new, |r| r.splits);
+            runs.push(RunDetail { run, splits });
+        }
+        if last {
+            return Ok(runs);
+        }
+        q.offset = Some(q.offset.unwrap_or(0) + PAGE);
+    }
+}
+
+fn field(out: &mut String, value: &str) {
+    // A leading = + - or @ would be read as a formula by spreadsheets
+    let value = match value.starts_with(['=', '+', '-', '@']) {
+        true => format!("'{}", value),
+        false => value.to_string(),
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        let _ = write!(out, "\"{}\",", value.replace('"', "\"\""));
+    } else {
+        let _ = write!(out, "{},", value);
+    }
+}
+
+// One row per run, with a column per checkpoint any of them reached
+fn csv(runs: &[RunDetail]) -> String {
+    let checkpoints: BTreeSet<i64> = runs
+        .iter()
+        .flat_map(|r| r.splits.iter().map(|s| s.checkpoint))
+        .collect();
+    let mut out = String::from(
+        "id,started_at,started_at_utc,map,username,server,time,time_ms,fails,practice,excluded,tags",
+    );
+    for cp in &checkpoints {
+        let _ = write!(out, ",cp{}_ms", cp);
+    }
+    out.push('\n');
+    for RunDetail { run, splits } in runs {
+        let mut row = String::new();
+        let _ = write!(row, "{},", run.id);
+        field(&mut row, &run.started_at);
+        field(&mut row, run.started_at_utc.as_deref().unwrap_or(""));
+        field(&mut row, &run.map);
+        field(&mut row, &run.username);
+        field(&mut row, run.server.as_deref().unwrap_or(""));
+        field(&mut row, &run.time_ms.map(format_ms).unwrap_or_default());
+        field(
+            &mut row,
+            &run.time_ms.map(|t| t.to_string()).unwrap_or_default(),
+        );
+        let _ = write!(row, "{},{},{},", run.fails, run.practice, run.excluded);
+        field(&mut row, &run.tags.join(";"));
+        for cp in &checkpoints {
+            match splits.iter().find(|s| s.checkpoint == *cp) {
+                Some(split) => field(&mut row, &split.time_ms.to_string()),
+                None => row.push(','),
+            }
+        }
+        row.pop();
+        out.push_str(&row);
+        out.push('\n');
+    }
+    out
+}
+
+fn export_text(runs: &[RunDetail], format: ExportFormat, bom: bool) -> Result<String, String> {
+    match format {
+        // Excel reads a CSV without a BOM in the system code page
+        ExportFormat::Csv if bom => Ok(format!("\u{feff}{}", csv(runs))),
+        ExportFormat::Csv => Ok(csv(runs)),
+        ExportFormat::Json => serde_json::to_string_pretty(runs)
+            .map_err(|e| format!("Failed to serialize runs: {}", e)),
+    }
+}
+
+/// Writes the completed runs `filter` matches to `path`, with their splits,
+/// and returns how many there were. CSV has a row per run and a `cpN_ms`
+/// column per checkpoint, and starts with a UTF-8 BOM when `bom` is set so
+/// Excel reads non-ASCII names right; JSON is a list shaped like `get_run`'s
+/// result. Invalidated runs are left out unless `filter.invalidated` is set.
+#[tauri::command]
+pub fn export_runs(
+    format: ExportFormat,
+    path: String,
+    filter: Option<RunQuery>,
+    bom: Option<bool>,
+    db: State<'_, Db>,
+) -> Result<usize, String> {
+    let runs = completed_runs(&*db, filter.unwrap_or_default())?;
+    let text = export_text(&runs, format, bom.unwrap_or(false))?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(runs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parkour_core::duels::Patterns;
+    use parkour_core::import;
+    use parkour_core::store::MemoryStore;
+
+    const MAPS: [&str; 4] = [
+        "Parkour Duels — Café",
+        "Parkour Duels — Башня",
+        "Parkour Duels — Ледяная крепость",
+        "Parkour Duels — 天空の塔",
+    ];
+
+    fn unicode_log(name: &str) -> (import::LogFile, String) {
+        let dir =
+            std::env::temp_dir().join(format!("parkour-export-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("2026-01-01-1.log");
+        fs::write(&path, include_str!("../fixtures/unicode.log")).unwrap();
+        let file = import::log_file(path.clone()).unwrap();
+        let content = import::read_log(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        (file, content)
+    }
+
+    fn imported(name: &str) -> Db {
+        let (file, content) = unicode_log(name);
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        let result = import::import_log(&conn, &file, &content, &Patterns::default()).unwrap();
+        assert_eq!(result.runs_added, MAPS.len());
+        // Fixed instants, so the export doesn't depend on the time zone
+        conn.execute_batch("UPDATE runs SET started_at_utc = replace(started_at, ' ', 'T') || 'Z'")
+            .unwrap();
+        drop(conn);
+        db
+    }
+
+    #[test]
+    fn unicode_map_names_survive_storing_and_querying() {
+        let db = imported("query");
+        let spellings = [
+            ("PARKOUR DUELS — CAFÉ", MAPS[0]),
+            ("Parkour Duels — Cafe\u{301}", MAPS[0]),
+            ("parkour duels — башня", MAPS[1]),
+            ("PARKOUR DUELS — ЛЕДЯНАЯ КРЕПОСТЬ", MAPS[2]),
+            ("Parkour Duels — 天空の塔", MAPS[3]),
+        ];
+        for (query, stored) in spellings {
+            let page = db
+                .query_runs(&RunQuery {
+                    map: Some(query.into()),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(page.runs.len(), 1, "{}", query);
+            assert_eq!(page.runs[0].map.as_bytes(), stored.as_bytes());
+        }
+    }
+
+    #[test]
+    fn unicode_map_names_are_exported_byte_for_byte() {
+        let db = imported("export");
+        let runs = completed_runs(&db, RunQuery::default()).unwrap();
+        let mut maps: Vec<&str> = runs.iter().map(|r| r.run.map.as_str()).collect();
+        maps.sort();
+        let mut expected = MAPS;
+        expected.sort();
+        assert_eq!(maps, expected);
+
+        assert_eq!(csv(&runs), include_str!("../fixtures/unicode.csv"));
+        let json = serde_json::to_string_pretty(&runs).unwrap();
+        let read: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        let read: Vec<&str> = read.iter().map(|r| r["map"].as_str().unwrap()).collect();
+        let stored: Vec<&str> = runs.iter().map(|r| r.run.map.as_str()).collect();
+        assert_eq!(read, stored);
+    }
+
+    #[test]
+    fn csv_starts_with_a_bom_only_when_asked() {
+        let db = imported("bom");
+        let runs = completed_runs(&db, RunQuery::default()).unwrap();
+        let fixture = include_str!("../fixtures/unicode.csv");
+        let plain = export_text(&runs, ExportFormat::Csv, false).unwrap();
+        assert_eq!(plain, fixture);
+        let with_bom = export_text(&runs, ExportFormat::Csv, true).unwrap();
+        assert_eq!(&with_bom.as_bytes()[..3], b"\xef\xbb\xbf");
+        assert_eq!(&with_bom[3..], fixture);
+        // JSON parsers reject a BOM, so it never gets one
+        let json = export_text(&runs, ExportFormat::Json, true).unwrap();
+        assert!(json.starts_with('['));
+    }
+
+    #[test]
+    fn runs_in_memory_are_exported_like_stored_ones() {
+        let (file, content) = unicode_log("memory");
+        let store = MemoryStore::default();
+        let analysis = import::analyze_log(&file, &content, &Patterns::default());
+        store.import(&file, &analysis).unwrap();
+        let mut runs = completed_runs(&store, RunQuery::default()).unwrap();
+        for RunDetail { run, .. } in &mut runs {
+            run.started_at_utc = Some(format!("{}Z", run.started_at.replace(' ', "T")));
+        }
+        assert_eq!(csv(&runs), include_str!("../fixtures/unicode.csv"));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::db::{self, Db};
+use crate::goals;
+use crate::live::{now_ms, Live};
+use crate::settings::{FollowedLog, LogMode, Settings, SettingsStore};
+use parkour_core::import::{self, LogFile, LogImport};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+// How often followed logs are checked for changes
+const INTERVAL: Duration = Duration::from_secs(5);
+// A client log written to more recently than this may hold a game in progress
+const CLIENT_QUIET_MS: i64 = 60_000;
+
+// The followed logs, plus the watched log while it's watched. Its runs are
+// stored like an instance log's, once the game has stopped writing to it.
+fn logs(app: &AppHandle, settings: &Settings) -> Vec<FollowedLog> {
+    let mut logs = settings.followed_logs();
+    let live = app.state::<Live>().snapshot();
+    if let Some(path) = live.log_path.filter(|_| live.watching && !live.paused) {
+        if !logs.iter().any(|l| l.path == path) {
+            logs.push(FollowedLog {
+                path,
+                ..Default::default()
+            });
+        }
+    }
+    logs
+}
+
+/// Imports the runs in the watched log, every followed log and every
+/// instance log as it grows, see [`FollowedLog`].
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        // Last error for each path, so a missing log is reported once
+        let mut errors: BTreeMap<String, String> = BTreeMap::new();
+        while !crate::stopping(&app) {
+            let settings = app.state::<SettingsStore>().get();
+            let mut added = 0;
+            for log in &logs(&app, &settings) {
+                match import_followed(&app, &settings, log) {
+                    Ok(n) => {
+                        added += n;
+                        errors.remove(&log.path);
+                    }
+                    Err(e) => {
+                        if errors.get(&log.path) != Some(&e) {
+                            eprintln!("{}", e);
+                            errors.insert(log.path.clone(), e);
+                        }
+                    }
+                }
+            }
+            if added > 0 {
+                goals::sweep(&app);
+            }
+            crate::sleep_unless_stopping(&app, INTERVAL);
+        }
+    });
+}
+
+/// Stores the runs in a log that's no longer watched right away, without
+/// waiting for it to go quiet as a followed log would.
+pub fn store(app: &AppHandle, path: &Path) -> Result<LogImport, String> {
+    let read = |e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+    let file = import::log_file(fs::canonicalize(path).map_err(read)?).map_err(read)?;
+    let content = import::read_log(&file.path).map_err(read)?;
+    let patterns = app.state::<SettingsStore>().get().patterns()?;
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    import::import_log(&conn, &file, &content, &patterns)
+}
+
+fn files(path: &Path) -> Result<Vec<LogFile>, String> {
+    let read = |e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+    if path.is_dir() {
+        return import::find_logs(path);
+    }
+    // Imports are recorded by path, as for a folder
+    let path = fs::canonicalize(path).map_err(read)?;
+    Ok(vec![import::log_file(path).map_err(read)?])
+}
+
+// Imports the files of one followed log that changed since they were last
+// read, returning how many runs were added
+fn import_followed(
+    app: &AppHandle,
+    settings: &Settings,
+    log: &FollowedLog,
+) -> Result<usize, String> {
+    let patterns = settings.followed_patterns(log)?;
+    let db = app.state::<Db>();
+    let mut added = 0;
+    for file in files(Path::new(&log.path))? {
+        let settling = log.mode == LogMode::Client
+            && file
+                .modified_ms
+                .is_some_and(|modified| now_ms() as i64 - modified < CLIENT_QUIET_MS);
+        if settling {
+            continue;
+        }
+        let imported = {
+            let conn = db.conn()?;
+            db::is_imported(&conn, &file.key(), file.size, file.modified_ms)?
+        };
+        if imported {
+            continue;
+        }
+        // Read before locking, since a server log can be large
+        let content = import::read_log(&file.path)
+            .map_err(|e| format!("Failed to read {}: {}", file.path.display(), e))?;
+        let conn = db.conn()?;
+        added += import::import_log(&conn, &file, &content, &patterns)?.runs_added;
+    }
+    Ok(added)
+}
@@ -0,0 +1,94 @@
+use crate::db::{self, Db, Goal, GoalProgress, NewGoal};
+use crate::events;
+use crate::notifications;
+use crate::timestamps::LOCAL_FORMAT;
+use crate::webhooks::{self, GoalSummary, WebhookEvent};
+use tauri::{AppHandle, Manager, State};
+
+// Tells every listener about newly achieved goals
+fn announce(app: &AppHandle, achieved: Vec<Goal>) {
+    for goal in achieved {
+        events::emit_all(app, events::GOAL_ACHIEVED, &goal);
+        let Some(time_ms) = goal.achieved_time_ms else {
+            continue;
+        };
+        notifications::notify_goal(app, &goal.map, time_ms, goal.target_time_ms);
+        webhooks::notify(
+            app,
+            WebhookEvent::Goal(GoalSummary {
+                map: goal.map,
+                username: goal.username,
+                target_time_ms: goal.target_time_ms,
+                time_ms,
+                deadline: goal.deadline,
+            }),
+        );
+    }
+}
+
+/// Checks the open goals against a completion as it happens.
+pub fn on_completion(app: &AppHandle, map: &str, username: Option<&str>, time_ms: i64) {
+    let now = chrono::Local::now().format(LOCAL_FORMAT).to_string();
+    let db = app.state::<Db>();
+    let achieved = db
+        .conn()
+        .and_then(|conn| db::achieve_goals_by(&conn, map, username, time_ms, &now));
+    match achieved {
+        Ok(achieved) => announce(app, achieved),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+/// Checks the open goals against the stored runs, after runs were added.
+pub fn sweep(app: &AppHandle) {
+    let db = app.state::<Db>();
+    match db.conn().and_then(|conn| db::achieve_goals(&conn)) {
+        Ok(achieved) => announce(app, achieved),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+/// Every goal with the best time toward it. Goals met by runs stored since,
+/// e.g. by the command line import, are marked achieved first.
+#[tauri::command]
+pub fn get_goals(db: State<'_, Db>) -> Result<Vec<GoalProgress>, String> {
+    let conn = db.conn()?;
+    db::achieve_goals(&conn)?;
+    db::goals(&conn, chrono::Local::now().date_naive())
+}
+
+/// Adds a goal. One the stored runs already meet is achieved right away.
+#[tauri::command]
+pub fn create_goal(goal: NewGoal, app: AppHandle) -> Result<Goal, String> {
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    let created_at = chrono::Local::now().format(LOCAL_FORMAT).to_string();
+    let id = db::insert_goal(&conn, &goal, &created_at)?;
+    let achieved = db::achieve_goals(&conn)?;
+    let goal = db::get_goal(&conn, id)?.ok_or("Goal disappeared after saving")?;
+    drop(conn);
+    announce(&app, achieved);
+    Ok(goal)
+}
+
+#[tauri::command]
+pub fn update_goal(goal_id: i64, goal: NewGoal, app: AppHandle) -> Result<Goal, String> {
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    if !db::update_goal(&conn, goal_id, &goal)? {
+        return Err(format!("No goal with id {}", goal_id));
+    }
+    let achieved = db::achieve_goals(&conn)?;
+    let goal = db::get_goal(&conn, goal_id)?.ok_or("Goal disappeared after saving")?;
+    drop(conn);
+    announce(&app, achieved);
+    Ok(goal)
+}
+
+#[tauri::command]
+pub fn delete_goal(goal_id: i64, db: State<'_, Db>) -> Result<(), String> {
+    if !db::delete_goal(&*db.conn()?, goal_id)? {
+        return Err(format!("No goal with id {}", goal_id));
+    }
+    Ok(())
+}
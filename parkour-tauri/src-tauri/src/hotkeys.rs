@@ -0,0 +1,177 @@
+use crate::events;
+use crate::lifecycle;
+use crate::live::{now_ms, source_field, Live, MAIN};
+use crate::markers;
+use crate::settings::{Settings, SettingsStore};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Hotkey {
+    Marker,
+    Reset,
+    Split,
+    Pause,
+}
+
+/// `{"map": "Tower", "elapsed_ms": 41250, "splits": 3}`
+///
+/// The reset hotkey, or the server's reset message, giving up on a log's run
+/// in progress. The run isn't stored; the game's next start begins a new one.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct RunResetPayload {
+    pub map: Option<String>,
+    pub elapsed_ms: Option<u64>,
+    pub splits: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// `{"map": "Tower", "paused": true, "elapsed_ms": 41250}`
+///
+/// The pause hotkey stopping or resuming the live run's timer. Paused time
+/// is left out of `elapsed_ms` from then on, and no `timer-tick` is sent
+/// while paused.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct TimerPausedPayload {
+    pub map: Option<String>,
+    pub paused: bool,
+    pub elapsed_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+pub fn parse_shortcut(combo: &str) -> Result<Shortcut, String> {
+    combo
+        .parse()
+        .map_err(|e| format!("Invalid shortcut '{}': {}", combo, e))
+}
+
+// Every hotkey's combination, when one is set
+fn bindings(settings: &Settings) -> [(Hotkey, Option<&str>); 4] {
+    let hotkeys = &settings.hotkeys;
+    [
+        (Hotkey::Marker, settings.markers.shortcut.as_deref()),
+        (Hotkey::Reset, hotkeys.reset.as_deref()),
+        (Hotkey::Split, hotkeys.split.as_deref()),
+        (Hotkey::Pause, hotkeys.pause.as_deref()),
+    ]
+    .map(|(hotkey, combo)| (hotkey, combo.map(str::trim).filter(|c| !c.is_empty())))
+}
+
+/// Checks every combination parses and none is bound to two hotkeys.
+pub fn validate(settings: &Settings) -> Result<(), String> {
+    let mut seen = Vec::new();
+    for (_, combo) in bindings(settings) {
+        let Some(combo) = combo else {
+            continue;
+        };
+        let shortcut = parse_shortcut(combo)?;
+        if seen.contains(&shortcut) {
+            return Err(format!("Shortcut '{}' is bound twice", combo));
+        }
+        seen.push(shortcut);
+    }
+    Ok(())
+}
+
+/// Gives up on the watched log's run in progress, telling LiveSplit to reset
+/// too. Does nothing when no run is going.
+pub fn reset(app: &AppHandle) {
+    let mut reset = None;
+    app.state::<Live>().update(|s| reset = s.current_run.take());
+    if let Some(run) = reset {
+        lifecycle::reset(app, MAIN, run);
+    }
+}
+
+/// Pauses the live run's timer, or resumes it when paused.
+pub fn toggle_pause(app: &AppHandle) {
+    let mut paused = None;
+    app.state::<Live>().update(|s| {
+        let Some(run) = s.current_run.as_mut() else {
+            return;
+        };
+        match run.paused_since_ms.take() {
+            Some(since) => run.paused_ms += now_ms().saturating_sub(since),
+            None => run.paused_since_ms = Some(now_ms()),
+        }
+        paused = Some(run.clone());
+    });
+    let Some(run) = paused else {
+        return;
+    };
+    let payload = TimerPausedPayload {
+        paused: run.paused_since_ms.is_some(),
+        elapsed_ms: run.elapsed_ms(),
+        map: run.map,
+        source: source_field(MAIN),
+    };
+    events::emit_all(app, events::TIMER_PAUSED, &payload);
+}
+
+pub fn on_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let settings = app.state::<SettingsStore>().get();
+    let hotkey = bindings(&settings)
+        .into_iter()
+        .find(|(_, combo)| combo.and_then(|c| parse_shortcut(c).ok()).as_ref() == Some(shortcut));
+    match hotkey.map(|(hotkey, _)| hotkey) {
+        Some(Hotkey::Marker | Hotkey::Split) => {
+            if let Err(e) = markers::record(app, None) {
+                eprintln!("{}", e);
+            }
+        }
+        Some(Hotkey::Reset) => reset(app),
+        Some(Hotkey::Pause) => toggle_pause(app),
+        None => {}
+    }
+}
+
+// Registers `next` in place of `previous`, restoring `previous` if the new
+// combination is already taken by another application.
+fn apply_shortcut(
+    app: &AppHandle,
+    previous: Option<&str>,
+    next: Option<&str>,
+) -> Result<(), String> {
+    let new = next.map(parse_shortcut).transpose()?;
+    let old = previous.and_then(|c| parse_shortcut(c).ok());
+    let shortcuts = app.global_shortcut();
+    if let Some(old) = old {
+        let _ = shortcuts.unregister(old);
+    }
+    let (Some(combo), Some(new)) = (next, new) else {
+        return Ok(());
+    };
+    if let Err(e) = shortcuts.register(new) {
+        if let Some(old) = old {
+            let _ = shortcuts.register(old);
+        }
+        return Err(format!("Could not register shortcut '{}': {}", combo, e));
+    }
+    Ok(())
+}
+
+/// Registers the hotkeys whose combination changed from `previous`.
+pub fn apply(app: &AppHandle, previous: &Settings, next: &Settings) -> Result<(), String> {
+    for ((_, old), (_, new)) in bindings(previous).into_iter().zip(bindings(next)) {
+        if old != new {
+            apply_shortcut(app, old, new)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn init(app: &AppHandle) {
+    let settings = app.state::<SettingsStore>().get();
+    for (_, combo) in bindings(&settings) {
+        if let Err(e) = apply_shortcut(app, None, combo) {
+            eprintln!("{}", e);
+        }
+    }
+}
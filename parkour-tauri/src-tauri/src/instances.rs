@@ -0,0 +1,269 @@
+use crate::events::{self, LocationSource, LogErrorPayload, LogLocationPayload, LogUpdatePayload};
+use crate::lines::Capped;
+use crate::live::{CurrentRun, Live, LiveState};
+use crate::settings::{InstanceLog, SettingsStore};
+use crate::tail::{self, LogTail};
+use crate::{
+    crash, definitions, followed, goals, line_filter, metrics, parser, path_check, pipeline,
+    servers,
+};
+use parkour_core::import::LogImport;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+// How often instance logs are checked for new lines and settings changes
+const INTERVAL: Duration = Duration::from_millis(500);
+
+/// `{"source": "alt", "abandoned_run": {"map": "Tower", ...}, "imported": {"runs_found": 4, "runs_added": 2, ...}}`
+///
+/// Emitted when an instance log is taken out of `watcher.instances`, or
+/// given another path. Its run in progress is dropped and sent as
+/// `abandoned_run`; `imported` is what storing the log's runs did, left out
+/// when that failed.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct SourceRemovedPayload {
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abandoned_run: Option<CurrentRun>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported: Option<LogImport>,
+}
+
+struct Watched {
+    path: PathBuf,
+    tail: LogTail,
+    feeder: pipeline::Feeder,
+}
+
+/// Watches every instance log in the settings, see [`InstanceLog`], picking
+/// up ones added or removed while running. Pausing the watcher pauses these
+/// as well.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut watched: BTreeMap<String, Watched> = BTreeMap::new();
+        while !crate::stopping(&app) {
+            let configured = app.state::<SettingsStore>().get().watcher.instances;
+            let stale: Vec<String> = watched
+                .iter()
+                .filter(|(id, w)| {
+                    !configured
+                        .iter()
+                        .any(|i| i.id == **id && Path::new(&i.path) == w.path)
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in stale {
+                if let Some(w) = watched.remove(&id) {
+                    remove(&app, &id, w);
+                }
+            }
+            for instance in &configured {
+                watched
+                    .entry(instance.id.clone())
+                    .or_insert_with(|| add(&app, instance));
+            }
+            let paused = app.state::<Live>().snapshot().paused;
+            for (id, w) in watched.iter_mut() {
+                w.feeder.flush();
+                if !paused {
+                    poll(&app, id, w);
+                }
+            }
+            std::thread::sleep(INTERVAL);
+        }
+        // Let the workers finish what was read, as the main log's does
+        for (_, w) in watched {
+            w.feeder.finish();
+        }
+    });
+}
+
+// Tracking starts from scratch, in case the id was used before
+fn forget(app: &AppHandle, id: &str) {
+    crash::forget(app, id);
+    servers::forget(app, id);
+    parser::forget(app, id);
+    definitions::forget(app, id);
+}
+
+fn add(app: &AppHandle, instance: &InstanceLog) -> Watched {
+    let path = PathBuf::from(&instance.path);
+    forget(app, &instance.id);
+    app.state::<Live>().add_instance(
+        &instance.id,
+        LiveState {
+            log_path: Some(instance.path.clone()),
+            watching: true,
+            ..Default::default()
+        },
+    );
+    if !crate::legacy_events(app) {
+        metrics::record(app, |m| &m.events_emitted, 1);
+        let payload = LogLocationPayload {
+            path: instance.path.clone(),
+            source: LocationSource::User,
+            instance: Some(instance.id.clone()),
+        };
+        events::emit_all(app, events::LOG_LOCATION, &payload);
+    }
+    Watched {
+        tail: LogTail::new(&path),
+        feeder: pipeline::start(app, &instance.id, &path),
+        path,
+    }
+}
+
+// Chunks still queued are dropped by the worker once the state is gone, so
+// the other sources carry on untouched
+fn remove(app: &AppHandle, id: &str, w: Watched) {
+    let state = app.state::<Live>().remove_instance(id);
+    drop(w.feeder);
+    forget(app, id);
+    let imported = match followed::store(app, &w.path) {
+        Ok(imported) => Some(imported),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    };
+    if imported.as_ref().is_some_and(|i| i.runs_added > 0) {
+        goals::sweep(app);
+    }
+    let payload = SourceRemovedPayload {
+        source: id.to_string(),
+        abandoned_run: state.and_then(|s| s.current_run),
+        imported,
+    };
+    events::emit_all(app, events::SOURCE_REMOVED, &payload);
+}
+
+fn poll(app: &AppHandle, id: &str, w: &mut Watched) {
+    match tail::with_retry(|| w.tail.poll()) {
+        Ok(Some(chunk)) => {
+            metrics::record(app, |m| &m.bytes_read, chunk.content.len() as u64);
+            if chunk.offset == 0 {
+                let log_id = w.tail.id();
+                app.state::<Live>().update_source(id, |s| s.log_id = log_id);
+            }
+            if let Some(rotation) = chunk.rotation {
+                crate::emit_rotated(app, id, &w.path, rotation);
+            }
+            w.feeder.push(chunk);
+        }
+        Ok(None) => {}
+        Err(e) => emit_error(
+            app,
+            id,
+            LogErrorPayload::io("Failed to read log", &w.path, &e),
+        ),
+    }
+}
+
+// Reported once until the log can be read again, since it's polled
+fn emit_error(app: &AppHandle, id: &str, payload: LogErrorPayload) {
+    let payload = payload.with_source(id);
+    let mut repeated = false;
+    app.state::<Live>().update_source(id, |s| {
+        repeated = s.last_error.as_ref() == Some(&payload.message);
+        s.watching = false;
+        s.last_error = Some(payload.message.clone());
+    });
+    if repeated || crate::legacy_events(app) {
+        return;
+    }
+    metrics::record(app, |m| &m.events_emitted, 1);
+    events::emit_all(app, events::LOG_ERROR, &payload);
+}
+
+/// Emits what was read from the instance log `id` between `offset` and
+/// `end`. Frontends on legacy events only ever see the main log.
+pub(crate) fn emit_update(
+    app: &AppHandle,
+    id: &str,
+    path: &Path,
+    offset: u64,
+    end: u64,
+    chunk: Capped,
+) {
+    let watcher = app.state::<SettingsStore>().get().watcher;
+    app.state::<Live>().update_source(id, |s| {
+        crate::note_read(s, &watcher, offset, end, &chunk.content)
+    });
+    if crate::legacy_events(app) {
+        return;
+    }
+    metrics::record(app, |m| &m.events_emitted, 1);
+    let (content, truncated, dropped) = line_filter::apply(app, id, chunk);
+    let payload = LogUpdatePayload {
+        content,
+        offset,
+        end,
+        path: path.to_string_lossy().to_string(),
+        truncated,
+        dropped,
+        replayed: false,
+        source: Some(id.to_string()),
+    };
+    events::emit_all(app, events::LOG_UPDATE, &payload);
+}
+
+fn save<T>(
+    store: &SettingsStore,
+    edit: impl FnOnce(&mut Vec<InstanceLog>) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut settings = store.get();
+    let edited = edit(&mut settings.watcher.instances)?;
+    crate::settings::validate(&settings)?;
+    store.replace(settings)?;
+    Ok(edited)
+}
+
+/// Watches `path` alongside the main log from now on, as an entry of
+/// `watcher.instances` named `instance-1`, `instance-2` and so on, and
+/// returns that id: it's the `source` of the log's events. A log watched
+/// already keeps its id.
+#[tauri::command]
+pub fn add_watch(path: String, store: State<'_, SettingsStore>) -> Result<String, String> {
+    let check = path_check::check(Path::new(&path));
+    if let Some(e) = check.error {
+        return Err(e.message);
+    }
+    if !check.usable() {
+        return Err(format!("{} is not a readable file", path));
+    }
+    save(&store, |instances| {
+        if let Some(watched) = instances
+            .iter()
+            .find(|i| Path::new(&i.path) == Path::new(&path))
+        {
+            return Ok(watched.id.clone());
+        }
+        let id = (1..)
+            .map(|n| format!("instance-{}", n))
+            .find(|id| !instances.iter().any(|i| &i.id == id))
+            .unwrap_or_default();
+        instances.push(InstanceLog {
+            id: id.clone(),
+            path,
+        });
+        Ok(id)
+    })
+}
+
+/// Stops watching the instance log `source_id`, storing its runs as taking
+/// it out of `watcher.instances` does.
+#[tauri::command]
+pub fn remove_watch(source_id: String, store: State<'_, SettingsStore>) -> Result<(), String> {
+    save(&store, |instances| {
+        let before = instances.len();
+        instances.retain(|i| i.id != source_id);
+        match instances.len() < before {
+            true => Ok(()),
+            false => Err(format!("No instance log with the id {}", source_id)),
+        }
+    })
+}
@@ -0,0 +1,264 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Launcher {
+    Vanilla,
+    Lunar,
+    Badlion,
+    Feather,
+    Prism,
+    MultiMc,
+    CurseForge,
+    GdLauncher,
+}
+
+/// `{"launcher": "prism", "name": "Hypixel 1.8.9", "path": "/home/me/.local/share/PrismLauncher/instances/Hypixel 1.8.9/.minecraft/logs/latest.log", "modified_ms": 1714600000000}`
+///
+/// A game log some launcher writes. `name` is the instance's folder name for
+/// launchers with instances, and the launcher's name otherwise.
+#[derive(Serialize, Clone, Debug)]
+pub struct Instance {
+    pub launcher: Launcher,
+    pub name: String,
+    pub path: String,
+    pub modified_ms: Option<i64>,
+}
+
+// Where a launcher keeps its log, relative to one of the platform's base
+// folders
+enum Layout {
+    Log(&'static str),
+    // A folder of instances, each with its log at one of these paths
+    Instances(&'static str, &'static [&'static str]),
+}
+
+const INSTANCE_LOGS: &[&str] = &[
+    ".minecraft/logs/latest.log",
+    "minecraft/logs/latest.log",
+    "logs/latest.log",
+];
+const GDLAUNCHER_LOGS: &[&str] = &["instance/logs/latest.log", "logs/latest.log"];
+
+fn name(launcher: Launcher) -> &'static str {
+    match launcher {
+        Launcher::Vanilla => "Minecraft",
+        Launcher::Lunar => "Lunar Client",
+        Launcher::Badlion => "Badlion Client",
+        Launcher::Feather => "Feather Client",
+        Launcher::Prism => "Prism Launcher",
+        Launcher::MultiMc => "MultiMC",
+        Launcher::CurseForge => "CurseForge",
+        Launcher::GdLauncher => "GDLauncher",
+    }
+}
+
+// Each launcher's layouts under the base folders of this platform, in the
+// order get_log_path has always tried vanilla and Lunar
+fn known() -> Vec<(Launcher, PathBuf, Layout)> {
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new();
+    };
+    let mut known = Vec::new();
+    let mut add =
+        |launcher, base: &Path, layout| known.push((launcher, base.to_path_buf(), layout));
+    if cfg!(target_os = "windows") {
+        let Some(appdata) = std::env::var_os("APPDATA").map(PathBuf::from) else {
+            return Vec::new();
+        };
+        add(
+            Launcher::Vanilla,
+            &appdata,
+            Layout::Log(".minecraft/logs/latest.log"),
+        );
+        add(
+            Launcher::Lunar,
+            &appdata,
+            Layout::Log(".lunarclient/offline/multiver/logs/latest.log"),
+        );
+        add(
+            Launcher::Badlion,
+            &appdata,
+            Layout::Log(".minecraft/logs/blclient/minecraft/latest.log"),
+        );
+        add(
+            Launcher::Feather,
+            &appdata,
+            Layout::Log(".feather/logs/latest.log"),
+        );
+        add(
+            Launcher::Prism,
+            &appdata,
+            Layout::Instances("PrismLauncher/instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::MultiMc,
+            &appdata,
+            Layout::Instances("MultiMC/instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::CurseForge,
+            &home,
+            Layout::Instances("curseforge/minecraft/Instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::GdLauncher,
+            &appdata,
+            Layout::Instances("gdlauncher_next/instances", GDLAUNCHER_LOGS),
+        );
+        add(
+            Launcher::GdLauncher,
+            &appdata,
+            Layout::Instances("gdlauncher_carbon/data/instances", GDLAUNCHER_LOGS),
+        );
+    } else if cfg!(target_os = "macos") {
+        let support = home.join("Library/Application Support");
+        add(
+            Launcher::Vanilla,
+            &support,
+            Layout::Log("minecraft/logs/latest.log"),
+        );
+        add(
+            Launcher::Lunar,
+            &home,
+            Layout::Log(".lunarclient/offline/multiver/logs/latest.log"),
+        );
+        add(
+            Launcher::Badlion,
+            &support,
+            Layout::Log("minecraft/logs/blclient/minecraft/latest.log"),
+        );
+        add(
+            Launcher::Feather,
+            &support,
+            Layout::Log("feather/logs/latest.log"),
+        );
+        add(
+            Launcher::Prism,
+            &support,
+            Layout::Instances("PrismLauncher/instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::MultiMc,
+            &support,
+            Layout::Instances("MultiMC/instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::CurseForge,
+            &home,
+            Layout::Instances("Documents/curseforge/minecraft/Instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::GdLauncher,
+            &support,
+            Layout::Instances("gdlauncher_next/instances", GDLAUNCHER_LOGS),
+        );
+        add(
+            Launcher::GdLauncher,
+            &support,
+            Layout::Instances("gdlauncher_carbon/data/instances", GDLAUNCHER_LOGS),
+        );
+    } else {
+        let share = home.join(".local/share");
+        add(
+            Launcher::Vanilla,
+            &home,
+            Layout::Log(".minecraft/logs/latest.log"),
+        );
+        add(
+            Launcher::Lunar,
+            &home,
+            Layout::Log(".lunarclient/offline/multiver/logs/latest.log"),
+        );
+        add(
+            Launcher::Prism,
+            &share,
+            Layout::Instances("PrismLauncher/instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::Prism,
+            &home,
+            Layout::Instances(
+                ".var/app/org.prismlauncher.PrismLauncher/data/PrismLauncher/instances",
+                INSTANCE_LOGS,
+            ),
+        );
+        add(
+            Launcher::MultiMc,
+            &share,
+            Layout::Instances("multimc/instances", INSTANCE_LOGS),
+        );
+        add(
+            Launcher::GdLauncher,
+            &home,
+            Layout::Instances(".config/gdlauncher_next/instances", GDLAUNCHER_LOGS),
+        );
+        add(
+            Launcher::GdLauncher,
+            &share,
+            Layout::Instances("gdlauncher_carbon/data/instances", GDLAUNCHER_LOGS),
+        );
+    }
+    known
+}
+
+fn modified_ms(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
+fn instance(launcher: Launcher, name: String, path: PathBuf) -> Instance {
+    Instance {
+        launcher,
+        name,
+        modified_ms: modified_ms(&path),
+        path: path.to_string_lossy().to_string(),
+    }
+}
+
+/// Every game log found in the launchers' usual folders, in the order they
+/// were looked for. A launcher installed somewhere else isn't found.
+pub fn discover() -> Vec<Instance> {
+    let mut found = Vec::new();
+    for (launcher, base, layout) in known() {
+        match layout {
+            Layout::Log(log) => {
+                let path = base.join(log);
+                if path.is_file() {
+                    found.push(instance(launcher, name(launcher).to_string(), path));
+                }
+            }
+            Layout::Instances(dir, logs) => {
+                let Ok(entries) = fs::read_dir(base.join(dir)) else {
+                    continue;
+                };
+                let mut instances: Vec<Instance> = entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let log = logs
+                            .iter()
+                            .map(|log| entry.path().join(log))
+                            .find(|p| p.is_file())?;
+                        let name = entry.file_name().to_string_lossy().to_string();
+                        Some(instance(launcher, name, log))
+                    })
+                    .collect();
+                instances.sort_by(|a, b| a.name.cmp(&b.name));
+                found.extend(instances);
+            }
+        }
+    }
+    found
+}
+
+/// Every game log the launchers on this machine write, most recently
+/// written first, to pick one to watch with `watch_path`.
+#[tauri::command]
+pub fn list_instances() -> Vec<Instance> {
+    let mut instances = discover();
+    instances.sort_by_key(|i| std::cmp::Reverse(i.modified_ms));
+    instances
+}
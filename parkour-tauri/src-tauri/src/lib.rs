@@ -1,56 +1,358 @@
+pub mod analyze;
+pub mod anonymize;
+pub mod api;
+pub mod archives;
+pub mod autostart;
+pub mod chat_commands;
+pub mod compare;
+pub mod crash;
+pub mod db;
+pub mod debounce;
+pub mod deep_link;
+pub mod definitions;
+pub mod events;
+pub mod export;
+pub mod followed;
+pub mod goals;
+pub mod hotkeys;
+pub mod instances;
+pub mod launchers;
+pub mod lifecycle;
+pub mod line_filter;
+pub mod line_index;
+pub mod live;
+pub mod livesplit;
+pub mod lss;
+pub mod markers;
+pub mod metrics;
+pub mod notifications;
+pub mod overlay;
+pub mod parser;
+pub mod path_check;
+pub mod pb;
+pub mod perf;
+pub mod permissions;
+pub mod pipeline;
+pub mod presence;
+pub mod rate_limit;
+pub mod remote;
+pub mod replay;
+pub mod report;
+pub mod routing;
+pub mod servers;
+pub mod settings;
+pub mod stats;
+pub mod summary;
+pub mod sync;
+pub mod templates;
+pub mod text_output;
+pub mod timer;
+pub mod tray;
+pub mod webhooks;
+pub mod ws;
+
+pub use parkour_core::{duration, lines, names, tail, timestamps};
+
+use events::{LocationSource, LogErrorKind, LogErrorPayload, LogLocationPayload, LogUpdatePayload};
+use live::{now_ms, Live, LiveState};
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use parkour_core::client::{self, Client};
+use parkour_core::log_line::{detect_format, LogFormat};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
-use std::sync::mpsc::channel;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Manager};
 
+// Vanilla and Lunar come first, as they always have; other launchers' logs
+// are a fallback, the most recently written one
 fn get_log_path() -> Option<PathBuf> {
-    let candidates: Vec<PathBuf> = if cfg!(target_os = "windows") {
-        let appdata = std::env::var("APPDATA").ok()?;
-        vec![
-            PathBuf::from(&appdata).join(".minecraft\\logs\\latest.log"),
-            PathBuf::from(&appdata).join(".lunarclient\\offline\\multiver\\logs\\latest.log"),
-        ]
-    } else if cfg!(target_os = "macos") {
-        let home = dirs::home_dir()?;
-        vec![
-            home.join("Library/Application Support/minecraft/logs/latest.log"),
-            home.join(".lunarclient/offline/multiver/logs/latest.log"),
-        ]
+    let found = launchers::discover();
+    found
+        .iter()
+        .find(|i| matches!(i.launcher, launchers::Launcher::Vanilla | launchers::Launcher::Lunar))
+        .or_else(|| found.iter().max_by_key(|i| i.modified_ms))
+        .map(|i| PathBuf::from(&i.path))
+}
+
+// The log picked with set_log_path, while it's still there
+fn saved_log_path(app: &AppHandle) -> Option<PathBuf> {
+    let path = app.state::<settings::SettingsStore>().get().watcher.log_path?;
+    Some(PathBuf::from(path)).filter(|p| p.is_file())
+}
+
+fn log_not_found() -> LogErrorPayload {
+    LogErrorPayload::new(LogErrorKind::LogNotFound, "Could not find Minecraft log file")
+}
+
+fn legacy_events(app: &AppHandle) -> bool {
+    app.state::<settings::SettingsStore>().get().legacy_events
+}
+
+fn emit_location(app: &AppHandle, path: String, source: LocationSource) {
+    app.state::<Live>().update(|s| {
+        s.log_path = Some(path.clone());
+        s.log_format = None;
+        s.client = None;
+        s.server = None;
+        s.parser = None;
+        s.server_settings = None;
+        s.world = None;
+        s.watching = true;
+        s.last_error = None;
+    });
+    metrics::record(app, |m| &m.events_emitted, 1);
+    let payload = LogLocationPayload {
+        path,
+        source,
+        instance: None,
+    };
+    if legacy_events(app) {
+        app.state::<ws::WsHub>().broadcast(events::LOG_LOCATION, &payload);
+        routing::emit(app, events::LOG_LOCATION, payload.path);
     } else {
-        let home = dirs::home_dir()?;
-        vec![
-            home.join(".minecraft/logs/latest.log"),
-            home.join(".lunarclient/offline/multiver/logs/latest.log"),
-        ]
+        events::emit_all(app, events::LOG_LOCATION, &payload);
+    }
+    tray::refresh(app);
+}
+
+// Brings a log's live state up to date with the text just read from it
+pub(crate) fn note_read(
+    s: &mut LiveState,
+    watcher: &settings::WatcherSettings,
+    offset: u64,
+    end: u64,
+    content: &str,
+) {
+    s.last_update_ms = Some(now_ms());
+    // A successful read clears any earlier read failure
+    s.watching = true;
+    s.last_error = None;
+    if offset == 0 {
+        s.log_head = Some(sync::head(content).to_string());
+    }
+    // Sticky until the log starts over
+    if offset == 0 || s.log_format.is_none() {
+        s.log_format = detect_format(content);
+    }
+    let seen = if offset == 0 { None } else { s.client };
+    s.client = watcher
+        .client
+        .or_else(|| Client::prefer(seen, client::detect(content)));
+    s.log_end = end;
+}
+
+// Emits what was read from the watched log between `offset` and `end`
+pub(crate) fn emit_update(
+    app: &AppHandle,
+    path: &std::path::Path,
+    offset: u64,
+    end: u64,
+    chunk: lines::Capped,
+) {
+    let watcher = app.state::<settings::SettingsStore>().get().watcher;
+    let max = watcher.max_line_bytes;
+    app.state::<Live>()
+        .update(|s| note_read(s, &watcher, offset, end, &chunk.content));
+    metrics::record(app, |m| &m.events_emitted, 1);
+    if legacy_events(app) {
+        // Legacy listeners always expect the whole log
+        let content = match offset {
+            0 => chunk.content,
+            _ => read_log(app, path)
+                .map(|c| lines::cap_lines(tail::strip_bom(&c).to_string(), 0, max).0)
+                .unwrap_or(chunk.content),
+        };
+        routing::emit(app, events::LOG_UPDATE, content);
+    } else {
+        let (content, truncated, dropped) = line_filter::apply(app, live::MAIN, chunk);
+        let payload = LogUpdatePayload {
+            content,
+            offset,
+            end,
+            path: path.to_string_lossy().to_string(),
+            truncated,
+            dropped,
+            replayed: false,
+            source: None,
+        };
+        events::emit_all(app, events::LOG_UPDATE, &payload);
+    }
+    tray::refresh(app);
+}
+
+// Announces a log starting over, ahead of the chunk that reads it afresh
+pub(crate) fn emit_rotated(
+    app: &AppHandle,
+    source: &str,
+    path: &std::path::Path,
+    rotation: tail::Rotation,
+) {
+    let payload = events::LogRotatedPayload {
+        path: path.to_string_lossy().to_string(),
+        reason: match rotation.truncated {
+            true => events::RotationReason::Truncated,
+            false => events::RotationReason::Replaced,
+        },
+        previous_end: rotation.previous_end,
+        source: live::source_field(source),
     };
-    candidates.into_iter().find(|p| p.exists())
+    events::emit_all(app, events::LOG_ROTATED, &payload);
 }
 
-fn hash_content(s: &str) -> u64 {
-    use std::hash::{Hash, Hasher};
-    let mut hasher = std::collections::hash_map::DefaultHasher::new();
-    s.len().hash(&mut hasher);
-    let bytes = s.as_bytes();
-    if bytes.len() > 1024 {
-        bytes[..512].hash(&mut hasher);
-        bytes[bytes.len() - 512..].hash(&mut hasher);
+fn emit_error(app: &AppHandle, payload: LogErrorPayload) {
+    app.state::<Live>().update(|s| {
+        s.watching = false;
+        s.last_error = Some(payload.message.clone());
+    });
+    metrics::record(app, |m| &m.events_emitted, 1);
+    if legacy_events(app) {
+        app.state::<ws::WsHub>().broadcast(events::LOG_ERROR, &payload);
+        routing::emit(app, events::LOG_ERROR, payload.message);
     } else {
-        bytes.hash(&mut hasher);
+        events::emit_all(app, events::LOG_ERROR, &payload);
+    }
+    tray::refresh(app);
+}
+
+pub(crate) fn set_paused(app: &AppHandle, paused: bool) {
+    app.state::<Live>().update(|s| s.paused = paused);
+    events::emit_all(app, events::WATCHER_PAUSED, &events::WatcherPausedPayload { paused });
+    tray::refresh(app);
+}
+
+// Stops the watcher thread and the overlay and API servers before the process
+// exits.
+pub(crate) fn shutdown(app: &AppHandle) {
+    app.state::<Live>().update(|s| {
+        s.stopping = true;
+        s.watching = false;
+    });
+    app.state::<pipeline::Pipeline>().drain();
+    lifecycle::end_sessions(app);
+    // The watched log may be rotated away before it would have gone quiet
+    if let Some(path) = app.state::<Live>().snapshot().log_path {
+        if let Err(e) = followed::store(app, std::path::Path::new(&path)) {
+            eprintln!("{}", e);
+        }
+    }
+    overlay::stop(app);
+    api::stop(app);
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WatcherStatus {
+    pub log_path: Option<String>,
+    pub log_format: Option<LogFormat>,
+    pub client: Option<Client>,
+    pub server: Option<String>,
+    pub world: Option<String>,
+    pub parser: Option<String>,
+    pub watching: bool,
+    pub last_update_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub paused: bool,
+    pub overlay_port: Option<u16>,
+    pub overlay_error: Option<String>,
+    pub ws_clients: usize,
+    pub seq: u64,
+    pub queue_depth: usize,
+    pub line_index_bytes: usize,
+    pub debounce_ms: u64,
+    pub watcher_restarts: u64,
+    // The live state of each instance log, by id
+    pub instances: BTreeMap<String, LiveState>,
+}
+
+pub(crate) fn watcher_status(app: &AppHandle) -> WatcherStatus {
+    let live = app.state::<Live>().snapshot();
+    let overlay = app.state::<overlay::OverlayServer>();
+    WatcherStatus {
+        log_path: live.log_path,
+        log_format: live.log_format,
+        client: live.client,
+        server: live.server,
+        world: live.world,
+        parser: live.parser,
+        watching: live.watching,
+        last_update_ms: live.last_update_ms,
+        last_error: live.last_error,
+        paused: live.paused,
+        overlay_port: overlay.port(),
+        overlay_error: overlay.last_error(),
+        ws_clients: app.state::<ws::WsHub>().client_count(),
+        seq: app.state::<events::EventLog>().seq(),
+        queue_depth: app.state::<pipeline::Pipeline>().depth(),
+        line_index_bytes: app.state::<line_index::LineIndexState>().memory_bytes(),
+        debounce_ms: live.debounce_ms,
+        watcher_restarts: app
+            .state::<metrics::Metrics>()
+            .watcher_restarts
+            .load(std::sync::atomic::Ordering::Relaxed),
+        instances: app.state::<Live>().instances(),
+    }
+}
+
+pub(crate) fn read_log(app: &AppHandle, path: &std::path::Path) -> std::io::Result<String> {
+    read_log_bytes(app, path).map(tail::decode)
+}
+
+// The raw bytes of the watched log, for offsets that have to match the file
+pub(crate) fn read_log_bytes(
+    app: &AppHandle,
+    path: &std::path::Path,
+) -> std::io::Result<Vec<u8>> {
+    let remote = app.state::<settings::SettingsStore>().get().watcher.remote;
+    let bytes = match remote.filter(|r| std::path::Path::new(&remote::url(r)) == path) {
+        Some(remote) => remote::read(&remote)?,
+        None => tail::with_retry(|| std::fs::read(path))?,
+    };
+    metrics::record(app, |m| &m.bytes_read, bytes.len() as u64);
+    Ok(bytes)
+}
+
+fn poll_log(
+    app: &AppHandle,
+    tail: &mut tail::LogTail,
+    feeder: &mut pipeline::Feeder,
+    path: &std::path::Path,
+) -> bool {
+    match perf::time(app, |p| &p.file_read_us, || tail::with_retry(|| tail.poll())) {
+        Ok(Some(chunk)) => {
+            metrics::record(app, |m| &m.bytes_read, chunk.content.len() as u64);
+            perf::record(app, |p| &p.read_bytes, chunk.content.len() as u64);
+            if chunk.offset == 0 {
+                app.state::<Live>().update(|s| s.log_id = tail.id());
+            }
+            if let Some(rotation) = chunk.rotation {
+                emit_rotated(app, live::MAIN, path, rotation);
+            }
+            feeder.push(chunk);
+            true
+        }
+        Ok(None) => false,
+        Err(e) => {
+            emit_error(app, LogErrorPayload::io("Failed to read log", path, &e));
+            false
+        }
     }
-    hasher.finish()
 }
 
 #[tauri::command]
-fn get_log_content() -> Result<String, String> {
-    let path = get_log_path().ok_or("Could not find Minecraft log file")?;
-    fs::read_to_string(&path).map_err(|e| format!("Failed to read log: {}", e))
+fn get_log_content(app: AppHandle) -> Result<String, LogErrorPayload> {
+    let path = saved_log_path(&app).or_else(get_log_path).ok_or_else(log_not_found)?;
+    tail::read_to_string(&path)
+        .map(|content| tail::strip_bom(&content).to_string())
+        .map_err(|e| LogErrorPayload::io("Failed to read log", &path, &e))
 }
 
 #[tauri::command]
-fn get_log_location() -> Result<String, String> {
-    let path = get_log_path().ok_or("Could not find Minecraft log file")?;
+fn get_log_location(app: AppHandle) -> Result<String, LogErrorPayload> {
+    let path = saved_log_path(&app).or_else(get_log_path).ok_or_else(log_not_found)?;
     Ok(path.to_string_lossy().to_string())
 }
 
@@ -78,84 +380,395 @@ fn get_default_paths() -> Vec<String> {
     paths
 }
 
+// Kept for older frontends; check_path says why a path is unusable. Ok(false)
+// means there's no readable regular file there, and a file that exists but
+// can't be read is an error so the frontend can show why.
 #[tauri::command]
-fn validate_path(path: String) -> bool {
-    std::path::Path::new(&path).exists()
+fn validate_path(path: String) -> Result<bool, LogErrorPayload> {
+    let check = path_check::check(std::path::Path::new(&path));
+    match check.error {
+        Some(e) => Err(e),
+        None => Ok(check.usable()),
+    }
 }
 
+/// Saves the log to watch from the next start on, over the auto-detected
+/// one; `None` goes back to auto-detection. A saved log that's gone is
+/// skipped until it's back.
 #[tauri::command]
-fn watch_path(path: String, app: AppHandle) -> Result<(), String> {
-    if !std::path::Path::new(&path).exists() {
-        return Err("File not found".into());
+fn set_log_path(path: Option<String>, app: AppHandle) -> Result<(), String> {
+    if let Some(path) = &path {
+        let check = path_check::check(std::path::Path::new(path));
+        if let Some(e) = check.error {
+            return Err(e.message);
+        }
+        if !check.usable() {
+            return Err(format!("{} is not a readable file", path));
+        }
     }
-    let _ = app.emit("log-location", path.clone());
-    if let Ok(content) = fs::read_to_string(&path) {
-        let _ = app.emit("log-update", content);
+    let store = app.state::<settings::SettingsStore>();
+    let mut settings = store.get();
+    settings.watcher.log_path = path;
+    store.replace(settings)
+}
+
+#[tauri::command]
+fn get_saved_log_path(app: AppHandle) -> Option<String> {
+    app.state::<settings::SettingsStore>().get().watcher.log_path
+}
+
+/// `{"debounce_ms": 250, "poll_ms": 500}`
+#[derive(Serialize, serde::Deserialize, Clone, Debug)]
+pub struct WatcherConfig {
+    pub debounce_ms: u64,
+    pub poll_ms: u64,
+}
+
+/// Saves how long the watcher waits after a change before reading the log,
+/// and how often it checks the log without one, and applies both right
+/// away. The debounce is used as is from then on, turning the adaptive one
+/// off.
+#[tauri::command]
+fn set_watcher_config(config: WatcherConfig, app: AppHandle) -> Result<WatcherConfig, String> {
+    let store = app.state::<settings::SettingsStore>();
+    let mut settings = store.get();
+    settings.watcher.debounce_ms = config.debounce_ms;
+    settings.watcher.adaptive_debounce = false;
+    settings.watcher.poll_ms = config.poll_ms;
+    settings::validate(&settings)?;
+    store.replace(settings)?;
+    app.state::<pipeline::Pipeline>().wake();
+    Ok(config)
+}
+
+#[tauri::command]
+fn get_watcher_status(app: AppHandle) -> WatcherStatus {
+    watcher_status(&app)
+}
+
+#[tauri::command]
+fn set_watching_paused(paused: bool, app: AppHandle) {
+    set_paused(&app, paused);
+}
+
+/// Switches the watcher to another log for the rest of the session; the
+/// previous one's queued lines are emitted first. Use `set_log_path` to keep
+/// the choice across restarts.
+#[tauri::command]
+fn watch_path(path: String, app: AppHandle) -> Result<(), LogErrorPayload> {
+    let path = PathBuf::from(path);
+    if !path.exists() {
+        return Err(
+            LogErrorPayload::new(LogErrorKind::LogNotFound, "File not found").with_path(&path),
+        );
+    }
+    if let Some(err) = path_check::check(&path).error {
+        emit_error(&app, err.clone());
+        return Err(err);
     }
+    let control = app.state::<WatchControl>();
+    if control.remote.load(Ordering::Relaxed) {
+        return Err(
+            LogErrorPayload::new(LogErrorKind::Internal, "A remote log is being watched")
+                .with_path(&path),
+        );
+    }
+    if let Ok(mut target) = control.target.lock() {
+        *target = Some(path);
+    }
+    app.state::<pipeline::Pipeline>().wake();
     Ok(())
 }
 
-fn start_watcher(app: AppHandle) {
-    std::thread::spawn(move || {
-        let path = loop {
-            if let Some(p) = get_log_path() {
-                break p;
-            }
-            let _ = app.emit("log-error", "Minecraft log file not found");
-            std::thread::sleep(Duration::from_secs(5));
-        };
+fn watch_dir(path: &std::path::Path) -> Option<&std::path::Path> {
+    path.parent().filter(|dir| !dir.as_os_str().is_empty())
+}
+
+// Watching the folder survives the game replacing the file; a path
+// without one falls back to watching the file itself, with an error saying so
+fn watch_target(path: &std::path::Path) -> (&std::path::Path, Option<LogErrorPayload>) {
+    match watch_dir(path) {
+        Some(dir) => (dir, None),
+        None => {
+            let err = LogErrorPayload::new(
+                LogErrorKind::WatchSetupFailed,
+                "Log path has no parent folder, watching the file directly",
+            )
+            .with_path(path);
+            (path, Some(err))
+        }
+    }
+}
+
+fn stopping(app: &AppHandle) -> bool {
+    app.state::<Live>().snapshot().stopping
+}
 
-        let _ = app.emit("log-location", path.to_string_lossy().to_string());
+// Sleeps in short steps so shutdown isn't held up by a long backoff.
+fn sleep_unless_stopping(app: &AppHandle, duration: Duration) {
+    let until = Instant::now() + duration;
+    while !stopping(app) && Instant::now() < until {
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
 
-        let mut last_hash: u64 = 0;
-        if let Ok(content) = fs::read_to_string(&path) {
-            last_hash = hash_content(&content);
-            let _ = app.emit("log-update", content);
+fn resolve_log_path(app: &AppHandle) -> Option<(PathBuf, LocationSource)> {
+    loop {
+        if stopping(app) {
+            return None;
+        }
+        if let Some(p) = app.state::<WatchControl>().target() {
+            return Some((p, LocationSource::User));
         }
+        if let Some(p) = saved_log_path(app) {
+            return Some((p, LocationSource::User));
+        }
+        if let Some(p) = get_log_path() {
+            return Some((p, LocationSource::AutoDetected));
+        }
+        emit_error(
+            app,
+            LogErrorPayload::new(LogErrorKind::LogNotFound, "Minecraft log file not found"),
+        );
+        sleep_unless_stopping(app, Duration::from_secs(5));
+    }
+}
 
-        let (tx, rx) = channel::<Result<Event, notify::Error>>();
-        let mut watcher = match RecommendedWatcher::new(
-            tx,
-            Config::default().with_poll_interval(Duration::from_secs(2)),
-        ) {
-            Ok(w) => w,
-            Err(e) => {
-                let _ = app.emit("log-error", format!("Watcher error: {}", e));
-                return;
-            }
+type WatchEvent = notify::Result<Event>;
+
+// The notify handler holds the only strong sender, so the channel
+// disconnects once the backend drops it; the pipeline just gets a way to
+// wake the loop
+fn watch_channel(
+    pipeline: &pipeline::Pipeline,
+) -> (impl FnMut(WatchEvent) + Send + 'static, Receiver<WatchEvent>) {
+    let (tx, rx) = channel();
+    let tx = Arc::new(tx);
+    pipeline.set_wake(Arc::downgrade(&tx));
+    (move |event| drop(tx.send(event)), rx)
+}
+
+// Whether what the watcher received is a change to the log, or an error when
+// the watcher's gone
+fn log_event(
+    received: Result<WatchEvent, RecvTimeoutError>,
+    path: &std::path::Path,
+) -> Result<bool, LogErrorPayload> {
+    match received {
+        Ok(Ok(event)) => Ok(event
+            .paths
+            .iter()
+            .any(|p| p.file_name().is_some() && p.file_name() == path.file_name())),
+        Ok(Err(_)) | Err(RecvTimeoutError::Timeout) => Ok(false),
+        Err(RecvTimeoutError::Disconnected) => Err(LogErrorPayload::new(
+            LogErrorKind::Internal,
+            "Watcher stopped unexpectedly",
+        )
+        .with_path(path)),
+    }
+}
+
+// Runs the notify event loop until shutdown, a switch to another log or the
+// log being deleted (Ok), or until the watcher can't continue (Err), in
+// which case the caller rebuilds it.
+fn watch_log(
+    app: &AppHandle,
+    path: &std::path::Path,
+    tail: &mut tail::LogTail,
+    feeder: &mut pipeline::Feeder,
+) -> Result<(), LogErrorPayload> {
+    let (handler, rx) = watch_channel(&app.state::<pipeline::Pipeline>());
+    // A new poll interval rebuilds the watcher, which only takes it here
+    let poll_ms = app.state::<settings::SettingsStore>().get().watcher.poll_ms;
+    let poll = Duration::from_millis(poll_ms);
+    let mut watcher = RecommendedWatcher::new(handler, Config::default().with_poll_interval(poll))
+        .map_err(|e| LogErrorPayload::watch("Watcher error", path, &e))?;
+
+    let (target, fallback) = watch_target(path);
+    if let Some(err) = fallback {
+        emit_error(app, err);
+    }
+    watcher
+        .watch(target, RecursiveMode::NonRecursive)
+        .map_err(|e| LogErrorPayload::watch("Watch error", path, &e))?;
+
+    let mut last_emit = Instant::now();
+    let mut adaptive = debounce::AdaptiveDebounce::default();
+    let mut debounce = Duration::from_secs(2);
+    let mut was_paused = false;
+    // A change that arrived inside the debounce window, read once it has passed
+    let mut deferred = false;
+
+    loop {
+        let timeout = if deferred {
+            debounce
+                .saturating_sub(last_emit.elapsed())
+                .max(Duration::from_millis(10))
+        } else {
+            poll
         };
+        let received = rx.recv_timeout(timeout);
+        let state = app.state::<Live>().snapshot();
+        if state.stopping {
+            // Pick up the session's last lines before the worker drains
+            if !state.paused {
+                poll_log(app, tail, feeder, path);
+            }
+            return Ok(());
+        }
+        feeder.flush();
+        if app.state::<WatchControl>().target().is_some_and(|t| t != path) {
+            return Ok(());
+        }
+        // Gone with its folder, the folder's watch goes too
+        if !path.exists() {
+            return Ok(());
+        }
+        if state.paused {
+            was_paused = true;
+            continue;
+        }
+        // Changes made while paused were dropped, so catch up on resume
+        let resumed = std::mem::take(&mut was_paused);
+        // Nothing reported for a poll interval: look anyway, in case the
+        // platform's change events missed a write
+        let polled = !deferred && matches!(received, Err(RecvTimeoutError::Timeout));
+        let is_log = log_event(received, path)?;
 
-        if let Err(e) = watcher.watch(path.parent().unwrap(), RecursiveMode::NonRecursive) {
-            let _ = app.emit("log-error", format!("Watch error: {}", e));
-            return;
+        let now = Instant::now();
+        if is_log {
+            adaptive.observe(now);
+        }
+        let settings = app.state::<settings::SettingsStore>().get().watcher;
+        if settings.poll_ms != poll_ms {
+            return Ok(());
+        }
+        debounce = adaptive.effective(&settings, now);
+        app.state::<Live>()
+            .update(|s| s.debounce_ms = debounce.as_millis() as u64);
+
+        deferred |= is_log;
+        if resumed || polled || (deferred && last_emit.elapsed() >= debounce) {
+            deferred = false;
+            if poll_log(app, tail, feeder, path) {
+                last_emit = Instant::now();
+            }
         }
+    }
+}
+
+// Waits for a deleted log to be created again, looking every second since
+// its folder may be gone too and can't be watched. Gives up on a switch to
+// another log or shutdown.
+fn wait_for_log(app: &AppHandle, path: &std::path::Path) {
+    let waiting = || {
+        !stopping(app)
+            && !path.exists()
+            && app.state::<WatchControl>().target().is_none_or(|t| t == path)
+    };
+    if !waiting() {
+        return;
+    }
+    emit_error(
+        app,
+        LogErrorPayload::new(
+            LogErrorKind::LogNotFound,
+            "Log was deleted, waiting for it to be created again",
+        )
+        .with_path(path),
+    );
+    while waiting() {
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(30);
+// A watcher that ran this long without failing resets the backoff
+const RESTART_STABLE_AFTER: Duration = Duration::from_secs(60);
+
+// Counts a watcher failure and gives the wait before rebuilding it, which
+// doubles while the watcher keeps failing
+fn restart_delay(metrics: &metrics::Metrics, backoff: &mut Duration, ran: Duration) -> Duration {
+    if ran >= RESTART_STABLE_AFTER {
+        *backoff = Duration::from_secs(1);
+    }
+    metrics.watcher_restarts.fetch_add(1, Ordering::Relaxed);
+    let delay = *backoff;
+    *backoff = (*backoff * 2).min(RESTART_BACKOFF_MAX);
+    delay
+}
+
+// Steers the watcher thread; it reads these each time it wakes
+#[derive(Default)]
+pub struct WatchControl {
+    // The log picked with watch_path, over the saved and detected ones
+    target: Mutex<Option<PathBuf>>,
+    // Following a remote log, which watch_path can't switch away from
+    remote: AtomicBool,
+}
+
+impl WatchControl {
+    fn target(&self) -> Option<PathBuf> {
+        self.target.lock().ok().and_then(|t| t.clone())
+    }
+}
 
-        let mut last_emit = Instant::now();
-        let debounce = Duration::from_secs(2);
-
-        loop {
-            match rx.recv_timeout(Duration::from_secs(3)) {
-                Ok(Ok(event)) => {
-                    let is_log = event
-                        .paths
-                        .iter()
-                        .any(|p| p.file_name().map(|f| f == "latest.log").unwrap_or(false));
-
-                    if is_log && last_emit.elapsed() >= debounce {
-                        if let Ok(content) = fs::read_to_string(&path) {
-                            let new_hash = hash_content(&content);
-                            if new_hash != last_hash {
-                                last_hash = new_hash;
-                                last_emit = Instant::now();
-                                let _ = app.emit("log-update", content);
-                            }
-                        }
+fn start_watcher(app: AppHandle) {
+    std::thread::spawn(move || {
+        if let Some(source) = app.state::<settings::SettingsStore>().get().watcher.remote {
+            app.state::<WatchControl>().remote.store(true, Ordering::Relaxed);
+            remote::follow(&app, source);
+            return;
+        }
+        let mut current: Option<(PathBuf, tail::LogTail, pipeline::Feeder)> = None;
+        let mut backoff = Duration::from_secs(1);
+        // The log was deleted and is back, to be announced again
+        let mut reattach = false;
+        // Re-resolved on every restart and switch in case the log moved
+        while let Some((path, source)) = resolve_log_path(&app) {
+            let (mut tail, mut feeder) = match current.take() {
+                Some((previous, mut tail, mut feeder)) if previous == path => {
+                    if std::mem::take(&mut reattach) {
+                        emit_location(&app, path.to_string_lossy().to_string(), source);
+                        poll_log(&app, &mut tail, &mut feeder, &path);
                     }
+                    (tail, feeder)
                 }
-                Ok(Err(_)) => {}
-                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
-                Err(_) => break,
-            }
+                previous => {
+                    reattach = false;
+                    if let Some((_, _, feeder)) = previous {
+                        feeder.finish();
+                    }
+                    emit_location(&app, path.to_string_lossy().to_string(), source);
+                    let mut tail = tail::LogTail::new(&path);
+                    let mut feeder = pipeline::start(&app, live::MAIN, &path);
+                    poll_log(&app, &mut tail, &mut feeder, &path);
+                    (tail, feeder)
+                }
+            };
+
+            let started = Instant::now();
+            let result = watch_log(&app, &path, &mut tail, &mut feeder);
+            let deleted = !path.exists();
+            current = Some((path.clone(), tail, feeder));
+            let Err(mut err) = result else {
+                if stopping(&app) {
+                    break;
+                }
+                if deleted {
+                    wait_for_log(&app, &path);
+                    reattach = path.exists();
+                }
+                continue;
+            };
+            let metrics = app.state::<metrics::Metrics>();
+            let delay = restart_delay(&metrics, &mut backoff, started.elapsed());
+            err.message = format!("{}; restarting in {}s", err.message, delay.as_secs());
+            emit_error(&app, err);
+            sleep_unless_stopping(&app, delay);
+        }
+        if let Some((_, _, feeder)) = current {
+            feeder.finish();
         }
     });
 }
@@ -164,18 +777,274 @@ fn start_watcher(app: AppHandle) {
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![autostart::AUTOSTART_FLAG]),
+        ))
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(hotkeys::on_shortcut)
+                .build(),
+        )
         .invoke_handler(tauri::generate_handler![
             get_log_content,
             get_log_location,
             get_default_paths,
+            launchers::list_instances,
             validate_path,
-            watch_path
+            path_check::check_path,
+            crash::get_last_crash,
+            servers::get_server_parsers,
+            servers::set_server_parser,
+            servers::list_server_settings,
+            servers::get_server_settings,
+            servers::set_server_settings,
+            definitions::get_run_definitions,
+            definitions::save_run_definition,
+            definitions::delete_run_definition,
+            definitions::reorder_run_definitions,
+            goals::get_goals,
+            goals::create_goal,
+            goals::update_goal,
+            goals::delete_goal,
+            watch_path,
+            instances::add_watch,
+            instances::remove_watch,
+            set_log_path,
+            get_saved_log_path,
+            set_watcher_config,
+            get_watcher_status,
+            set_watching_paused,
+            db::query_runs,
+            db::get_run,
+            db::get_sessions,
+            db::get_sum_of_best,
+            pb::get_pb,
+            pb::reset_pb,
+            db::get_player_leaderboard,
+            db::update_run,
+            db::delete_run,
+            db::list_map_aliases,
+            db::merge_maps,
+            db::split_alias,
+            stats::get_activity_stats,
+            stats::get_activity_heatmap,
+            stats::get_split_distribution,
+            stats::get_map_stats,
+            lss::export_lss,
+            export::export_runs,
+            analyze::analyze_file,
+            archives::get_archived_logs,
+            archives::read_archived_log,
+            anonymize::export_anonymized_log,
+            anonymize::export_sanitized_log,
+            anonymize::set_redaction_rules,
+            overlay::start_overlay_server,
+            overlay::stop_overlay_server,
+            api::set_api_enabled,
+            api::reset_api_token,
+            settings::get_settings,
+            settings::set_settings,
+            webhooks::get_webhook_status,
+            webhooks::test_webhook,
+            livesplit::test_livesplit_connection,
+            presence::set_presence_enabled,
+            remote::test_remote_connection,
+            remote::set_remote_secret,
+            notifications::set_notification_events,
+            notifications::test_notification,
+            summary::copy_run_summary,
+            templates::render_template,
+            report::generate_session_report,
+            report::generate_comparison_report,
+            compare::compare_runs,
+            markers::add_marker,
+            markers::list_markers,
+            chat_commands::set_chat_commands,
+            autostart::get_autostart,
+            autostart::set_autostart,
+            deep_link::take_pending_navigation,
+            sync::sync,
+            line_filter::set_line_filters,
+            events::get_event_schema,
+            events::get_events_since,
+            replay::replay_from_offset,
+            replay::cancel_replay,
+            replay::start_log_replay,
+            replay::replay_log,
+            replay::pause_replay,
+            replay::seek_replay,
+            replay::stop_replay,
+            rate_limit::get_rate_limits,
+            routing::subscribe,
+            routing::open_overlay_window,
+            line_index::get_lines,
+            line_index::get_line_at_offset,
+            perf::get_performance_stats,
+            perf::reset_performance_stats,
+            permissions::request_file_access
         ])
         .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            fs::create_dir_all(&data_dir)?;
+            let db = db::Db::open(&data_dir.join("history.db")).or_else(|e| {
+                eprintln!("{}, falling back to in-memory history", e);
+                db::Db::open_in_memory()
+            })?;
+            app.manage(db);
+            app.manage(Live::default());
+            app.manage(events::EventLog::new(
+                settings::EventSettings::default().buffer_size,
+            ));
+            app.manage(metrics::Metrics::default());
+            app.manage(perf::Perf::default());
+            app.manage(overlay::OverlayServer::default());
+            app.manage(api::ApiServer::default());
+            app.manage(ws::WsHub::default());
+            app.manage(webhooks::Webhooks::default());
+            app.manage(livesplit::LiveSplit::default());
+            app.manage(text_output::TextOutput::default());
+            app.manage(deep_link::PendingNavigation::default());
+            app.manage(replay::Replay::default());
+            app.manage(rate_limit::RateLimiter::default());
+            app.manage(routing::Subscriptions::default());
+            app.manage(pipeline::Pipeline::default());
+            app.manage(WatchControl::default());
+            app.manage(line_index::LineIndexState::default());
+            app.manage(line_filter::LineFilters::default());
+            app.manage(crash::CrashState::default());
+            app.manage(servers::ServerState::default());
+            app.manage(definitions::DefinedRuns::default());
+            app.manage(parser::LiveRuns::default());
+            app.manage(pb::LiveBests::default());
+            app.manage(lifecycle::Sessions::default());
+
+            let config_dir = app.path().app_config_dir()?;
+            let store = settings::SettingsStore::load(config_dir.join("settings.json"));
+            let overlay_settings = store.get().overlay;
+            let api_settings = store.get().api;
+            let event_settings = store.get().events;
+            app.state::<events::EventLog>()
+                .set_capacity(event_settings.buffer_size);
+            app.state::<rate_limit::RateLimiter>()
+                .set_limits(&event_settings.rate_limits);
+            app.manage(store);
+            notifications::init(app.handle());
+            hotkeys::init(app.handle());
+            if let Err(e) = tray::init(app.handle()) {
+                eprintln!("Failed to create tray icon: {}", e);
+            }
+            deep_link::init(app.handle());
+            if autostart::launched_at_login() {
+                autostart::start_hidden(app.handle());
+            }
+            text_output::refresh(app.handle());
+            if overlay_settings.enabled {
+                if let Err(e) = overlay::start(app.handle(), overlay_settings.port) {
+                    eprintln!("{}", e);
+                }
+            }
+            if let Err(e) = api::apply_settings(app.handle(), &api_settings) {
+                eprintln!("{}", e);
+            }
+
             let handle = app.handle().clone();
             start_watcher(handle);
+            followed::start(app.handle().clone());
+            instances::start(app.handle().clone());
+            timer::start(app.handle().clone());
+            presence::start(app.handle().clone());
+            line_filter::start(app.handle().clone());
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::CloseRequested { api, .. } => {
+                let store = window.state::<settings::SettingsStore>();
+                if window.label() == "main" && store.get().tray.close_to_tray {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+            tauri::WindowEvent::Destroyed => {
+                window.state::<routing::Subscriptions>().remove(window.label());
+            }
+            tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. })
+                if window.label() == "main" =>
+            {
+                analyze::on_drop(window.app_handle(), paths);
+            }
+            _ => {}
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                shutdown(app);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn parentless_paths_fall_back_to_the_file() {
+        for path in ["/", "", "latest.log"] {
+            let path = Path::new(path);
+            let (target, err) = watch_target(path);
+            assert_eq!(target, path);
+            let err = err.unwrap();
+            assert_eq!(err.kind, LogErrorKind::WatchSetupFailed);
+            assert_eq!(err.path.as_deref(), path.to_str());
+        }
+        let (target, err) = watch_target(Path::new("/latest.log"));
+        assert_eq!((target, err.is_none()), (Path::new("/"), true));
+        let (target, err) = watch_target(Path::new("logs/latest.log"));
+        assert_eq!((target, err.is_none()), (Path::new("logs"), true));
+    }
+
+    #[test]
+    fn parentless_paths_are_checked_and_watched() {
+        let root = Path::new("/");
+        let (target, _) = watch_target(root);
+        let mut watcher = notify::recommended_watcher(|_| {}).unwrap();
+        assert!(watcher.watch(target, RecursiveMode::NonRecursive).is_ok());
+        let check = path_check::check(root);
+        assert!(check.exists && !check.is_file && check.error.is_none());
+        let check = path_check::check(Path::new(""));
+        assert!(!check.exists && check.error.is_none());
+    }
+
+    #[test]
+    fn a_dropped_watcher_is_restarted() {
+        let path = Path::new("logs/latest.log");
+        let pipeline = pipeline::Pipeline::default();
+        let (handler, rx) = watch_channel(&pipeline);
+        let watcher = RecommendedWatcher::new(handler, Config::default()).unwrap();
+        // Waking isn't mistaken for a change or a failure
+        pipeline.wake();
+        let woken = rx.recv_timeout(Duration::from_secs(5));
+        assert!(matches!(woken, Ok(Err(_))));
+        assert!(matches!(log_event(woken, path), Ok(false)));
+
+        drop(watcher);
+        let received = rx.recv_timeout(Duration::from_secs(5));
+        assert!(matches!(received, Err(RecvTimeoutError::Disconnected)));
+        let err = log_event(received, path).unwrap_err();
+        assert_eq!(err.kind, LogErrorKind::Internal);
+        pipeline.wake();
+
+        let metrics = metrics::Metrics::default();
+        let mut backoff = Duration::from_secs(1);
+        let delays: Vec<_> = [0, 0, 0, 61]
+            .map(|s| restart_delay(&metrics, &mut backoff, Duration::from_secs(s)).as_secs())
+            .into();
+        assert_eq!(delays, [1, 2, 4, 1]);
+        assert_eq!(metrics.watcher_restarts.load(Ordering::Relaxed), 4);
+    }
 }
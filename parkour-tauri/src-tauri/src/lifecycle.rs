@@ -0,0 +1,158 @@
+use crate::events;
+use crate::goals;
+use crate::hotkeys::RunResetPayload;
+use crate::live::{now_ms, source_field, CurrentRun, Live, MAIN};
+use crate::livesplit::{LiveSplit, RunSignal};
+use crate::metrics;
+use crate::notifications;
+use crate::pb;
+use crate::text_output;
+use crate::timer;
+use crate::tray;
+use crate::webhooks::{self, RunSummary, SessionSummary, WebhookEvent};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+// The player's runs on one source since its log started
+struct Session {
+    username: Option<String>,
+    started_ms: u64,
+    attempts: i64,
+    completions: i64,
+    pbs: i64,
+}
+
+// One session per watched source, from its first run on
+#[derive(Default)]
+pub struct Sessions(Mutex<BTreeMap<String, Session>>);
+
+fn tally(app: &AppHandle, source: &str, username: Option<&str>, f: impl FnOnce(&mut Session)) {
+    let state = app.state::<Sessions>();
+    let Ok(mut sessions) = state.0.lock() else {
+        return;
+    };
+    let session = sessions
+        .entry(source.to_string())
+        .or_insert_with(|| Session {
+            username: None,
+            started_ms: now_ms(),
+            attempts: 0,
+            completions: 0,
+            pbs: 0,
+        });
+    if let Some(username) = username {
+        session.username = Some(username.to_string());
+    }
+    f(session);
+}
+
+// LiveSplit, the text files and the tray follow the watched log's run; the
+// rest applies to every source
+fn signal(app: &AppHandle, source: &str, map: Option<&str>, signal: RunSignal) {
+    if source != MAIN {
+        return;
+    }
+    app.state::<LiveSplit>().signal(app, map, signal);
+    text_output::refresh(app);
+}
+
+/// A live run just started on `source`.
+pub fn started(app: &AppHandle, source: &str, run: &CurrentRun) {
+    metrics::record(app, |m| &m.runs_detected, 1);
+    tally(app, source, run.username.as_deref(), |s| s.attempts += 1);
+    signal(app, source, run.map.as_deref(), RunSignal::Start);
+}
+
+/// The player's live run on `source` just reached the last of its splits.
+pub fn split(app: &AppHandle, source: &str, run: &CurrentRun) {
+    timer::split_recorded(app, source, run);
+    pb::compare(app, source, run);
+    signal(app, source, run.map.as_deref(), RunSignal::Checkpoint);
+}
+
+/// The player's live run on `source` just finished in `time_ms`. A practice
+/// run only stops the timer, as a reset would.
+pub fn finished(app: &AppHandle, source: &str, run: &CurrentRun, time_ms: i64) {
+    let map = run.map.as_deref().unwrap_or_default();
+    let username = run.username.as_deref();
+    if run.practice {
+        signal(app, source, run.map.as_deref(), RunSignal::Reset);
+        return;
+    }
+    signal(app, source, run.map.as_deref(), RunSignal::Finish);
+    goals::on_completion(app, map, username, time_ms);
+    let Some(finish) = pb::finish(app, map, username, time_ms) else {
+        return;
+    };
+    let pb = finish.is_pb();
+    tally(app, source, username, |s| {
+        s.completions += 1;
+        s.pbs += pb as i64;
+    });
+    notifications::notify_finish(app, &finish);
+    if pb {
+        tray::pb_achieved(app, &finish.map, time_ms);
+    }
+    let summary = RunSummary {
+        map: finish.map.clone(),
+        username: username.unwrap_or_default().to_string(),
+        server: app.state::<Live>().source(source).and_then(|s| s.server),
+        time_ms,
+        improvement_ms: finish.improvement_ms(),
+        // Live runs don't count fails; the import of the log does
+        fails: 0,
+    };
+    let event = if pb {
+        WebhookEvent::Pb(summary)
+    } else {
+        WebhookEvent::Completion(summary)
+    };
+    webhooks::notify(app, event);
+}
+
+/// The live run on `source` was given up on, by the reset hotkey or the
+/// server's reset message.
+pub fn reset(app: &AppHandle, source: &str, run: CurrentRun) {
+    signal(app, source, run.map.as_deref(), RunSignal::Reset);
+    let payload = RunResetPayload {
+        elapsed_ms: run.elapsed_ms(),
+        splits: run.splits.len(),
+        map: run.map,
+        source: source_field(source),
+    };
+    events::emit_all(app, events::RUN_RESET, &payload);
+}
+
+/// Closes the session on `source`, e.g. when its log starts over or the
+/// game crashed, sending its summary to webhooks if any run was started.
+pub fn end_session(app: &AppHandle, source: &str) {
+    let session = match app.state::<Sessions>().0.lock() {
+        Ok(mut sessions) => sessions.remove(source),
+        Err(_) => return,
+    };
+    let Some(session) = session.filter(|s| s.attempts > 0) else {
+        return;
+    };
+    webhooks::notify(
+        app,
+        WebhookEvent::SessionSummary(SessionSummary {
+            username: session.username.unwrap_or_default(),
+            duration_ms: now_ms().saturating_sub(session.started_ms) as i64,
+            attempts: session.attempts,
+            completions: session.completions,
+            pbs: session.pbs,
+        }),
+    );
+}
+
+/// Closes every source's session, at shutdown.
+pub fn end_sessions(app: &AppHandle) {
+    let sources: Vec<String> = match app.state::<Sessions>().0.lock() {
+        Ok(sessions) => sessions.keys().cloned().collect(),
+        Err(_) => return,
+    };
+    for source in sources {
+        end_session(app, &source);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::events;
+use crate::lines::{Capped, TruncatedLine};
+use crate::live::source_field;
+use crate::settings::{LineFilterSettings, SettingsStore};
+use parkour_core::line_filter::LineFilter;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+/// `{"kept": 40, "dropped": 1520, "interval_ms": 10000}`
+///
+/// How many lines of a log `watcher.line_filters` let through to
+/// `log-update` and left out over the last `interval_ms`. Sent every
+/// `summary_ms` for each log that had lines left out.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct LinesFilteredPayload {
+    pub kept: usize,
+    pub dropped: usize,
+    pub interval_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Default)]
+struct Filters {
+    // Compiled from the saved patterns, recompiled when they change
+    filter: LineFilter,
+    // Lines kept and dropped per source since the last summary
+    counts: BTreeMap<String, (usize, usize)>,
+}
+
+#[derive(Default)]
+pub struct LineFilters(Mutex<Filters>);
+
+/// The lines of `chunk` from `source` that `log-update` carries, and how
+/// many were left out when filters are set.
+pub fn apply(
+    app: &AppHandle,
+    source: &str,
+    chunk: Capped,
+) -> (String, Vec<TruncatedLine>, Option<usize>) {
+    let settings = app.state::<SettingsStore>().get().watcher.line_filters;
+    let state = app.state::<LineFilters>();
+    let Ok(mut filters) = state.0.lock() else {
+        return (chunk.content, chunk.truncated, None);
+    };
+    if filters.filter.patterns() != settings.patterns {
+        // Saved patterns were validated, so this only fails on a hand-edited
+        // settings file
+        filters.filter = LineFilter::compile(&settings.patterns).unwrap_or_default();
+    }
+    if filters.filter.is_empty() {
+        return (chunk.content, chunk.truncated, None);
+    }
+    let filtered = filters.filter.apply(&chunk);
+    let counts = filters.counts.entry(source.to_string()).or_default();
+    counts.0 += filtered.kept;
+    counts.1 += filtered.dropped;
+    (filtered.content, filtered.truncated, Some(filtered.dropped))
+}
+
+/// Sends `lines-filtered` every `summary_ms` while summaries are on, until
+/// shutdown.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut since = Instant::now();
+        while !crate::stopping(&app) {
+            let summary_ms = app
+                .state::<SettingsStore>()
+                .get()
+                .watcher
+                .line_filters
+                .summary_ms;
+            std::thread::sleep(Duration::from_millis(summary_ms.unwrap_or(1000)));
+            let counts = match app.state::<LineFilters>().0.lock() {
+                Ok(mut filters) => std::mem::take(&mut filters.counts),
+                Err(_) => continue,
+            };
+            let interval_ms = since.elapsed().as_millis() as u64;
+            since = Instant::now();
+            if summary_ms.is_none() {
+                continue;
+            }
+            for (source, (kept, dropped)) in counts {
+                if dropped == 0 {
+                    continue;
+                }
+                let payload = LinesFilteredPayload {
+                    kept,
+                    dropped,
+                    interval_ms,
+                    source: source_field(&source),
+                };
+                events::emit(&app, events::LINES_FILTERED, &payload);
+            }
+        }
+    });
+}
+
+/// `{"patterns": ["\\[CHAT\\].*Checkpoint"], "summary_ms": 10000}`
+///
+/// Saves the regexes a line has to match one of to be sent in `log-update`;
+/// none sends every line again. The run parsers still read every line.
+/// `summary_ms` turns on `lines-filtered` summaries that often.
+#[tauri::command]
+pub fn set_line_filters(
+    patterns: Vec<String>,
+    summary_ms: Option<u64>,
+    store: State<'_, SettingsStore>,
+) -> Result<LineFilterSettings, String> {
+    let mut settings = store.get();
+    settings.watcher.line_filters = LineFilterSettings {
+        patterns,
+        summary_ms,
+    };
+    crate::settings::validate(&settings)?;
+    store.replace(settings.clone())?;
+    Ok(settings.watcher.line_filters)
+}
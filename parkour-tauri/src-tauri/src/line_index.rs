@@ -0,0 +1,219 @@
+use crate::lines::{cap, strip_terminator_bytes, TruncatedLine};
+use crate::live::Live;
+use crate::settings::SettingsStore;
+use crate::tail::{decode, Chunk};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+// Byte offset of every STRIDE-th line.
+const STRIDE: u64 = 1000;
+const MAX_LINES: usize = 10_000;
+
+pub struct LineIndex {
+    path: PathBuf,
+    checkpoints: Vec<u64>,
+    lines: u64,
+    end: u64,
+}
+
+impl LineIndex {
+    fn new(path: &Path) -> LineIndex {
+        LineIndex {
+            path: path.to_path_buf(),
+            checkpoints: vec![0],
+            lines: 0,
+            end: 0,
+        }
+    }
+
+    fn build(path: &Path) -> io::Result<LineIndex> {
+        let mut index = LineIndex::new(path);
+        let mut reader = BufReader::with_capacity(64 * 1024, File::open(path)?);
+        loop {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let len = buf.len();
+            index.append(index.end, buf);
+            reader.consume(len);
+        }
+        Ok(index)
+    }
+
+    // `start` is the raw file offset of `bytes`.
+    fn append(&mut self, start: u64, bytes: &[u8]) {
+        for (i, _) in bytes.iter().enumerate().filter(|(_, b)| **b == b'\n') {
+            self.lines += 1;
+            if self.lines.is_multiple_of(STRIDE) {
+                self.checkpoints.push(start + i as u64 + 1);
+            }
+        }
+        self.end = start + bytes.len() as u64;
+    }
+
+    // Nearest indexed line at or before `line`, as (line, byte offset).
+    fn before_line(&self, line: u64) -> (u64, u64) {
+        let k = ((line / STRIDE) as usize).min(self.checkpoints.len() - 1);
+        (k as u64 * STRIDE, self.checkpoints[k])
+    }
+
+    fn before_offset(&self, offset: u64) -> (u64, u64) {
+        let k = self.checkpoints.partition_point(|&c| c <= offset).max(1) - 1;
+        (k as u64 * STRIDE, self.checkpoints[k])
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.checkpoints.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+// Built lazily on the first query and then kept up to date from the chunks
+// the watcher reads; nothing is persisted.
+#[derive(Default)]
+pub struct LineIndexState(Mutex<Option<LineIndex>>);
+
+impl LineIndexState {
+    pub fn memory_bytes(&self) -> usize {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|i| i.as_ref().map(LineIndex::memory_bytes))
+            .unwrap_or(0)
+    }
+
+    // A chunk decoded from cp1252 doesn't hold the file's bytes, so its
+    // newlines can't be placed from it
+    pub fn record(&self, path: &Path, chunk: &Chunk) {
+        let Ok(mut index) = self.0.lock() else {
+            return;
+        };
+        match index.as_mut() {
+            Some(i) if chunk.is_raw() && chunk.offset == 0 && i.path == path => {
+                *i = LineIndex::new(path)
+            }
+            Some(i) if chunk.is_raw() && i.path == path && i.end == chunk.offset => {}
+            // Missed a range, switched files or got a decoded chunk; rebuild
+            // on the next query
+            _ => {
+                *index = None;
+                return;
+            }
+        }
+        if let Some(i) = index.as_mut() {
+            i.append(chunk.start, chunk.content.as_bytes());
+        }
+    }
+
+    fn start_for(
+        &self,
+        path: &Path,
+        f: impl FnOnce(&LineIndex) -> (u64, u64),
+    ) -> Result<(u64, u64), String> {
+        let mut index = self.0.lock().map_err(|_| "Line index lock poisoned")?;
+        if index.as_ref().is_none_or(|i| i.path != path) {
+            let built =
+                LineIndex::build(path).map_err(|e| format!("Failed to index log: {}", e))?;
+            *index = Some(built);
+        }
+        Ok(index.as_ref().map(f).unwrap_or((0, 0)))
+    }
+}
+
+fn watched_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.state::<Live>()
+        .snapshot()
+        .log_path
+        .map(PathBuf::from)
+        .ok_or_else(|| "No log file is being watched".into())
+}
+
+fn reader_at(path: &Path, offset: u64) -> Result<BufReader<File>, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to read log: {}", e))?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to read log: {}", e))?;
+    Ok(BufReader::new(file))
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Lines {
+    pub start_line: u64,
+    pub lines: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub truncated: Vec<TruncatedLine>,
+}
+
+/// Line numbers start at 0. Lines over `watcher.max_line_bytes` are cut short
+/// and listed in `truncated`.
+#[tauri::command]
+pub fn get_lines(
+    start_line: u64,
+    count: usize,
+    app: AppHandle,
+    index: State<'_, LineIndexState>,
+) -> Result<Lines, String> {
+    let path = watched_path(&app)?;
+    let max = app.state::<SettingsStore>().get().watcher.max_line_bytes;
+    let (mut line, mut offset) = index.start_for(&path, |i| i.before_line(start_line))?;
+    let mut lines = Vec::new();
+    let mut truncated = Vec::new();
+    let mut reader = reader_at(&path, offset)?;
+    let mut buf = Vec::new();
+    while lines.len() < count.min(MAX_LINES) {
+        buf.clear();
+        let read = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| format!("Failed to read log: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        if line >= start_line {
+            let raw = strip_terminator_bytes(&buf);
+            let text = decode(raw.to_vec());
+            let text = if line == 0 {
+                crate::tail::strip_bom(&text)
+            } else {
+                &text
+            };
+            let bom = if line == 0 && raw.starts_with("\u{feff}".as_bytes()) {
+                3
+            } else {
+                0
+            };
+            if raw.len() as u64 - bom > max as u64 {
+                truncated.push(TruncatedLine {
+                    offset: offset + bom,
+                    length: raw.len() as u64 - bom,
+                });
+            }
+            lines.push(cap(text, max).to_string());
+        }
+        offset += read as u64;
+        line += 1;
+    }
+    Ok(Lines {
+        start_line,
+        lines,
+        truncated,
+    })
+}
+
+#[tauri::command]
+pub fn get_line_at_offset(
+    offset: u64,
+    app: AppHandle,
+    index: State<'_, LineIndexState>,
+) -> Result<u64, String> {
+    let path = watched_path(&app)?;
+    let (line, start) = index.start_for(&path, |i| i.before_offset(offset))?;
+    let mut bytes = Vec::new();
+    reader_at(&path, start)?
+        .take(offset.saturating_sub(start))
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read log: {}", e))?;
+    Ok(line + bytes.iter().filter(|b| **b == b'\n').count() as u64)
+}
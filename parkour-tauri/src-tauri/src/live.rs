@@ -0,0 +1,213 @@
+use crate::db::Split;
+use crate::servers::EffectiveServerSettings;
+use crate::tail::FileId;
+use chrono::Timelike;
+use parkour_core::client::Client;
+use parkour_core::log_line::{seconds_of_day, LogFormat};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, JsonSchema, Clone, Debug, Default)]
+pub struct CurrentRun {
+    pub map: Option<String>,
+    pub username: Option<String>,
+    pub started_at: String,
+    pub started_ms: Option<u64>,
+    pub splits: Vec<Split>,
+    // Set once the server's practice pattern matches during the run
+    pub practice: bool,
+    // Real time spent paused by the pause hotkey, and the pause going on
+    pub paused_ms: u64,
+    pub paused_since_ms: Option<u64>,
+}
+
+impl CurrentRun {
+    /// Real time since the run started, less the time it was paused.
+    pub fn elapsed_ms(&self) -> Option<u64> {
+        let now = now_ms();
+        let paused = self.paused_ms + self.paused_since_ms.map_or(0, |p| now.saturating_sub(p));
+        Some(now.saturating_sub(self.started_ms?).saturating_sub(paused))
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LiveState {
+    pub log_path: Option<String>,
+    pub log_format: Option<LogFormat>,
+    pub client: Option<Client>,
+    pub server: Option<String>,
+    // Server parser picked for `server`, and the settings that apply there
+    pub parser: Option<String>,
+    pub server_settings: Option<EffectiveServerSettings>,
+    // Singleplayer world being played, when not on a server
+    pub world: Option<String>,
+    pub watching: bool,
+    pub last_update_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub paused: bool,
+    #[serde(skip)]
+    pub stopping: bool,
+    // Start of the last emitted content and its length, used to validate sync offsets
+    #[serde(skip)]
+    pub log_head: Option<String>,
+    #[serde(skip)]
+    pub log_end: u64,
+    #[serde(skip)]
+    pub log_id: Option<FileId>,
+    #[serde(skip)]
+    pub debounce_ms: u64,
+    pub current_run: Option<CurrentRun>,
+}
+
+/// Source id of the game log the watcher finds or is pointed at. Instance
+/// logs go by the id they're configured with, see
+/// [`InstanceLog`](crate::settings::InstanceLog).
+pub const MAIN: &str = "main";
+
+/// Source id of the run events `replay_log` re-emits. It has no live state,
+/// so replayed runs never touch the live run, splits or PBs.
+pub const REPLAY: &str = "replay";
+
+/// The `source` field of an event from `source`, left out for the main log
+/// so its events look as they always have.
+pub fn source_field(source: &str) -> Option<String> {
+    (source != MAIN).then(|| source.to_string())
+}
+
+#[derive(Default)]
+struct Sources {
+    main: LiveState,
+    instances: BTreeMap<String, LiveState>,
+}
+
+// Pausing and stopping are only ever set on the main log's state and apply
+// to every source.
+#[derive(Default)]
+pub struct Live(Mutex<Sources>);
+
+// Lines logged longer ago than this are history, e.g. the rest of a log read
+// when watching starts, and set nothing off
+const STALE_SECS: u32 = 30;
+
+/// Whether a line logged at `time`, in a read that began at raw offset
+/// `offset`, was just written. A line without a time is, unless it came with
+/// the rest of the log on its first read.
+pub fn is_fresh(time: Option<&str>, offset: u64) -> bool {
+    let Some(time) = time.and_then(seconds_of_day) else {
+        return offset != 0;
+    };
+    let now = chrono::Local::now().num_seconds_from_midnight();
+    // The log only has the time of day, so this can cross midnight, and a
+    // clock a little behind the game's puts lines just ahead of now
+    let age = (now + 86_400 - time) % 86_400;
+    age <= STALE_SECS || age >= 86_400 - STALE_SECS
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl Live {
+    pub fn snapshot(&self) -> LiveState {
+        self.0.lock().map(|s| s.main.clone()).unwrap_or_default()
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut LiveState)) {
+        if let Ok(mut state) = self.0.lock() {
+            f(&mut state.main);
+        }
+    }
+
+    /// The state of one source, `None` for an instance that isn't watched.
+    pub fn source(&self, source: &str) -> Option<LiveState> {
+        let state = self.0.lock().ok()?;
+        match source {
+            MAIN => Some(state.main.clone()),
+            _ => state.instances.get(source).cloned(),
+        }
+    }
+
+    /// Runs `f` on a source's state. Instances are left alone once removed,
+    /// so a chunk still in the pipeline can't bring one back.
+    pub fn update_source(&self, source: &str, f: impl FnOnce(&mut LiveState)) {
+        let Ok(mut state) = self.0.lock() else {
+            return;
+        };
+        match source {
+            MAIN => f(&mut state.main),
+            _ => {
+                if let Some(instance) = state.instances.get_mut(source) {
+                    f(instance);
+                }
+            }
+        }
+    }
+
+    pub fn add_instance(&self, source: &str, state: LiveState) {
+        if let Ok(mut sources) = self.0.lock() {
+            sources.instances.insert(source.to_string(), state);
+        }
+    }
+
+    pub fn remove_instance(&self, source: &str) -> Option<LiveState> {
+        self.0.lock().ok()?.instances.remove(source)
+    }
+
+    /// The run going on each source that has one.
+    pub fn current_runs(&self) -> Vec<(String, CurrentRun)> {
+        let Ok(state) = self.0.lock() else {
+            return Vec::new();
+        };
+        let main = state
+            .main
+            .current_run
+            .clone()
+            .map(|run| (MAIN.to_string(), run));
+        let instances = state
+            .instances
+            .iter()
+            .filter_map(|(id, s)| Some((id.clone(), s.current_run.clone()?)));
+        main.into_iter().chain(instances).collect()
+    }
+
+    pub fn instances(&self) -> BTreeMap<String, LiveState> {
+        self.0
+            .lock()
+            .map(|s| s.instances.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ago(secs: i64) -> String {
+        (chrono::Local::now() - chrono::Duration::seconds(secs))
+            .format("%H:%M:%S")
+            .to_string()
+    }
+
+    #[test]
+    fn only_lines_just_written_are_fresh() {
+        assert!(is_fresh(Some(&ago(0)), 0));
+        assert!(is_fresh(Some(&ago(5)), 4096));
+        // The game's clock a little ahead
+        assert!(is_fresh(Some(&ago(-5)), 4096));
+        assert!(!is_fresh(Some(&ago(120)), 4096));
+        assert!(!is_fresh(Some(&ago(3 * 3600)), 0));
+    }
+
+    #[test]
+    fn untimed_lines_are_stale_only_on_the_first_read() {
+        assert!(!is_fresh(None, 0));
+        assert!(is_fresh(None, 4096));
+        assert!(!is_fresh(Some("not a time"), 0));
+    }
+}
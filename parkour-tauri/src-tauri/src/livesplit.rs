@@ -0,0 +1,148 @@
+use crate::events::{self, MessagePayload};
+use crate::settings::{LiveSplitSettings, SettingsStore};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunSignal {
+    Start,
+    Checkpoint,
+    Reset,
+    Finish,
+}
+
+#[derive(Default)]
+pub struct LiveSplit(Mutex<Option<Sender<String>>>);
+
+impl LiveSplit {
+    pub fn signal(&self, app: &AppHandle, map: Option<&str>, signal: RunSignal) {
+        let settings = app.state::<SettingsStore>().get().livesplit;
+        if !settings.enabled {
+            return;
+        }
+        let mapping = map
+            .and_then(|m| settings.maps.get(m))
+            .unwrap_or(&settings.default_mapping);
+        let command = match signal {
+            RunSignal::Start => &mapping.on_start,
+            RunSignal::Checkpoint => &mapping.on_checkpoint,
+            RunSignal::Reset => &mapping.on_reset,
+            RunSignal::Finish => &mapping.on_finish,
+        };
+        let Some(command) = command.clone().filter(|c| !c.trim().is_empty()) else {
+            return;
+        };
+
+        let Ok(mut tx) = self.0.lock() else {
+            return;
+        };
+        if let Some(sender) = tx.as_ref() {
+            if sender.send(command.clone()).is_ok() {
+                return;
+            }
+        }
+        let (sender, rx) = channel();
+        let _ = sender.send(command);
+        *tx = Some(sender);
+        let app = app.clone();
+        std::thread::spawn(move || worker(app, rx));
+    }
+}
+
+fn resolve(host: &str, port: u16) -> Result<SocketAddr, String> {
+    (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Cannot resolve {}:{}: {}", host, port, e))?
+        .next()
+        .ok_or_else(|| format!("Cannot resolve {}:{}", host, port))
+}
+
+fn connect(settings: &LiveSplitSettings) -> Result<TcpStream, String> {
+    let addr = resolve(&settings.host, settings.port)?;
+    let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Cannot connect to LiveSplit at {}: {}", addr, e))?;
+    let _ = stream.set_nodelay(true);
+    Ok(stream)
+}
+
+// Owns the TCP connection; commands issued while LiveSplit is unreachable are
+// dropped since replaying them late would only desync the timer.
+fn worker(app: AppHandle, rx: Receiver<String>) {
+    let mut conn: Option<(String, u16, TcpStream)> = None;
+    let mut last_attempt: Option<Instant> = None;
+    let mut reported = false;
+
+    for command in rx {
+        let settings = app.state::<SettingsStore>().get().livesplit;
+        if let Some((host, port, _)) = &conn {
+            if *host != settings.host || *port != settings.port {
+                conn = None;
+            }
+        }
+
+        let line = format!("{}\r\n", command);
+        for _ in 0..2 {
+            if conn.is_none() {
+                if last_attempt.is_some_and(|t| t.elapsed() < RECONNECT_INTERVAL) {
+                    break;
+                }
+                last_attempt = Some(Instant::now());
+                match connect(&settings) {
+                    Ok(stream) => {
+                        conn = Some((settings.host.clone(), settings.port, stream));
+                        reported = false;
+                    }
+                    Err(e) => {
+                        if !reported {
+                            let payload = MessagePayload { message: e };
+                            events::emit(&app, events::LIVESPLIT_ERROR, &payload);
+                            reported = true;
+                        }
+                        break;
+                    }
+                }
+            }
+            let Some((_, _, stream)) = conn.as_mut() else {
+                break;
+            };
+            if stream.write_all(line.as_bytes()).is_ok() {
+                break;
+            }
+            // Stale connection (LiveSplit restarted): reconnect once and resend
+            conn = None;
+            last_attempt = None;
+        }
+    }
+}
+
+#[tauri::command]
+pub fn test_livesplit_connection(
+    host: Option<String>,
+    port: Option<u16>,
+    store: State<'_, SettingsStore>,
+) -> Result<String, String> {
+    let mut settings = store.get().livesplit;
+    if let Some(host) = host {
+        settings.host = host;
+    }
+    if let Some(port) = port {
+        settings.port = port;
+    }
+    let mut stream = connect(&settings)?;
+    stream
+        .set_read_timeout(Some(CONNECT_TIMEOUT))
+        .and_then(|_| stream.write_all(b"getcurrenttimerphase\r\n"))
+        .map_err(|e| format!("LiveSplit connection failed: {}", e))?;
+    let mut phase = String::new();
+    BufReader::new(stream)
+        .read_line(&mut phase)
+        .map_err(|e| format!("LiveSplit did not respond: {}", e))?;
+    Ok(phase.trim().to_string())
+}
@@ -0,0 +1,301 @@
+use crate::db::{self, Db, Run, Split};
+use crate::timestamps;
+use chrono::Duration as ChronoDuration;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use tauri::State;
+
+// Segment endpoints: Some(checkpoint) or None for the finish line
+type Endpoint = Option<i64>;
+
+struct Segment {
+    name: String,
+    end: Endpoint,
+    pb_ms: i64,
+}
+
+struct Attempt<'a> {
+    run: &'a Run,
+    points: HashMap<i64, i64>,
+}
+
+impl Attempt<'_> {
+    fn at(&self, point: Endpoint) -> Option<i64> {
+        match point {
+            Some(cp) => self.points.get(&cp).copied(),
+            None => self.run.time_ms.filter(|_| self.run.completed),
+        }
+    }
+
+    fn segment(&self, start: Option<Endpoint>, end: Endpoint) -> Option<i64> {
+        let from = match start {
+            Some(p) => self.at(p)?,
+            None => 0,
+        };
+        self.at(end).map(|to| to - from).filter(|t| *t > 0)
+    }
+}
+
+// XML 1.0 can't hold control characters other than tab and newlines, or
+// U+FFFE and U+FFFF, even as character references, so those are dropped
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn lss_time(ms: i64) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}0000",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+// LiveSplit records attempt times in UTC.
+fn lss_date(started_at_utc: Option<&str>, offset_ms: i64) -> Option<String> {
+    let t = timestamps::parse_utc(started_at_utc?)?;
+    Some((t + ChronoDuration::milliseconds(offset_ms)).format("%m/%d/%Y %H:%M:%S").to_string())
+}
+
+fn segment_name(split: &Split) -> String {
+    match &split.name {
+        Some(name) if !name.trim().is_empty() => name.clone(),
+        _ => format!("Checkpoint {}", split.checkpoint),
+    }
+}
+
+pub fn build_lss(
+    conn: &Connection,
+    map: &str,
+    username: &str,
+    include_invalidated: bool,
+) -> Result<String, String> {
+    // Practice runs would skew the PB and best segments LiveSplit keeps, and
+    // so would runs a legitimacy rule fired in unless asked for
+    let mut runs = db::runs_for_map(conn, map, username)?;
+    runs.retain(|r| !r.practice && (include_invalidated || r.invalidated_by.is_empty()));
+    let pb = runs
+        .iter()
+        .filter(|r| r.completed && r.time_ms.is_some())
+        .min_by_key(|r| (r.time_ms, r.started_at.clone(), r.id))
+        .ok_or_else(|| format!("No completed run on {} for {}", map, username))?;
+
+    let mut attempts = Vec::with_capacity(runs.len());
+    for run in &runs {
+        let points = db::run_splits(conn, run.id)?
+            .into_iter()
+            .map(|s| (s.checkpoint, s.time_ms))
+            .collect();
+        attempts.push(Attempt { run, points });
+    }
+
+    let pb_splits = db::run_splits(conn, pb.id)?;
+    let mut segments: Vec<Segment> = pb_splits
+        .iter()
+        .map(|s| Segment {
+            name: segment_name(s),
+            end: Some(s.checkpoint),
+            pb_ms: s.time_ms,
+        })
+        .collect();
+    segments.push(Segment {
+        name: "Finish".into(),
+        end: None,
+        pb_ms: pb.time_ms.unwrap_or_default(),
+    });
+
+    let mut xml = String::new();
+    // Writing into a String cannot fail
+    macro_rules! out {
+        ($($arg:tt)*) => {
+            let _ = writeln!(xml, $($arg)*);
+        };
+    }
+    out!(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    out!(r#"<Run version="1.7.0">"#);
+    out!("  <GameIcon />");
+    out!("  <GameName>Minecraft Parkour</GameName>");
+    out!("  <CategoryName>{}</CategoryName>", escape(map));
+    out!("  <Metadata>");
+    out!(r#"    <Run id="" />"#);
+    out!(r#"    <Platform usesEmulator="False"></Platform>"#);
+    out!("    <Region></Region>");
+    out!("    <Variables />");
+    out!("  </Metadata>");
+    out!("  <Offset>00:00:00</Offset>");
+    out!("  <AttemptCount>{}</AttemptCount>", attempts.len());
+
+    out!("  <AttemptHistory>");
+    for (i, a) in attempts.iter().enumerate() {
+        let mut attrs = format!(r#"id="{}""#, i + 1);
+        let started_at = a.run.started_at_utc.as_deref();
+        if let Some(started) = lss_date(started_at, 0) {
+            let _ = write!(attrs, r#" started="{}" isStartedSynced="False""#, started);
+            if let Some(ended) = a.at(None).and_then(|t| lss_date(started_at, t)) {
+                let _ = write!(attrs, r#" ended="{}" isEndedSynced="False""#, ended);
+            }
+        }
+        match a.at(None) {
+            Some(t) => {
+                out!("    <Attempt {}>", attrs);
+                out!("      <RealTime>{}</RealTime>", lss_time(t));
+                out!("    </Attempt>");
+            }
+            None => {
+                out!("    <Attempt {} />", attrs);
+            }
+        }
+    }
+    out!("  </AttemptHistory>");
+
+    out!("  <Segments>");
+    let mut prev: Option<Endpoint> = None;
+    for seg in &segments {
+        let history: Vec<(usize, i64)> = attempts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, a)| a.segment(prev, seg.end).map(|t| (i + 1, t)))
+            .collect();
+        let gold = history.iter().map(|(_, t)| *t).min();
+
+        out!("    <Segment>");
+        out!("      <Name>{}</Name>", escape(&seg.name));
+        out!("      <Icon />");
+        out!("      <SplitTimes>");
+        out!(r#"        <SplitTime name="Personal Best">"#);
+        out!("          <RealTime>{}</RealTime>", lss_time(seg.pb_ms));
+        out!("        </SplitTime>");
+        out!("      </SplitTimes>");
+        match gold {
+            Some(g) => {
+                out!("      <BestSegmentTime>");
+                out!("        <RealTime>{}</RealTime>", lss_time(g));
+                out!("      </BestSegmentTime>");
+            }
+            None => {
+                out!("      <BestSegmentTime />");
+            }
+        }
+        if history.is_empty() {
+            out!("      <SegmentHistory />");
+        } else {
+            out!("      <SegmentHistory>");
+            for (id, t) in &history {
+                out!(r#"        <Time id="{}">"#, id);
+                out!("          <RealTime>{}</RealTime>", lss_time(*t));
+                out!("        </Time>");
+            }
+            out!("      </SegmentHistory>");
+        }
+        out!("    </Segment>");
+        prev = Some(seg.end);
+    }
+    out!("  </Segments>");
+    out!("  <AutoSplitterSettings />");
+    out!("</Run>");
+    Ok(xml)
+}
+
+#[tauri::command]
+pub fn export_lss(
+    map: String,
+    username: String,
+    dest_path: String,
+    include_invalidated: Option<bool>,
+    db: State<'_, Db>,
+) -> Result<(), String> {
+    let xml = {
+        let conn = db.conn()?;
+        build_lss(&conn, &map, &username, include_invalidated.unwrap_or(false))?
+    };
+    fs::write(&dest_path, xml).map_err(|e| format!("Failed to write {}: {}", dest_path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::NewRun;
+
+    fn run(started_at: &str, time_ms: Option<i64>, splits: &[(i64, Option<&str>, i64)]) -> NewRun {
+        NewRun {
+            map: "Tower <A&B>".into(),
+            username: "Steve".into(),
+            server: None,
+            world: None,
+            started_at: started_at.into(),
+            time_ms,
+            completed: time_ms.is_some(),
+            fails: 0,
+            practice: false,
+            invalidated_by: Vec::new(),
+            splits: splits
+                .iter()
+                .map(|&(checkpoint, name, time_ms)| Split {
+                    checkpoint,
+                    name: name.map(str::to_string),
+                    time_ms,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn export_matches_the_fixture() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        let runs = [
+            run(
+                "2024-05-01 20:00:00",
+                Some(32_000),
+                &[(1, None, 9_000), (2, Some("Ladder \"top\""), 21_000)],
+            ),
+            run(
+                "2024-05-01 20:05:00",
+                Some(30_000),
+                &[(1, None, 10_000), (2, Some("Ladder \"top\""), 20_000)],
+            ),
+            run("2024-05-01 20:10:00", None, &[(1, None, 9_500)]),
+            // A route through an extra checkpoint, before the map changed
+            run(
+                "2024-05-01 20:15:00",
+                Some(31_000),
+                &[(1, None, 9_800), (2, None, 15_000), (3, None, 19_000)],
+            ),
+        ];
+        for run in &runs {
+            db::insert_run(&conn, run).unwrap();
+        }
+        let mut practice = run("2024-05-01 20:20:00", Some(1_000), &[]);
+        practice.practice = true;
+        db::insert_run(&conn, &practice).unwrap();
+        // Fixed instants, so the attempt dates don't depend on the time zone
+        conn.execute_batch("UPDATE runs SET started_at_utc = replace(started_at, ' ', 'T') || 'Z'")
+            .unwrap();
+
+        let xml = build_lss(&conn, "Tower <A&B>", "Steve", false).unwrap();
+        assert_eq!(xml, include_str!("../fixtures/tower.lss"));
+    }
+
+    #[test]
+    fn escape_drops_what_xml_cannot_hold() {
+        assert_eq!(escape("<a & 'b'>"), "&lt;a &amp; &apos;b&apos;&gt;");
+        assert_eq!(escape("a\u{0}b\u{1b}[0m\tc\r\n\u{fffe}é"), "ab[0m\tc\r\né");
+    }
+}
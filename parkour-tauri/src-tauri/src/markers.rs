@@ -0,0 +1,63 @@
+use crate::db::{self, Db, Marker};
+use crate::events;
+use crate::live::Live;
+use crate::perf;
+use crate::settings::SettingsStore;
+use tauri::{AppHandle, Manager, State};
+
+pub fn record(app: &AppHandle, label: Option<String>) -> Result<Marker, String> {
+    let settings = app.state::<SettingsStore>().get().markers;
+    let run = app.state::<Live>().snapshot().current_run;
+    let db = app.state::<Db>();
+    let conn = db.conn()?;
+    let session_id = db::active_session(&conn)?;
+    let label = match label.filter(|l| !l.trim().is_empty()) {
+        Some(label) => Some(label),
+        None if settings.auto_label => Some(format!(
+            "{} {}",
+            settings.label_prefix,
+            db::count_markers(&conn, session_id)? + 1
+        )),
+        None => None,
+    };
+    let mut marker = Marker {
+        id: 0,
+        created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        label,
+        session_id,
+        map: run.as_ref().and_then(|r| r.map.clone()),
+        run_started_at: run.as_ref().map(|r| r.started_at.clone()),
+        run_elapsed_ms: run
+            .as_ref()
+            .and_then(|r| r.elapsed_ms())
+            .map(|elapsed| elapsed as i64),
+    };
+    marker.id = perf::time(app, |p| &p.db_write_us, || db::insert_marker(&conn, &marker))?;
+    drop(conn);
+
+    events::emit_all(app, events::MANUAL_MARKER, &marker);
+    Ok(marker)
+}
+
+#[tauri::command]
+pub fn add_marker(label: Option<String>, app: AppHandle) -> Result<Marker, String> {
+    record(&app, label)
+}
+
+#[tauri::command]
+pub fn list_markers(
+    session_id: Option<i64>,
+    run_id: Option<i64>,
+    db: State<'_, Db>,
+) -> Result<Vec<Marker>, String> {
+    let conn = db.conn()?;
+    let run = match run_id {
+        Some(id) => Some(
+            parkour_core::db::get_run(&conn, id)?
+                .ok_or_else(|| format!("Run {} not found", id))?
+                .run,
+        ),
+        None => None,
+    };
+    db::markers(&conn, session_id, run.as_ref())
+}
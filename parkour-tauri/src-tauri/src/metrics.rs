@@ -0,0 +1,99 @@
+use crate::db::Db;
+use crate::live::{now_ms, Live};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::{AppHandle, Manager};
+
+#[derive(Default)]
+pub struct Metrics {
+    pub events_emitted: AtomicU64,
+    pub bytes_read: AtomicU64,
+    pub parse_errors: AtomicU64,
+    pub runs_detected: AtomicU64,
+    pub watcher_restarts: AtomicU64,
+}
+
+pub fn record(app: &AppHandle, pick: impl FnOnce(&Metrics) -> &AtomicU64, n: u64) {
+    if let Some(metrics) = app.try_state::<Metrics>() {
+        pick(&metrics).fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn database_bytes(app: &AppHandle) -> Option<i64> {
+    let db = app.state::<Db>();
+    let conn = db.conn().ok()?;
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+// Prometheus text exposition format, version 0.0.4.
+pub fn render(app: &AppHandle) -> String {
+    let metrics = app.state::<Metrics>();
+    let load = |c: &AtomicU64| c.load(Ordering::Relaxed);
+    let mut out = String::new();
+    metric(
+        &mut out,
+        "parkour_events_emitted_total",
+        "counter",
+        "Log events emitted to the frontend.",
+        load(&metrics.events_emitted),
+    );
+    metric(
+        &mut out,
+        "parkour_log_bytes_read_total",
+        "counter",
+        "Bytes read from the watched log.",
+        load(&metrics.bytes_read),
+    );
+    metric(
+        &mut out,
+        "parkour_parse_errors_total",
+        "counter",
+        "Log lines that looked relevant but failed to parse.",
+        load(&metrics.parse_errors),
+    );
+    metric(
+        &mut out,
+        "parkour_runs_detected_total",
+        "counter",
+        "Runs detected in the log.",
+        load(&metrics.runs_detected),
+    );
+    metric(
+        &mut out,
+        "parkour_watcher_restarts_total",
+        "counter",
+        "Times the file watcher was restarted.",
+        load(&metrics.watcher_restarts),
+    );
+    if let Some(bytes) = database_bytes(app) {
+        metric(
+            &mut out,
+            "parkour_database_bytes",
+            "gauge",
+            "Size of the run history database.",
+            bytes,
+        );
+    }
+    if let Some(last) = app.state::<Live>().snapshot().last_update_ms {
+        let age = now_ms().saturating_sub(last) as f64 / 1000.0;
+        metric(
+            &mut out,
+            "parkour_last_update_age_seconds",
+            "gauge",
+            "Seconds since the log last changed.",
+            format!("{:.3}", age),
+        );
+    }
+    out
+}
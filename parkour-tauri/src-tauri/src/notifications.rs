@@ -0,0 +1,101 @@
+use crate::duration::{format_delta, format_ms};
+use crate::pb::Finish;
+use crate::settings::{NotificationSettings, SettingsStore};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+fn show(app: &AppHandle, title: &str, body: &str) {
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
+
+// improvement_ms is None for the first completion of a map, which always counts.
+pub fn notify_pb(app: &AppHandle, map: &str, time_ms: i64, improvement_ms: Option<i64>) {
+    let settings = app.state::<SettingsStore>().get().notifications;
+    if !settings.enabled || settings.do_not_disturb || !settings.pbs {
+        return;
+    }
+    if improvement_ms.is_some_and(|d| d < settings.min_improvement_ms) {
+        return;
+    }
+    let body = match improvement_ms {
+        Some(delta) => format!("{} ({})", format_ms(time_ms), format_delta(-delta)),
+        None => format_ms(time_ms),
+    };
+    show(app, &format!("New PB on {}", map), &body);
+}
+
+/// Notifies about a run just finished: as a PB when it beat every time
+/// before it, otherwise as a completion with how far off the PB it was.
+pub fn notify_finish(app: &AppHandle, finish: &Finish) {
+    match finish.improvement_ms() {
+        Some(delta) if delta <= 0 => notify_completion(app, &finish.map, finish.time_ms, -delta),
+        improvement => notify_pb(app, &finish.map, finish.time_ms, improvement),
+    }
+}
+
+fn notify_completion(app: &AppHandle, map: &str, time_ms: i64, behind_ms: i64) {
+    let settings = app.state::<SettingsStore>().get().notifications;
+    if !settings.enabled || settings.do_not_disturb || !settings.completions {
+        return;
+    }
+    let body = format!("{} ({} on PB)", format_ms(time_ms), format_delta(behind_ms));
+    show(app, &format!("Finished {}", map), &body);
+}
+
+pub fn notify_goal(app: &AppHandle, map: &str, time_ms: i64, target_time_ms: i64) {
+    let settings = app.state::<SettingsStore>().get().notifications;
+    if !settings.enabled || settings.do_not_disturb || !settings.goals {
+        return;
+    }
+    let body = format!("{} (target {})", format_ms(time_ms), format_ms(target_time_ms));
+    show(app, &format!("Goal reached on {}", map), &body);
+}
+
+pub fn focus_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    let result = app.notification().on_action(move |action| {
+        if action.action_id() == "tap" {
+            focus_main_window(&handle);
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("Notification clicks unavailable: {}", e);
+    }
+}
+
+/// Turns notifications for PBs, other finished runs and goals on or off and
+/// saves it; a kind left out keeps its setting.
+#[tauri::command]
+pub fn set_notification_events(
+    pbs: Option<bool>,
+    completions: Option<bool>,
+    goals: Option<bool>,
+    store: State<'_, SettingsStore>,
+) -> Result<NotificationSettings, String> {
+    let mut settings = store.get();
+    let notifications = &mut settings.notifications;
+    notifications.pbs = pbs.unwrap_or(notifications.pbs);
+    notifications.completions = completions.unwrap_or(notifications.completions);
+    notifications.goals = goals.unwrap_or(notifications.goals);
+    store.replace(settings.clone())?;
+    Ok(settings.notifications)
+}
+
+#[tauri::command]
+pub fn test_notification(app: AppHandle) {
+    show(
+        &app,
+        "New PB on Test Map",
+        &format!("{} ({})", format_ms(83_456), format_delta(-1_234)),
+    );
+}
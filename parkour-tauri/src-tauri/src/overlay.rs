@@ -0,0 +1,228 @@
+use crate::db::{self, Db};
+use crate::live::{Live, MAIN};
+use crate::settings::{OverlaySettings, SettingsStore};
+use crate::ws::{self, WsHub};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const OVERLAY_HTML: &str = include_str!("overlay.html");
+
+struct Running {
+    port: u16,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    running: Option<Running>,
+    last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct OverlayServer(Mutex<Inner>);
+
+impl OverlayServer {
+    pub fn port(&self) -> Option<u16> {
+        self.0.lock().ok()?.running.as_ref().map(|r| r.port)
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.0.lock().ok()?.last_error.clone()
+    }
+}
+
+fn shutdown(running: Running) {
+    running.server.unblock();
+    if let Some(thread) = running.thread {
+        let _ = thread.join();
+    }
+}
+
+pub fn start(app: &AppHandle, port: u16) -> Result<u16, String> {
+    let overlay = app.state::<OverlayServer>();
+    let mut inner = overlay.0.lock().map_err(|_| "Overlay lock poisoned")?;
+    if let Some(running) = inner.running.take() {
+        if running.port == port {
+            let bound = running.port;
+            inner.running = Some(running);
+            return Ok(bound);
+        }
+        shutdown(running);
+    }
+
+    let server = match Server::http(("127.0.0.1", port)) {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            let msg = format!("Could not start overlay server on port {}: {}", port, e);
+            inner.last_error = Some(msg.clone());
+            return Err(msg);
+        }
+    };
+    let bound = server
+        .server_addr()
+        .to_ip()
+        .map(|a| a.port())
+        .unwrap_or(port);
+
+    let handle = app.clone();
+    let srv = server.clone();
+    let thread = std::thread::spawn(move || {
+        for request in srv.incoming_requests() {
+            handle_request(&handle, request);
+        }
+    });
+
+    inner.running = Some(Running {
+        port: bound,
+        server,
+        thread: Some(thread),
+    });
+    inner.last_error = None;
+    Ok(bound)
+}
+
+pub fn stop(app: &AppHandle) {
+    let overlay = app.state::<OverlayServer>();
+    let running = overlay.0.lock().ok().and_then(|mut inner| inner.running.take());
+    if let Some(running) = running {
+        shutdown(running);
+    }
+    app.state::<WsHub>().close_all();
+}
+
+pub fn apply_settings(app: &AppHandle, settings: &OverlaySettings) -> Result<(), String> {
+    if settings.enabled {
+        start(app, settings.port).map(|_| ())
+    } else {
+        stop(app);
+        Ok(())
+    }
+}
+
+pub(crate) fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("static header is valid")
+}
+
+pub(crate) fn json_response<T: Serialize>(value: &T) -> Response<std::io::Cursor<Vec<u8>>> {
+    let body = serde_json::to_string(value).unwrap_or_else(|_| "null".into());
+    Response::from_string(body)
+        .with_header(header("Content-Type", "application/json"))
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+        .with_header(header("Cache-Control", "no-store"))
+}
+
+pub(crate) fn error_response(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(&serde_json::json!({ "error": message })).with_status_code(status)
+}
+
+pub(crate) fn query_param(query: &str, key: &str) -> Option<String> {
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.into_owned())
+}
+
+pub(crate) fn header_value(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+// `?events=run-started,timer-tick` limits a client to those events, and is
+// how it gets `log-update`, which isn't sent by default
+fn upgrade_websocket(app: &AppHandle, request: Request, query: &str) {
+    let key = header_value(&request, "Sec-WebSocket-Key");
+    let is_upgrade = header_value(&request, "Upgrade")
+        .map(|v| v.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false);
+    let Some(key) = key.filter(|_| is_upgrade) else {
+        let _ = request.respond(error_response(400, "Expected a WebSocket upgrade"));
+        return;
+    };
+
+    let response = Response::empty(101).with_header(header(
+        "Sec-WebSocket-Accept",
+        &tungstenite::handshake::derive_accept_key(key.as_bytes()),
+    ));
+    let stream = request.upgrade("websocket", response);
+    let hello = serde_json::json!({
+        "event": "hello",
+        "payload": {
+            "protocol": ws::PROTOCOL_VERSION,
+            "state": app.state::<Live>().snapshot(),
+        },
+    })
+    .to_string();
+    let events = query_param(query, "events").map(|events| {
+        events
+            .split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect()
+    });
+    app.state::<WsHub>().attach(app, stream, hello, events);
+}
+
+fn handle_request(app: &AppHandle, request: Request) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    if path == "/ws" {
+        upgrade_websocket(app, request, query);
+        return;
+    }
+
+    let response = if request.method() != &Method::Get {
+        error_response(405, "Method not allowed")
+    } else {
+        match path {
+            "/" | "/index.html" => Response::from_string(OVERLAY_HTML)
+                .with_header(header("Content-Type", "text/html; charset=utf-8")),
+            "/status" => json_response(&crate::watcher_status(app)),
+            "/metrics" => Response::from_string(crate::metrics::render(app))
+                .with_header(header("Content-Type", "text/plain; version=0.0.4")),
+            // `?source=alt` for an instance log's run
+            "/current_run" => {
+                let source = query_param(query, "source").unwrap_or_else(|| MAIN.into());
+                match app.state::<Live>().source(&source) {
+                    Some(state) => json_response(&state.current_run),
+                    None => error_response(404, "No such source"),
+                }
+            }
+            "/pb" => match query_param(query, "map") {
+                None => error_response(400, "Missing map parameter"),
+                Some(map) => {
+                    let username = query_param(query, "username");
+                    let db = app.state::<Db>();
+                    let pb = db
+                        .conn()
+                        .and_then(|conn| db::personal_best(&conn, &map, username.as_deref()));
+                    match pb {
+                        Ok(pb) => json_response(&pb),
+                        Err(e) => error_response(500, &e),
+                    }
+                }
+            },
+            _ => error_response(404, "Not found"),
+        }
+    };
+    let _ = request.respond(response);
+}
+
+#[tauri::command]
+pub fn start_overlay_server(
+    port: Option<u16>,
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+) -> Result<u16, String> {
+    start(&app, port.unwrap_or(store.get().overlay.port))
+}
+
+#[tauri::command]
+pub fn stop_overlay_server(app: AppHandle) {
+    stop(&app);
+}
@@ -0,0 +1,375 @@
+use crate::db::Split;
+use crate::events;
+use crate::lifecycle;
+use crate::lines::Capped;
+use crate::live::{is_fresh, now_ms, source_field, CurrentRun, Live};
+use crate::metrics;
+use crate::servers::EffectiveServerSettings;
+use crate::settings::{Settings, SettingsStore};
+use crate::timestamps::LOCAL_FORMAT;
+use parkour_core::client::Client;
+use parkour_core::duels::{DuelEvent, EventKind, Parser, PatternSpec, Patterns};
+use parkour_core::log_line::parse_line;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// `{"offset": 1024, "map": "Parkour Duels"}`
+///
+/// A built-in Parkour Duels run starting, as read from the watched log. Runs
+/// timed by a [`RunDefinition`](parkour_core::definitions::RunDefinition)
+/// are reported as `defined-run` instead.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct RunStartedPayload {
+    pub offset: u64,
+    pub map: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// `{"offset": 2048, "map": "Parkour Duels", "player": "Steve", "index": 3, "time": "0:41.250", "time_ms": 41250}`
+///
+/// A player reaching checkpoint `index` of the run in progress, the first
+/// time they do. `time` is the timer as shown in chat.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct CheckpointReachedPayload {
+    pub offset: u64,
+    pub map: String,
+    pub player: String,
+    pub index: u32,
+    pub time: String,
+    pub time_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// `{"offset": 4096, "map": "Parkour Duels", "player": "Steve", "time": "1:02.500", "total_ms": 62500}`
+///
+/// A player finishing the run in progress. Opponents' finishes are reported
+/// as well as the logged-in player's.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct RunFinishedPayload {
+    pub offset: u64,
+    pub map: String,
+    pub player: String,
+    pub time: String,
+    pub total_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+enum RunEvent {
+    Started(RunStartedPayload),
+    Checkpoint(CheckpointReachedPayload),
+    Finished(RunFinishedPayload),
+    // The logged-in player's run starting over, sent as `run-reset`
+    Reset,
+}
+
+// What the parser's patterns are made from, to notice changes
+type Key = (PatternSpec, bool, Option<Client>);
+
+#[derive(Default)]
+pub(crate) struct Tracker {
+    key: Option<Key>,
+    parser: Option<Parser>,
+    // Title of the game in progress
+    map: Option<String>,
+}
+
+// One tracker per watched source
+#[derive(Default)]
+pub struct LiveRuns(Mutex<BTreeMap<String, Tracker>>);
+
+// The built-in patterns with what applies on the server being played on.
+// Run definitions are left to the definitions module.
+fn key(settings: &Settings, server: Option<&EffectiveServerSettings>) -> Key {
+    match server {
+        Some(server) => (
+            server.patterns.clone(),
+            server.trust_player_chat,
+            settings.watcher.client,
+        ),
+        None => (
+            PatternSpec::default(),
+            settings.watcher.trust_player_chat,
+            settings.watcher.client,
+        ),
+    }
+}
+
+fn patterns((spec, trust_player_chat, client): &Key) -> Patterns {
+    // Saved patterns were validated, so this only fails on a hand-edited
+    // settings file
+    Patterns::from_spec(spec)
+        .unwrap_or_default()
+        .with_client(*client)
+        .with_trust_player_chat(*trust_player_chat)
+}
+
+impl Tracker {
+    // Events found in the lines, each with the logged-in player then and
+    // whether its line was just written
+    fn scan(&mut self, key: Key, chunk: &Capped) -> Vec<(RunEvent, Option<String>, bool)> {
+        let offset = chunk.offset;
+        match self.parser.as_mut() {
+            Some(parser) if offset != 0 => {
+                // The player stays known across a server switch
+                if self.key.as_ref() != Some(&key) {
+                    parser.set_patterns(patterns(&key));
+                }
+            }
+            _ => {
+                self.parser = Some(Parser::new(patterns(&key)));
+                self.map = None;
+            }
+        }
+        self.key = Some(key);
+        let Tracker {
+            parser: Some(parser),
+            map,
+            ..
+        } = self
+        else {
+            return Vec::new();
+        };
+        let mut found = Vec::new();
+        for line in chunk.lines() {
+            let Some(event) = parser.line(line.start as u64, line.text) else {
+                continue;
+            };
+            let event = match event {
+                DuelEvent::GameStarted { offset, title } => {
+                    *map = Some(title.clone());
+                    RunEvent::Started(RunStartedPayload {
+                        offset,
+                        map: title,
+                        source: None,
+                    })
+                }
+                DuelEvent::Progress {
+                    offset,
+                    player,
+                    step,
+                } => {
+                    let map = map.clone().unwrap_or_default();
+                    let time_ms = step.time_ms();
+                    match step.kind {
+                        EventKind::Checkpoint => RunEvent::Checkpoint(CheckpointReachedPayload {
+                            offset,
+                            map,
+                            player,
+                            index: step.cp,
+                            time: step.time,
+                            time_ms,
+                            source: None,
+                        }),
+                        EventKind::Finish => RunEvent::Finished(RunFinishedPayload {
+                            offset,
+                            map,
+                            player,
+                            time: step.time,
+                            total_ms: time_ms,
+                            source: None,
+                        }),
+                    }
+                }
+                DuelEvent::Reset { .. } => RunEvent::Reset,
+            };
+            let time = parse_line(line.start as u64, line.text).time;
+            found.push((
+                event,
+                parser.username().map(str::to_string),
+                is_fresh(time.as_deref(), offset),
+            ));
+        }
+        found
+    }
+}
+
+// Keeps the live run in step with the logged-in player's progress. Events
+// from lines logged a while ago, like the rest of the log read at startup,
+// only end the run they came after and set nothing off.
+fn apply(app: &AppHandle, source: &str, event: &RunEvent, username: Option<String>, fresh: bool) {
+    if !fresh {
+        if let RunEvent::Started(_) = event {
+            app.state::<Live>()
+                .update_source(source, |s| s.current_run = None);
+        }
+        return;
+    }
+    let mut started = None;
+    let mut split = None;
+    let mut finished = None;
+    let mut reset = None;
+    app.state::<Live>().update_source(source, |s| match event {
+        RunEvent::Started(run) => {
+            let run = CurrentRun {
+                map: Some(run.map.clone()),
+                username,
+                started_at: chrono::Local::now().format(LOCAL_FORMAT).to_string(),
+                started_ms: Some(now_ms()),
+                splits: Vec::new(),
+                practice: false,
+                paused_ms: 0,
+                paused_since_ms: None,
+            };
+            started = Some(run.clone());
+            s.current_run = Some(run);
+        }
+        RunEvent::Checkpoint(cp) if username.as_ref() == Some(&cp.player) => {
+            if let (Some(run), Some(time_ms)) = (s.current_run.as_mut(), cp.time_ms) {
+                run.splits.push(Split {
+                    checkpoint: cp.index as i64,
+                    name: None,
+                    time_ms,
+                });
+                split = Some(run.clone());
+            }
+        }
+        RunEvent::Finished(finish) if username.as_ref() == Some(&finish.player) => {
+            finished = s.current_run.take().zip(finish.total_ms);
+        }
+        RunEvent::Reset => reset = s.current_run.take(),
+        RunEvent::Checkpoint(_) | RunEvent::Finished(_) => {}
+    });
+    if let Some(run) = started {
+        lifecycle::started(app, source, &run);
+    }
+    if let Some(run) = split {
+        lifecycle::split(app, source, &run);
+    }
+    if let Some((run, total_ms)) = finished {
+        lifecycle::finished(app, source, &run, total_ms);
+    }
+    if let Some(run) = reset {
+        lifecycle::reset(app, source, run);
+    }
+}
+
+/// Reads the built-in Parkour Duels runs in newly read log text of `source`,
+/// with the patterns of the server being
+/// played on, and emits `run-started`, `checkpoint-reached` and
+/// `run-finished`.
+pub fn scan(app: &AppHandle, source: &str, chunk: &Capped) {
+    let settings = app.state::<SettingsStore>().get();
+    let server = app
+        .state::<Live>()
+        .source(source)
+        .and_then(|s| s.server_settings);
+    let key = key(&settings, server.as_ref());
+    // The log starting over ends the session played in it
+    if chunk.starts_log() {
+        lifecycle::end_session(app, source);
+    }
+    let found = match app.state::<LiveRuns>().0.lock() {
+        Ok(mut trackers) => trackers
+            .entry(source.to_string())
+            .or_default()
+            .scan(key, chunk),
+        Err(_) => return,
+    };
+    for (mut event, username, fresh) in found {
+        // A progress line whose timer didn't read as a time
+        if let RunEvent::Checkpoint(CheckpointReachedPayload { time_ms: None, .. })
+        | RunEvent::Finished(RunFinishedPayload { total_ms: None, .. }) = &event
+        {
+            metrics::record(app, |m| &m.parse_errors, 1);
+        }
+        apply(app, source, &event, username, fresh);
+        match &mut event {
+            RunEvent::Started(payload) => {
+                payload.source = source_field(source);
+                events::emit_all(app, events::RUN_STARTED, payload);
+            }
+            RunEvent::Checkpoint(payload) => {
+                payload.source = source_field(source);
+                events::emit_all(app, events::CHECKPOINT_REACHED, payload);
+            }
+            RunEvent::Finished(payload) => {
+                payload.source = source_field(source);
+                events::emit_all(app, events::RUN_FINISHED, payload);
+            }
+            // Sent with the run it gave up on, if one was going
+            RunEvent::Reset => {}
+        }
+    }
+}
+
+/// The player logged in on `source`'s log, as far as it's been read.
+pub fn username(app: &AppHandle, source: &str) -> Option<String> {
+    let state = app.state::<LiveRuns>();
+    let trackers = state.0.lock().ok()?;
+    let parser = trackers.get(source)?.parser.as_ref()?;
+    parser.username().map(str::to_string)
+}
+
+/// Drops what was tracked for a source that's no longer watched.
+pub fn forget(app: &AppHandle, source: &str) {
+    if let Ok(mut trackers) = app.state::<LiveRuns>().0.lock() {
+        trackers.remove(source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\u{feff}[09:59:00] [main/INFO]: Setting user: Steve\n\
+        [10:00:01] [Client thread/INFO]: [CHAT] §eParkour Duels\n\
+        [10:00:03] [Client thread/INFO]: [CHAT] CHECKPOINT! You reached checkpoint 1 in 0:05.250!\n";
+    const MORE: &str = "[10:00:05] [Client thread/INFO]: [CHAT] COMPLETED! You completed the parkour in 0:20.000!\n";
+
+    // The chunk LogTail reads first from a log with a BOM
+    fn first_read(log: &str) -> Capped {
+        let content = crate::tail::strip_bom(log).to_string();
+        let base = (log.len() - content.len()) as u64;
+        Capped {
+            offset: 0,
+            ..Capped::new(content, base, usize::MAX)
+        }
+    }
+
+    #[test]
+    fn bom_log_is_parsed_at_raw_offsets() {
+        let settings = Settings::default();
+        let mut tracker = Tracker::default();
+        let found = tracker.scan(key(&settings, None), &first_read(LOG));
+        assert_eq!(found.len(), 2);
+        let RunEvent::Started(started) = &found[0].0 else {
+            panic!("expected a start");
+        };
+        assert_eq!(started.offset, LOG.find("[10:00:01]").unwrap() as u64);
+        let RunEvent::Checkpoint(checkpoint) = &found[1].0 else {
+            panic!("expected a checkpoint");
+        };
+        assert_eq!(checkpoint.offset, LOG.find("[10:00:03]").unwrap() as u64);
+        assert_eq!((checkpoint.index, checkpoint.time_ms), (1, Some(5250)));
+        assert_eq!(found[1].1.as_deref(), Some("Steve"));
+
+        let more = Capped::new(MORE.to_string(), LOG.len() as u64, usize::MAX);
+        let found = tracker.scan(key(&settings, None), &more);
+        let RunEvent::Finished(finished) = &found[0].0 else {
+            panic!("expected a finish");
+        };
+        assert_eq!(finished.offset, LOG.len() as u64);
+        assert_eq!(
+            (finished.player.as_str(), finished.total_ms),
+            ("Steve", Some(20000))
+        );
+    }
+
+    #[test]
+    fn bom_log_starting_over_resets_the_run() {
+        let settings = Settings::default();
+        let mut tracker = Tracker::default();
+        tracker.scan(key(&settings, None), &first_read(LOG));
+        let found = tracker.scan(
+            key(&settings, None),
+            &first_read(&format!("\u{feff}{}", MORE)),
+        );
+        assert!(found.is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::events::LogErrorPayload;
+use crate::lines::strip_terminator;
+use crate::tail::strip_bom;
+use parkour_core::log_line::parse_line;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+const FIRST_LINE_BYTES: u64 = 4096;
+
+/// `{"exists": true, "is_file": false, "readable": false, "size": null, "looks_like_log": false, "suggestion": "/home/me/.minecraft/logs/latest.log"}`
+///
+/// `suggestion` is set when a directory holding a `latest.log` was given
+/// instead of the file, and `error` (same shape as `log-error`) when the
+/// file exists but couldn't be opened.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PathCheck {
+    pub exists: bool,
+    pub is_file: bool,
+    pub readable: bool,
+    pub size: Option<u64>,
+    // First line has the `[12:34:56] [thread/LEVEL]: ...` shape; false for an
+    // empty file the game hasn't written to yet
+    pub looks_like_log: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<LogErrorPayload>,
+}
+
+impl PathCheck {
+    pub fn usable(&self) -> bool {
+        self.is_file && self.readable
+    }
+}
+
+fn first_line(file: File) -> io::Result<String> {
+    let mut line = Vec::new();
+    BufReader::new(file.take(FIRST_LINE_BYTES)).read_until(b'\n', &mut line)?;
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+pub fn check(path: &Path) -> PathCheck {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return PathCheck::default(),
+        Err(e) => {
+            return PathCheck {
+                exists: true,
+                error: Some(LogErrorPayload::io("Failed to open log", path, &e)),
+                ..Default::default()
+            }
+        }
+    };
+    let mut result = PathCheck {
+        exists: true,
+        is_file: meta.is_file(),
+        ..Default::default()
+    };
+    if meta.is_dir() {
+        let log = path.join("latest.log");
+        let log = if log.is_file() {
+            Some(log)
+        } else {
+            Some(path.join("logs").join("latest.log")).filter(|l| l.is_file())
+        };
+        result.suggestion = log.map(|l| l.to_string_lossy().to_string());
+    }
+    // Opening a named pipe would block until something writes to it
+    if !result.is_file {
+        return result;
+    }
+    result.size = Some(meta.len());
+    match File::open(path) {
+        Ok(file) => {
+            result.readable = true;
+            result.looks_like_log = first_line(file).is_ok_and(|line| {
+                parse_line(0, strip_terminator(strip_bom(&line)))
+                    .time
+                    .is_some()
+            });
+        }
+        Err(e) => result.error = Some(LogErrorPayload::io("Failed to open log", path, &e)),
+    }
+    result
+}
+
+#[tauri::command]
+pub fn check_path(path: String) -> PathCheck {
+    check(Path::new(&path))
+}
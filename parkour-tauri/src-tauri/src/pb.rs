@@ -0,0 +1,188 @@
+use crate::db::{self, Db, RunDetail, SumOfBest};
+use crate::events;
+use crate::live::{source_field, CurrentRun};
+use crate::timestamps;
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+/// `{"map": "Tower", "pb": {"id": 42, "time_ms": 62500, "splits": [...], ...}, "routes": [{"checkpoints": [1, 2, 3], "golds": [...], "total_ms": 60100, ...}], "reset_at": null}`
+///
+/// A map's best run and best segments, see [`db::sum_of_best`]; both only
+/// count runs since `reset_at` when the PB was reset.
+#[derive(Serialize, Clone, Debug)]
+pub struct PersonalBests {
+    pub map: String,
+    pub pb: Option<RunDetail>,
+    pub routes: Vec<SumOfBest>,
+    pub reset_at: Option<String>,
+}
+
+/// `{"map": "Tower", "checkpoint": 3, "time_ms": 41250, "pb_time_ms": 42000, "delta_ms": -750, "ahead": true, "segment_delta_ms": -200, "gained": true}`
+///
+/// The live run against the PB at a checkpoint both reached. `segment_delta_ms`
+/// is what the segment into it gained (negative) or lost on the PB's, left
+/// out when the two came from different checkpoints.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct PbComparisonPayload {
+    pub map: String,
+    pub checkpoint: i64,
+    pub time_ms: i64,
+    pub pb_time_ms: i64,
+    pub delta_ms: i64,
+    pub ahead: bool,
+    pub segment_delta_ms: Option<i64>,
+    pub gained: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// A run just finished, against the best time before it.
+pub struct Finish {
+    // As stored, after map merges
+    pub map: String,
+    pub time_ms: i64,
+    // From the stored PB and the runs finished while watching
+    pub best_ms: Option<i64>,
+}
+
+impl Finish {
+    /// Whether it beat every time before it; a map's first finish does.
+    pub fn is_pb(&self) -> bool {
+        self.best_ms.is_none_or(|best| self.time_ms < best)
+    }
+
+    /// Time gained on the best before it, negative when slower.
+    pub fn improvement_ms(&self) -> Option<i64> {
+        self.best_ms.map(|best| best - self.time_ms)
+    }
+}
+
+// Best time of runs finished while watching, by map and player. The
+// watched log is only imported once it goes quiet, so the stored PB can be
+// behind.
+#[derive(Default)]
+pub struct LiveBests(Mutex<HashMap<(String, Option<String>), i64>>);
+
+/// Compares a run just finished on `map` with the best before it and keeps
+/// it as the live best when it beats it. `None` when the database can't be
+/// read.
+pub fn finish(app: &AppHandle, map: &str, username: Option<&str>, time_ms: i64) -> Option<Finish> {
+    let stored = {
+        let db = app.state::<Db>();
+        db.conn().and_then(|conn| {
+            let pb = db::personal_best(&conn, map, username)?;
+            Ok((db::canonical_map(&conn, map)?, pb))
+        })
+    };
+    let (map, pb) = match stored {
+        Ok(stored) => stored,
+        Err(e) => {
+            eprintln!("{}", e);
+            return None;
+        }
+    };
+    let key = (map.to_lowercase(), username.map(str::to_string));
+    let live = app.state::<LiveBests>();
+    let mut bests = live.0.lock().ok()?;
+    let stored_ms = pb.and_then(|pb| pb.run.time_ms);
+    let best_ms = bests.get(&key).copied().into_iter().chain(stored_ms).min();
+    let finish = Finish {
+        map,
+        time_ms,
+        best_ms,
+    };
+    if finish.is_pb() {
+        bests.insert(key, time_ms);
+    }
+    Some(finish)
+}
+
+fn comparison(run: &CurrentRun, pb: &RunDetail) -> Option<PbComparisonPayload> {
+    let (split, before) = run.splits.split_last()?;
+    let at = pb
+        .splits
+        .iter()
+        .position(|s| s.checkpoint == split.checkpoint)?;
+    let pb_split = &pb.splits[at];
+    let delta_ms = split.time_ms - pb_split.time_ms;
+    let previous = before.last();
+    let pb_previous = at.checked_sub(1).map(|i| &pb.splits[i]);
+    let segment_delta_ms = (previous.map(|s| s.checkpoint) == pb_previous.map(|s| s.checkpoint))
+        .then(|| {
+            let segment = split.time_ms - previous.map_or(0, |s| s.time_ms);
+            let pb_segment = pb_split.time_ms - pb_previous.map_or(0, |s| s.time_ms);
+            segment - pb_segment
+        });
+    Some(PbComparisonPayload {
+        map: pb.run.map.clone(),
+        checkpoint: split.checkpoint,
+        time_ms: split.time_ms,
+        pb_time_ms: pb_split.time_ms,
+        delta_ms,
+        ahead: delta_ms < 0,
+        segment_delta_ms,
+        gained: segment_delta_ms.map(|d| d < 0),
+        source: None,
+    })
+}
+
+/// Sends `pb-comparison` for the split just added to `run`, when its map
+/// has a PB that reached the same checkpoint.
+pub fn compare(app: &AppHandle, source: &str, run: &CurrentRun) {
+    let Some(map) = run.map.as_deref() else {
+        return;
+    };
+    let pb = {
+        let db = app.state::<Db>();
+        let Ok(conn) = db.conn() else {
+            return;
+        };
+        db::personal_best(&conn, map, run.username.as_deref())
+    };
+    let Some(mut payload) = pb.ok().flatten().and_then(|pb| comparison(run, &pb)) else {
+        return;
+    };
+    payload.source = source_field(source);
+    events::emit_all(app, events::PB_COMPARISON, &payload);
+}
+
+pub fn personal_bests(
+    conn: &Connection,
+    map: &str,
+    username: Option<&str>,
+) -> Result<PersonalBests, String> {
+    Ok(PersonalBests {
+        pb: db::personal_best(conn, map, username)?,
+        routes: db::sum_of_best(conn, map, username)?,
+        reset_at: db::pb_reset_at(conn, map)?,
+        map: db::canonical_map(conn, map)?,
+    })
+}
+
+#[tauri::command]
+pub fn get_pb(
+    map: String,
+    username: Option<String>,
+    db: State<'_, Db>,
+) -> Result<PersonalBests, String> {
+    let conn = db.conn()?;
+    personal_bests(&conn, &map, username.as_deref())
+}
+
+/// Starts `map`'s PB and golds over for every player. Earlier runs are kept
+/// and still show in history and stats.
+#[tauri::command]
+pub fn reset_pb(map: String, app: AppHandle) -> Result<(), String> {
+    {
+        let db = app.state::<Db>();
+        let conn = db.conn()?;
+        let now = timestamps::format_utc(chrono::Utc::now());
+        db::reset_pb(&conn, &map, &now)?;
+    }
+    crate::text_output::refresh(&app);
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+
+const BUCKETS: usize = 40;
+
+// Power-of-two buckets: bucket i holds values in [2^(i-1), 2^i), so the
+// reported p95 is an upper bound at most twice the true value.
+pub struct Histogram {
+    count: AtomicU64,
+    sum: AtomicU64,
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Summary {
+    pub count: u64,
+    pub mean: f64,
+    pub p95: u64,
+}
+
+impl Histogram {
+    pub fn observe(&self, value: u64) {
+        let bucket = ((u64::BITS - value.leading_zeros()) as usize).min(BUCKETS - 1);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn summary(&self) -> Summary {
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum.load(Ordering::Relaxed);
+        let target = (count * 95).div_ceil(100);
+        let mut seen = 0;
+        let mut p95 = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if count > 0 && seen >= target {
+                p95 = 1u64 << i;
+                break;
+            }
+        }
+        Summary {
+            count,
+            mean: if count == 0 {
+                0.0
+            } else {
+                sum as f64 / count as f64
+            },
+            p95,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Perf {
+    pub file_read_us: Histogram,
+    pub read_bytes: Histogram,
+    pub chunk_us_per_kb: Histogram,
+    pub emit_serialize_us: Histogram,
+    pub db_write_us: Histogram,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PerformanceStats {
+    pub file_read_us: Summary,
+    pub read_bytes: Summary,
+    pub chunk_us_per_kb: Summary,
+    pub emit_serialize_us: Summary,
+    pub db_write_us: Summary,
+}
+
+pub fn record(app: &AppHandle, pick: impl FnOnce(&Perf) -> &Histogram, value: u64) {
+    if let Some(perf) = app.try_state::<Perf>() {
+        pick(&perf).observe(value);
+    }
+}
+
+pub fn time<T>(
+    app: &AppHandle,
+    pick: impl FnOnce(&Perf) -> &Histogram,
+    f: impl FnOnce() -> T,
+) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(app, pick, start.elapsed().as_micros() as u64);
+    result
+}
+
+#[tauri::command]
+pub fn get_performance_stats(perf: State<'_, Perf>) -> PerformanceStats {
+    PerformanceStats {
+        file_read_us: perf.file_read_us.summary(),
+        read_bytes: perf.read_bytes.summary(),
+        chunk_us_per_kb: perf.chunk_us_per_kb.summary(),
+        emit_serialize_us: perf.emit_serialize_us.summary(),
+        db_write_us: perf.db_write_us.summary(),
+    }
+}
+
+#[tauri::command]
+pub fn reset_performance_stats(perf: State<'_, Perf>) {
+    for histogram in [
+        &perf.file_read_us,
+        &perf.read_bytes,
+        &perf.chunk_us_per_kb,
+        &perf.emit_serialize_us,
+        &perf.db_write_us,
+    ] {
+        histogram.reset();
+    }
+}
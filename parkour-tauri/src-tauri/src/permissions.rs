@@ -0,0 +1,56 @@
+use std::path::Path;
+use tauri::AppHandle;
+use tauri_plugin_opener::OpenerExt;
+
+const PRIVACY_SETTINGS: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_AllFiles";
+
+fn running_as_snap() -> bool {
+    std::env::var_os("SNAP").is_some()
+}
+
+/// What the user can do about a permission-denied error on this platform.
+pub fn hint(path: &Path) -> String {
+    if cfg!(target_os = "macos") {
+        "macOS is blocking access to this folder. Allow Parkour Analyzer under System Settings \
+         > Privacy & Security > Full Disk Access (or Files and Folders), then try again."
+            .into()
+    } else if cfg!(windows) {
+        format!(
+            "Windows denied access to {}. Check the file's Security tab in Properties, or run \
+             the game and Parkour Analyzer as the same user.",
+            path.display()
+        )
+    } else if running_as_snap() {
+        "Parkour Analyzer is running as a snap and can't see this folder. Run \
+         `snap connect parkour-analyzer:home`, or use a non-snap build for logs outside your \
+         home directory."
+            .into()
+    } else {
+        format!(
+            "Your user can't read {}. Check its permissions and those of the folders above it \
+             with `ls -l`.",
+            path.display()
+        )
+    }
+}
+
+/// Opens the place where access can be granted: Privacy settings on macOS,
+/// otherwise the file manager at the log so its permissions can be edited.
+/// Returns false when there is nothing to open and the hint is all we have.
+#[tauri::command]
+pub fn request_file_access(path: String, app: AppHandle) -> Result<bool, String> {
+    if cfg!(target_os = "macos") {
+        app.opener()
+            .open_url(PRIVACY_SETTINGS, None::<&str>)
+            .map_err(|e| format!("Failed to open Privacy settings: {}", e))?;
+        return Ok(true);
+    }
+    if running_as_snap() {
+        return Ok(false);
+    }
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| format!("Failed to show {}: {}", path, e))?;
+    Ok(true)
+}
@@ -0,0 +1,274 @@
+use crate::chat_commands;
+use crate::crash;
+use crate::definitions;
+use crate::instances;
+use crate::line_index::LineIndexState;
+use crate::lines::Capped;
+use crate::live::{Live, MAIN};
+use crate::parser;
+use crate::perf;
+use crate::servers;
+use crate::settings::SettingsStore;
+use crate::tail::Chunk;
+use notify::Event;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Sender, SyncSender, TrySendError};
+use std::sync::{Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+const CAPACITY: usize = 32;
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The watcher thread only reads the log; chunks are handed to a worker
+// thread that does the parsing and emitting, so slow parsing never holds up
+// filesystem events.
+#[derive(Default)]
+pub struct Pipeline {
+    depth: AtomicUsize,
+    // The watcher's handler owns the sender, so its channel still
+    // disconnects when the notify backend goes away
+    wake: Mutex<Weak<Sender<notify::Result<Event>>>>,
+    // Workers still running; a restarted watcher's new worker can overlap
+    // with the previous one draining
+    running: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl Pipeline {
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    pub fn set_wake(&self, tx: Weak<Sender<notify::Result<Event>>>) {
+        if let Ok(mut wake) = self.wake.lock() {
+            *wake = tx;
+        }
+    }
+
+    // Wakes the watcher without stopping it, so it notices a new target
+    pub fn wake(&self) {
+        if let Some(tx) = self.wake.lock().ok().and_then(|w| w.upgrade()) {
+            let _ = tx.send(Err(notify::Error::generic("log switched")));
+        }
+    }
+
+    // Wakes the watcher so it notices it should stop, then waits for the
+    // worker to emit whatever was still queued.
+    pub fn drain(&self) {
+        let wake = self.wake.lock().ok().map(|mut w| std::mem::take(&mut *w));
+        if let Some(tx) = wake.and_then(|w| w.upgrade()) {
+            let _ = tx.send(Err(notify::Error::generic("shutting down")));
+        }
+        if let Ok(running) = self.running.lock() {
+            let _ = self
+                .finished
+                .wait_timeout_while(running, DRAIN_TIMEOUT, |running| *running > 0);
+        }
+    }
+
+    fn add_running(&self, delta: isize) {
+        if let Ok(mut running) = self.running.lock() {
+            *running = running.saturating_add_signed(delta);
+        }
+        self.finished.notify_all();
+    }
+}
+
+// Chunks the worker had no room for yet. A chunk carrying on where the last
+// one ended is merged into it; one that starts the log over is kept apart,
+// so the old log's last lines still reach the worker before it.
+#[derive(Default)]
+struct Pending(VecDeque<Chunk>);
+
+impl Pending {
+    fn push(&mut self, chunk: Chunk) {
+        match self.0.back_mut() {
+            Some(last) if chunk.offset != 0 && chunk.offset == last.end => {
+                last.content.push_str(&chunk.content);
+                last.end = chunk.end;
+            }
+            _ => self.0.push_back(chunk),
+        }
+    }
+
+    // Queues as many chunks as there's room for, returning how many
+    fn send(&mut self, tx: &SyncSender<Chunk>) -> usize {
+        let mut sent = 0;
+        while let Some(chunk) = self.0.pop_front() {
+            match tx.try_send(chunk) {
+                Ok(()) => sent += 1,
+                Err(TrySendError::Full(chunk)) => {
+                    self.0.push_front(chunk);
+                    break;
+                }
+                Err(TrySendError::Disconnected(_)) => self.0.clear(),
+            }
+        }
+        sent
+    }
+}
+
+pub struct Feeder {
+    app: AppHandle,
+    tx: SyncSender<Chunk>,
+    pending: Pending,
+}
+
+impl Feeder {
+    // When the worker falls behind, new chunks wait in `pending` instead of
+    // blocking the watcher.
+    pub fn push(&mut self, chunk: Chunk) {
+        self.pending.push(chunk);
+        self.flush();
+    }
+
+    pub fn flush(&mut self) {
+        let sent = self.pending.send(&self.tx);
+        self.app
+            .state::<Pipeline>()
+            .depth
+            .fetch_add(sent, Ordering::Relaxed);
+    }
+
+    // Blocks until the last pending chunks are queued; dropping the sender
+    // then lets the worker finish once the queue is empty.
+    pub fn finish(mut self) {
+        let pipeline = self.app.state::<Pipeline>();
+        for chunk in self.pending.0.drain(..) {
+            if self.tx.send(chunk).is_err() {
+                break;
+            }
+            pipeline.depth.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Starts the worker for the log of `source`, [`MAIN`] for the watched log
+/// or an instance's id.
+pub fn start(app: &AppHandle, source: &str, path: &Path) -> Feeder {
+    let (tx, rx) = sync_channel::<Chunk>(CAPACITY);
+    let pipeline = app.state::<Pipeline>();
+    pipeline.add_running(1);
+    let worker_app = app.clone();
+    let source = source.to_string();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let pipeline = worker_app.state::<Pipeline>();
+        for chunk in rx {
+            pipeline.depth.fetch_sub(1, Ordering::Relaxed);
+            // Chunks still queued for an instance that was just removed
+            if worker_app.state::<Live>().source(&source).is_none() {
+                continue;
+            }
+            let start = Instant::now();
+            let kb = (chunk.content.len() as u64).div_ceil(1024).max(1);
+            if source == MAIN {
+                worker_app.state::<LineIndexState>().record(&path, &chunk);
+            }
+            // Long lines are cut once, before any regex sees them
+            let max = worker_app
+                .state::<SettingsStore>()
+                .get()
+                .watcher
+                .max_line_bytes;
+            let Chunk {
+                offset,
+                end,
+                start: base,
+                content,
+                ..
+            } = chunk;
+            let capped = Capped {
+                offset,
+                ..Capped::new(content, base, max)
+            };
+            crash::scan(&worker_app, &source, &capped);
+            servers::scan(&worker_app, &source, &capped);
+            parser::scan(&worker_app, &source, &capped);
+            definitions::scan(&worker_app, &source, &capped);
+            chat_commands::scan(&worker_app, &source, &capped);
+            match source.as_str() {
+                MAIN => crate::emit_update(&worker_app, &path, offset, end, capped),
+                _ => instances::emit_update(&worker_app, &source, &path, offset, end, capped),
+            }
+            let us = start.elapsed().as_micros() as u64;
+            perf::record(&worker_app, |p| &p.chunk_us_per_kb, us / kb);
+        }
+        pipeline.add_running(-1);
+    });
+    Feeder {
+        app: app.clone(),
+        tx,
+        pending: Pending::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tail::Rotation;
+
+    fn chunk(offset: u64, content: &str) -> Chunk {
+        Chunk {
+            offset,
+            end: offset + content.len() as u64,
+            start: offset,
+            content: content.to_string(),
+            rotation: None,
+        }
+    }
+
+    #[test]
+    fn a_rotation_under_backpressure_keeps_the_old_logs_last_lines() {
+        let (tx, rx) = sync_channel(1);
+        let mut pending = Pending::default();
+        pending.push(chunk(0, "a\n"));
+        assert_eq!(pending.send(&tx), 1);
+        // The worker is behind: the rest of the old log waits, merged
+        pending.push(chunk(2, "b\n"));
+        pending.push(chunk(4, "COMPLETED!\n"));
+        assert_eq!(pending.send(&tx), 0);
+        let rotated = Chunk {
+            rotation: Some(Rotation {
+                truncated: false,
+                previous_end: 15,
+            }),
+            ..chunk(0, "new\n")
+        };
+        pending.push(rotated);
+        pending.push(chunk(4, "more\n"));
+        assert_eq!(pending.0.len(), 2);
+
+        let received = |pending: &mut Pending| {
+            let chunk = rx.try_recv().unwrap();
+            assert_eq!(pending.send(&tx), 1);
+            chunk
+        };
+        assert_eq!(received(&mut pending).content, "a\n");
+        let old = received(&mut pending);
+        assert_eq!((old.offset, old.end), (2, 15));
+        assert_eq!(old.content, "b\nCOMPLETED!\n");
+        let new = rx.try_recv().unwrap();
+        assert!(new.rotation.is_some());
+        assert_eq!(
+            (new.offset, new.end, new.content.as_str()),
+            (0, 9, "new\nmore\n")
+        );
+        assert!(pending.0.is_empty());
+    }
+
+    #[test]
+    fn chunks_that_dont_follow_on_are_kept_apart() {
+        let (tx, _rx) = sync_channel(0);
+        let mut pending = Pending::default();
+        pending.push(chunk(0, "a\n"));
+        pending.push(chunk(0, "b\n"));
+        pending.push(chunk(10, "c\n"));
+        assert_eq!(pending.send(&tx), 0);
+        let contents: Vec<&str> = pending.0.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(contents, ["a\n", "b\n", "c\n"]);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::db::{self, Db};
+use crate::events::{self, MessagePayload};
+use crate::live::{CurrentRun, Live};
+use crate::settings::{PresenceSettings, SettingsStore};
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+// Discord takes five activity updates per 20 seconds
+const POLL_INTERVAL: Duration = Duration::from_secs(4);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(15);
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+const OP_CLOSE: u32 = 2;
+
+trait Pipe: Read + Write + Send {}
+impl<T: Read + Write + Send> Pipe for T {}
+
+// The socket Discord's desktop app listens on, trying each of the ten it
+// may have picked
+#[cfg(unix)]
+fn open_pipe() -> io::Result<Box<dyn Pipe>> {
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    let bases = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .chain([PathBuf::from("/tmp")]);
+    for base in bases {
+        // Flatpak and Snap installs put theirs in a subfolder
+        for dir in ["", "app/com.discordapp.Discord", "snap.discord"] {
+            for i in 0..10 {
+                if let Ok(stream) =
+                    UnixStream::connect(base.join(dir).join(format!("discord-ipc-{}", i)))
+                {
+                    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+                    return Ok(Box::new(stream));
+                }
+            }
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Discord isn't running",
+    ))
+}
+
+#[cfg(windows)]
+fn open_pipe() -> io::Result<Box<dyn Pipe>> {
+    for i in 0..10 {
+        let pipe = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!(r"\\.\pipe\discord-ipc-{}", i));
+        if let Ok(pipe) = pipe {
+            return Ok(Box::new(pipe));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Discord isn't running",
+    ))
+}
+
+// A frame is its opcode and length, both little-endian u32, then JSON
+fn send(pipe: &mut dyn Pipe, op: u32, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    let mut frame = Vec::with_capacity(8 + body.len());
+    frame.extend(op.to_le_bytes());
+    frame.extend((body.len() as u32).to_le_bytes());
+    frame.extend(body.as_bytes());
+    pipe.write_all(&frame)
+}
+
+fn receive(pipe: &mut dyn Pipe) -> io::Result<(u32, Value)> {
+    let mut header = [0u8; 8];
+    pipe.read_exact(&mut header)?;
+    let op = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let mut body = vec![0u8; len as usize];
+    pipe.read_exact(&mut body)?;
+    let body =
+        serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((op, body))
+}
+
+fn connect(client_id: &str) -> Result<Box<dyn Pipe>, String> {
+    let failed = |e: io::Error| format!("Failed to connect to Discord: {}", e);
+    let mut pipe = open_pipe().map_err(failed)?;
+    send(
+        &mut *pipe,
+        OP_HANDSHAKE,
+        &json!({ "v": 1, "client_id": client_id }),
+    )
+    .map_err(failed)?;
+    match receive(&mut *pipe).map_err(failed)? {
+        (OP_CLOSE, body) => Err(format!(
+            "Discord turned the connection down: {}",
+            body["message"].as_str().unwrap_or("no reason given")
+        )),
+        _ => Ok(pipe),
+    }
+}
+
+// `activity` of None clears what's shown
+fn set_activity(pipe: &mut dyn Pipe, activity: Option<Value>) -> io::Result<()> {
+    let body = json!({
+        "cmd": "SET_ACTIVITY",
+        "args": { "pid": std::process::id(), "activity": activity },
+        "nonce": crate::live::now_ms().to_string(),
+    });
+    send(pipe, OP_FRAME, &body)?;
+    // Discord answers every command; reading keeps its replies from piling up
+    match receive(pipe)? {
+        (OP_CLOSE, _) => Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            "Discord closed the connection",
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Map, attempt number and start of the run shown, to notice when it changes
+type Shown = (String, i64, u64);
+
+fn shown(app: &AppHandle, run: &CurrentRun) -> Option<Shown> {
+    let map = run.map.clone()?;
+    let db = app.state::<Db>();
+    let conn = db.conn().ok()?;
+    // Runs are stored when they end, so this one isn't counted yet
+    let attempts = db::attempt_count(&conn, &map, run.username.as_deref()).ok()?;
+    Some((map, attempts + 1, run.started_ms.unwrap_or_default()))
+}
+
+fn activity((map, attempt, started_ms): &Shown) -> Value {
+    json!({
+        "details": map,
+        "state": format!("Attempt {}", attempt),
+        // Discord counts up the elapsed time from here by itself
+        "timestamps": { "start": started_ms / 1000 },
+    })
+}
+
+/// Shows the watched log's current run in Discord while presence is enabled,
+/// until shutdown.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        // The connection, with the application id it was opened for
+        let mut conn: Option<(String, Box<dyn Pipe>)> = None;
+        let mut last_attempt: Option<Instant> = None;
+        let mut reported = false;
+        // What was last sent, None when the activity is clear
+        let mut current: Option<Shown> = None;
+        // The run `current` was made for, so attempts aren't counted every poll
+        let mut run_key: Option<(Option<String>, Option<u64>)> = None;
+        while !crate::stopping(&app) {
+            std::thread::sleep(POLL_INTERVAL);
+            let settings = app.state::<SettingsStore>().get().presence;
+            let client_id = settings.client_id.filter(|_| settings.enabled);
+            if conn.as_ref().map(|(id, _)| id) != client_id.as_ref() {
+                if let Some((_, pipe)) = conn.as_mut() {
+                    let _ = set_activity(&mut **pipe, None);
+                }
+                conn = None;
+                current = None;
+                run_key = None;
+            }
+            let Some(client_id) = client_id else {
+                continue;
+            };
+
+            let run = app.state::<Live>().snapshot().current_run;
+            let key = run.as_ref().map(|r| (r.map.clone(), r.started_ms));
+            let wanted = match &run {
+                Some(_) if key == run_key => current.clone(),
+                _ => run.as_ref().and_then(|run| shown(&app, run)),
+            };
+            if conn.is_some() && wanted == current {
+                continue;
+            }
+
+            if conn.is_none() {
+                if last_attempt.is_some_and(|t| t.elapsed() < RECONNECT_INTERVAL) {
+                    continue;
+                }
+                last_attempt = Some(Instant::now());
+                match connect(&client_id) {
+                    Ok(pipe) => {
+                        conn = Some((client_id, pipe));
+                        reported = false;
+                    }
+                    Err(e) => {
+                        if !reported {
+                            events::emit(
+                                &app,
+                                events::PRESENCE_ERROR,
+                                &MessagePayload { message: e },
+                            );
+                            reported = true;
+                        }
+                        continue;
+                    }
+                }
+            }
+            let Some((_, pipe)) = conn.as_mut() else {
+                continue;
+            };
+            if set_activity(&mut **pipe, wanted.as_ref().map(activity)).is_ok() {
+                current = wanted;
+                run_key = key;
+            } else {
+                // Discord restarted: reconnect on the next poll
+                conn = None;
+                last_attempt = None;
+            }
+        }
+    });
+}
+
+/// `{"enabled": true, "client_id": "1234567890123456789"}`
+///
+/// Turns Discord Rich Presence on or off and saves it. `client_id` is the
+/// application id of a Discord app, whose name shows above the map; the
+/// saved one is kept when it's left out.
+#[tauri::command]
+pub fn set_presence_enabled(
+    enabled: bool,
+    client_id: Option<String>,
+    store: State<'_, SettingsStore>,
+) -> Result<PresenceSettings, String> {
+    let mut settings = store.get();
+    settings.presence.enabled = enabled;
+    if let Some(client_id) = client_id {
+        settings.presence.client_id = Some(client_id.trim().to_string());
+    }
+    crate::settings::validate(&settings)?;
+    store.replace(settings.clone())?;
+    Ok(settings.presence)
+}
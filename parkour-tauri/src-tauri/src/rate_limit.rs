@@ -0,0 +1,171 @@
+use crate::events;
+use crate::routing;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+// Late run results would make splits and PB popups lag behind the game, so
+// these channels always go out immediately.
+pub const EXEMPT: &[&str] = &[
+    events::RUN_FINISHED,
+    events::CHECKPOINT_REACHED,
+    events::SPLIT_RECORDED,
+    events::GOAL_ACHIEVED,
+    events::PB_COMPARISON,
+];
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    pending: Vec<Value>,
+    flushing: bool,
+}
+
+impl Bucket {
+    fn refill(&mut self, rate: f64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate.max(1.0));
+        self.refilled = now;
+    }
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    limits: Mutex<BTreeMap<String, f64>>,
+    buckets: Mutex<HashMap<&'static str, Bucket>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RateLimit {
+    pub channel: String,
+    pub max_per_second: Option<f64>,
+    pub exempt: bool,
+}
+
+impl RateLimiter {
+    pub fn set_limits(&self, limits: &BTreeMap<String, f64>) {
+        if let Ok(mut current) = self.limits.lock() {
+            *current = limits.clone();
+        }
+    }
+
+    fn rate(&self, event: &str) -> Option<f64> {
+        if EXEMPT.contains(&event) {
+            return None;
+        }
+        self.limits.lock().ok()?.get(event).copied()
+    }
+
+    pub fn effective(&self) -> Vec<RateLimit> {
+        let limits = self.limits.lock().map(|l| l.clone()).unwrap_or_default();
+        let mut out: Vec<RateLimit> = limits
+            .into_iter()
+            .filter(|(channel, _)| !EXEMPT.contains(&channel.as_str()))
+            .map(|(channel, rate)| RateLimit {
+                channel,
+                max_per_second: Some(rate),
+                exempt: false,
+            })
+            .collect();
+        out.extend(EXEMPT.iter().map(|channel| RateLimit {
+            channel: channel.to_string(),
+            max_per_second: None,
+            exempt: true,
+        }));
+        out
+    }
+}
+
+fn flush(app: &AppHandle, event: &'static str, rate: f64) {
+    let pending = {
+        let limiter = app.state::<RateLimiter>();
+        let Ok(mut buckets) = limiter.buckets.lock() else {
+            return;
+        };
+        let Some(bucket) = buckets.get_mut(event) else {
+            return;
+        };
+        bucket.refill(rate);
+        bucket.tokens -= 1.0;
+        bucket.flushing = false;
+        std::mem::take(&mut bucket.pending)
+    };
+    routing::emit(app, event, json!({ "events": pending }));
+}
+
+/// Emits `payload` on `event`, or queues it when the channel is over its
+/// configured rate. Queued payloads go out together as one
+/// `{"events": [...]}` payload once the channel has room again.
+pub fn emit(app: &AppHandle, event: &'static str, payload: Value) {
+    let Some(limiter) = app.try_state::<RateLimiter>() else {
+        routing::emit(app, event, payload);
+        return;
+    };
+    let Some(rate) = limiter.rate(event) else {
+        routing::emit(app, event, payload);
+        return;
+    };
+    let wait = {
+        let Ok(mut buckets) = limiter.buckets.lock() else {
+            return;
+        };
+        let bucket = buckets.entry(event).or_insert_with(|| Bucket {
+            tokens: rate.max(1.0),
+            refilled: Instant::now(),
+            pending: Vec::new(),
+            flushing: false,
+        });
+        bucket.refill(rate);
+        if bucket.pending.is_empty() && bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            drop(buckets);
+            routing::emit(app, event, payload);
+            return;
+        }
+        bucket.pending.push(payload);
+        if bucket.flushing {
+            return;
+        }
+        bucket.flushing = true;
+        Duration::from_secs_f64(((1.0 - bucket.tokens) / rate).max(0.0))
+    };
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(wait);
+        flush(&app, event, rate);
+    });
+}
+
+#[tauri::command]
+pub fn get_rate_limits(limiter: State<'_, RateLimiter>) -> Vec<RateLimit> {
+    limiter.effective()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exempt_channels_are_sent_events() {
+        let schema = events::get_event_schema();
+        for channel in EXEMPT {
+            assert!(schema["payloads"].get(*channel).is_some(), "{}", channel);
+        }
+    }
+
+    #[test]
+    fn exempt_channels_ignore_limits() {
+        let limiter = RateLimiter::default();
+        let limits = BTreeMap::from([
+            (events::RUN_FINISHED.to_string(), 1.0),
+            (events::TIMER_TICK.to_string(), 2.0),
+        ]);
+        limiter.set_limits(&limits);
+        assert_eq!(limiter.rate(events::RUN_FINISHED), None);
+        assert_eq!(limiter.rate(events::TIMER_TICK), Some(2.0));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::events::{LocationSource, LogErrorKind, LogErrorPayload};
+use crate::live::{Live, MAIN};
+use crate::settings::{RemoteAuth, RemoteSource, SettingsStore};
+use crate::tail::{Chunk, Rotation};
+use crate::webhooks::{self, LogErrorSummary, WebhookEvent};
+use crate::{metrics, pipeline};
+use serde::Serialize;
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Session, Sftp};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const KEYRING_SERVICE: &str = "parkour-analyzer";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const KEEPALIVE_SECS: u32 = 30;
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+// Compared on every change, to tell a replaced log from one that grew
+const HEAD_BYTES: u64 = 64;
+// libssh2's LIBSSH2_ERROR_AUTHENTICATION_FAILED and _PUBLICKEY_UNVERIFIED
+const AUTH_FAILED: [i32; 2] = [-18, -19];
+// LIBSSH2_FX_NO_SUCH_FILE and LIBSSH2_FX_PERMISSION_DENIED
+const NO_SUCH_FILE: i32 = 2;
+const PERMISSION_DENIED: i32 = 3;
+
+/// `sftp://user@host:port/path`, the path events give for a remote log.
+pub fn url(source: &RemoteSource) -> String {
+    format!(
+        "sftp://{}@{}:{}/{}",
+        source.username,
+        source.host,
+        source.port,
+        source.path.trim_start_matches('/')
+    )
+}
+
+fn error(source: &RemoteSource, kind: LogErrorKind, message: String) -> LogErrorPayload {
+    LogErrorPayload::new(kind, message).with_path(Path::new(&url(source)))
+}
+
+fn ssh_error(source: &RemoteSource, context: &str, err: &ssh2::Error) -> LogErrorPayload {
+    let kind = match err.code() {
+        ErrorCode::Session(code) if AUTH_FAILED.contains(&code) => LogErrorKind::AuthFailed,
+        ErrorCode::SFTP(NO_SUCH_FILE) => LogErrorKind::LogNotFound,
+        ErrorCode::SFTP(PERMISSION_DENIED) => LogErrorKind::PermissionDenied,
+        _ => LogErrorKind::ConnectionFailed,
+    };
+    error(source, kind, format!("{}: {}", context, err))
+}
+
+fn io_error(source: &RemoteSource, err: &io::Error) -> LogErrorPayload {
+    let kind = match err.kind() {
+        io::ErrorKind::InvalidData => LogErrorKind::Utf8Decode,
+        _ => LogErrorKind::ReadFailed,
+    };
+    error(source, kind, format!("Failed to read log: {}", err))
+}
+
+// Stored per login, so two accounts on one host keep their own
+fn keyring_entry(source: &RemoteSource) -> Result<keyring::Entry, String> {
+    let account = format!("{}@{}:{}", source.username, source.host, source.port);
+    keyring::Entry::new(KEYRING_SERVICE, &account)
+        .map_err(|e| format!("Failed to open the keyring: {}", e))
+}
+
+/// The password or key passphrase saved for `source`, if any.
+pub fn stored_secret(source: &RemoteSource) -> Result<Option<String>, String> {
+    match keyring_entry(source)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read the keyring: {}", e)),
+    }
+}
+
+fn check_host_key(source: &RemoteSource, session: &Session) -> Result<(), LogErrorPayload> {
+    let rejected = |message: String| error(source, LogErrorKind::HostKeyRejected, message);
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| rejected(format!("{} sent no host key", source.host)))?;
+    let mut known = session
+        .known_hosts()
+        .map_err(|e| ssh_error(source, "Failed to check the host key", &e))?;
+    // A missing file only means no host is known yet
+    if let Some(home) = dirs::home_dir() {
+        let _ = known.read_file(&home.join(".ssh/known_hosts"), KnownHostFileKind::OpenSSH);
+    }
+    match known.check_port(&source.host, source.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(rejected(format!(
+            "Host key for {} doesn't match the one in known_hosts",
+            source.host
+        ))),
+        _ => Err(rejected(format!(
+            "{} isn't in known_hosts; connect once with ssh to add it",
+            source.host
+        ))),
+    }
+}
+
+fn connect(source: &RemoteSource, secret: Option<&str>) -> Result<Sftp, LogErrorPayload> {
+    let unreachable = |e: io::Error| {
+        error(
+            source,
+            LogErrorKind::ConnectionFailed,
+            format!("Failed to connect to {}: {}", source.host, e),
+        )
+    };
+    let addr = (source.host.as_str(), source.port)
+        .to_socket_addrs()
+        .map_err(unreachable)?
+        .next()
+        .ok_or_else(|| unreachable(io::ErrorKind::NotFound.into()))?;
+    let tcp = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(unreachable)?;
+    let mut session = Session::new().map_err(|e| ssh_error(source, "SSH setup failed", &e))?;
+    session.set_timeout(TIMEOUT.as_millis() as u32);
+    session.set_tcp_stream(tcp);
+    session
+        .handshake()
+        .map_err(|e| ssh_error(source, "SSH handshake failed", &e))?;
+    check_host_key(source, &session)?;
+    match &source.auth {
+        RemoteAuth::Password => {
+            session.userauth_password(&source.username, secret.unwrap_or_default())
+        }
+        RemoteAuth::Key { key_path } => {
+            session.userauth_pubkey_file(&source.username, None, Path::new(key_path), secret)
+        }
+    }
+    .map_err(|e| ssh_error(source, "Login failed", &e))?;
+    session.set_keepalive(true, KEEPALIVE_SECS);
+    session
+        .sftp()
+        .map_err(|e| ssh_error(source, "Failed to start SFTP", &e))
+}
+
+/// Reads what was appended to a remote log, as [`LogTail`](crate::tail::LogTail)
+/// does for a local one. The connection is made on the first poll and again
+/// after any error, carrying on from the same offset.
+pub struct RemoteTail {
+    source: RemoteSource,
+    secret: Option<String>,
+    sftp: Option<Sftp>,
+    pos: u64,
+    // Size and modification time at the last change
+    seen: Option<(u64, Option<u64>)>,
+    // First bytes of the log, which stay put as it grows
+    head: Vec<u8>,
+}
+
+impl RemoteTail {
+    pub fn new(source: RemoteSource, secret: Option<String>) -> RemoteTail {
+        RemoteTail {
+            source,
+            secret,
+            sftp: None,
+            pos: 0,
+            seen: None,
+            head: Vec::new(),
+        }
+    }
+
+    /// Same as [`LogTail::poll`](crate::tail::LogTail::poll): only complete
+    /// lines, with `offset` 0 when the log was rotated or truncated.
+    pub fn poll(&mut self) -> Result<Option<Chunk>, LogErrorPayload> {
+        let result = self.read();
+        if result.is_err() {
+            self.sftp = None;
+        }
+        result
+    }
+
+    fn read(&mut self) -> Result<Option<Chunk>, LogErrorPayload> {
+        if self.sftp.is_none() {
+            self.sftp = Some(connect(&self.source, self.secret.as_deref())?);
+        }
+        let Some(sftp) = &self.sftp else {
+            return Ok(None);
+        };
+        let source = &self.source;
+        let path = Path::new(&source.path);
+        let stat = sftp
+            .stat(path)
+            .map_err(|e| ssh_error(source, "Failed to read log", &e))?;
+        let seen = (stat.size.unwrap_or(0), stat.mtime);
+        if self.seen == Some(seen) {
+            return Ok(None);
+        }
+        let len = seen.0;
+        let mut file = sftp
+            .open(path)
+            .map_err(|e| ssh_error(source, "Failed to open log", &e))?;
+        let mut head = Vec::new();
+        (&mut file)
+            .take(HEAD_BYTES)
+            .read_to_end(&mut head)
+            .map_err(|e| io_error(source, &e))?;
+        let truncated = len < self.pos;
+        let replaced = !head.starts_with(&self.head);
+        let rotation = (self.seen.is_some() && (truncated || replaced)).then_some(Rotation {
+            truncated: truncated && !replaced,
+            previous_end: self.pos,
+        });
+        let reopened = self.seen.is_none() || rotation.is_some();
+        if reopened {
+            self.pos = 0;
+        }
+        file.seek(SeekFrom::Start(self.pos))
+            .map_err(|e| io_error(source, &e))?;
+        let mut bytes = Vec::new();
+        file.take(len - self.pos)
+            .read_to_end(&mut bytes)
+            .map_err(|e| io_error(source, &e))?;
+        let mut chunk = Chunk::complete(self.pos, bytes).map_err(|e| io_error(source, &e))?;
+        chunk.rotation = rotation;
+        self.seen = Some(seen);
+        self.head = head;
+        self.pos = chunk.end;
+        if chunk.end == chunk.offset && !reopened {
+            return Ok(None);
+        }
+        Ok(Some(chunk))
+    }
+}
+
+/// The whole remote log in one read, for `sync`.
+pub fn read(source: &RemoteSource) -> io::Result<Vec<u8>> {
+    let secret = stored_secret(source).map_err(io::Error::other)?;
+    let sftp = connect(source, secret.as_deref()).map_err(|e| io::Error::other(e.message))?;
+    let mut bytes = Vec::new();
+    sftp.open(Path::new(&source.path))
+        .map_err(io::Error::from)?
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Webhooks go to third parties, so they don't learn where the server is
+fn redacted(err: &LogErrorPayload, source: &RemoteSource) -> LogErrorSummary {
+    LogErrorSummary {
+        kind: err.kind,
+        message: err.message.replace(&source.host, "…"),
+    }
+}
+
+/// Tails the remote log in place of the local one until shutdown.
+pub fn follow(app: &AppHandle, source: RemoteSource) {
+    let url = url(&source);
+    let secret = stored_secret(&source).unwrap_or_else(|e| {
+        crate::emit_error(app, error(&source, LogErrorKind::AuthFailed, e));
+        None
+    });
+    crate::emit_location(app, url.clone(), LocationSource::Remote);
+    let mut tail = RemoteTail::new(source.clone(), secret);
+    let mut feeder = pipeline::start(app, MAIN, Path::new(&url));
+    let mut backoff = Duration::from_secs(1);
+    // Webhooks hear about the first failure of each outage
+    let mut failing = false;
+    loop {
+        let state = app.state::<Live>().snapshot();
+        if !state.paused {
+            match tail.poll() {
+                Ok(chunk) => {
+                    backoff = Duration::from_secs(1);
+                    failing = false;
+                    if let Some(chunk) = chunk {
+                        metrics::record(app, |m| &m.bytes_read, chunk.content.len() as u64);
+                        if let Some(rotation) = chunk.rotation {
+                            crate::emit_rotated(app, MAIN, Path::new(&url), rotation);
+                        }
+                        feeder.push(chunk);
+                    }
+                }
+                Err(mut err) => {
+                    metrics::record(app, |m| &m.watcher_restarts, 1);
+                    if !std::mem::replace(&mut failing, true) {
+                        webhooks::notify(app, WebhookEvent::LogError(redacted(&err, &source)));
+                    }
+                    err.message =
+                        format!("{}; reconnecting in {}s", err.message, backoff.as_secs());
+                    crate::emit_error(app, err);
+                    crate::sleep_unless_stopping(app, backoff);
+                    backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+                }
+            }
+        }
+        feeder.flush();
+        if state.stopping {
+            break;
+        }
+        let interval = app.state::<SettingsStore>().get().watcher.debounce_ms;
+        crate::sleep_unless_stopping(app, Duration::from_millis(interval.max(200)));
+    }
+    feeder.finish();
+}
+
+/// `{"size": 52340, "modified_ms": 1718000000000}`
+#[derive(Serialize, Clone, Debug)]
+pub struct RemoteFileInfo {
+    pub size: u64,
+    pub modified_ms: Option<i64>,
+}
+
+/// Logs in and looks up the log, so a remote source can be checked before
+/// it's saved. `secret` is tried in place of the stored one when given.
+#[tauri::command]
+pub fn test_remote_connection(
+    remote: RemoteSource,
+    secret: Option<String>,
+) -> Result<RemoteFileInfo, LogErrorPayload> {
+    let secret = match secret {
+        Some(secret) => Some(secret),
+        None => stored_secret(&remote).map_err(|e| error(&remote, LogErrorKind::AuthFailed, e))?,
+    };
+    let sftp = connect(&remote, secret.as_deref())?;
+    let stat = sftp
+        .stat(Path::new(&remote.path))
+        .map_err(|e| ssh_error(&remote, "Failed to read log", &e))?;
+    if stat.is_dir() {
+        return Err(error(
+            &remote,
+            LogErrorKind::LogNotFound,
+            format!("{} is a folder, not a log", remote.path),
+        ));
+    }
+    Ok(RemoteFileInfo {
+        size: stat.size.unwrap_or(0),
+        modified_ms: stat.mtime.map(|secs| secs as i64 * 1000),
+    })
+}
+
+/// Saves the password or key passphrase for `remote` in the OS keyring, or
+/// removes it when `secret` is null.
+#[tauri::command]
+pub fn set_remote_secret(remote: RemoteSource, secret: Option<String>) -> Result<(), String> {
+    let entry = keyring_entry(&remote)?;
+    match secret {
+        Some(secret) => entry
+            .set_password(&secret)
+            .map_err(|e| format!("Failed to save to the keyring: {}", e)),
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to remove from the keyring: {}", e)),
+        },
+    }
+}
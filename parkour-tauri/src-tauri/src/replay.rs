@@ -0,0 +1,581 @@
+use crate::crash::{self, CrashPayload};
+use crate::db::Db;
+use crate::definitions;
+use crate::events::{self, LogUpdatePayload};
+use crate::goals;
+use crate::lines::{cap, cap_lines, lines, Capped, Line};
+use crate::live::{Live, REPLAY};
+use crate::parser;
+use crate::routing;
+use crate::settings::SettingsStore;
+use crate::tail::strip_bom;
+use parkour_core::import::{self, LogFile};
+use parkour_core::log_line::{parse_line, seconds_of_day, LogLine};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+enum Control {
+    Pause(bool),
+    // Seconds of the day, as in the log timestamps
+    Seek(u32),
+    Stop,
+}
+
+struct Running {
+    id: u64,
+    tx: Sender<Control>,
+    // Started by start_log_replay, the only kind that can be paused or seeked
+    log: bool,
+}
+
+#[derive(Default)]
+pub struct Replay {
+    worker: Mutex<Option<Running>>,
+    next_id: AtomicU64,
+}
+
+impl Replay {
+    fn claim(&self, log: bool) -> Result<(u64, Receiver<Control>), String> {
+        let mut slot = self.worker.lock().map_err(|_| "Replay lock poisoned")?;
+        if slot.is_some() {
+            return Err("A replay is already running".into());
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = channel();
+        *slot = Some(Running { id, tx, log });
+        Ok((id, rx))
+    }
+
+    fn release(&self, id: u64) {
+        if let Ok(mut slot) = self.worker.lock() {
+            if slot.as_ref().is_some_and(|running| running.id == id) {
+                *slot = None;
+            }
+        }
+    }
+
+    fn send(&self, control: Control) -> Result<(), String> {
+        let slot = self.worker.lock().map_err(|_| "Replay lock poisoned")?;
+        match slot.as_ref() {
+            Some(running) if running.log => running
+                .tx
+                .send(control)
+                .map_err(|_| "The replay has already finished".into()),
+            Some(_) => Err("Only replays started with start_log_replay can be controlled".into()),
+            None => Err("No replay is running".into()),
+        }
+    }
+
+    fn stop(&self) -> bool {
+        let running = self.worker.lock().ok().and_then(|mut w| w.take());
+        match running {
+            Some(running) => {
+                let _ = running.tx.send(Control::Stop);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// `{"cancelled": false, "end": 5120}`
+///
+/// A `start_log_replay` also reports the file it stopped in as `path`, and
+/// with `record` set, how many new runs it stored as `runs_recorded`.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct ReplayFinished {
+    pub cancelled: bool,
+    pub end: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runs_recorded: Option<usize>,
+}
+
+fn parse_capped(line: &Line, max: usize) -> LogLine {
+    let mut parsed = parse_line(line.start as u64, cap(line.text, max));
+    if line.text.len() > max {
+        parsed.truncated = true;
+        parsed.length = Some(line.text.len() as u64);
+    }
+    parsed
+}
+
+// Consecutive lines sharing a timestamp are replayed together; untimed lines
+// stick to the chunk before them.
+struct Chunk {
+    offset: usize,
+    end: usize,
+    seconds: Option<u32>,
+}
+
+fn chunks(content: &str, base: usize, max: usize) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    for line in lines(content, base) {
+        let seconds = parse_capped(&line, max)
+            .time
+            .and_then(|t| seconds_of_day(&t));
+        match chunks.last_mut() {
+            Some(last) if seconds.is_none() || seconds == last.seconds => last.end = line.end,
+            _ => chunks.push(Chunk {
+                offset: line.start,
+                end: line.end,
+                seconds,
+            }),
+        }
+    }
+    chunks
+}
+
+fn delay(from: Option<u32>, to: Option<u32>, speed: f32) -> Option<Duration> {
+    let (from, to) = (from?, to?);
+    // Logs only carry the time of day, so a smaller time means midnight passed
+    let secs = if to >= from {
+        to - from
+    } else {
+        to + 86_400 - from
+    };
+    Some(Duration::from_secs_f32(secs as f32 / speed))
+}
+
+fn emit_chunk(app: &AppHandle, job: &Job, chunk: &Chunk) {
+    let text = &job.content[chunk.offset..chunk.end];
+    if job.as_events {
+        for line in lines(text, chunk.offset) {
+            routing::emit(
+                app,
+                events::REPLAY_LINE,
+                parse_capped(&line, job.max_line_bytes),
+            );
+        }
+    } else {
+        let (content, truncated) =
+            cap_lines(text.to_string(), chunk.offset as u64, job.max_line_bytes);
+        routing::emit(
+            app,
+            events::REPLAY_CHUNK,
+            LogUpdatePayload {
+                content,
+                offset: chunk.offset as u64,
+                end: chunk.end as u64,
+                path: job.path.clone(),
+                truncated,
+                dropped: None,
+                replayed: false,
+                source: None,
+            },
+        );
+    }
+}
+
+struct Job {
+    id: u64,
+    content: String,
+    offset: usize,
+    path: String,
+    speed: f32,
+    as_events: bool,
+    max_line_bytes: usize,
+}
+
+// Replayed events go out with plain emit so they never take up room in the
+// live event buffer or reach WebSocket clients.
+fn worker(app: AppHandle, job: Job, rx: Receiver<Control>) {
+    let mut cancelled = false;
+    let mut previous: Option<u32> = None;
+    let mut end = job.offset;
+    for chunk in chunks(&job.content[job.offset..], job.offset, job.max_line_bytes) {
+        let stop = if job.speed > 0.0 {
+            let wait = delay(previous, chunk.seconds, job.speed).unwrap_or_default();
+            !matches!(rx.recv_timeout(wait), Err(RecvTimeoutError::Timeout))
+        } else {
+            !matches!(rx.try_recv(), Err(TryRecvError::Empty))
+        };
+        if stop {
+            cancelled = true;
+            break;
+        }
+        previous = chunk.seconds.or(previous);
+        emit_chunk(&app, &job, &chunk);
+        end = chunk.end;
+    }
+
+    app.state::<Replay>().release(job.id);
+    routing::emit(
+        &app,
+        events::REPLAY_FINISHED,
+        ReplayFinished {
+            cancelled,
+            end: end as u64,
+            path: None,
+            runs_recorded: None,
+        },
+    );
+}
+
+/// `speed` scales the gaps between the original log timestamps; 0 replays
+/// everything at once.
+#[tauri::command]
+pub fn replay_from_offset(
+    offset: u64,
+    speed: f32,
+    as_events: bool,
+    app: AppHandle,
+    replay: State<'_, Replay>,
+) -> Result<(), String> {
+    if !speed.is_finite() || speed < 0.0 {
+        return Err("Replay speed must be a non-negative number".into());
+    }
+    let path = app
+        .state::<Live>()
+        .snapshot()
+        .log_path
+        .ok_or("No log file is being watched")?;
+    let content = crate::read_log(&app, Path::new(&path))
+        .map_err(|e| format!("Failed to read log: {}", e))?;
+    let mut start = offset as usize;
+    if start > content.len() {
+        return Err(format!(
+            "Offset {} is not a valid position in the log",
+            offset
+        ));
+    }
+    // An offset inside a multi-byte character starts from that character
+    while !content.is_char_boundary(start) {
+        start -= 1;
+    }
+
+    let (id, rx) = replay.claim(false)?;
+    // Offsets are raw file bytes, so a BOM is skipped rather than stripped
+    let bom = content.len() - strip_bom(&content).len();
+    let job = Job {
+        id,
+        offset: start.max(bom),
+        content,
+        path,
+        speed,
+        as_events,
+        max_line_bytes: app.state::<SettingsStore>().get().watcher.max_line_bytes,
+    };
+    std::thread::spawn(move || worker(app, job, rx));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_replay(replay: State<'_, Replay>) -> bool {
+    replay.stop()
+}
+
+// One file of a log replay
+struct Segment {
+    file: LogFile,
+    path: String,
+    content: String,
+    chunks: Vec<Chunk>,
+}
+
+struct LogJob {
+    id: u64,
+    segments: Vec<Segment>,
+    speed: f32,
+    record: bool,
+    // Whether runs are read from the text too, started by replay_log
+    parse: bool,
+    max_line_bytes: usize,
+}
+
+// Waits out `wait` unless told to seek or stop first. Time spent paused
+// doesn't count towards it.
+fn wait(rx: &Receiver<Control>, wait: Duration, paused: &mut bool) -> Option<Control> {
+    let mut remaining = wait;
+    loop {
+        let started = Instant::now();
+        let received = if *paused {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(remaining)
+        };
+        if !*paused {
+            remaining = remaining.saturating_sub(started.elapsed());
+        }
+        match received {
+            Err(RecvTimeoutError::Timeout) => return None,
+            Ok(Control::Pause(pause)) => *paused = pause,
+            Ok(seek @ Control::Seek(_)) => return Some(seek),
+            Ok(Control::Stop) | Err(RecvTimeoutError::Disconnected) => return Some(Control::Stop),
+        }
+    }
+}
+
+// First chunk stamped at or after `seconds`, in any file
+fn find(segments: &[Segment], seconds: u32) -> Option<(usize, usize)> {
+    segments.iter().enumerate().find_map(|(s, segment)| {
+        let chunk = segment
+            .chunks
+            .iter()
+            .position(|c| c.seconds.is_some_and(|t| t >= seconds))?;
+        Some((s, chunk))
+    })
+}
+
+// Goes through the same events as the live log, so crashes in the replayed
+// text are reported too; they never end a stored session though. With
+// `parse`, the run events come out as well, under the replay source.
+fn emit_log(
+    app: &AppHandle,
+    job: &LogJob,
+    segment: &Segment,
+    range: (usize, usize),
+    crashes: &mut crash::Tracker,
+) {
+    let (offset, end) = range;
+    let chunk = Capped::new(
+        segment.content[offset..end].to_string(),
+        offset as u64,
+        job.max_line_bytes,
+    );
+    if let Some((at, summary)) = crashes.scan(&chunk) {
+        events::emit_all(
+            app,
+            events::GAME_CRASHED,
+            &CrashPayload {
+                offset: at,
+                summary,
+                abandoned_run: None,
+                replayed: true,
+                source: None,
+            },
+        );
+    }
+    if job.parse {
+        parser::scan(app, REPLAY, &chunk);
+        definitions::scan(app, REPLAY, &chunk);
+    }
+    let Capped {
+        content, truncated, ..
+    } = chunk;
+    events::emit(
+        app,
+        events::LOG_UPDATE,
+        &LogUpdatePayload {
+            content,
+            offset: offset as u64,
+            end: end as u64,
+            path: segment.path.clone(),
+            truncated,
+            dropped: None,
+            replayed: true,
+            source: None,
+        },
+    );
+}
+
+// Imports the runs of a file that was replayed to its end
+fn record(app: &AppHandle, segment: &Segment) -> usize {
+    let db = app.state::<Db>();
+    let patterns = app.state::<SettingsStore>().get().patterns();
+    let imported = patterns.and_then(|patterns| {
+        let conn = db.conn()?;
+        import::import_log(&conn, &segment.file, &segment.content, &patterns)
+    });
+    match imported {
+        Ok(imported) => {
+            if imported.runs_added > 0 {
+                goals::sweep(app);
+            }
+            imported.runs_added
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            0
+        }
+    }
+}
+
+fn log_worker(app: AppHandle, job: LogJob, rx: Receiver<Control>) {
+    let mut crashes = crash::Tracker::default();
+    let (mut paused, mut cancelled) = (false, false);
+    let (mut segment, mut chunk) = (0, 0);
+    // End of what was emitted of the current file
+    let mut emitted = 0;
+    let mut previous: Option<u32> = None;
+    let mut recorded = 0;
+    let mut path = None;
+    while let Some(current) = job.segments.get(segment) {
+        let Some(next) = current.chunks.get(chunk) else {
+            if job.record {
+                recorded += record(&app, current);
+            }
+            // The next file starts over at offset 0, like a rotated log
+            segment += 1;
+            chunk = 0;
+            previous = None;
+            if segment < job.segments.len() {
+                emitted = 0;
+            }
+            continue;
+        };
+        let delay = if job.speed > 0.0 {
+            delay(previous, next.seconds, job.speed).unwrap_or_default()
+        } else {
+            Duration::ZERO
+        };
+        match wait(&rx, delay, &mut paused) {
+            None => {
+                emit_log(&app, &job, current, (emitted, next.end), &mut crashes);
+                path = Some(current.path.clone());
+                emitted = next.end;
+                previous = next.seconds.or(previous);
+                chunk += 1;
+            }
+            // Everything up to the target is emitted at once, as if the log
+            // had already been written that far. A seek back, or into
+            // another file, starts that file over.
+            Some(Control::Seek(seconds)) => {
+                let Some((s, c)) = find(&job.segments, seconds) else {
+                    // Past the last line: the replay is over
+                    break;
+                };
+                if s != segment || c < chunk {
+                    emitted = 0;
+                }
+                let target = &job.segments[s];
+                let to = target.chunks[c].offset;
+                if to > emitted {
+                    emit_log(&app, &job, target, (emitted, to), &mut crashes);
+                    path = Some(target.path.clone());
+                    emitted = to;
+                }
+                (segment, chunk) = (s, c);
+                previous = target.chunks[c].seconds;
+            }
+            Some(_) => {
+                cancelled = true;
+                break;
+            }
+        }
+    }
+
+    if job.parse {
+        parser::forget(&app, REPLAY);
+        definitions::forget(&app, REPLAY);
+    }
+    app.state::<Replay>().release(job.id);
+    routing::emit(
+        &app,
+        events::REPLAY_FINISHED,
+        ReplayFinished {
+            cancelled,
+            end: emitted as u64,
+            path,
+            runs_recorded: job.record.then_some(recorded),
+        },
+    );
+}
+
+fn start(
+    app: AppHandle,
+    replay: &Replay,
+    path: String,
+    speed: f32,
+    record: bool,
+    parse: bool,
+) -> Result<(), String> {
+    if !speed.is_finite() || speed < 0.0 {
+        return Err("Replay speed must be a non-negative number".into());
+    }
+    let path = fs::canonicalize(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let files = if path.is_dir() {
+        import::find_logs(&path)?
+    } else {
+        let file = import::log_file(path.clone())
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        vec![file]
+    };
+    if files.is_empty() {
+        return Err(format!("No logs found in {}", path.display()));
+    }
+    let max_line_bytes = app.state::<SettingsStore>().get().watcher.max_line_bytes;
+    let mut segments = Vec::new();
+    for file in files {
+        let content = import::read_log(&file.path)
+            .map_err(|e| format!("Failed to read {}: {}", file.path.display(), e))?;
+        segments.push(Segment {
+            path: file.key(),
+            chunks: chunks(&content, 0, max_line_bytes),
+            content,
+            file,
+        });
+    }
+
+    let (id, rx) = replay.claim(true)?;
+    let job = LogJob {
+        id,
+        segments,
+        speed,
+        record,
+        parse,
+        max_line_bytes,
+    };
+    std::thread::spawn(move || log_worker(app, job, rx));
+    Ok(())
+}
+
+/// Feeds a saved log, or every log in a folder oldest first, through the
+/// live `log-update` events as if it were being written now, with the gaps
+/// between timestamps divided by `speed` (0 replays everything at once).
+/// Each file after the first starts over at offset 0, the way a rotated log
+/// does.
+///
+/// Nothing is stored unless `record` is true; then the runs of every file
+/// replayed to its end are imported the same way as by `parkour-cli import`.
+#[tauri::command]
+pub fn start_log_replay(
+    path: String,
+    speed: f32,
+    record: Option<bool>,
+    app: AppHandle,
+    replay: State<'_, Replay>,
+) -> Result<(), String> {
+    start(app, &replay, path, speed, record.unwrap_or(false), false)
+}
+
+/// Replays an archived log like `start_log_replay`, and reads its runs as
+/// they go by too, so `run-started`, `checkpoint-reached`, `run-finished`
+/// and `defined-run` come out paced as they did live. Those carry
+/// `"source": "replay"` and leave the live run, PBs and goals alone; nothing
+/// is stored. It is paused, seeked and stopped like any log replay.
+#[tauri::command]
+pub fn replay_log(
+    path: String,
+    speed: f32,
+    app: AppHandle,
+    replay: State<'_, Replay>,
+) -> Result<(), String> {
+    start(app, &replay, path, speed, false, true)
+}
+
+#[tauri::command]
+pub fn pause_replay(paused: bool, replay: State<'_, Replay>) -> Result<(), String> {
+    replay.send(Control::Pause(paused))
+}
+
+/// Jumps to the first line stamped `to_time` (e.g. `"14:02:11"`) or later.
+#[tauri::command]
+pub fn seek_replay(to_time: String, replay: State<'_, Replay>) -> Result<(), String> {
+    let seconds = seconds_of_day(&to_time)
+        .ok_or_else(|| format!("'{}' is not a log time like 14:02:11", to_time))?;
+    replay.send(Control::Seek(seconds))
+}
+
+#[tauri::command]
+pub fn stop_replay(replay: State<'_, Replay>) -> bool {
+    replay.stop()
+}
@@ -0,0 +1,412 @@
+use crate::compare::{self, RunComparison};
+use crate::db::{self, Db, Run, Session};
+use crate::duration::{format_delta, format_ms, format_span};
+use crate::timestamps;
+use chrono::{DateTime, Utc};
+use parkour_core::duels::FINISH;
+use parkour_core::import::ActiveTime;
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use tauri::State;
+
+const TOP_RUNS: usize = 10;
+
+macro_rules! out {
+    ($md:expr) => {{
+        let _ = writeln!($md);
+    }};
+    ($md:expr, $($arg:tt)*) => {{
+        let _ = writeln!($md, $($arg)*);
+    }};
+}
+
+// Durations come from the UTC copies so a DST change mid-session doesn't skew them.
+fn parse_time(s: &Option<String>) -> Option<DateTime<Utc>> {
+    timestamps::parse_utc(s.as_deref()?)
+}
+
+fn cell(s: &str) -> String {
+    s.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+// Live sessions end at their latest finished run rather than "now" so the
+// report only changes when new runs arrive.
+fn wall_clock_ms(session: &Session, runs: &[Run]) -> Option<i64> {
+    let start = parse_time(&session.started_at_utc)?;
+    let end = match &session.ended_at {
+        Some(_) => parse_time(&session.ended_at_utc)?,
+        None => runs
+            .iter()
+            .filter_map(|r| {
+                parse_time(&r.started_at_utc)
+                    .map(|t| t + chrono::Duration::milliseconds(r.time_ms.unwrap_or(0)))
+            })
+            .max()
+            .unwrap_or(start),
+    };
+    Some((end - start).num_milliseconds().max(0))
+}
+
+// A live session has no active time stored yet, so it's taken from the
+// starts and finishes of its runs, with the idle gaps between them left out
+fn active_ms(session: &Session, runs: &[Run]) -> i64 {
+    if session.ended_at.is_some() {
+        return session.active_ms;
+    }
+    let mut times: Vec<DateTime<Utc>> = runs
+        .iter()
+        .filter_map(|r| {
+            let start = parse_time(&r.started_at_utc)?;
+            let end = start + chrono::Duration::milliseconds(r.time_ms.unwrap_or(0));
+            Some([start, end])
+        })
+        .flatten()
+        .collect();
+    times.extend(parse_time(&session.started_at_utc));
+    times.sort();
+    let mut active = ActiveTime::default();
+    for t in times {
+        active.add(t.naive_utc());
+    }
+    active.ms
+}
+
+fn best_streak(runs: &[Run]) -> usize {
+    let (mut best, mut current) = (0, 0);
+    for run in runs {
+        if run.completed {
+            current += 1;
+            best = best.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    best
+}
+
+struct Pb<'a> {
+    run: &'a Run,
+    time_ms: i64,
+    previous_ms: Option<i64>,
+}
+
+pub fn build_report(conn: &Connection, session_id: i64) -> Result<String, String> {
+    let session = db::get_session(conn, session_id)?
+        .ok_or_else(|| format!("Session {} not found", session_id))?;
+    let runs = db::session_runs(conn, &session)?;
+
+    let mut pbs = Vec::new();
+    for run in &runs {
+        let counts = run.completed && !run.practice && run.invalidated_by.is_empty();
+        let Some(time_ms) = run.time_ms.filter(|_| counts) else {
+            continue;
+        };
+        let previous_ms = db::previous_best_time(conn, run)?;
+        if previous_ms.is_none_or(|p| time_ms < p) {
+            pbs.push(Pb {
+                run,
+                time_ms,
+                previous_ms,
+            });
+        }
+    }
+
+    let mut maps: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for run in &runs {
+        let entry = maps.entry(&run.map).or_default();
+        entry.0 += 1;
+        if run.completed {
+            entry.1 += 1;
+        }
+    }
+    let mut maps: Vec<_> = maps.into_iter().collect();
+    maps.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(b.0)));
+
+    let mut top: Vec<&Run> = runs
+        .iter()
+        .filter(|r| r.completed && r.time_ms.is_some())
+        .collect();
+    top.sort_by_key(|r| (r.time_ms, r.started_at.clone(), r.id));
+    top.truncate(TOP_RUNS);
+
+    let live = if session.ended_at.is_none() {
+        " (live)"
+    } else {
+        ""
+    };
+    let mut md = String::new();
+    out!(md, "# Session report{}", live);
+    out!(md);
+    out!(md, "- Player: {}", session.username);
+    if let Some(server) = &session.server {
+        out!(md, "- Server: {}", server);
+    }
+    match &session.ended_at {
+        Some(end) => out!(md, "- Time: {} – {}", session.started_at, end),
+        None => out!(md, "- Started: {}", session.started_at),
+    }
+    let wall = wall_clock_ms(&session, &runs)
+        .map(format_span)
+        .unwrap_or_else(|| "unknown".into());
+    out!(
+        md,
+        "- Duration: {} active / {} wall clock",
+        format_span(active_ms(&session, &runs)),
+        wall
+    );
+    let completions = runs.iter().filter(|r| r.completed).count();
+    out!(
+        md,
+        "- Attempts: {}, completions: {}, PBs: {}",
+        runs.len(),
+        completions,
+        pbs.len()
+    );
+    out!(
+        md,
+        "- Best streak: {} completions in a row",
+        best_streak(&runs)
+    );
+
+    out!(md);
+    out!(md, "## Maps played");
+    out!(md);
+    if maps.is_empty() {
+        out!(md, "No runs recorded.");
+    } else {
+        out!(md, "| Map | Attempts | Completions |");
+        out!(md, "|---|---:|---:|");
+        for (map, (attempts, completions)) in &maps {
+            out!(md, "| {} | {} | {} |", cell(map), attempts, completions);
+        }
+    }
+
+    out!(md);
+    out!(md, "## Personal bests");
+    out!(md);
+    if pbs.is_empty() {
+        out!(md, "No new personal bests.");
+    } else {
+        out!(md, "| Map | Time | Improvement | Set at |");
+        out!(md, "|---|---:|---:|---|");
+        for pb in &pbs {
+            let delta = pb
+                .previous_ms
+                .map(|p| format_delta(pb.time_ms - p))
+                .unwrap_or_else(|| "first completion".into());
+            out!(
+                md,
+                "| {} | {} | {} | {} |",
+                cell(&pb.run.map),
+                format_ms(pb.time_ms),
+                delta,
+                pb.run.started_at
+            );
+        }
+    }
+
+    out!(md);
+    out!(md, "## Top runs");
+    out!(md);
+    if top.is_empty() {
+        out!(md, "No completed runs.");
+    } else {
+        out!(md, "| # | Map | Time | Fails | Started |");
+        out!(md, "|---:|---|---:|---:|---|");
+        for (i, run) in top.iter().enumerate() {
+            out!(
+                md,
+                "| {} | {} | {} | {} | {} |",
+                i + 1,
+                cell(&run.map),
+                run.time_ms.map(format_ms).unwrap_or_default(),
+                run.fails,
+                run.started_at
+            );
+        }
+    }
+
+    let markers = db::markers(conn, Some(session.id), None)?;
+    if !markers.is_empty() {
+        out!(md);
+        out!(md, "## Markers");
+        out!(md);
+        for marker in &markers {
+            let label = marker.label.as_deref().unwrap_or("Marker");
+            match (&marker.map, marker.run_elapsed_ms) {
+                (Some(map), Some(elapsed)) => out!(
+                    md,
+                    "- {} {} ({} at {})",
+                    marker.created_at,
+                    label,
+                    map,
+                    format_ms(elapsed)
+                ),
+                _ => out!(md, "- {} {}", marker.created_at, label),
+            }
+        }
+    }
+    Ok(md)
+}
+
+fn delta(ms: Option<i64>) -> String {
+    ms.map(format_delta).unwrap_or_default()
+}
+
+pub fn build_comparison_report(comparison: &RunComparison) -> String {
+    let (a, b) = (&comparison.a.run, &comparison.b.run);
+    let time = |run: &Run| match run.time_ms.filter(|_| run.completed) {
+        Some(ms) => format_ms(ms),
+        None => "not finished".into(),
+    };
+    let mut md = String::new();
+    out!(md, "# Run comparison: {}", a.map);
+    out!(md);
+    out!(md, "| | A | B | Delta |");
+    out!(md, "|---|---|---|---:|");
+    out!(md, "| Run | #{} | #{} | |", a.id, b.id);
+    out!(
+        md,
+        "| Player | {} | {} | |",
+        cell(&a.username),
+        cell(&b.username)
+    );
+    out!(md, "| Started | {} | {} | |", a.started_at, b.started_at);
+    out!(
+        md,
+        "| Time | {} | {} | {} |",
+        time(a),
+        time(b),
+        delta(comparison.time_delta_ms)
+    );
+    out!(
+        md,
+        "| Fails | {} | {} | {:+} |",
+        a.fails,
+        b.fails,
+        comparison.fails_delta
+    );
+
+    out!(md);
+    out!(md, "## Checkpoints");
+    out!(md);
+    if comparison.checkpoints.is_empty() {
+        out!(md, "No split data; only the totals are compared.");
+        return md;
+    }
+    out!(
+        md,
+        "| Checkpoint | A | B | Delta | A segment | B segment | Segment delta |"
+    );
+    out!(md, "|---|---:|---:|---:|---:|---:|---:|");
+    for cp in &comparison.checkpoints {
+        let label = match &cp.name {
+            Some(name) => cell(name),
+            None if cp.checkpoint == FINISH as i64 => "Finish".into(),
+            None => cp.checkpoint.to_string(),
+        };
+        let label = match cp.only_in {
+            Some(side) => format!("{} (only {})", label, side.to_uppercase()),
+            None => label,
+        };
+        let ms = |v: Option<i64>| v.map(format_ms).unwrap_or_else(|| "–".into());
+        out!(
+            md,
+            "| {} | {} | {} | {} | {} | {} | {} |",
+            label,
+            ms(cp.a_cumulative_ms),
+            ms(cp.b_cumulative_ms),
+            delta(cp.cumulative_delta_ms),
+            ms(cp.a_segment_ms),
+            ms(cp.b_segment_ms),
+            delta(cp.segment_delta_ms)
+        );
+    }
+    md
+}
+
+/// Markdown table of [`compare::compare`], written to `dest_path` if given.
+#[tauri::command]
+pub fn generate_comparison_report(
+    run_a: i64,
+    run_b: i64,
+    dest_path: Option<String>,
+    db: State<'_, Db>,
+) -> Result<String, String> {
+    let report = {
+        let conn = db.conn()?;
+        build_comparison_report(&compare::compare(&conn, run_a, run_b)?)
+    };
+    if let Some(dest) = dest_path {
+        fs::write(&dest, &report).map_err(|e| format!("Failed to write report: {}", e))?;
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn generate_session_report(
+    session_id: i64,
+    dest_path: Option<String>,
+    db: State<'_, Db>,
+) -> Result<String, String> {
+    let report = {
+        let conn = db.conn()?;
+        build_report(&conn, session_id)?
+    };
+    if let Some(dest) = dest_path {
+        fs::write(&dest, &report).map_err(|e| format!("Failed to write report: {}", e))?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn duration_line(report: &str) -> &str {
+        report
+            .lines()
+            .find(|l| l.starts_with("- Duration:"))
+            .unwrap()
+    }
+
+    #[test]
+    fn live_sessions_leave_idle_gaps_out_of_active_time() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        conn.execute_batch(
+            "INSERT INTO sessions (username, started_at, ended_at, active_ms) VALUES
+                ('me', '2026-01-01 10:00:00', NULL, 0);
+            INSERT INTO runs (map, username, started_at, time_ms, completed) VALUES
+                ('Tower', 'me', '2026-01-01 10:00:00', 20000, 1),
+                ('Tower', 'me', '2026-01-01 10:01:00', NULL, 0),
+                ('Tower', 'me', '2026-01-01 10:30:00', 20000, 1);",
+        )
+        .unwrap();
+        let report = build_report(&conn, 1).unwrap();
+        // 20s, 40s to the next run and the last run's 20s; the 29 minutes
+        // away before it don't count
+        assert_eq!(
+            duration_line(&report),
+            "- Duration: 1m 20s active / 30m 20s wall clock"
+        );
+    }
+
+    #[test]
+    fn ended_sessions_keep_their_stored_active_time() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        conn.execute_batch(
+            "INSERT INTO sessions (username, started_at, ended_at, active_ms) VALUES
+                ('me', '2026-01-01 10:00:00', '2026-01-01 11:00:00', 600000);",
+        )
+        .unwrap();
+        let report = build_report(&conn, 1).unwrap();
+        assert_eq!(
+            duration_line(&report),
+            "- Duration: 10m 00s active / 1h 00m wall clock"
+        );
+    }
+}
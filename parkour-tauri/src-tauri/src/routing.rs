@@ -0,0 +1,79 @@
+use parkour_core::schema;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder};
+
+pub const OVERLAY_WINDOW: &str = "overlay";
+
+// Channels each window asked for. Windows that never subscribed get every
+// event, which is what the main window relies on.
+#[derive(Default)]
+pub struct Subscriptions(Mutex<HashMap<String, HashSet<String>>>);
+
+impl Subscriptions {
+    pub fn remove(&self, label: &str) {
+        if let Ok(mut subs) = self.0.lock() {
+            subs.remove(label);
+        }
+    }
+}
+
+pub fn emit<S: Serialize>(app: &AppHandle, event: &str, payload: S) {
+    let payload = schema::stamp(serde_json::to_value(payload).unwrap_or(Value::Null));
+    let subs = app
+        .try_state::<Subscriptions>()
+        .and_then(|s| s.0.lock().ok().map(|s| s.clone()))
+        .unwrap_or_default();
+    if subs.is_empty() {
+        let _ = app.emit(event, payload);
+        return;
+    }
+    for label in app.webview_windows().into_keys() {
+        if subs
+            .get(&label)
+            .is_none_or(|channels| channels.contains(event))
+        {
+            let _ = app.emit_to(label, event, payload.clone());
+        }
+    }
+}
+
+#[tauri::command]
+pub fn subscribe(
+    window_label: String,
+    channels: Vec<String>,
+    app: AppHandle,
+    subs: State<'_, Subscriptions>,
+) -> Result<(), String> {
+    if app.get_webview_window(&window_label).is_none() {
+        return Err(format!("No window labelled '{}'", window_label));
+    }
+    let mut subs = subs.0.lock().map_err(|_| "Subscriptions lock poisoned")?;
+    subs.insert(window_label, channels.into_iter().collect());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn open_overlay_window(app: AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(OVERLAY_WINDOW) {
+        return window
+            .set_focus()
+            .map_err(|e| format!("Failed to focus overlay window: {}", e));
+    }
+    WebviewWindowBuilder::new(
+        &app,
+        OVERLAY_WINDOW,
+        WebviewUrl::App("index.html?window=overlay".into()),
+    )
+    .title("Parkour Analyzer Overlay")
+    .inner_size(320.0, 120.0)
+    .always_on_top(true)
+    .decorations(false)
+    .resizable(true)
+    .skip_taskbar(true)
+    .build()
+    .map_err(|e| format!("Failed to create overlay window: {}", e))?;
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use crate::events;
+use crate::lines::Capped;
+use crate::live::{source_field, CurrentRun, Live};
+use crate::settings::{ServerSettings, Settings, SettingsStore};
+use parkour_core::duels::PatternSpec;
+use parkour_core::log_line::{parse_line, seconds_of_day};
+use parkour_core::servers::{self, PlayContext, ServerParser, GENERIC};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+// How long after joining chat still counts as join messages
+const WINDOW_SECS: u32 = 60;
+
+/// `{"server": "mc.hypixel.net", "parser": "hypixel", "manual": false, "settings": {...}}`
+///
+/// Emitted when joining a server and again if its join messages point to a
+/// different parser. `parser` is `"generic"` when nothing matched, `manual`
+/// is true for a parser picked in the server's settings, and `settings` is
+/// what applies on the server. Switching servers drops the run in progress,
+/// sent as `abandoned_run`.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct ParserSelectedPayload {
+    pub server: String,
+    pub parser: String,
+    pub manual: bool,
+    pub settings: EffectiveServerSettings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abandoned_run: Option<CurrentRun>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// `{"server": "mc.hypixel.net", "world": null}`
+///
+/// Emitted when the log shows the player joining a server or loading a
+/// singleplayer world; see [`PlayContext`]. Runs imported from the log are
+/// stored with the same `server` and `world`.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct ContextChangedPayload {
+    #[serde(flatten)]
+    pub context: PlayContext,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+// What a resolver found in newly read text, in log order
+#[derive(Debug, PartialEq)]
+enum Found {
+    // Server, parser, whether it was picked manually, and whether the
+    // server was switched
+    Parser(String, String, bool, bool),
+    Context(PlayContext),
+}
+
+#[derive(Default)]
+pub(crate) struct Resolver {
+    context: PlayContext,
+    server: Option<String>,
+    parser: Option<String>,
+    // Log time the server was joined, while join messages are still collected
+    joined_at: Option<u32>,
+    messages: Vec<String>,
+    manual: bool,
+}
+
+// One resolver per watched source, since two clients can be on different
+// servers
+#[derive(Default)]
+pub struct ServerState(Mutex<BTreeMap<String, Resolver>>);
+
+// Parser picked in the server's settings, if any
+fn manual(settings: &Settings, server: &str) -> Option<String> {
+    settings
+        .servers
+        .get(&servers::normalize(server, &settings.server_aliases))
+        .and_then(|s| s.parser.clone())
+}
+
+impl Resolver {
+    fn pick(&self, parsers: &[ServerParser], server: &str) -> String {
+        servers::resolve(parsers, server, &self.messages)
+            .map_or(GENERIC, |p| p.name.as_str())
+            .to_string()
+    }
+
+    fn scan(&mut self, settings: &Settings, chunk: &Capped) -> Vec<Found> {
+        if chunk.starts_log() {
+            *self = Resolver::default();
+        }
+        let parsers = servers::registry(&settings.parsers);
+        let mut selected = Vec::new();
+        for line in chunk.lines() {
+            let parsed = parse_line(line.start as u64, line.text);
+            let time = parsed.time.as_deref().and_then(seconds_of_day);
+            if let Some(context) = self.context.next(&parsed.message) {
+                self.context = context.clone();
+                // Singleplayer: no server's parser applies
+                if context.server.is_none() {
+                    *self = Resolver {
+                        context: context.clone(),
+                        ..Resolver::default()
+                    };
+                }
+                selected.push(Found::Context(context));
+            }
+            if let Some(server) = servers::connecting_to(&parsed.message) {
+                let switched = self.server.is_some();
+                self.server = Some(server.to_string());
+                self.messages.clear();
+                let chosen = manual(settings, server);
+                self.manual = chosen.is_some();
+                self.joined_at = if self.manual { None } else { time };
+                let parser = chosen.unwrap_or_else(|| self.pick(&parsers, server));
+                self.parser = Some(parser.clone());
+                selected.push(Found::Parser(
+                    server.to_string(),
+                    parser,
+                    self.manual,
+                    switched,
+                ));
+                continue;
+            }
+            let (Some(joined), Some(time), Some(server)) = (self.joined_at, time, &self.server)
+            else {
+                continue;
+            };
+            // The window can cross midnight
+            if (time + 86_400 - joined) % 86_400 > WINDOW_SECS {
+                self.joined_at = None;
+                continue;
+            }
+            if !parsed.message.starts_with("[CHAT]") {
+                continue;
+            }
+            self.messages.push(parsed.message);
+            let parser = self.pick(&parsers, server);
+            if self.parser.as_ref() != Some(&parser) {
+                self.parser = Some(parser.clone());
+                selected.push(Found::Parser(server.clone(), parser, false, false));
+            }
+        }
+        selected
+    }
+}
+
+/// What applies on a server once its own settings are layered over the
+/// global ones:
+///
+/// `{"address": "hypixel.net", "parser": "hypixel", "trust_player_chat": true, "patterns": {"game_start": "^Parkour Duels$"}, "webhooks": true}`
+///
+/// `parser` is the one picked in the server's settings, `null` when it's
+/// detected. `patterns` are the server's own over its parser's.
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct EffectiveServerSettings {
+    pub address: String,
+    pub parser: Option<String>,
+    pub trust_player_chat: bool,
+    pub patterns: PatternSpec,
+    pub webhooks: bool,
+}
+
+impl EffectiveServerSettings {
+    pub fn resolve(settings: &Settings, address: &str, parser: Option<&str>) -> Self {
+        let address = servers::normalize(address, &settings.server_aliases);
+        let own = settings.servers.get(&address).cloned().unwrap_or_default();
+        let parser = own.parser.as_deref().or(parser);
+        let base = servers::registry(&settings.parsers)
+            .into_iter()
+            .find(|p| Some(p.name.as_str()) == parser)
+            .map(|p| p.patterns)
+            .unwrap_or_default();
+        EffectiveServerSettings {
+            address,
+            parser: own.parser.clone(),
+            trust_player_chat: own
+                .trust_player_chat
+                .unwrap_or(settings.watcher.trust_player_chat),
+            patterns: own.patterns.unwrap_or_default().or(base),
+            // Otherwise webhooks post as configured globally
+            webhooks: own.webhooks.unwrap_or(true),
+        }
+    }
+}
+
+/// A server's stored settings next to what applies on it.
+#[derive(Serialize, Clone, Debug)]
+pub struct ServerSettingsView {
+    pub address: String,
+    pub settings: ServerSettings,
+    pub effective: EffectiveServerSettings,
+}
+
+fn view(settings: &Settings, address: &str) -> ServerSettingsView {
+    let effective = EffectiveServerSettings::resolve(settings, address, None);
+    ServerSettingsView {
+        address: effective.address.clone(),
+        settings: settings
+            .servers
+            .get(&effective.address)
+            .cloned()
+            .unwrap_or_default(),
+        effective,
+    }
+}
+
+fn select(
+    app: &AppHandle,
+    source: &str,
+    server: String,
+    parser: String,
+    manual: bool,
+    switched: bool,
+) {
+    let settings = EffectiveServerSettings::resolve(
+        &app.state::<SettingsStore>().get(),
+        &server,
+        Some(&parser),
+    );
+    let mut abandoned_run = None;
+    app.state::<Live>().update_source(source, |s| {
+        if switched {
+            abandoned_run = s.current_run.take();
+        }
+        s.server = Some(server.clone());
+        s.parser = Some(parser.clone());
+        s.server_settings = Some(settings.clone());
+    });
+    events::emit_all(
+        app,
+        events::PARSER_SELECTED,
+        &ParserSelectedPayload {
+            server,
+            parser,
+            manual,
+            settings,
+            abandoned_run,
+            source: source_field(source),
+        },
+    );
+}
+
+// Leaving for singleplayer also drops the server's parser and settings
+fn change_context(app: &AppHandle, source: &str, context: PlayContext) {
+    app.state::<Live>().update_source(source, |s| {
+        if context.server.is_none() {
+            s.server = None;
+            s.parser = None;
+            s.server_settings = None;
+        }
+        s.world = context.world.clone();
+    });
+    events::emit_all(
+        app,
+        events::CONTEXT_CHANGED,
+        &ContextChangedPayload {
+            context,
+            source: source_field(source),
+        },
+    );
+}
+
+/// Follows server and world changes in newly read log text of `source`,
+/// and picks the parser for each server from
+/// its address and the first minute of chat after joining it.
+pub fn scan(app: &AppHandle, source: &str, chunk: &Capped) {
+    let settings = app.state::<SettingsStore>().get();
+    let selected = match app.state::<ServerState>().0.lock() {
+        Ok(mut resolvers) => resolvers
+            .entry(source.to_string())
+            .or_default()
+            .scan(&settings, chunk),
+        Err(_) => return,
+    };
+    for found in selected {
+        match found {
+            Found::Parser(server, parser, manual, switched) => {
+                select(app, source, server, parser, manual, switched)
+            }
+            Found::Context(context) => change_context(app, source, context),
+        }
+    }
+}
+
+/// Drops what was tracked for a source that's no longer watched.
+pub fn forget(app: &AppHandle, source: &str) {
+    if let Ok(mut resolvers) = app.state::<ServerState>().0.lock() {
+        resolvers.remove(source);
+    }
+}
+
+/// Whether webhooks post for runs on `server`.
+pub fn webhooks_enabled(app: &AppHandle, server: &str) -> bool {
+    EffectiveServerSettings::resolve(&app.state::<SettingsStore>().get(), server, None).webhooks
+}
+
+/// Every parser a server can use, custom ones first.
+#[tauri::command]
+pub fn get_server_parsers(store: State<'_, SettingsStore>) -> Vec<ServerParser> {
+    servers::registry(&store.get().parsers)
+}
+
+/// Every server with settings of its own, whether or not it was played on
+/// lately.
+#[tauri::command]
+pub fn list_server_settings(store: State<'_, SettingsStore>) -> Vec<ServerSettingsView> {
+    let settings = store.get();
+    settings
+        .servers
+        .keys()
+        .map(|address| view(&settings, address))
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_server_settings(
+    address: String,
+    store: State<'_, SettingsStore>,
+) -> Result<ServerSettingsView, String> {
+    if address.trim().is_empty() {
+        return Err("Server address is empty".into());
+    }
+    Ok(view(&store.get(), &address))
+}
+
+/// Changes only the fields in `partial`; `null` unsets one so it falls back
+/// to the global setting again:
+///
+/// `{"trust_player_chat": false, "parser": null}`
+///
+/// Applies right away on every watched log playing on `address`.
+#[tauri::command]
+pub fn set_server_settings(
+    address: String,
+    partial: Value,
+    store: State<'_, SettingsStore>,
+    state: State<'_, ServerState>,
+    app: AppHandle,
+) -> Result<ServerSettingsView, String> {
+    let Value::Object(partial) = partial else {
+        return Err("Server settings must be an object".into());
+    };
+    let mut settings = store.get();
+    if address.trim().is_empty() {
+        return Err("Server address is empty".into());
+    }
+    let key = servers::normalize(&address, &settings.server_aliases);
+    let mut merged = serde_json::to_value(settings.servers.get(&key).cloned().unwrap_or_default())
+        .map_err(|e| format!("Failed to update server settings: {}", e))?;
+    if let Value::Object(fields) = &mut merged {
+        fields.extend(partial);
+    }
+    let updated: ServerSettings =
+        serde_json::from_value(merged).map_err(|e| format!("Invalid server settings: {}", e))?;
+    if updated.is_empty() {
+        settings.servers.remove(&key);
+    } else {
+        settings.servers.insert(key.clone(), updated);
+    }
+    crate::settings::validate(&settings)?;
+    store.replace(settings.clone())?;
+
+    let mut resolvers = state.0.lock().map_err(|_| "Server state lock poisoned")?;
+    let parsers = servers::registry(&settings.parsers);
+    let mut selected = Vec::new();
+    for (source, resolver) in resolvers.iter_mut() {
+        let current = resolver
+            .server
+            .clone()
+            .filter(|s| servers::normalize(s, &settings.server_aliases) == key);
+        if let Some(current) = current {
+            let chosen = manual(&settings, &current);
+            resolver.manual = chosen.is_some();
+            let parser = chosen.unwrap_or_else(|| resolver.pick(&parsers, &current));
+            resolver.parser = Some(parser.clone());
+            selected.push((source.clone(), current, parser, resolver.manual));
+        }
+    }
+    drop(resolvers);
+    for (source, current, parser, manual) in selected {
+        select(&app, &source, current, parser, manual, false);
+    }
+    Ok(view(&settings, &key))
+}
+
+/// Always uses `parser` on `server`, or detects it again with `None`.
+#[tauri::command]
+pub fn set_server_parser(
+    server: String,
+    parser: Option<String>,
+    store: State<'_, SettingsStore>,
+    state: State<'_, ServerState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let partial = serde_json::json!({ "parser": parser });
+    set_server_settings(server, partial, store, state, app).map(|_| ())
+}
@@ -0,0 +1,788 @@
+use crate::templates::{self, TemplateContext};
+use parkour_core::anonymize::RedactionRules;
+use parkour_core::client::Client;
+use parkour_core::definitions::RunDefinition;
+use parkour_core::duels::{PatternSpec, Patterns};
+use parkour_core::legitimacy::{self, LegitimacyRule};
+use parkour_core::line_filter::LineFilter;
+use parkour_core::server_log::ServerLogSpec;
+use parkour_core::servers::{self, ServerParser, GENERIC};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub overlay: OverlaySettings,
+    pub api: ApiSettings,
+    pub webhooks: Vec<WebhookSettings>,
+    pub livesplit: LiveSplitSettings,
+    pub presence: PresenceSettings,
+    pub notifications: NotificationSettings,
+    pub summary: SummarySettings,
+    pub text_output: TextOutputSettings,
+    pub markers: MarkerSettings,
+    pub hotkeys: HotkeySettings,
+    pub chat_commands: ChatCommandSettings,
+    pub tray: TraySettings,
+    pub events: EventSettings,
+    pub watcher: WatcherSettings,
+    // Server parsers defined in JSON, tried before the built-in ones
+    pub parsers: Vec<ServerParser>,
+    // Keyed by normalized server address, see servers::normalize
+    pub servers: BTreeMap<String, ServerSettings>,
+    // Addresses that share another's settings, e.g. "mc.example.net": "example.net"
+    pub server_aliases: BTreeMap<String, String>,
+    // Runs defined by their start, split and end messages, in precedence order
+    pub run_definitions: Vec<RunDefinition>,
+    pub legitimacy: LegitimacySettings,
+    // What export_sanitized_log scrubs from a shared log
+    pub redaction: RedactionRules,
+    // Emit log events as bare strings like releases before typed payloads
+    pub legacy_events: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct OverlaySettings {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        OverlaySettings {
+            enabled: false,
+            port: 17833,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    // Sent as `Authorization: Bearer <token>`, made when the API is first enabled
+    pub token: Option<String>,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings {
+            enabled: false,
+            port: 17834,
+            token: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookTrigger {
+    Pb,
+    Completion,
+    SessionSummary,
+    Goal,
+    // A remote log's connection failing, once per outage
+    LogError,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct WebhookSettings {
+    pub url: String,
+    pub events: Vec<WebhookTrigger>,
+    pub discord_embed: bool,
+    pub template: Option<String>,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        WebhookSettings {
+            url: String::new(),
+            events: vec![WebhookTrigger::Pb],
+            discord_embed: false,
+            template: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LiveSplitMapping {
+    pub on_start: Option<String>,
+    pub on_checkpoint: Option<String>,
+    pub on_reset: Option<String>,
+    pub on_finish: Option<String>,
+}
+
+impl Default for LiveSplitMapping {
+    fn default() -> Self {
+        LiveSplitMapping {
+            on_start: Some("starttimer".into()),
+            on_checkpoint: Some("split".into()),
+            on_reset: Some("reset".into()),
+            on_finish: Some("split".into()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LiveSplitSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub default_mapping: LiveSplitMapping,
+    pub maps: HashMap<String, LiveSplitMapping>,
+}
+
+impl Default for LiveSplitSettings {
+    fn default() -> Self {
+        LiveSplitSettings {
+            enabled: false,
+            host: "localhost".into(),
+            port: 16834,
+            default_mapping: LiveSplitMapping::default(),
+            maps: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct PresenceSettings {
+    pub enabled: bool,
+    // Application id of the Discord app the presence is shown as
+    pub client_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub do_not_disturb: bool,
+    pub min_improvement_ms: i64,
+    // Which events notify: a new PB, any other finished run, a goal reached
+    pub pbs: bool,
+    pub completions: bool,
+    pub goals: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            enabled: false,
+            do_not_disturb: false,
+            min_improvement_ms: 100,
+            pbs: true,
+            completions: true,
+            goals: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct SummarySettings {
+    pub plain_template: Option<String>,
+    pub markdown_template: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TextFile {
+    CurrentMap,
+    CurrentTime,
+    Pb,
+    AttemptCount,
+    LastSplitDelta,
+}
+
+impl TextFile {
+    pub const ALL: [TextFile; 5] = [
+        TextFile::CurrentMap,
+        TextFile::CurrentTime,
+        TextFile::Pb,
+        TextFile::AttemptCount,
+        TextFile::LastSplitDelta,
+    ];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            TextFile::CurrentMap => "current_map.txt",
+            TextFile::CurrentTime => "current_time.txt",
+            TextFile::Pb => "pb.txt",
+            TextFile::AttemptCount => "attempt_count.txt",
+            TextFile::LastSplitDelta => "last_split_delta.txt",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TextOutputSettings {
+    pub enabled: bool,
+    pub directory: String,
+    pub files: Vec<TextFile>,
+    pub interval_ms: u64,
+    pub clear_on_disable: bool,
+}
+
+impl Default for TextOutputSettings {
+    fn default() -> Self {
+        TextOutputSettings {
+            enabled: false,
+            directory: String::new(),
+            files: TextFile::ALL.to_vec(),
+            interval_ms: 1000,
+            clear_on_disable: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MarkerSettings {
+    pub shortcut: Option<String>,
+    pub auto_label: bool,
+    pub label_prefix: String,
+}
+
+impl Default for MarkerSettings {
+    fn default() -> Self {
+        MarkerSettings {
+            shortcut: None,
+            auto_label: true,
+            label_prefix: "Marker".into(),
+        }
+    }
+}
+
+// Global shortcuts, e.g. "CommandOrControl+Shift+R"; the marker one is in
+// MarkerSettings
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct HotkeySettings {
+    // Gives up on the run in progress
+    pub reset: Option<String>,
+    // Adds a marker, like the marker shortcut
+    pub split: Option<String>,
+    // Pauses or resumes the live run's timer
+    pub pause: Option<String>,
+}
+
+/// `{"enabled": true, "prefix": "!", "username": null}`
+///
+/// Commands typed in chat on the watched log: `!reset` and `!split` act
+/// like their hotkeys, and `!note <text>` adds a marker labelled `text`.
+/// Only chat sent by `username` counts, the logged-in player by default.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ChatCommandSettings {
+    pub enabled: bool,
+    pub prefix: String,
+    pub username: Option<String>,
+}
+
+impl Default for ChatCommandSettings {
+    fn default() -> Self {
+        ChatCommandSettings {
+            enabled: false,
+            prefix: "!".into(),
+            username: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct TraySettings {
+    pub close_to_tray: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct EventSettings {
+    // Recent events kept for get_events_since
+    pub buffer_size: usize,
+    // Maximum emits per second per channel; channels not listed are unlimited
+    pub rate_limits: BTreeMap<String, f64>,
+}
+
+impl Default for EventSettings {
+    fn default() -> Self {
+        EventSettings {
+            buffer_size: 256,
+            rate_limits: BTreeMap::from([(crate::events::LOG_UPDATE.to_string(), 2.0)]),
+        }
+    }
+}
+
+/// Messages that invalidate the run they're logged during, like a /fly
+/// confirmation. `rules` replace built-in ones of the same name and add to
+/// the rest; with `builtin_rules` off only `rules` apply.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LegitimacySettings {
+    pub builtin_rules: bool,
+    pub rules: Vec<LegitimacyRule>,
+}
+
+impl Default for LegitimacySettings {
+    fn default() -> Self {
+        LegitimacySettings {
+            builtin_rules: true,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl LegitimacySettings {
+    pub fn rules(&self) -> Vec<LegitimacyRule> {
+        match self.builtin_rules {
+            true => legitimacy::with_builtin(&self.rules),
+            false => self.rules.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct WatcherSettings {
+    pub debounce_ms: u64,
+    // Scale the debounce between these bounds based on how often the log changes
+    pub adaptive_debounce: bool,
+    pub min_debounce_ms: u64,
+    pub max_debounce_ms: u64,
+    // How often the log is checked when no change is reported, and how often
+    // the fallback polling watcher looks at it
+    pub poll_ms: u64,
+    // Longer lines are cut short before parsing and emitting
+    pub max_line_bytes: usize,
+    // Which lines log-update events carry; the parsers still read them all
+    pub line_filters: LineFilterSettings,
+    // Read logs as this client's instead of detecting it from startup lines
+    pub client: Option<Client>,
+    // Count checkpoint messages that look typed by a player
+    pub trust_player_chat: bool,
+    // More logs to import runs from as they're written
+    pub followed: Vec<FollowedLog>,
+    // Tail this log over SFTP instead of the local one, from the next start
+    pub remote: Option<RemoteSource>,
+    // Logs of other game instances, watched live alongside the main one
+    pub instances: Vec<InstanceLog>,
+    // Watch this log instead of the auto-detected one, while it exists
+    pub log_path: Option<String>,
+}
+
+
+impl Default for WatcherSettings {
+    fn default() -> Self {
+        WatcherSettings {
+            debounce_ms: 2000,
+            adaptive_debounce: true,
+            min_debounce_ms: 200,
+            max_debounce_ms: 5000,
+            poll_ms: 2000,
+            max_line_bytes: 256 * 1024,
+            line_filters: LineFilterSettings::default(),
+            client: None,
+            trust_player_chat: true,
+            followed: Vec::new(),
+            remote: None,
+            instances: Vec::new(),
+            log_path: None,
+        }
+    }
+}
+
+/// `{"patterns": ["\\[CHAT\\].*Checkpoint"], "summary_ms": 10000}`
+///
+/// With `patterns`, `log-update` events only carry the lines matching one of
+/// them. With `summary_ms`, `lines-filtered` reports how many were left out
+/// that often.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LineFilterSettings {
+    pub patterns: Vec<String>,
+    pub summary_ms: Option<u64>,
+}
+
+/// A log whose runs are imported as it's written, as the watched log's are.
+/// A folder follows every log in it, so a server's archives are read as it
+/// rotates them:
+///
+/// `{"path": "/srv/parkour/logs", "mode": "server"}`
+///
+/// A client log is read once it has gone a while without changes, so the
+/// game being played isn't stored half done.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FollowedLog {
+    pub path: String,
+    pub mode: LogMode,
+    // Plugin messages to read a server log by, in place of the built-in ones
+    pub server_patterns: ServerLogSpec,
+}
+
+/// Another game instance's log, for playing on two accounts at once. It's
+/// watched live with runs, server and crash tracking of its own, and its
+/// events and status carry `id` as their source:
+///
+/// `{"id": "alt", "path": "/home/me/.minecraft-alt/logs/latest.log"}`
+///
+/// Its runs are stored like a followed client log's, under the player it
+/// names. Removing it stores what it holds right away.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct InstanceLog {
+    pub id: String,
+    pub path: String,
+}
+
+/// Who a log belongs to: `client` is a game's log with the logged-in
+/// player's view of chat, `server` a Paper or Spigot console log with every
+/// player's runs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogMode {
+    #[default]
+    Client,
+    Server,
+}
+
+/// A log on another machine, tailed over SFTP:
+///
+/// `{"host": "vps.example.net", "username": "mc", "auth": {"method": "key", "key_path": "/home/me/.ssh/id_ed25519"}, "path": "/srv/parkour/logs/latest.log"}`
+///
+/// The password, or the key's passphrase, is kept in the OS keyring rather
+/// than here, see `set_remote_secret`. The host's key has to be in
+/// `~/.ssh/known_hosts`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteSource {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth: RemoteAuth,
+    pub path: String,
+}
+
+impl Default for RemoteSource {
+    fn default() -> Self {
+        RemoteSource {
+            host: String::new(),
+            port: 22,
+            username: String::new(),
+            auth: RemoteAuth::default(),
+            path: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum RemoteAuth {
+    #[default]
+    Password,
+    // Private key file, with the keyring secret as its passphrase if set
+    Key { key_path: String },
+}
+
+/// Settings for one server. Unset fields fall back to the global settings.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    // Parser to use instead of detecting one
+    pub parser: Option<String>,
+    pub trust_player_chat: Option<bool>,
+    // Replaces the parser's patterns field by field
+    pub patterns: Option<PatternSpec>,
+    // Post webhooks for runs on this server
+    pub webhooks: Option<bool>,
+}
+
+impl ServerSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == ServerSettings::default()
+    }
+}
+
+impl Settings {
+    /// The built-in patterns with the global parser settings, run
+    /// definitions and legitimacy rules applied.
+    pub fn patterns(&self) -> Result<Patterns, String> {
+        Patterns::default()
+            .with_client(self.watcher.client)
+            .with_trust_player_chat(self.watcher.trust_player_chat)
+            .with_definitions(&self.run_definitions)?
+            .with_legitimacy_rules(&self.legitimacy.rules())
+    }
+
+    /// The followed logs and the instance logs, read as client logs.
+    pub fn followed_logs(&self) -> Vec<FollowedLog> {
+        let instances = self.watcher.instances.iter().map(|i| FollowedLog {
+            path: i.path.clone(),
+            ..Default::default()
+        });
+        self.watcher.followed.iter().cloned().chain(instances).collect()
+    }
+
+    /// The patterns to read a followed log with.
+    pub fn followed_patterns(&self, log: &FollowedLog) -> Result<Patterns, String> {
+        let patterns = self.patterns()?;
+        match log.mode {
+            LogMode::Client => Ok(patterns),
+            LogMode::Server => patterns.with_server_log(&log.server_patterns),
+        }
+    }
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    current: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    pub fn load(path: PathBuf) -> SettingsStore {
+        let current = match fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                eprintln!("Ignoring malformed settings file: {}", e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        };
+        SettingsStore {
+            path,
+            current: Mutex::new(current),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn replace(&self, settings: Settings) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&settings)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create settings dir: {}", e))?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json)
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .map_err(|e| format!("Failed to save settings: {}", e))?;
+        let mut current = self.current.lock().map_err(|_| "Settings lock poisoned")?;
+        *current = settings;
+        Ok(())
+    }
+}
+
+pub(crate) fn validate(settings: &Settings) -> Result<(), String> {
+    let summary = [
+        ("Plain summary", &settings.summary.plain_template),
+        ("Markdown summary", &settings.summary.markdown_template),
+    ];
+    for (label, template) in summary {
+        if let Some(template) = template {
+            templates::validate(template, TemplateContext::Plain)
+                .map_err(|e| format!("{} template: {}", label, e))?;
+        }
+    }
+    for (i, hook) in settings.webhooks.iter().enumerate() {
+        if let Some(template) = &hook.template {
+            templates::validate(template, TemplateContext::Json)
+                .map_err(|e| format!("Webhook {} template: {}", i + 1, e))?;
+        }
+    }
+    let text = &settings.text_output;
+    if text.enabled && text.directory.trim().is_empty() {
+        return Err("Text output needs a directory".into());
+    }
+    if text.interval_ms < 100 {
+        return Err("Text output interval must be at least 100 ms".into());
+    }
+    if !(1..=10_000).contains(&settings.events.buffer_size) {
+        return Err("Event buffer size must be between 1 and 10000".into());
+    }
+    let watcher = &settings.watcher;
+    if watcher.min_debounce_ms < 50 || watcher.min_debounce_ms > watcher.max_debounce_ms {
+        return Err("Debounce floor must be at least 50 ms and below the ceiling".into());
+    }
+    if watcher.debounce_ms > 60_000 || watcher.max_debounce_ms > 60_000 {
+        return Err("Debounce can't exceed 60 seconds".into());
+    }
+    if !(50..=60_000).contains(&watcher.poll_ms) {
+        return Err("Poll interval must be between 50 ms and 60 seconds".into());
+    }
+    if watcher.max_line_bytes < 1024 {
+        return Err("Maximum line length must be at least 1024 bytes".into());
+    }
+    LineFilter::compile(&watcher.line_filters.patterns)?;
+    if watcher.line_filters.summary_ms.is_some_and(|ms| ms < 1000) {
+        return Err("Line filter summaries can't be more often than every second".into());
+    }
+    for (i, parser) in settings.parsers.iter().enumerate() {
+        let name = parser.name.trim();
+        if name.is_empty() || name == GENERIC {
+            return Err(format!(
+                "Server parser {} needs a name other than {}",
+                i + 1,
+                GENERIC
+            ));
+        }
+        if settings.parsers[..i].iter().any(|p| p.name == parser.name) {
+            return Err(format!("There are two server parsers named {}", name));
+        }
+        Patterns::from_spec(&parser.patterns).map_err(|e| format!("Parser {}: {}", name, e))?;
+    }
+    let parsers = servers::registry(&settings.parsers);
+    for (server, chosen) in &settings.servers {
+        if let Some(parser) = &chosen.parser {
+            if parser != GENERIC && !parsers.iter().any(|p| &p.name == parser) {
+                return Err(format!("{} uses the unknown parser {}", server, parser));
+            }
+        }
+        if let Some(patterns) = &chosen.patterns {
+            Patterns::from_spec(patterns).map_err(|e| format!("{}: {}", server, e))?;
+        }
+    }
+    for (i, definition) in settings.run_definitions.iter().enumerate() {
+        if definition.name.trim().is_empty() {
+            return Err(format!("Run definition {} needs a name", i + 1));
+        }
+        if settings.run_definitions[..i]
+            .iter()
+            .any(|d| d.name == definition.name)
+        {
+            return Err(format!(
+                "There are two run definitions named {}",
+                definition.name
+            ));
+        }
+    }
+    for (i, rule) in settings.legitimacy.rules.iter().enumerate() {
+        if settings.legitimacy.rules[..i].iter().any(|r| r.name == rule.name) {
+            return Err(format!("There are two legitimacy rules named {}", rule.name));
+        }
+    }
+    settings.redaction.compile()?;
+    settings.patterns()?;
+    for (i, log) in settings.watcher.followed.iter().enumerate() {
+        if log.path.trim().is_empty() {
+            return Err(format!("Followed log {} needs a path", i + 1));
+        }
+        settings.followed_patterns(log)?;
+    }
+    for (i, instance) in settings.watcher.instances.iter().enumerate() {
+        let id = instance.id.trim();
+        if id.is_empty() || instance.path.trim().is_empty() {
+            return Err(format!("Instance log {} needs an id and a path", i + 1));
+        }
+        if id == crate::live::MAIN {
+            return Err(format!("The instance id {} is taken by the main log", id));
+        }
+        if id == crate::live::REPLAY {
+            return Err(format!("The instance id {} is taken by log replays", id));
+        }
+        if settings.watcher.instances[..i].iter().any(|other| other.id == instance.id) {
+            return Err(format!("There are two instance logs with the id {}", id));
+        }
+    }
+    if settings.watcher.log_path.as_ref().is_some_and(|p| p.trim().is_empty()) {
+        return Err("Saved log path can't be empty".into());
+    }
+    if let Some(remote) = &settings.watcher.remote {
+        if [&remote.host, &remote.username, &remote.path]
+            .iter()
+            .any(|field| field.trim().is_empty())
+        {
+            return Err("Remote log needs a host, username and path".into());
+        }
+        if remote.port == 0 {
+            return Err("Remote log port must be between 1 and 65535".into());
+        }
+        if matches!(&remote.auth, RemoteAuth::Key { key_path } if key_path.trim().is_empty()) {
+            return Err("Remote key login needs a key file".into());
+        }
+    }
+    for (alias, address) in &settings.server_aliases {
+        if alias.trim().is_empty() || address.trim().is_empty() {
+            return Err("Server aliases need both addresses".into());
+        }
+    }
+    crate::hotkeys::validate(settings)?;
+    let commands = &settings.chat_commands;
+    if commands.prefix.is_empty() || commands.prefix.contains(char::is_whitespace) {
+        return Err("Chat command prefix must be set and have no spaces".into());
+    }
+    if commands.username.as_ref().is_some_and(|u| u.trim().is_empty()) {
+        return Err("Chat command username can't be empty".into());
+    }
+    match settings.api.token.as_deref() {
+        Some(token) if token.trim().is_empty() => {
+            return Err("API token can't be empty".into());
+        }
+        None if settings.api.enabled => {
+            return Err("The local API needs a token".into());
+        }
+        _ => {}
+    }
+    match settings.presence.client_id.as_deref() {
+        Some(id) if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) => {
+            return Err("Discord application id must be a number".into());
+        }
+        None if settings.presence.enabled => {
+            return Err("Discord presence needs an application id".into());
+        }
+        _ => {}
+    }
+    for (channel, rate) in &settings.events.rate_limits {
+        if crate::rate_limit::EXEMPT.contains(&channel.as_str()) {
+            return Err(format!("The {} channel can't be rate limited", channel));
+        }
+        if !rate.is_finite() || *rate <= 0.0 {
+            return Err(format!("Rate limit for {} must be a positive number", channel));
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_settings(store: State<'_, SettingsStore>) -> Settings {
+    store.get()
+}
+
+#[tauri::command]
+pub fn set_settings(
+    settings: Settings,
+    store: State<'_, SettingsStore>,
+    app: AppHandle,
+) -> Result<Settings, String> {
+    validate(&settings)?;
+    let previous = store.get();
+    crate::hotkeys::apply(&app, &previous, &settings)?;
+    store.replace(settings.clone())?;
+    if previous.overlay != settings.overlay {
+        crate::overlay::apply_settings(&app, &settings.overlay)?;
+    }
+    if previous.api != settings.api {
+        crate::api::apply_settings(&app, &settings.api)?;
+    }
+    if previous.text_output != settings.text_output {
+        crate::text_output::apply_settings(&app, &previous.text_output);
+    }
+    if previous.events != settings.events {
+        app.state::<crate::events::EventLog>()
+            .set_capacity(settings.events.buffer_size);
+        app.state::<crate::rate_limit::RateLimiter>()
+            .set_limits(&settings.events.rate_limits);
+    }
+    Ok(settings)
+}
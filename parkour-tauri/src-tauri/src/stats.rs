@@ -0,0 +1,810 @@
+use crate::db::{self, Db};
+use crate::timestamps;
+use chrono::{Local, NaiveDate, Weekday};
+use parkour_core::duels::FINISH;
+use rusqlite::{named_params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+const MAX_BUCKETS: i64 = 5000;
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    fn start_of(self, col: &str) -> String {
+        match self {
+            Bucket::Day => format!("date({})", col),
+            Bucket::Week => format!(
+                "date({c}, '-' || ((CAST(strftime('%w', {c}) AS INTEGER) - :week_start + 7) % 7) || ' days')",
+                c = col
+            ),
+            Bucket::Month => format!("date({}, 'start of month')", col),
+        }
+    }
+
+    fn step(self) -> &'static str {
+        match self {
+            Bucket::Day => "'+1 day'",
+            Bucket::Week => "'+7 days'",
+            Bucket::Month => "'+1 month'",
+        }
+    }
+
+    fn approx_days(self) -> i64 {
+        match self {
+            Bucket::Day => 1,
+            Bucket::Week => 7,
+            Bucket::Month => 28,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ActivityBucket {
+    // Local date, and local midnight on it as RFC 3339 with the offset then in effect
+    pub start: String,
+    pub start_at: String,
+    pub active_ms: i64,
+    pub attempts: i64,
+    pub completions: i64,
+    pub distinct_maps: i64,
+    pub pbs: i64,
+}
+
+pub fn activity_stats(
+    conn: &Connection,
+    bucket: Bucket,
+    date_from: &str,
+    date_to: &str,
+    username: Option<&str>,
+    week_start: Weekday,
+    include_invalidated: bool,
+) -> Result<Vec<ActivityBucket>, String> {
+    // Buckets are local calendar days, so timestamps are reduced to their local date
+    let from = timestamps::local_date("date_from", date_from)?;
+    let to = timestamps::local_date("date_to", date_to)?;
+    if from > to {
+        return Err(format!("date_from ({}) is after date_to ({})", from, to));
+    }
+    if (to - from).num_days() / bucket.approx_days() > MAX_BUCKETS {
+        return Err(format!("Range too large: more than {} buckets", MAX_BUCKETS));
+    }
+
+    let sql = format!(
+        "WITH RECURSIVE buckets(start) AS (
+            SELECT {first}
+            UNION ALL
+            SELECT date(start, {step}) FROM buckets WHERE date(start, {step}) <= :to
+        ),
+        run_stats AS (
+            SELECT {run_bucket} AS bucket,
+                COUNT(*) AS attempts,
+                SUM(r.completed) AS completions,
+                COUNT(DISTINCT r.map_key) AS maps,
+                SUM(CASE WHEN {is_pb} THEN 1 ELSE 0 END) AS pbs
+            FROM runs r
+            WHERE r.started_at >= :from AND r.started_at < date(:to, '+1 day')
+                AND (:username IS NULL OR r.username = :username) AND r.excluded = 0
+                AND (:invalidated OR r.invalidated_by IS NULL)
+            GROUP BY bucket
+        ),
+        session_stats AS (
+            SELECT {session_bucket} AS bucket, SUM(s.active_ms) AS active_ms
+            FROM sessions s
+            WHERE s.started_at >= :from AND s.started_at < date(:to, '+1 day')
+                AND (:username IS NULL OR s.username = :username)
+            GROUP BY bucket
+        )
+        SELECT b.start, COALESCE(s.active_ms, 0), COALESCE(r.attempts, 0),
+            COALESCE(r.completions, 0), COALESCE(r.maps, 0), COALESCE(r.pbs, 0)
+        FROM buckets b
+        LEFT JOIN run_stats r ON r.bucket = b.start
+        LEFT JOIN session_stats s ON s.bucket = b.start
+        ORDER BY b.start",
+        first = bucket.start_of(":from"),
+        step = bucket.step(),
+        run_bucket = bucket.start_of("r.started_at"),
+        session_bucket = bucket.start_of("s.started_at"),
+        is_pb = db::set_pb("r"),
+    );
+
+    let err = |e: rusqlite::Error| format!("Activity query failed: {}", e);
+    let mut stmt = conn.prepare(&sql).map_err(err)?;
+    let from = from.format("%Y-%m-%d").to_string();
+    let to = to.format("%Y-%m-%d").to_string();
+    let week_start = week_start.num_days_from_sunday();
+    let mut params = named_params! {
+        ":from": from,
+        ":to": to,
+        ":username": username,
+        ":week_start": week_start,
+        ":invalidated": include_invalidated,
+    }
+    .to_vec();
+    // Only the week expression references :week_start
+    if !matches!(bucket, Bucket::Week) {
+        params.retain(|(name, _)| *name != ":week_start");
+    }
+    let rows = stmt
+        .query_map(params.as_slice(), |row| {
+            let start: String = row.get(0)?;
+            Ok(ActivityBucket {
+                start_at: start_at(&start),
+                start,
+                active_ms: row.get(1)?,
+                attempts: row.get(2)?,
+                completions: row.get(3)?,
+                distinct_maps: row.get(4)?,
+                pbs: row.get(5)?,
+            })
+        })
+        .map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+fn start_at(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|d| timestamps::local_rfc3339(timestamps::local_to_utc(&Local, d.and_time(Default::default()))))
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HeatmapMetric {
+    // Active milliseconds, the idle gaps between lines left out (see
+    // parkour_core::import::ActiveTime)
+    Playtime,
+    Attempts,
+    Pbs,
+}
+
+/// `{"metric": "attempts", "cells": [[0, 0, 3, ...], ...], "max": 12}`
+///
+/// `cells[day][hour]`, days from Monday, hours of the local day 0–23. `max`
+/// is the largest cell, 0 when there's nothing in the range.
+#[derive(Serialize, Clone, Debug)]
+pub struct ActivityHeatmap {
+    pub metric: HeatmapMetric,
+    pub cells: Vec<Vec<i64>>,
+    pub max: i64,
+}
+
+/// When `username`, or anyone, plays: `metric` summed by weekday and hour
+/// over the local days `date_from` to `date_to`. Times are read as the local
+/// wall-clock times they were stored with. A session's active time is
+/// spread over the hours it spans in proportion to the time spent in each.
+pub fn activity_heatmap(
+    conn: &Connection,
+    metric: HeatmapMetric,
+    date_from: &str,
+    date_to: &str,
+    username: Option<&str>,
+    include_invalidated: bool,
+) -> Result<ActivityHeatmap, String> {
+    let from = timestamps::local_date("date_from", date_from)?;
+    let to = timestamps::local_date("date_to", date_to)?;
+    let mut cells = vec![vec![0; 24]; 7];
+    let range = |t: &str| {
+        format!(
+            "{t}started_at >= :from AND {t}started_at < date(:to, '+1 day')
+                AND (:username IS NULL OR {t}username = :username)",
+            t = t
+        )
+    };
+    let sql = match metric {
+        // A session still going has all its active time in the hour it started
+        HeatmapMetric::Playtime => format!(
+            "WITH RECURSIVE slices(at, ended_at, active_ms, total) AS (
+                SELECT started_at, MAX(COALESCE(ended_at, started_at), started_at), active_ms,
+                    MAX(COALESCE(strftime('%s', ended_at) - strftime('%s', started_at), 0), 0)
+                FROM sessions WHERE {range}
+                UNION ALL
+                SELECT strftime('%Y-%m-%d %H:00:00', at, '+1 hour'), ended_at, active_ms, total
+                FROM slices WHERE strftime('%Y-%m-%d %H:00:00', at, '+1 hour') < ended_at
+            )
+            SELECT strftime('%w', at), strftime('%H', at), CAST(ROUND(SUM(CASE total
+                WHEN 0 THEN active_ms
+                ELSE active_ms * (MIN(strftime('%s', ended_at),
+                    strftime('%s', strftime('%Y-%m-%d %H:00:00', at, '+1 hour')))
+                    - strftime('%s', at)) / CAST(total AS REAL)
+            END)) AS INTEGER)
+            FROM slices GROUP BY 1, 2",
+            range = range(""),
+        ),
+        HeatmapMetric::Attempts | HeatmapMetric::Pbs => format!(
+            "SELECT strftime('%w', r.started_at), strftime('%H', r.started_at), {value}
+            FROM runs r
+            WHERE {range} AND r.excluded = 0 AND (:invalidated OR r.invalidated_by IS NULL)
+            GROUP BY 1, 2",
+            value = match metric {
+                HeatmapMetric::Pbs => format!("SUM(CASE WHEN {} THEN 1 ELSE 0 END)", db::set_pb("r")),
+                _ => "COUNT(*)".to_string(),
+            },
+            range = range("r."),
+        ),
+    };
+    let err = |e: rusqlite::Error| format!("Heatmap query failed: {}", e);
+    let mut stmt = conn.prepare(&sql).map_err(err)?;
+    let from = from.format("%Y-%m-%d").to_string();
+    let to = to.format("%Y-%m-%d").to_string();
+    let mut params = named_params! {
+        ":from": from,
+        ":to": to,
+        ":username": username,
+        ":invalidated": include_invalidated,
+    }
+    .to_vec();
+    if metric == HeatmapMetric::Playtime {
+        params.retain(|(name, _)| *name != ":invalidated");
+    }
+    let rows = stmt
+        .query_map(params.as_slice(), |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+            ))
+        })
+        .map_err(err)?;
+    for row in rows {
+        let (day, hour, value) = row.map_err(err)?;
+        // strftime's %w counts from Sunday; rows for unparseable times are skipped
+        let day = day.and_then(|d| d.parse::<usize>().ok()).map(|d| (d + 6) % 7);
+        let hour = hour.and_then(|h| h.parse::<usize>().ok()).filter(|h| *h < 24);
+        if let (Some(day), Some(hour)) = (day, hour) {
+            cells[day][hour] += value.unwrap_or(0);
+        }
+    }
+    let max = cells.iter().flatten().copied().max().unwrap_or(0);
+    Ok(ActivityHeatmap { metric, cells, max })
+}
+
+// Segments longer than this many medians count as outliers by default
+const OUTLIER_FACTOR: f64 = 3.0;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DistributionBucket {
+    pub start_ms: i64,
+    pub count: i64,
+}
+
+/// `{"checkpoint": 2, "bucket_ms": 500, "buckets": [{"start_ms": 8000, "count": 3}, ...], "count": 41, "p50_ms": 8920, "p90_ms": 10110, "outliers": 2}`
+///
+/// Buckets run without gaps from the fastest segment to the slowest one
+/// kept. `count` and the quantiles leave the outliers out.
+#[derive(Serialize, Clone, Debug)]
+pub struct SplitDistribution {
+    pub checkpoint: i64,
+    pub bucket_ms: i64,
+    pub buckets: Vec<DistributionBucket>,
+    pub count: i64,
+    pub p50_ms: Option<i64>,
+    pub p90_ms: Option<i64>,
+    pub outliers: i64,
+}
+
+/// Times of one segment on `map`, the one into `checkpoint` (9999 for the
+/// finish), over every completed run. Segments that skip a checkpoint or
+/// don't move forward, as from splits logged out of order, are left out, as
+/// are outliers over `outlier_factor` times the median.
+pub fn split_distribution(
+    conn: &Connection,
+    map: &str,
+    checkpoint: i64,
+    bucket_ms: i64,
+    username: Option<&str>,
+    outlier_factor: f64,
+    include_invalidated: bool,
+) -> Result<SplitDistribution, String> {
+    if bucket_ms <= 0 {
+        return Err(format!("bucket_ms must be positive, got {}", bucket_ms));
+    }
+    if outlier_factor.is_nan() || outlier_factor < 1.0 {
+        return Err(format!("outlier_factor must be at least 1, got {}", outlier_factor));
+    }
+
+    // Each split's segment is its time less the run's split before it, when
+    // that's the checkpoint right before; the finish segment starts at the
+    // run's last split
+    let segments = format!(
+        "WITH runs_on_map AS (
+            SELECT id, time_ms FROM runs
+            WHERE map_key = name_key(:map) AND completed = 1 AND time_ms IS NOT NULL
+                AND (:username IS NULL OR username = :username) AND excluded = 0
+                AND (:invalidated OR invalidated_by IS NULL)
+        ),
+        split_segments AS (
+            SELECT s.checkpoint,
+                COALESCE(LAG(s.checkpoint) OVER run_splits, 0) AS previous,
+                s.time_ms - COALESCE(LAG(s.time_ms) OVER run_splits, 0) AS ms
+            FROM splits s JOIN runs_on_map r ON r.id = s.run_id
+            WINDOW run_splits AS (PARTITION BY s.run_id ORDER BY s.checkpoint)
+        ),
+        all_segments AS (
+            SELECT checkpoint, ms FROM split_segments WHERE previous = checkpoint - 1
+            UNION ALL
+            SELECT {finish}, r.time_ms - COALESCE((SELECT MAX(s.time_ms) FROM splits s WHERE s.run_id = r.id), 0)
+            FROM runs_on_map r
+        ),
+        segments AS (
+            SELECT ms, ROW_NUMBER() OVER (ORDER BY ms) AS n, COUNT(*) OVER () AS total
+            FROM all_segments WHERE checkpoint = :checkpoint AND ms > 0
+        ),
+        kept AS (
+            SELECT ms, ROW_NUMBER() OVER (ORDER BY ms) AS n, COUNT(*) OVER () AS total
+            FROM segments
+            WHERE ms <= (SELECT ms FROM segments WHERE n = (total + 1) / 2) * :factor
+        )",
+        finish = FINISH
+    );
+    let err = |e: rusqlite::Error| format!("Split distribution query failed: {}", e);
+    let params = named_params! {
+        ":map": map,
+        ":username": username,
+        ":checkpoint": checkpoint,
+        ":factor": outlier_factor,
+        ":invalidated": include_invalidated,
+    };
+
+    // Nearest-rank quantiles over the kept segments
+    let (all, count, p50_ms, p90_ms, min, max) = conn
+        .query_row(
+            &format!(
+                "{}
+                SELECT (SELECT COUNT(*) FROM segments), COUNT(*),
+                    MAX(CASE WHEN n = (total * 50 + 99) / 100 THEN ms END),
+                    MAX(CASE WHEN n = (total * 90 + 99) / 100 THEN ms END),
+                    MIN(ms), MAX(ms)
+                FROM kept",
+                segments
+            ),
+            params,
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                ))
+            },
+        )
+        .map_err(err)?;
+    if let (Some(min), Some(max)) = (min, max) {
+        if (max - min) / bucket_ms > MAX_BUCKETS {
+            return Err(format!("Range too large: more than {} buckets", MAX_BUCKETS));
+        }
+    }
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "{}
+            SELECT ms / :bucket * :bucket AS start, COUNT(*) FROM kept GROUP BY start ORDER BY start",
+            segments
+        ))
+        .map_err(err)?;
+    let mut bucket_params = params.to_vec();
+    bucket_params.push((":bucket", &bucket_ms));
+    let rows = stmt
+        .query_map(bucket_params.as_slice(), |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))
+        .map_err(err)?;
+    let mut buckets: Vec<DistributionBucket> = Vec::new();
+    for row in rows {
+        let (start_ms, count) = row.map_err(err)?;
+        // Empty buckets in between, so the histogram has no holes
+        let mut next = buckets.last().map_or(start_ms, |b| b.start_ms + bucket_ms);
+        while next < start_ms {
+            buckets.push(DistributionBucket { start_ms: next, count: 0 });
+            next += bucket_ms;
+        }
+        buckets.push(DistributionBucket { start_ms, count });
+    }
+    Ok(SplitDistribution {
+        checkpoint,
+        bucket_ms,
+        buckets,
+        count,
+        p50_ms,
+        p90_ms,
+        outliers: all - count,
+    })
+}
+
+// Attempts in each of the two windows the trend compares, by default
+const TREND_WINDOW: usize = 10;
+
+/// How steady the times on a map are, from `get_map_stats` with
+/// `"detailed": true`:
+///
+/// `{"median_ms": 44100, "stddev_ms": 2310, "completion_rate": 0.75, "window": 10, "recent_median_ms": 43200, "previous_median_ms": 45900, "trend_ms": -2700}`
+///
+/// `stddev_ms` is the sample standard deviation of the completion times.
+/// The trend compares the median completion of the last `window` attempts
+/// with the `window` before them, negative when getting faster; it's `null`
+/// until there are that many attempts in both, and either one has a
+/// completion.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Consistency {
+    pub median_ms: Option<i64>,
+    pub stddev_ms: Option<i64>,
+    pub completion_rate: Option<f64>,
+    pub window: usize,
+    pub recent_median_ms: Option<i64>,
+    pub previous_median_ms: Option<i64>,
+    pub trend_ms: Option<i64>,
+}
+
+fn median(times: &[i64]) -> Option<i64> {
+    let mut sorted = times.to_vec();
+    sorted.sort_unstable();
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((sorted[mid - 1] + sorted[mid]) / 2),
+        _ => Some(sorted[mid]),
+    }
+}
+
+fn stddev(times: &[i64]) -> Option<i64> {
+    if times.len() < 2 {
+        return None;
+    }
+    let n = times.len() as f64;
+    let mean = times.iter().sum::<i64>() as f64 / n;
+    let squares: f64 = times.iter().map(|&t| (t as f64 - mean).powi(2)).sum();
+    Some((squares / (n - 1.0)).sqrt().round() as i64)
+}
+
+/// Consistency of `attempts`, oldest first, each the completion time or
+/// `None` for a run that wasn't finished.
+pub fn consistency(attempts: &[Option<i64>], window: usize) -> Consistency {
+    let completed = |attempts: &[Option<i64>]| -> Vec<i64> {
+        attempts.iter().flatten().copied().collect()
+    };
+    let times = completed(attempts);
+    let (recent_median_ms, previous_median_ms) = match attempts.len().checked_sub(2 * window) {
+        Some(start) if window > 0 => {
+            let (previous, recent) = attempts[start..].split_at(window);
+            (median(&completed(recent)), median(&completed(previous)))
+        }
+        _ => (None, None),
+    };
+    Consistency {
+        median_ms: median(&times),
+        stddev_ms: stddev(&times),
+        completion_rate: (!attempts.is_empty()).then(|| times.len() as f64 / attempts.len() as f64),
+        window,
+        recent_median_ms,
+        previous_median_ms,
+        trend_ms: recent_median_ms.zip(previous_median_ms).map(|(r, p)| r - p),
+    }
+}
+
+/// `{"map": "Tower Parkour", "attempts": 40, "completions": 30, "best_ms": 41250, "average_ms": 45020}`
+///
+/// Totals for one map from the stored runs, under the name it was last
+/// played as. `consistency` is only there when asked for.
+#[derive(Serialize, Clone, Debug)]
+pub struct StoredMapStats {
+    pub map: String,
+    pub attempts: i64,
+    pub completions: i64,
+    pub best_ms: Option<i64>,
+    pub average_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<Consistency>,
+}
+
+/// Per-map totals over every stored run by `username`, or anyone, most
+/// attempted first. With `detailed`, each map also gets its [`Consistency`]
+/// over a trend `window` of that many attempts.
+pub fn stored_map_stats(
+    conn: &Connection,
+    username: Option<&str>,
+    detailed: bool,
+    window: usize,
+    include_invalidated: bool,
+) -> Result<Vec<StoredMapStats>, String> {
+    if window == 0 {
+        return Err("window must be at least 1".to_string());
+    }
+    let err = |e: rusqlite::Error| format!("Map stats query failed: {}", e);
+    let mut stmt = conn
+        .prepare(
+            "SELECT map_key,
+                (SELECT map FROM runs l WHERE l.map_key = r.map_key
+                    AND (:username IS NULL OR l.username = :username) AND l.excluded = 0
+                    AND (:invalidated OR l.invalidated_by IS NULL)
+                    ORDER BY started_at_utc DESC, id DESC LIMIT 1),
+                COUNT(*), SUM(completed),
+                MIN(CASE WHEN completed = 1 THEN time_ms END),
+                CAST(ROUND(AVG(CASE WHEN completed = 1 THEN time_ms END)) AS INTEGER)
+            FROM runs r
+            WHERE (:username IS NULL OR username = :username) AND excluded = 0
+                AND (:invalidated OR invalidated_by IS NULL)
+            GROUP BY map_key
+            ORDER BY COUNT(*) DESC, map_key",
+        )
+        .map_err(err)?;
+    let rows = stmt
+        .query_map(
+            named_params! { ":username": username, ":invalidated": include_invalidated },
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    StoredMapStats {
+                        map: row.get(1)?,
+                        attempts: row.get(2)?,
+                        completions: row.get(3)?,
+                        best_ms: row.get(4)?,
+                        average_ms: row.get(5)?,
+                        consistency: None,
+                    },
+                ))
+            },
+        )
+        .map_err(err)?;
+    let maps = rows.collect::<Result<Vec<_>, _>>().map_err(err)?;
+    if !detailed {
+        return Ok(maps.into_iter().map(|(_, stats)| stats).collect());
+    }
+
+    let mut attempts = conn
+        .prepare(
+            "SELECT CASE WHEN completed = 1 THEN time_ms END FROM runs
+            WHERE map_key = :key AND (:username IS NULL OR username = :username) AND excluded = 0
+                AND (:invalidated OR invalidated_by IS NULL)
+            ORDER BY started_at_utc, id",
+        )
+        .map_err(err)?;
+    let mut detailed = Vec::new();
+    for (key, mut stats) in maps {
+        let times = attempts
+            .query_map(
+                named_params! {
+                    ":key": key,
+                    ":username": username,
+                    ":invalidated": include_invalidated,
+                },
+                |row| row.get(0),
+            )
+            .map_err(err)?
+            .collect::<Result<Vec<Option<i64>>, _>>()
+            .map_err(err)?;
+        stats.consistency = Some(consistency(&times, window));
+        detailed.push(stats);
+    }
+    Ok(detailed)
+}
+
+#[tauri::command]
+pub fn get_activity_stats(
+    bucket: Bucket,
+    date_from: String,
+    date_to: String,
+    username: Option<String>,
+    week_start: Option<Weekday>,
+    include_invalidated: Option<bool>,
+    db: State<'_, Db>,
+) -> Result<Vec<ActivityBucket>, String> {
+    let conn = db.conn()?;
+    activity_stats(
+        &conn,
+        bucket,
+        &date_from,
+        &date_to,
+        username.as_deref(),
+        week_start.unwrap_or(Weekday::Mon),
+        include_invalidated.unwrap_or(false),
+    )
+}
+
+/// A reversed range is empty, so it gives an all-zero grid as well.
+#[tauri::command]
+pub fn get_activity_heatmap(
+    date_from: String,
+    date_to: String,
+    metric: HeatmapMetric,
+    username: Option<String>,
+    include_invalidated: Option<bool>,
+    db: State<'_, Db>,
+) -> Result<ActivityHeatmap, String> {
+    let conn = db.conn()?;
+    activity_heatmap(
+        &conn,
+        metric,
+        &date_from,
+        &date_to,
+        username.as_deref(),
+        include_invalidated.unwrap_or(false),
+    )
+}
+
+#[tauri::command]
+pub fn get_split_distribution(
+    map: String,
+    checkpoint: i64,
+    bucket_ms: i64,
+    username: Option<String>,
+    outlier_factor: Option<f64>,
+    include_invalidated: Option<bool>,
+    db: State<'_, Db>,
+) -> Result<SplitDistribution, String> {
+    let conn = db.conn()?;
+    split_distribution(
+        &conn,
+        &map,
+        checkpoint,
+        bucket_ms,
+        username.as_deref(),
+        outlier_factor.unwrap_or(OUTLIER_FACTOR),
+        include_invalidated.unwrap_or(false),
+    )
+}
+
+#[tauri::command]
+pub fn get_map_stats(
+    username: Option<String>,
+    detailed: Option<bool>,
+    window: Option<usize>,
+    include_invalidated: Option<bool>,
+    db: State<'_, Db>,
+) -> Result<Vec<StoredMapStats>, String> {
+    let conn = db.conn()?;
+    stored_map_stats(
+        &conn,
+        username.as_deref(),
+        detailed.unwrap_or(false),
+        window.unwrap_or(TREND_WINDOW),
+        include_invalidated.unwrap_or(false),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&[30, 10, 20]), Some(20));
+        assert_eq!(median(&[40, 10, 30, 20]), Some(25));
+        assert_eq!(median(&[]), None);
+    }
+
+    #[test]
+    fn stddev_is_the_sample_deviation() {
+        // Mean 25, squares 225 + 25 + 25 + 225 over n - 1 = 3
+        assert_eq!(stddev(&[10, 20, 30, 40]), Some(13));
+        assert_eq!(stddev(&[5_000, 5_000]), Some(0));
+        assert_eq!(stddev(&[5_000]), None);
+    }
+
+    #[test]
+    fn trend_compares_the_last_two_windows() {
+        let attempts = [Some(10), None, Some(20), Some(30), None, Some(40)];
+        let c = consistency(&attempts, 3);
+        assert_eq!(c.median_ms, Some(25));
+        assert_eq!(c.stddev_ms, Some(13));
+        assert_eq!(c.completion_rate, Some(4.0 / 6.0));
+        assert_eq!(c.previous_median_ms, Some(15));
+        assert_eq!(c.recent_median_ms, Some(35));
+        assert_eq!(c.trend_ms, Some(20));
+
+        // Getting slower is a positive trend
+        let c = consistency(&[Some(10), Some(12), Some(30), Some(32)], 2);
+        assert_eq!(c.trend_ms, Some(20));
+        // Only the last 2 * window attempts count
+        let c = consistency(&[Some(1_000), Some(10), Some(12), Some(8), Some(9)], 2);
+        assert_eq!(
+            (c.previous_median_ms, c.recent_median_ms, c.trend_ms),
+            (Some(11), Some(8), Some(-3))
+        );
+    }
+
+    #[test]
+    fn few_runs_leave_fields_null() {
+        let attempts = [Some(10), None, Some(20), Some(30), None, Some(40)];
+        let c = consistency(&attempts, 4);
+        assert_eq!(c.median_ms, Some(25));
+        assert_eq!(
+            (c.recent_median_ms, c.previous_median_ms, c.trend_ms),
+            (None, None, None)
+        );
+
+        // A window without a completion has no median to compare
+        let c = consistency(&[Some(10), Some(20), None, None], 2);
+        assert_eq!(
+            (c.previous_median_ms, c.recent_median_ms, c.trend_ms),
+            (Some(15), None, None)
+        );
+
+        let c = consistency(&[None], 1);
+        assert_eq!(
+            (c.median_ms, c.stddev_ms, c.completion_rate),
+            (None, None, Some(0.0))
+        );
+        let c = consistency(&[Some(10)], 1);
+        assert_eq!((c.median_ms, c.stddev_ms), (Some(10), None));
+        assert_eq!(consistency(&[], 1).completion_rate, None);
+        assert_eq!(consistency(&attempts, 0).trend_ms, None);
+    }
+
+    #[test]
+    fn stored_stats_carry_consistency_when_asked() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        conn.execute_batch(
+            "INSERT INTO runs (map, username, started_at, time_ms, completed) VALUES
+                ('tower', 'me', '2026-01-01 10:00:00', 5000, 1),
+                ('Tower', 'me', '2026-01-02 10:00:00', NULL, 0),
+                ('Tower', 'me', '2026-01-03 10:00:00', 4000, 1),
+                ('B', 'me', '2026-01-05 11:00:00', NULL, 0);",
+        )
+        .unwrap();
+        let stats = stored_map_stats(&conn, None, true, 1, false).unwrap();
+        assert_eq!(stats[0].map, "Tower");
+        assert_eq!((stats[0].attempts, stats[0].average_ms), (3, Some(4500)));
+        let c = stats[0].consistency.as_ref().unwrap();
+        // The window before the last attempt was the unfinished one
+        assert_eq!(
+            (c.recent_median_ms, c.previous_median_ms),
+            (Some(4000), None)
+        );
+        assert!(stored_map_stats(&conn, None, false, 1, false).unwrap()[0]
+            .consistency
+            .is_none());
+        assert!(stored_map_stats(&conn, None, true, 0, false).is_err());
+    }
+
+    #[test]
+    fn pbs_are_counted_as_personal_best_counts_them() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.conn().unwrap();
+        conn.execute_batch(
+            "INSERT INTO runs (map, username, started_at, time_ms, completed, practice) VALUES
+                ('Tower', 'me', '2026-01-01 11:00:00', 5000, 1, 0),
+                ('Tower', 'me', '2026-01-02 10:00:00', 6000, 1, 0),
+                ('Tower', 'me', '2026-01-04 09:00:00', 1000, 1, 1),
+                ('Tower', 'me', '2026-01-04 10:00:00', 7000, 1, 0);",
+        )
+        .unwrap();
+        let cut = parkour_core::timestamps::utc_column("2026-01-03 00:00:00").unwrap();
+        db::reset_pb(&conn, "Tower", &cut).unwrap();
+        let pb = db::personal_best(&conn, "Tower", None).unwrap().unwrap();
+        assert_eq!(pb.run.time_ms, Some(7000));
+
+        let days = activity_stats(
+            &conn,
+            Bucket::Day,
+            "2026-01-01",
+            "2026-01-04",
+            None,
+            Weekday::Mon,
+            false,
+        )
+        .unwrap();
+        // The PB from before the reset no longer counts, nor does practice
+        let pbs: Vec<i64> = days.iter().map(|d| d.pbs).collect();
+        assert_eq!(pbs, [0, 0, 0, 1]);
+
+        let heatmap = activity_heatmap(
+            &conn,
+            HeatmapMetric::Pbs,
+            "2026-01-01",
+            "2026-01-04",
+            None,
+            false,
+        )
+        .unwrap();
+        // Sunday 10:00
+        assert_eq!(heatmap.cells[6][10], 1);
+        assert_eq!(heatmap.cells.iter().flatten().sum::<i64>(), 1);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::db::{self, Db, RunDetail};
+use crate::duration::{format_delta, format_ms};
+use crate::settings::SettingsStore;
+use crate::templates::{self, TemplateContext, TemplateVars};
+use serde::Deserialize;
+use std::fmt::Write;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryFormat {
+    Plain,
+    Markdown,
+}
+
+fn time_text(detail: &RunDetail) -> String {
+    match detail.run.time_ms {
+        Some(ms) if detail.run.completed => format_ms(ms),
+        _ => "DNF".into(),
+    }
+}
+
+fn delta_text(detail: &RunDetail, previous_best: Option<i64>) -> String {
+    match (
+        detail.run.time_ms.filter(|_| detail.run.completed),
+        previous_best,
+    ) {
+        (Some(time), Some(best)) => format_delta(time - best),
+        (Some(_), None) => "first completion".into(),
+        _ => String::new(),
+    }
+}
+
+fn splits_table(detail: &RunDetail) -> String {
+    let mut table = String::from("| # | Checkpoint | Time | Segment |\n|---|---|---|---|\n");
+    let mut previous = 0;
+    for split in &detail.splits {
+        let name = split
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Checkpoint {}", split.checkpoint));
+        let _ = writeln!(
+            table,
+            "| {} | {} | {} | {} |",
+            split.checkpoint,
+            name.replace('|', "\\|"),
+            format_ms(split.time_ms),
+            format_ms(split.time_ms - previous)
+        );
+        previous = split.time_ms;
+    }
+    table
+}
+
+pub fn render_summary(
+    detail: &RunDetail,
+    previous_best: Option<i64>,
+    format: SummaryFormat,
+    template: Option<&str>,
+) -> Result<String, String> {
+    if let Some(template) = template.filter(|t| !t.trim().is_empty()) {
+        let vars = TemplateVars::from_run(detail, previous_best);
+        return templates::render(template, &vars, TemplateContext::Plain)
+            .map_err(|errors| templates::format_errors(&errors));
+    }
+
+    let run = &detail.run;
+    let delta = delta_text(detail, previous_best);
+    let delta = if delta.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", delta)
+    };
+    let fails = match run.fails {
+        1 => "1 fail".to_string(),
+        n => format!("{} fails", n),
+    };
+    Ok(match format {
+        SummaryFormat::Plain => format!(
+            "{} - {}{}\n{} · {}",
+            run.map,
+            time_text(detail),
+            delta,
+            fails,
+            run.started_at
+        ),
+        SummaryFormat::Markdown => {
+            let mut out = format!(
+                "**{}** - `{}`{}\n{} · {}\n",
+                run.map,
+                time_text(detail),
+                delta,
+                fails,
+                run.started_at
+            );
+            if !detail.splits.is_empty() {
+                out.push('\n');
+                out.push_str(&splits_table(detail));
+            }
+            out.trim_end().to_string()
+        }
+    })
+}
+
+pub fn copy_summary(app: &AppHandle, run_id: i64, format: SummaryFormat) -> Result<String, String> {
+    let (detail, previous_best) = {
+        let db = app.state::<Db>();
+        let conn = db.conn()?;
+        let detail = parkour_core::db::get_run(&conn, run_id)?
+            .ok_or_else(|| format!("Run {} not found", run_id))?;
+        let previous_best = db::previous_best_time(&conn, &detail.run)?;
+        (detail, previous_best)
+    };
+    let settings = app.state::<SettingsStore>().get().summary;
+    let template = match format {
+        SummaryFormat::Plain => settings.plain_template,
+        SummaryFormat::Markdown => settings.markdown_template,
+    };
+    let text = render_summary(&detail, previous_best, format, template.as_deref())?;
+    app.clipboard()
+        .write_text(text.clone())
+        .map_err(|e| format!("Failed to copy to clipboard: {}", e))?;
+    Ok(text)
+}
+
+#[tauri::command]
+pub fn copy_run_summary(run_id: i64, format: SummaryFormat, app: AppHandle) -> Result<String, String> {
+    copy_summary(&app, run_id, format)
+}
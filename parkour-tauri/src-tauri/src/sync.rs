@@ -0,0 +1,131 @@
+use crate::events::{LogErrorKind, LogErrorPayload};
+use crate::lines::{cap, cap_lines, TruncatedLine};
+use crate::live::Live;
+use crate::settings::SettingsStore;
+use crate::tail::{decode, strip_bom, Chunk, FileId};
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+
+const HEAD_BYTES: usize = 512;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncResponse {
+    Snapshot {
+        path: String,
+        content: String,
+        offset: u64,
+        end: u64,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        truncated: Vec<TruncatedLine>,
+    },
+    Delta {
+        path: String,
+        content: String,
+        offset: u64,
+        end: u64,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        truncated: Vec<TruncatedLine>,
+    },
+    SnapshotRequired {
+        reason: String,
+    },
+}
+
+// The first bytes of the log identify the file: latest.log only ever grows
+// until the game rotates it, so a changed head means a different file.
+pub fn head(content: &str) -> &str {
+    cap(content, HEAD_BYTES)
+}
+
+// Text of the first lines of `bytes`, enough to compare with a head.
+// Decoding never makes text shorter than its bytes.
+fn head_text(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .skip(HEAD_BYTES)
+        .position(|b| *b == b'\n')
+        .map_or(bytes.len(), |i| HEAD_BYTES + i + 1);
+    decode(bytes[..end].to_vec())
+}
+
+#[tauri::command]
+pub fn sync(from_offset: Option<u64>, app: AppHandle) -> Result<SyncResponse, LogErrorPayload> {
+    let state = app.state::<Live>().snapshot();
+    let path = state.log_path.ok_or_else(|| {
+        LogErrorPayload::new(LogErrorKind::LogNotFound, "No log file is being watched")
+    })?;
+    // Offsets are raw file bytes, as the watcher sends them, so the log is
+    // only decoded once the range to send is known
+    let mut bytes = crate::read_log_bytes(&app, Path::new(&path))
+        .map_err(|e| LogErrorPayload::io("Failed to read log", Path::new(&path), &e))?;
+    // Same as the watcher: a line still being written isn't sent yet
+    bytes.truncate(bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1));
+    let end = bytes.len() as u64;
+    let max = app.state::<SettingsStore>().get().watcher.max_line_bytes;
+
+    let Some(offset) = from_offset else {
+        let chunk = Chunk::complete(0, bytes)
+            .map_err(|e| LogErrorPayload::io("Failed to read log", Path::new(&path), &e))?;
+        let (content, truncated) = cap_lines(chunk.content, chunk.start, max);
+        return Ok(SyncResponse::Snapshot {
+            path,
+            content,
+            offset: 0,
+            end,
+            truncated,
+        });
+    };
+    // A recreated log can match the old head exactly, so identity wins
+    let replaced = state
+        .log_id
+        .is_some_and(|id| FileId::of_path(Path::new(&path)).is_ok_and(|now| now != id));
+    let rotated = replaced
+        || state
+            .log_head
+            .is_some_and(|h| !strip_bom(&head_text(&bytes)).starts_with(&h));
+    let reason = if rotated {
+        Some("Log file was rotated or truncated")
+    } else if offset > end {
+        Some("Offset is past the end of the log")
+    } else if bytes.get(offset as usize).is_some_and(|b| b & 0xc0 == 0x80) {
+        Some("Offset does not fall on a character boundary")
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        return Ok(SyncResponse::SnapshotRequired {
+            reason: reason.into(),
+        });
+    }
+    bytes.drain(..offset as usize);
+    let delta = Chunk::complete(offset, bytes)
+        .map_err(|e| LogErrorPayload::io("Failed to read log", Path::new(&path), &e))?;
+    let (content, truncated) = cap_lines(delta.content, delta.start, max);
+    Ok(SyncResponse::Delta {
+        path,
+        content,
+        offset,
+        end,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn head_text_covers_the_watchers_head() {
+        let mut bytes = b"\xef\xbb\xbf[10:00:00] [main/INFO]: Setting user: Steve\n".to_vec();
+        bytes.extend(b"[10:00:01] [CHAT] caf\xe9\n".repeat(40));
+        let text = decode(bytes.clone());
+        let start = head_text(&bytes);
+        assert!(text.starts_with(&start));
+        // What note_read stores from the first chunk, BOM already stripped
+        let seen = head(strip_bom(&text));
+        assert_eq!(seen.len(), HEAD_BYTES);
+        assert!(strip_bom(&start).starts_with(seen));
+    }
+}
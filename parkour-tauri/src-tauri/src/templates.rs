@@ -0,0 +1,311 @@
+use crate::db::{self, Db, RunDetail};
+use crate::duration::{format_delta, format_ms};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+// `{name}` substitutes a value, `{#name}...{/name}` keeps its body only when
+// the value is present (`pb` is true for personal bests) and `{^name}` is the
+// inverse. Braces that don't form a tag are literal, so JSON bodies need no
+// escaping of their own.
+pub const PLACEHOLDERS: &[&str] = &[
+    "map",
+    "time",
+    "time_ms",
+    "time_formatted",
+    "delta",
+    "username",
+    "server",
+    "fails",
+    "date",
+];
+const FLAGS: &[&str] = &["pb"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateContext {
+    #[default]
+    Plain,
+    Json,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct TemplateError {
+    pub position: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TemplateVars {
+    pub map: String,
+    pub username: String,
+    pub server: Option<String>,
+    pub time_ms: Option<i64>,
+    pub delta_ms: Option<i64>,
+    pub fails: Option<i64>,
+    pub date: Option<String>,
+    pub is_pb: bool,
+}
+
+impl TemplateVars {
+    pub fn from_run(detail: &RunDetail, previous_best: Option<i64>) -> TemplateVars {
+        let run = &detail.run;
+        let time_ms = run.time_ms.filter(|_| run.completed);
+        TemplateVars {
+            map: run.map.clone(),
+            username: run.username.clone(),
+            server: run.server.clone(),
+            time_ms,
+            delta_ms: time_ms.zip(previous_best).map(|(t, b)| t - b),
+            fails: Some(run.fails),
+            date: Some(run.started_at.clone()),
+            is_pb: !run.practice
+                && run.invalidated_by.is_empty()
+                && time_ms.is_some_and(|t| previous_best.is_none_or(|b| t < b)),
+        }
+    }
+
+    pub fn sample() -> TemplateVars {
+        TemplateVars {
+            map: "Test Map".into(),
+            username: "Steve".into(),
+            server: Some("mc.example.net".into()),
+            time_ms: Some(83_456),
+            delta_ms: Some(-1_234),
+            fails: Some(3),
+            date: Some("2024-01-01 12:00:00".into()),
+            is_pb: true,
+        }
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        match name {
+            "map" => Some(self.map.clone()),
+            "username" => Some(self.username.clone()),
+            "server" => self.server.clone(),
+            "time" | "time_formatted" => self.time_ms.map(format_ms),
+            "time_ms" => self.time_ms.map(|t| t.to_string()),
+            "delta" => self.delta_ms.map(format_delta),
+            "fails" => self.fails.map(|f| f.to_string()),
+            "date" => self.date.clone(),
+            _ => None,
+        }
+        .filter(|v| !v.is_empty())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        match name {
+            "pb" => self.is_pb,
+            _ => self.value(name).is_some(),
+        }
+    }
+}
+
+enum Node {
+    Text(String),
+    Value(String),
+    Section {
+        name: String,
+        inverted: bool,
+        body: Vec<Node>,
+    },
+}
+
+struct Frame {
+    name: String,
+    inverted: bool,
+    position: usize,
+    body: Vec<Node>,
+}
+
+// A tag is `{` + optional `#`, `^` or `/` + [a-z0-9_]+ + `}`; returns the
+// prefix, name and byte length of the whole tag.
+fn tag_at(s: &str) -> Option<(Option<char>, &str, usize)> {
+    let rest = s.strip_prefix('{')?;
+    let (prefix, rest) = match rest.chars().next()? {
+        c @ ('#' | '^' | '/') => (Some(c), &rest[1..]),
+        _ => (None, rest),
+    };
+    let len = rest
+        .find(|c: char| !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'))
+        .unwrap_or(rest.len());
+    if len == 0 || !rest[len..].starts_with('}') {
+        return None;
+    }
+    let consumed = 1 + prefix.map_or(0, |_| 1) + len + 1;
+    Some((prefix, &rest[..len], consumed))
+}
+
+fn parse(template: &str) -> Result<Vec<Node>, Vec<TemplateError>> {
+    let mut errors = Vec::new();
+    let mut stack = vec![Frame {
+        name: String::new(),
+        inverted: false,
+        position: 0,
+        body: Vec::new(),
+    }];
+    let mut text = String::new();
+    let mut chars = 0;
+    let mut i = 0;
+
+    while i < template.len() {
+        let Some((prefix, name, consumed)) = tag_at(&template[i..]) else {
+            let c = template[i..].chars().next().unwrap_or_default();
+            text.push(c);
+            i += c.len_utf8();
+            chars += 1;
+            continue;
+        };
+        let frame = stack.last_mut().expect("root frame");
+        if !text.is_empty() {
+            frame.body.push(Node::Text(std::mem::take(&mut text)));
+        }
+        let known_value = PLACEHOLDERS.contains(&name);
+        let known_flag = known_value || FLAGS.contains(&name);
+        match prefix {
+            None if known_value => frame.body.push(Node::Value(name.to_string())),
+            None => errors.push(TemplateError {
+                position: chars,
+                message: format!("Unknown placeholder {{{}}}", name),
+            }),
+            Some('/') => {
+                if stack.len() > 1 && stack.last().is_some_and(|f| f.name == name) {
+                    let done = stack.pop().expect("open section");
+                    stack
+                        .last_mut()
+                        .expect("root frame")
+                        .body
+                        .push(Node::Section {
+                            name: done.name,
+                            inverted: done.inverted,
+                            body: done.body,
+                        });
+                } else {
+                    errors.push(TemplateError {
+                        position: chars,
+                        message: format!("Unexpected {{/{}}}", name),
+                    });
+                }
+            }
+            Some(c) => {
+                if !known_flag {
+                    errors.push(TemplateError {
+                        position: chars,
+                        message: format!("Unknown section {{{}{}}}", c, name),
+                    });
+                }
+                stack.push(Frame {
+                    name: name.to_string(),
+                    inverted: c == '^',
+                    position: chars,
+                    body: Vec::new(),
+                });
+            }
+        }
+        // Tags are ASCII, so bytes and chars agree
+        i += consumed;
+        chars += consumed;
+    }
+
+    if !text.is_empty() {
+        stack
+            .last_mut()
+            .expect("root frame")
+            .body
+            .push(Node::Text(text));
+    }
+    while stack.len() > 1 {
+        let open = stack.pop().expect("open section");
+        errors.push(TemplateError {
+            position: open.position,
+            message: format!("Section {{{}}} is never closed", open.name),
+        });
+    }
+    if errors.is_empty() {
+        Ok(stack.pop().map(|f| f.body).unwrap_or_default())
+    } else {
+        errors.sort_by_key(|e| e.position);
+        Err(errors)
+    }
+}
+
+fn escape(value: &str, context: TemplateContext) -> String {
+    match context {
+        TemplateContext::Plain => value.to_string(),
+        TemplateContext::Json => {
+            let quoted = serde_json::Value::String(value.to_string()).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        }
+    }
+}
+
+fn render_nodes(nodes: &[Node], vars: &TemplateVars, context: TemplateContext, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value(name) => {
+                if let Some(value) = vars.value(name) {
+                    out.push_str(&escape(&value, context));
+                }
+            }
+            Node::Section {
+                name,
+                inverted,
+                body,
+            } => {
+                if vars.flag(name) != *inverted {
+                    render_nodes(body, vars, context, out);
+                }
+            }
+        }
+    }
+}
+
+pub fn format_errors(errors: &[TemplateError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("{} at position {}", e.message, e.position))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+pub fn render(
+    template: &str,
+    vars: &TemplateVars,
+    context: TemplateContext,
+) -> Result<String, Vec<TemplateError>> {
+    let nodes = parse(template)?;
+    let mut out = String::new();
+    render_nodes(&nodes, vars, context, &mut out);
+    Ok(out)
+}
+
+// JSON templates must also produce a valid document once filled in.
+pub fn validate(template: &str, context: TemplateContext) -> Result<(), String> {
+    let rendered = render(template, &TemplateVars::sample(), context)
+        .map_err(|errors| format_errors(&errors))?;
+    if context == TemplateContext::Json {
+        serde_json::from_str::<serde_json::Value>(&rendered)
+            .map_err(|e| format!("Template does not render valid JSON: {}", e))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn render_template(
+    template: String,
+    run_id: Option<i64>,
+    context: Option<TemplateContext>,
+    db: State<'_, Db>,
+) -> Result<String, String> {
+    let vars = match run_id {
+        Some(run_id) => {
+            let conn = db.conn()?;
+            let detail = parkour_core::db::get_run(&conn, run_id)?
+                .ok_or_else(|| format!("Run {} not found", run_id))?;
+            let previous_best = db::previous_best_time(&conn, &detail.run)?;
+            TemplateVars::from_run(&detail, previous_best)
+        }
+        None => TemplateVars::sample(),
+    };
+    render(&template, &vars, context.unwrap_or_default()).map_err(|errors| format_errors(&errors))
+}
@@ -0,0 +1,186 @@
+use crate::db::{self, Db};
+use crate::duration::{format_delta, format_ms};
+use crate::live::{now_ms, CurrentRun, Live};
+use crate::settings::{SettingsStore, TextFile, TextOutputSettings};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+#[derive(Default)]
+pub struct TextOutput {
+    worker: Mutex<Option<Sender<()>>>,
+    // Held around every write so disabling can't race a write in flight
+    writes: Mutex<()>,
+}
+
+// OBS polls these files, so each one is swapped in whole via rename.
+fn write_atomic(dir: &Path, file: TextFile, content: &str) -> Result<(), String> {
+    let path = dir.join(file.file_name());
+    let tmp = path.with_extension("txt.tmp");
+    fs::write(&tmp, content)
+        .and_then(|_| fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn current_time(run: Option<&CurrentRun>) -> String {
+    run.and_then(|r| r.started_ms)
+        .map(|started| format_ms(now_ms().saturating_sub(started) as i64))
+        .unwrap_or_default()
+}
+
+fn run_contents(app: &AppHandle, run: Option<&CurrentRun>) -> HashMap<TextFile, String> {
+    let mut contents = HashMap::new();
+    let map = run.and_then(|r| r.map.as_deref());
+    contents.insert(TextFile::CurrentMap, map.unwrap_or_default().to_string());
+    contents.insert(TextFile::CurrentTime, current_time(run));
+
+    let (mut pb, mut attempts, mut delta) = (String::new(), String::new(), String::new());
+    if let (Some(run), Some(map)) = (run, map) {
+        let db = app.state::<Db>();
+        if let Ok(conn) = db.conn() {
+            let username = run.username.as_deref();
+            let best = db::personal_best(&conn, map, username).ok().flatten();
+            if let Some(best) = &best {
+                pb = best.run.time_ms.map(format_ms).unwrap_or_default();
+            }
+            if let Ok(count) = db::attempt_count(&conn, map, username) {
+                attempts = (count + 1).to_string();
+            }
+            if let (Some(last), Some(best)) = (run.splits.last(), &best) {
+                if let Some(split) = best.splits.iter().find(|s| s.checkpoint == last.checkpoint) {
+                    delta = format_delta(last.time_ms - split.time_ms);
+                }
+            }
+        };
+    }
+    contents.insert(TextFile::Pb, pb);
+    contents.insert(TextFile::AttemptCount, attempts);
+    contents.insert(TextFile::LastSplitDelta, delta);
+    contents
+}
+
+// Run-derived files are recomputed when the run state changes; between
+// changes only the ticking timer is rewritten.
+fn worker(app: AppHandle, rx: Receiver<()>) {
+    let output = app.state::<TextOutput>();
+    let mut contents = HashMap::new();
+    let mut written: HashMap<TextFile, String> = HashMap::new();
+    let mut written_dir = String::new();
+    let mut dirty = true;
+    let mut reported = false;
+
+    loop {
+        let settings = app.state::<SettingsStore>().get().text_output;
+        if settings.enabled {
+            let run = app.state::<Live>().snapshot().current_run;
+            if dirty {
+                contents = run_contents(&app, run.as_ref());
+            } else {
+                contents.insert(TextFile::CurrentTime, current_time(run.as_ref()));
+            }
+            dirty = false;
+
+            let Ok(_guard) = output.writes.lock() else {
+                return;
+            };
+            let settings = app.state::<SettingsStore>().get().text_output;
+            if settings.enabled {
+                if written_dir != settings.directory {
+                    written.clear();
+                    written_dir = settings.directory.clone();
+                }
+                let dir = Path::new(&settings.directory);
+                let result = fs::create_dir_all(dir)
+                    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))
+                    .and_then(|_| {
+                        for file in &settings.files {
+                            let content = contents.get(file).cloned().unwrap_or_default();
+                            if written.get(file) != Some(&content) {
+                                write_atomic(dir, *file, &content)?;
+                                written.insert(*file, content);
+                            }
+                        }
+                        Ok(())
+                    });
+                match result {
+                    Ok(()) => reported = false,
+                    Err(e) if !reported => {
+                        eprintln!("{}", e);
+                        reported = true;
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        let settings = app.state::<SettingsStore>().get().text_output;
+        if !settings.enabled {
+            let Ok(mut slot) = output.worker.lock() else {
+                return;
+            };
+            // Re-check under the lock so a concurrent re-enable isn't lost
+            if !app.state::<SettingsStore>().get().text_output.enabled {
+                *slot = None;
+                return;
+            }
+            written.clear();
+        }
+        match rx.recv_timeout(Duration::from_millis(settings.interval_ms.max(100))) {
+            Ok(()) => dirty = true,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+// Called by the run-state machine whenever the current run changes.
+pub fn refresh(app: &AppHandle) {
+    if !app.state::<SettingsStore>().get().text_output.enabled {
+        return;
+    }
+    let output = app.state::<TextOutput>();
+    let Ok(mut slot) = output.worker.lock() else {
+        return;
+    };
+    if let Some(sender) = slot.as_ref() {
+        if sender.send(()).is_ok() {
+            return;
+        }
+    }
+    let (sender, rx) = channel();
+    *slot = Some(sender);
+    let app = app.clone();
+    std::thread::spawn(move || worker(app, rx));
+}
+
+fn clear(settings: &TextOutputSettings) {
+    let dir = Path::new(&settings.directory);
+    for file in &settings.files {
+        if dir.join(file.file_name()).exists() {
+            if let Err(e) = write_atomic(dir, *file, "") {
+                eprintln!("{}", e);
+            }
+        }
+    }
+}
+
+pub fn apply_settings(app: &AppHandle, previous: &TextOutputSettings) {
+    let current = app.state::<SettingsStore>().get().text_output;
+    let moved = current.directory != previous.directory;
+    if previous.enabled && (!current.enabled || moved) && current.clear_on_disable {
+        let output = app.state::<TextOutput>();
+        let _guard = output.writes.lock();
+        clear(previous);
+    }
+    if current.enabled {
+        refresh(app);
+    } else if let Ok(slot) = app.state::<TextOutput>().worker.lock() {
+        if let Some(sender) = slot.as_ref() {
+            let _ = sender.send(());
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::events;
+use crate::live::{source_field, CurrentRun, Live};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+// How often a running timer is reported
+const TICK: Duration = Duration::from_millis(100);
+
+/// `{"map": "Tower", "elapsed_ms": 41250, "splits": 3}`
+///
+/// The live run's real time since it started, sent every tenth of a second
+/// while one is going, so a throttled webview still shows the right time.
+/// Ticks aren't kept for `get_events_since`.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct TimerTickPayload {
+    pub map: Option<String>,
+    pub elapsed_ms: u64,
+    pub splits: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// `{"map": "Tower", "checkpoint": 3, "time_ms": 41250, "segment_ms": 12000, "elapsed_ms": 41310}`
+///
+/// A split added to the live run. `time_ms` is the game's timer at the
+/// checkpoint and `segment_ms` the time since the previous split, while
+/// `elapsed_ms` is the real time since the run started.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct SplitRecordedPayload {
+    pub map: Option<String>,
+    pub checkpoint: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub time_ms: i64,
+    pub segment_ms: i64,
+    pub elapsed_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Sends `timer-tick` for every source with a run going and not paused,
+/// until shutdown.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        while !crate::stopping(&app) {
+            for (source, run) in app.state::<Live>().current_runs() {
+                let Some(elapsed_ms) = run.elapsed_ms().filter(|_| run.paused_since_ms.is_none())
+                else {
+                    continue;
+                };
+                let payload = TimerTickPayload {
+                    map: run.map,
+                    elapsed_ms,
+                    splits: run.splits.len(),
+                    source: source_field(&source),
+                };
+                events::emit_transient(&app, events::TIMER_TICK, &payload);
+            }
+            std::thread::sleep(TICK);
+        }
+    });
+}
+
+/// Sends `split-recorded` for the split just added to `run`.
+pub fn split_recorded(app: &AppHandle, source: &str, run: &CurrentRun) {
+    let Some((split, before)) = run.splits.split_last() else {
+        return;
+    };
+    let payload = SplitRecordedPayload {
+        map: run.map.clone(),
+        checkpoint: split.checkpoint,
+        name: split.name.clone(),
+        time_ms: split.time_ms,
+        segment_ms: split.time_ms - before.last().map_or(0, |s| s.time_ms),
+        elapsed_ms: run.elapsed_ms(),
+        source: source_field(source),
+    };
+    events::emit_all(app, events::SPLIT_RECORDED, &payload);
+}
@@ -0,0 +1,150 @@
+use crate::db::{self, Db};
+use crate::duration::format_ms;
+use crate::events::{self, MessagePayload};
+use crate::live::{now_ms, Live};
+use crate::notifications::focus_main_window;
+use crate::summary::{self, SummaryFormat};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager};
+
+const TRAY_ID: &str = "main";
+const LIVE_WINDOW_MS: u64 = 5 * 60 * 1000;
+
+pub struct Tray {
+    pause: MenuItem<tauri::Wry>,
+    last_pb: Mutex<Option<String>>,
+}
+
+fn tooltip(app: &AppHandle) -> String {
+    let live = app.state::<Live>().snapshot();
+    let file = live
+        .log_path
+        .as_deref()
+        .and_then(|p| Path::new(p).file_name())
+        .map(|f| f.to_string_lossy().to_string());
+    let state = if live.paused {
+        "paused".to_string()
+    } else if let Some(err) = &live.last_error {
+        format!("error: {}", err)
+    } else if live
+        .last_update_ms
+        .is_some_and(|t| now_ms().saturating_sub(t) < LIVE_WINDOW_MS)
+    {
+        "live".to_string()
+    } else {
+        "idle".to_string()
+    };
+    let mut text = match file {
+        Some(file) => format!("Parkour Analyzer - {} ({})", file, state),
+        None => format!("Parkour Analyzer ({})", state),
+    };
+    let pb = app
+        .try_state::<Tray>()
+        .and_then(|t| t.last_pb.lock().ok().and_then(|p| p.clone()));
+    if let Some(pb) = pb {
+        text.push_str(&format!("\nNew PB: {}", pb));
+    }
+    text
+}
+
+// Called whenever watcher state changes so the tooltip never goes stale.
+pub fn refresh(app: &AppHandle) {
+    let Some(tray) = app.try_state::<Tray>() else {
+        return;
+    };
+    let live = app.state::<Live>().snapshot();
+    // Tray calls hop to the main thread, which is blocked while shutting down
+    if live.stopping {
+        return;
+    }
+    let _ = tray.pause.set_text(if live.paused {
+        "Resume watching"
+    } else {
+        "Pause watching"
+    });
+    if let Some(icon) = app.tray_by_id(TRAY_ID) {
+        let _ = icon.set_tooltip(Some(tooltip(app)));
+    }
+}
+
+pub fn pb_achieved(app: &AppHandle, map: &str, time_ms: i64) {
+    if let Some(tray) = app.try_state::<Tray>() {
+        if let Ok(mut last) = tray.last_pb.lock() {
+            *last = Some(format!("{} {}", map, format_ms(time_ms)));
+        }
+    }
+    refresh(app);
+}
+
+fn copy_last_summary(app: &AppHandle) -> Result<(), String> {
+    let run_id = {
+        let db = app.state::<Db>();
+        let conn = db.conn()?;
+        db::latest_run_id(&conn)?.ok_or("No runs recorded yet")?
+    };
+    summary::copy_summary(app, run_id, SummaryFormat::Plain).map(|_| ())
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        "pause" => {
+            let paused = !app.state::<Live>().snapshot().paused;
+            crate::set_paused(app, paused);
+        }
+        "open" => focus_main_window(app),
+        "copy_summary" => {
+            if let Err(e) = copy_last_summary(app) {
+                events::emit(app, events::TRAY_ERROR, &MessagePayload { message: e });
+            }
+        }
+        "quit" => {
+            crate::shutdown(app);
+            app.exit(0);
+        }
+        _ => {}
+    }
+}
+
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let pause = MenuItem::with_id(app, "pause", "Pause watching", true, None::<&str>)?;
+    let open = MenuItem::with_id(app, "open", "Open Parkour Analyzer", true, None::<&str>)?;
+    let copy = MenuItem::with_id(
+        app,
+        "copy_summary",
+        "Copy last run summary",
+        true,
+        None::<&str>,
+    )?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let separator = PredefinedMenuItem::separator(app)?;
+    let menu = Menu::with_items(app, &[&pause, &open, &copy, &separator, &quit])?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(tooltip(app))
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                focus_main_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(Tray {
+        pause,
+        last_pb: Mutex::new(None),
+    });
+    Ok(())
+}
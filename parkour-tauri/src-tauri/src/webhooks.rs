@@ -0,0 +1,328 @@
+use crate::duration::{format_delta, format_ms};
+use crate::events::{self, LogErrorKind};
+use crate::settings::{SettingsStore, WebhookSettings, WebhookTrigger};
+use crate::templates::{self, TemplateContext, TemplateVars};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `{"url": "https://discord.com/…", "message": "HTTP 404"}`
+///
+/// `url` is cut down to the host so the webhook token isn't leaked.
+#[derive(Serialize, JsonSchema, Clone, Debug)]
+pub struct WebhookErrorPayload {
+    pub url: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RunSummary {
+    pub map: String,
+    pub username: String,
+    pub server: Option<String>,
+    pub time_ms: i64,
+    pub improvement_ms: Option<i64>,
+    pub fails: i64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionSummary {
+    pub username: String,
+    pub duration_ms: i64,
+    pub attempts: i64,
+    pub completions: i64,
+    pub pbs: i64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GoalSummary {
+    pub map: String,
+    pub username: Option<String>,
+    pub target_time_ms: i64,
+    pub time_ms: i64,
+    pub deadline: Option<String>,
+}
+
+// The remote host is cut out of the message before it gets here
+#[derive(Serialize, Clone, Debug)]
+pub struct LogErrorSummary {
+    pub kind: LogErrorKind,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Pb(RunSummary),
+    Completion(RunSummary),
+    SessionSummary(SessionSummary),
+    Goal(GoalSummary),
+    LogError(LogErrorSummary),
+}
+
+impl WebhookEvent {
+    fn template_vars(&self) -> Option<TemplateVars> {
+        match self {
+            WebhookEvent::Pb(r) | WebhookEvent::Completion(r) => Some(TemplateVars {
+                map: r.map.clone(),
+                username: r.username.clone(),
+                server: r.server.clone(),
+                time_ms: Some(r.time_ms),
+                delta_ms: r.improvement_ms.map(|d| -d),
+                fails: Some(r.fails),
+                date: None,
+                is_pb: matches!(self, WebhookEvent::Pb(_)),
+            }),
+            // delta_ms is against the target
+            WebhookEvent::Goal(g) => Some(TemplateVars {
+                map: g.map.clone(),
+                username: g.username.clone().unwrap_or_default(),
+                server: None,
+                time_ms: Some(g.time_ms),
+                delta_ms: Some(g.time_ms - g.target_time_ms),
+                fails: None,
+                date: None,
+                is_pb: false,
+            }),
+            WebhookEvent::SessionSummary(_) | WebhookEvent::LogError(_) => None,
+        }
+    }
+
+    fn body(&self, hook: &WebhookSettings) -> String {
+        let rendered = hook
+            .template
+            .as_deref()
+            .zip(self.template_vars())
+            .map(|(template, vars)| templates::render(template, &vars, TemplateContext::Json));
+        match rendered {
+            Some(Ok(body)) => return body,
+            // Templates are validated on save; only a hand-edited settings file gets here
+            Some(Err(errors)) => eprintln!(
+                "Ignoring invalid webhook template: {}",
+                templates::format_errors(&errors)
+            ),
+            None => {}
+        }
+        if hook.discord_embed {
+            self.discord_embed()
+        } else {
+            serde_json::to_value(self).unwrap_or_default()
+        }
+        .to_string()
+    }
+
+    fn trigger(&self) -> WebhookTrigger {
+        match self {
+            WebhookEvent::Pb(_) => WebhookTrigger::Pb,
+            WebhookEvent::Completion(_) => WebhookTrigger::Completion,
+            WebhookEvent::SessionSummary(_) => WebhookTrigger::SessionSummary,
+            WebhookEvent::Goal(_) => WebhookTrigger::Goal,
+            WebhookEvent::LogError(_) => WebhookTrigger::LogError,
+        }
+    }
+
+    fn discord_embed(&self) -> serde_json::Value {
+        let (title, color, fields) = match self {
+            WebhookEvent::Pb(r) | WebhookEvent::Completion(r) => {
+                let pb = matches!(self, WebhookEvent::Pb(_));
+                let mut fields = vec![
+                    serde_json::json!({ "name": "Time", "value": format_ms(r.time_ms), "inline": true }),
+                    serde_json::json!({ "name": "Player", "value": r.username, "inline": true }),
+                ];
+                if let Some(delta) = r.improvement_ms {
+                    fields.push(serde_json::json!({
+                        "name": "Improvement",
+                        "value": format_delta(-delta),
+                        "inline": true,
+                    }));
+                }
+                let title = if pb {
+                    format!("New PB on {}", r.map)
+                } else {
+                    format!("Completed {}", r.map)
+                };
+                (title, if pb { 0xfbbf24 } else { 0x4ade80 }, fields)
+            }
+            WebhookEvent::SessionSummary(s) => (
+                format!("Session summary for {}", s.username),
+                0x60a5fa,
+                vec![
+                    serde_json::json!({ "name": "Duration", "value": format_ms(s.duration_ms), "inline": true }),
+                    serde_json::json!({ "name": "Attempts", "value": s.attempts.to_string(), "inline": true }),
+                    serde_json::json!({ "name": "Completions", "value": s.completions.to_string(), "inline": true }),
+                    serde_json::json!({ "name": "PBs", "value": s.pbs.to_string(), "inline": true }),
+                ],
+            ),
+            WebhookEvent::Goal(g) => {
+                let mut fields = vec![
+                    serde_json::json!({ "name": "Time", "value": format_ms(g.time_ms), "inline": true }),
+                    serde_json::json!({ "name": "Target", "value": format_ms(g.target_time_ms), "inline": true }),
+                ];
+                if let Some(username) = &g.username {
+                    fields.push(serde_json::json!({ "name": "Player", "value": username, "inline": true }));
+                }
+                (format!("Goal reached on {}", g.map), 0xa78bfa, fields)
+            }
+            WebhookEvent::LogError(e) => (
+                "Lost the remote log".into(),
+                0xf87171,
+                vec![serde_json::json!({ "name": "Error", "value": e.message, "inline": false })],
+            ),
+        };
+        serde_json::json!({ "embeds": [{ "title": title, "color": color, "fields": fields }] })
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct WebhookStatus {
+    pub url: String,
+    pub failures: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Webhooks(Mutex<HashMap<String, WebhookStatus>>);
+
+// Webhook URLs (Discord in particular) carry their token in the path and
+// query, so only scheme and host are ever shown.
+pub fn redact_url(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.rsplit('@').next().unwrap_or(host);
+    if scheme.is_empty() {
+        format!("{}/…", host)
+    } else {
+        format!("{}://{}/…", scheme, host)
+    }
+}
+
+fn post(url: &str, body: &str) -> Result<(), (bool, String)> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    match agent
+        .post(url)
+        .content_type("application/json")
+        .send(body)
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::StatusCode(code)) => Err((code >= 500, format!("HTTP {}", code))),
+        Err(e) => Err((true, e.to_string().replace(url, &redact_url(url)))),
+    }
+}
+
+fn deliver(app: &AppHandle, hook: &WebhookSettings, body: String) {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut result = Ok(());
+    for attempt in 1..=MAX_ATTEMPTS {
+        result = post(&hook.url, &body);
+        match &result {
+            Err((true, _)) if attempt < MAX_ATTEMPTS => {
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            _ => break,
+        }
+    }
+
+    let redacted = redact_url(&hook.url);
+    let webhooks = app.state::<Webhooks>();
+    let Ok(mut statuses) = webhooks.0.lock() else {
+        return;
+    };
+    let status = statuses.entry(hook.url.clone()).or_insert_with(|| WebhookStatus {
+        url: redacted.clone(),
+        ..Default::default()
+    });
+    match result {
+        Ok(()) => status.last_error = None,
+        Err((_, message)) => {
+            status.failures += 1;
+            status.last_error = Some(message.clone());
+            events::emit(
+                app,
+                events::WEBHOOK_ERROR,
+                &WebhookErrorPayload {
+                    url: redacted,
+                    message,
+                },
+            );
+        }
+    }
+}
+
+// Fire-and-forget: every delivery runs on its own thread so retries never
+// hold up the watcher or run assembly.
+pub fn notify(app: &AppHandle, event: WebhookEvent) {
+    if let WebhookEvent::Pb(run) | WebhookEvent::Completion(run) = &event {
+        if let Some(server) = &run.server {
+            if !crate::servers::webhooks_enabled(app, server) {
+                return;
+            }
+        }
+    }
+    let hooks: Vec<WebhookSettings> = app
+        .state::<SettingsStore>()
+        .get()
+        .webhooks
+        .into_iter()
+        .filter(|h| !h.url.is_empty() && h.events.contains(&event.trigger()))
+        .collect();
+    for hook in hooks {
+        let body = event.body(&hook);
+        let app = app.clone();
+        std::thread::spawn(move || deliver(&app, &hook, body));
+    }
+}
+
+#[tauri::command]
+pub fn get_webhook_status(webhooks: State<'_, Webhooks>) -> Vec<WebhookStatus> {
+    webhooks
+        .0
+        .lock()
+        .map(|s| s.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub fn test_webhook(trigger: WebhookTrigger, app: AppHandle) {
+    let run = RunSummary {
+        map: "Test Map".into(),
+        username: "Steve".into(),
+        server: None,
+        time_ms: 83_456,
+        improvement_ms: Some(1_234),
+        fails: 3,
+    };
+    let event = match trigger {
+        WebhookTrigger::Pb => WebhookEvent::Pb(run),
+        WebhookTrigger::Completion => WebhookEvent::Completion(run),
+        WebhookTrigger::SessionSummary => WebhookEvent::SessionSummary(SessionSummary {
+            username: "Steve".into(),
+            duration_ms: 3_600_000,
+            attempts: 42,
+            completions: 7,
+            pbs: 1,
+        }),
+        WebhookTrigger::Goal => WebhookEvent::Goal(GoalSummary {
+            map: run.map,
+            username: Some(run.username),
+            target_time_ms: 85_000,
+            time_ms: run.time_ms,
+            deadline: None,
+        }),
+        WebhookTrigger::LogError => WebhookEvent::LogError(LogErrorSummary {
+            kind: LogErrorKind::ConnectionFailed,
+            message: "Failed to connect to …: Connection refused (os error 111)".into(),
+        }),
+    };
+    notify(&app, event);
+}
@@ -0,0 +1,158 @@
+use crate::events;
+use parkour_core::schema;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tiny_http::ReadWrite;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+pub const PROTOCOL_VERSION: u32 = 2;
+const QUEUE_CAPACITY: usize = 256;
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+// Too large to send every client, so only those that ask get these
+const OPT_IN: &[&str] = &[events::LOG_UPDATE];
+
+#[derive(Default)]
+struct Queue {
+    messages: VecDeque<String>,
+    closed: bool,
+}
+
+type SharedQueue = Arc<(Mutex<Queue>, Condvar)>;
+
+struct Client {
+    id: u64,
+    queue: SharedQueue,
+    // Events the client asked for with `/ws?events=`, or None for the default set
+    events: Option<Vec<String>>,
+}
+
+impl Client {
+    fn wants(&self, event: &str) -> bool {
+        match &self.events {
+            Some(events) => events.iter().any(|e| e == event),
+            None => !OPT_IN.contains(&event),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WsHub {
+    clients: Mutex<Vec<Client>>,
+    next_id: AtomicU64,
+}
+
+fn push(queue: &SharedQueue, message: String) {
+    let (lock, cvar) = &**queue;
+    if let Ok(mut q) = lock.lock() {
+        // Slow clients lose their oldest messages instead of stalling the watcher
+        if q.messages.len() >= QUEUE_CAPACITY {
+            q.messages.pop_front();
+        }
+        q.messages.push_back(message);
+        cvar.notify_one();
+    }
+}
+
+fn close(queue: &SharedQueue) {
+    let (lock, cvar) = &**queue;
+    if let Ok(mut q) = lock.lock() {
+        q.closed = true;
+        cvar.notify_one();
+    }
+}
+
+impl WsHub {
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub fn broadcast<T: Serialize>(&self, event: &str, payload: &T) {
+        let Ok(clients) = self.clients.lock() else {
+            return;
+        };
+        if !clients.iter().any(|c| c.wants(event)) {
+            return;
+        }
+        let payload = schema::stamp(serde_json::to_value(payload).unwrap_or(Value::Null));
+        let message = serde_json::json!({ "event": event, "payload": payload }).to_string();
+        for client in clients.iter().filter(|c| c.wants(event)) {
+            push(&client.queue, message.clone());
+        }
+    }
+
+    pub fn close_all(&self) {
+        if let Ok(mut clients) = self.clients.lock() {
+            for client in clients.drain(..) {
+                close(&client.queue);
+            }
+        }
+    }
+
+    fn remove(&self, id: u64) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain(|c| c.id != id);
+        }
+    }
+
+    /// Starts sending events to a connected client, `events` only when given.
+    pub fn attach(
+        &self,
+        app: &AppHandle,
+        stream: Box<dyn ReadWrite + Send>,
+        hello: String,
+        events: Option<Vec<String>>,
+    ) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let queue: SharedQueue = Arc::default();
+        push(&queue, hello);
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.push(Client {
+                id,
+                queue: queue.clone(),
+                events,
+            });
+        }
+
+        let app = app.clone();
+        std::thread::spawn(move || {
+            let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+            let (lock, cvar) = &*queue;
+            loop {
+                let next = {
+                    let Ok(mut q) = lock.lock() else { break };
+                    if q.messages.is_empty() && !q.closed {
+                        q = match cvar.wait_timeout(q, PING_INTERVAL) {
+                            Ok((q, _)) => q,
+                            Err(_) => break,
+                        };
+                    }
+                    if q.closed {
+                        None
+                    } else {
+                        Some(q.messages.pop_front())
+                    }
+                };
+                let result = match next {
+                    None => {
+                        let _ = socket.close(None);
+                        let _ = socket.flush();
+                        break;
+                    }
+                    Some(Some(text)) => socket.send(Message::text(text)),
+                    // Idle: a ping doubles as disconnect detection
+                    Some(None) => socket.send(Message::Ping(Default::default())),
+                };
+                if result.is_err() {
+                    break;
+                }
+            }
+            app.state::<WsHub>().remove(id);
+        });
+    }
+}
@@ -24,9 +24,11 @@ const tauriReady = isTauri()
     ])
   : Promise.resolve();
 
+const errorText = (e) => (e?.hint ? `${e.message}. ${e.hint}` : e?.message ?? String(e));
+
 // --- Parser ---
 function parseLogs(raw) {
-  const lines = raw.split("\n");
+  const lines = raw.split(/\r?\n/);
   let username = null;
   const games = [];
   let cur = null;
@@ -334,15 +336,22 @@ function PathSettings({ logPath, onClose }) {
     setValidating(true);
     setPathError("");
     try {
-      const valid = await tauriInvoke("validate_path", { path: customPath });
-      if (valid) {
+      const check = await tauriInvoke("check_path", { path: customPath });
+      if (check.is_file && check.readable) {
         await tauriInvoke("watch_path", { path: customPath });
         onClose();
+      } else if (check.suggestion) {
+        setCustomPath(check.suggestion);
+        setPathError("That's a folder. Use the latest.log inside it instead?");
+      } else if (check.error) {
+        setPathError(errorText(check.error));
+      } else if (check.exists) {
+        setPathError("Not a regular file");
       } else {
         setPathError("File not found");
       }
     } catch (e) {
-      setPathError(String(e));
+      setPathError(errorText(e));
     }
     setValidating(false);
   };
@@ -431,8 +440,7 @@ export default function App() {
       await tauriReady;
       if (!tauriListen) return;
 
-      const u1 = await tauriListen("log-update", (event) => {
-        const content = event.payload;
+      const applyContent = (content) => {
         const result = parseLogs(content);
         if (result.games.length > 0) {
           setParsed((prev) => {
@@ -457,21 +465,63 @@ export default function App() {
             autoSelectRef.current = false;
           }
         }
+      };
+
+      // Mirror of the log as far as we've seen it, so reloads and missed
+      // updates can be repaired with a delta instead of a full re-read
+      let buffer = "";
+      let end = null;
+      const applySync = (r) => {
+        if (r.type === "snapshot_required") return resync(null);
+        buffer = r.type === "snapshot" ? r.content : buffer + r.content;
+        end = r.end;
+        applyContent(buffer);
+      };
+      const resync = (from) =>
+        tauriInvoke("sync", { fromOffset: from }).then(applySync).catch(() => {});
+
+      const onUpdate = ({ content, offset, end: next }) => {
+        if (offset === 0) {
+          buffer = content;
+        } else if (offset === end) {
+          buffer += content;
+        } else {
+          resync(end);
+          return false;
+        }
+        end = next;
+        return true;
+      };
+      // Rate-limited updates arrive batched as { events: [...] }. This view
+      // follows the main log, so other instances' updates are skipped
+      const u1 = await tauriListen("log-update", (event) => {
+        const updates = (event.payload.events || [event.payload]).filter((u) => !u.source);
+        if (updates.every(onUpdate)) applyContent(buffer);
       });
       unlisteners.push(u1);
 
       const u2 = await tauriListen("log-location", (event) => {
-        setLogPath(event.payload);
+        if (event.payload.instance) return;
+        setLogPath(event.payload.path);
         setWatching(true);
         setError("");
       });
       unlisteners.push(u2);
 
       const u3 = await tauriListen("log-error", (event) => {
-        setError(event.payload);
+        if (event.payload.source) return;
+        setError(errorText(event.payload));
         setWatching(false);
       });
       unlisteners.push(u3);
+
+      const u4 = await tauriListen("game-crashed", (event) => {
+        if (event.payload.source) return;
+        setError(`Game crashed: ${event.payload.summary}`);
+      });
+      unlisteners.push(u4);
+
+      resync(null);
     };
 
     setup();